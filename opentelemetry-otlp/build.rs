@@ -0,0 +1,18 @@
+//! Declares cfg aliases for the feature combinations used throughout the crate.
+
+use std::env;
+
+/// The features enabling a transport the exporters can be built with.
+const TRANSPORT_FEATURES: &[&str] = &["HTTP_PROTO", "HTTP_JSON", "GRPC_TONIC", "TCP", "TESTING"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(otlp_transport)");
+
+    if TRANSPORT_FEATURES
+        .iter()
+        .any(|feature| env::var_os(format!("CARGO_FEATURE_{feature}")).is_some())
+    {
+        println!("cargo:rustc-cfg=otlp_transport");
+    }
+}
@@ -0,0 +1,325 @@
+//! Keep the metrics of failed exports and merge them into the next export.
+//!
+//! Merging follows the temporality of the data: delta sums and histograms are added up, while
+//! cumulative data and gauges keep the latest value. Data points of failed exports which are
+//! missing from the next export are carried over as they are.
+
+use std::sync::Mutex;
+
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+use opentelemetry_proto::tonic::common::v1::KeyValue;
+use opentelemetry_proto::tonic::metrics::v1::exponential_histogram_data_point::Buckets;
+use opentelemetry_proto::tonic::metrics::v1::{
+    metric::Data, number_data_point::Value, AggregationTemporality, ExponentialHistogramDataPoint,
+    HistogramDataPoint, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics,
+};
+
+/// Metrics of failed exports, waiting to be merged into the next export.
+#[derive(Debug, Default)]
+pub(crate) struct MetricAccumulator {
+    pending: Mutex<Option<ExportMetricsServiceRequest>>,
+}
+
+impl MetricAccumulator {
+    /// Merge the metrics of the failed exports into `request`.
+    pub(crate) fn merge_pending(&self, request: &mut ExportMetricsServiceRequest) {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(pending) = pending {
+            merge_resources(pending.resource_metrics, &mut request.resource_metrics);
+        }
+    }
+
+    /// Keep the metrics of a failed export to merge them into the next export.
+    pub(crate) fn keep(&self, request: ExportMetricsServiceRequest) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        *pending = Some(match pending.take() {
+            // only happens when exports run concurrently
+            Some(older) => {
+                let mut request = request;
+                merge_resources(older.resource_metrics, &mut request.resource_metrics);
+                request
+            }
+            None => request,
+        });
+    }
+}
+
+fn merge_resources(older: Vec<ResourceMetrics>, newer: &mut Vec<ResourceMetrics>) {
+    for resource in older {
+        match newer
+            .iter_mut()
+            .find(|r| r.resource == resource.resource && r.schema_url == resource.schema_url)
+        {
+            Some(newer) => merge_scopes(resource.scope_metrics, &mut newer.scope_metrics),
+            None => newer.push(resource),
+        }
+    }
+}
+
+fn merge_scopes(older: Vec<ScopeMetrics>, newer: &mut Vec<ScopeMetrics>) {
+    for scope in older {
+        match newer
+            .iter_mut()
+            .find(|s| s.scope == scope.scope && s.schema_url == scope.schema_url)
+        {
+            Some(newer) => merge_metrics(scope.metrics, &mut newer.metrics),
+            None => newer.push(scope),
+        }
+    }
+}
+
+fn merge_metrics(older: Vec<Metric>, newer: &mut Vec<Metric>) {
+    for metric in older {
+        match newer.iter_mut().find(|m| m.name == metric.name) {
+            Some(newer) => {
+                if let (Some(older), Some(newer)) = (metric.data, newer.data.as_mut()) {
+                    merge_data(older, newer);
+                }
+            }
+            None => newer.push(metric),
+        }
+    }
+}
+
+fn is_delta(older: i32, newer: i32) -> bool {
+    older == newer && newer == AggregationTemporality::Delta as i32
+}
+
+fn merge_data(older: Data, newer: &mut Data) {
+    match (older, newer) {
+        (Data::Sum(older), Data::Sum(newer)) => {
+            let delta = is_delta(older.aggregation_temporality, newer.aggregation_temporality);
+            merge_points(
+                older.data_points,
+                &mut newer.data_points,
+                |p| &p.attributes,
+                |older, newer| {
+                    if delta {
+                        add_number_points(older, newer)
+                    }
+                },
+            );
+        }
+        (Data::Gauge(older), Data::Gauge(newer)) => {
+            merge_points(
+                older.data_points,
+                &mut newer.data_points,
+                |p| &p.attributes,
+                |_, _| {},
+            );
+        }
+        (Data::Histogram(older), Data::Histogram(newer)) => {
+            let delta = is_delta(older.aggregation_temporality, newer.aggregation_temporality);
+            merge_points(
+                older.data_points,
+                &mut newer.data_points,
+                |p| &p.attributes,
+                |older, newer| {
+                    if delta {
+                        add_histogram_points(older, newer)
+                    }
+                },
+            );
+        }
+        (Data::ExponentialHistogram(older), Data::ExponentialHistogram(newer)) => {
+            let delta = is_delta(older.aggregation_temporality, newer.aggregation_temporality);
+            merge_points(
+                older.data_points,
+                &mut newer.data_points,
+                |p| &p.attributes,
+                |older, newer| {
+                    if delta {
+                        add_exponential_histogram_points(older, newer)
+                    }
+                },
+            );
+        }
+        (Data::Summary(older), Data::Summary(newer)) => {
+            merge_points(
+                older.data_points,
+                &mut newer.data_points,
+                |p| &p.attributes,
+                |_, _| {},
+            );
+        }
+        // the type of the metric changed, the newer data wins
+        _ => {}
+    }
+}
+
+/// Merge each older data point into the newer one with the same attributes, or carry it over
+/// if there is none.
+fn merge_points<P>(
+    older: Vec<P>,
+    newer: &mut Vec<P>,
+    attributes: impl Fn(&P) -> &Vec<KeyValue>,
+    merge: impl Fn(P, &mut P),
+) {
+    for point in older {
+        match newer
+            .iter_mut()
+            .find(|p| attributes(p) == attributes(&point))
+        {
+            Some(newer) => merge(point, newer),
+            None => newer.push(point),
+        }
+    }
+}
+
+fn add_number_points(older: NumberDataPoint, newer: &mut NumberDataPoint) {
+    newer.value = match (older.value, newer.value) {
+        (Some(Value::AsInt(older)), Some(Value::AsInt(newer))) => {
+            Some(Value::AsInt(older.wrapping_add(newer)))
+        }
+        (Some(Value::AsDouble(older)), Some(Value::AsDouble(newer))) => {
+            Some(Value::AsDouble(older + newer))
+        }
+        (_, newer) => newer,
+    };
+    newer.start_time_unix_nano = older.start_time_unix_nano;
+    newer.exemplars.extend(older.exemplars);
+}
+
+fn add_histogram_points(older: HistogramDataPoint, newer: &mut HistogramDataPoint) {
+    if older.explicit_bounds != newer.explicit_bounds
+        || older.bucket_counts.len() != newer.bucket_counts.len()
+    {
+        // buckets with different boundaries can't be merged, the newer data wins
+        return;
+    }
+    for (newer, older) in newer.bucket_counts.iter_mut().zip(older.bucket_counts) {
+        *newer += older;
+    }
+    newer.count += older.count;
+    newer.sum = add_options(older.sum, newer.sum, |a, b| a + b);
+    newer.min = add_options(older.min, newer.min, f64::min);
+    newer.max = add_options(older.max, newer.max, f64::max);
+    newer.start_time_unix_nano = older.start_time_unix_nano;
+    newer.exemplars.extend(older.exemplars);
+}
+
+fn add_exponential_histogram_points(
+    older: ExponentialHistogramDataPoint,
+    newer: &mut ExponentialHistogramDataPoint,
+) {
+    // bring both points to the coarser scale before adding up their buckets
+    let scale = older.scale.min(newer.scale);
+    newer.positive = add_buckets(
+        downscale(older.positive, older.scale - scale),
+        downscale(newer.positive.take(), newer.scale - scale),
+    );
+    newer.negative = add_buckets(
+        downscale(older.negative, older.scale - scale),
+        downscale(newer.negative.take(), newer.scale - scale),
+    );
+    newer.scale = scale;
+    newer.count += older.count;
+    newer.zero_count += older.zero_count;
+    newer.zero_threshold = newer.zero_threshold.max(older.zero_threshold);
+    newer.sum = add_options(older.sum, newer.sum, |a, b| a + b);
+    newer.min = add_options(older.min, newer.min, f64::min);
+    newer.max = add_options(older.max, newer.max, f64::max);
+    newer.start_time_unix_nano = older.start_time_unix_nano;
+    newer.exemplars.extend(older.exemplars);
+}
+
+fn add_options(older: Option<f64>, newer: Option<f64>, add: fn(f64, f64) -> f64) -> Option<f64> {
+    match (older, newer) {
+        (Some(older), Some(newer)) => Some(add(older, newer)),
+        (older, newer) => newer.or(older),
+    }
+}
+
+/// Merge the buckets of an exponential histogram into `2^by` times wider buckets.
+fn downscale(buckets: Option<Buckets>, by: i32) -> Option<Buckets> {
+    let buckets = buckets?;
+    if by == 0 || buckets.bucket_counts.is_empty() {
+        return Some(buckets);
+    }
+
+    let offset = buckets.offset >> by;
+    let mut bucket_counts = Vec::new();
+    for (i, count) in buckets.bucket_counts.into_iter().enumerate() {
+        let index = ((buckets.offset + i as i32) >> by) - offset;
+        if bucket_counts.len() <= index as usize {
+            bucket_counts.resize(index as usize + 1, 0);
+        }
+        bucket_counts[index as usize] += count;
+    }
+    Some(Buckets {
+        offset,
+        bucket_counts,
+    })
+}
+
+/// Add up the buckets of two exponential histograms of the same scale.
+fn add_buckets(older: Option<Buckets>, newer: Option<Buckets>) -> Option<Buckets> {
+    let (older, newer) = match (older, newer) {
+        (Some(older), Some(newer)) => (older, newer),
+        (older, newer) => return newer.or(older),
+    };
+    if older.bucket_counts.is_empty() {
+        return Some(newer);
+    }
+    if newer.bucket_counts.is_empty() {
+        return Some(older);
+    }
+
+    let offset = older.offset.min(newer.offset);
+    let end = (older.offset + older.bucket_counts.len() as i32)
+        .max(newer.offset + newer.bucket_counts.len() as i32);
+    let mut bucket_counts = vec![0; (end - offset) as usize];
+    for buckets in [older, newer] {
+        let start = (buckets.offset - offset) as usize;
+        for (i, count) in buckets.bucket_counts.into_iter().enumerate() {
+            bucket_counts[start + i] += count;
+        }
+    }
+    Some(Buckets {
+        offset,
+        bucket_counts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry_proto::tonic::metrics::v1::exponential_histogram_data_point::Buckets;
+    use opentelemetry_proto::tonic::metrics::v1::ExponentialHistogramDataPoint;
+
+    use super::add_exponential_histogram_points;
+
+    fn point(scale: i32, offset: i32, bucket_counts: Vec<u64>) -> ExponentialHistogramDataPoint {
+        ExponentialHistogramDataPoint {
+            count: bucket_counts.iter().sum(),
+            scale,
+            positive: Some(Buckets {
+                offset,
+                bucket_counts,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_exponential_histograms_of_different_scales_are_merged() {
+        // buckets 4..=7 at scale 2 are buckets 2..=3 at scale 1
+        let older = point(2, 4, vec![1, 2, 3, 4]);
+        let mut newer = point(1, 3, vec![10, 20]);
+
+        add_exponential_histogram_points(older, &mut newer);
+
+        assert_eq!(newer.scale, 1);
+        assert_eq!(newer.count, 40);
+        assert_eq!(
+            newer.positive,
+            Some(Buckets {
+                offset: 2,
+                bucket_counts: vec![3, 17, 20],
+            })
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Fetch a fresh bearer token for every export, for backends authenticating with short-lived
+//! tokens.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use futures_core::future::BoxFuture;
+use http::HeaderValue;
+
+/// Returns the current bearer token, or why it couldn't be obtained.
+#[derive(Clone)]
+pub(crate) struct AuthProvider(Arc<TokenFn>);
+
+type TokenFn = dyn Fn() -> BoxFuture<'static, Result<String, String>> + Send + Sync;
+
+impl AuthProvider {
+    pub(crate) fn new<F, Fut, E>(provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, E>> + Send + 'static,
+        E: fmt::Display,
+    {
+        AuthProvider(Arc::new(move || {
+            let token = provider();
+            Box::pin(async move { token.await.map_err(|error| error.to_string()) })
+        }))
+    }
+}
+
+impl fmt::Debug for AuthProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuthProvider")
+    }
+}
+
+/// The value of the `authorization` header of the next export, `Bearer <token>`, if an auth
+/// provider is configured.
+pub(crate) async fn authorization(
+    provider: Option<&AuthProvider>,
+) -> Result<Option<HeaderValue>, crate::Error> {
+    let Some(provider) = provider else {
+        return Ok(None);
+    };
+    let token = (provider.0)()
+        .await
+        .map_err(crate::Error::AuthProviderFailed)?;
+    let mut value = HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| {
+        crate::Error::AuthProviderFailed("the token is not a valid header value".into())
+    })?;
+    value.set_sensitive(true);
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{authorization, AuthProvider};
+
+    #[tokio::test]
+    async fn test_authorization() {
+        assert_eq!(authorization(None).await.unwrap(), None);
+
+        let provider = AuthProvider::new(|| async { Ok::<_, String>("t0ken".to_string()) });
+        let value = authorization(Some(&provider)).await.unwrap().unwrap();
+        assert_eq!(value, "Bearer t0ken");
+        assert!(value.is_sensitive());
+
+        let provider = AuthProvider::new(|| async { Err::<String, _>("token expired") });
+        let error = authorization(Some(&provider)).await.unwrap_err();
+        assert!(
+            matches!(error, crate::Error::AuthProviderFailed(message) if message == "token expired")
+        );
+
+        let provider = AuthProvider::new(|| async { Ok::<_, String>("line\nbreak".to_string()) });
+        assert!(authorization(Some(&provider)).await.is_err());
+    }
+}
@@ -0,0 +1,2726 @@
+//! Options applied while converting telemetry into OTLP messages.
+//!
+//! The conversion from SDK types into OTLP messages lives in `opentelemetry-proto`. The options
+//! here are applied on top of that conversion, right before the messages are handed to the
+//! transport.
+
+#[cfg(feature = "metrics")]
+use std::collections::HashMap;
+#[cfg(feature = "trace")]
+use std::fmt;
+#[cfg(feature = "trace")]
+use std::sync::Arc;
+
+#[cfg(feature = "trace")]
+use opentelemetry::trace::TraceId;
+#[cfg(feature = "attribute-key-patterns")]
+use regex::Regex;
+
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+#[cfg(otlp_transport)]
+use opentelemetry_proto::tonic::common::v1::{any_value::Value, AnyValue, KeyValue};
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::ExportTraceServiceRequest, trace::v1::ResourceSpans,
+};
+#[cfg(otlp_transport)]
+use opentelemetry_proto::transform::common::tonic::Attributes;
+
+#[cfg(feature = "metrics")]
+#[cfg(any(
+    feature = "http-proto",
+    feature = "http-json",
+    feature = "grpc-tonic",
+    feature = "tcp"
+))]
+use super::accumulation::MetricAccumulator;
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+use super::start_time::MetricStartTimes;
+
+/// The attribute holding the depth of a span below its local root, see
+/// [`with_annotate_span_depth`](crate::span::SpanExporterBuilder::with_annotate_span_depth).
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+const SPAN_DEPTH_ATTRIBUTE: &str = "span.depth";
+
+/// The `service.name` the SDK falls back to when no service name is configured.
+#[cfg(otlp_transport)]
+const DEFAULT_SERVICE_NAME: &str = "unknown_service";
+
+/// The prefix of the resource attributes describing the SDK itself, set on every resource.
+#[cfg(otlp_transport)]
+const TELEMETRY_SDK_PREFIX: &str = "telemetry.sdk.";
+
+/// The resource attribute grouping services, injected by
+/// [`with_service_namespace`](crate::WithExportConfig::with_service_namespace).
+#[cfg(otlp_transport)]
+const SERVICE_NAMESPACE: &str = "service.namespace";
+
+/// Options applied to the OTLP messages produced by the exporters.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ConversionConfig {
+    /// Maximum number of spans a single `ResourceSpans` entry may contain.
+    #[allow(dead_code)]
+    pub(crate) max_spans_per_resource: Option<usize>,
+
+    /// Maximum number of `ResourceSpans` entries a single export request may contain.
+    #[allow(dead_code)]
+    pub(crate) max_resources_per_request: Option<usize>,
+
+    /// Maximum number of attributes of a single span, event, link, log record or data point.
+    #[allow(dead_code)]
+    pub(crate) max_attributes_per_record: Option<usize>,
+
+    /// Maximum number of attributes of a single instrumentation scope.
+    #[allow(dead_code)]
+    pub(crate) max_scope_attributes: Option<usize>,
+
+    /// Maximum number of attributes of a single span event, on top of the record maximum.
+    #[allow(dead_code)]
+    pub(crate) max_attributes_per_event: Option<usize>,
+
+    /// Maximum number of elements of a single array attribute value.
+    #[allow(dead_code)]
+    pub(crate) max_array_attribute_len: Option<usize>,
+
+    /// Whether non-string attribute values are exported as their string representation.
+    #[allow(dead_code)]
+    pub(crate) stringify_attributes: bool,
+
+    /// Patterns of the attribute keys removed from records, resources and scopes.
+    #[cfg(feature = "attribute-key-patterns")]
+    #[allow(dead_code)]
+    pub(crate) attribute_key_drop_patterns: Vec<Regex>,
+
+    /// Whether resource attributes are sorted by key, so identical resources encode identically.
+    #[allow(dead_code)]
+    pub(crate) sort_resource_attributes: bool,
+
+    /// Whether a `service.name` is injected into resources without attributes.
+    #[allow(dead_code)]
+    pub(crate) require_resource: bool,
+
+    /// Resource exported in place of resources without attributes.
+    #[allow(dead_code)]
+    pub(crate) fallback_resource: Option<opentelemetry_sdk::Resource>,
+
+    /// The `service.namespace` injected into resources without one.
+    #[allow(dead_code)]
+    pub(crate) service_namespace: Option<String>,
+
+    /// Precision the timestamps of the exported records are truncated to.
+    #[allow(dead_code)]
+    pub(crate) timestamp_precision: TimestampPrecision,
+
+    /// Prefixes of the names of the instrumentation scopes whose telemetry is exported or not.
+    #[allow(dead_code)]
+    pub(crate) scope_name_prefix_filter: Option<ScopeNamePrefixFilter>,
+
+    /// Description and unit to apply to metrics, keyed by metric name.
+    #[cfg(feature = "metrics")]
+    #[allow(dead_code)]
+    pub(crate) metric_metadata_overrides: HashMap<String, MetricMetadataOverride>,
+
+    /// Whether the metrics of failed exports are merged into the next export.
+    #[cfg(feature = "metrics")]
+    #[allow(dead_code)]
+    pub(crate) metric_accumulation_on_failure: bool,
+
+    /// Convention the start times of cumulative metric data points follow.
+    #[cfg(feature = "metrics")]
+    #[allow(dead_code)]
+    pub(crate) metric_start_time_strategy: MetricStartTimeStrategy,
+
+    /// How metrics sharing a name but not a type are handled.
+    #[cfg(feature = "metrics")]
+    #[allow(dead_code)]
+    pub(crate) metric_conflict_policy: MetricConflictPolicy,
+
+    /// Whether sum and gauge data points with a value of zero are left out.
+    #[cfg(feature = "metrics")]
+    #[allow(dead_code)]
+    pub(crate) suppress_zero_data_points: bool,
+
+    /// Prefix prepended to the names of the exported metrics.
+    #[cfg(feature = "metrics")]
+    #[allow(dead_code)]
+    pub(crate) metric_name_prefix: Option<String>,
+
+    /// Function remapping the trace ids of spans and links.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    pub(crate) trace_id_mapper: Option<TraceIdMapper>,
+
+    /// How spans ending before they start are handled.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    pub(crate) span_duration_policy: SpanDurationPolicy,
+
+    /// Whether spans are annotated with their depth below their local root.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    pub(crate) annotate_span_depth: bool,
+
+    /// Key of the attribute an error status is derived from for spans without a status.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    pub(crate) status_from_attribute: Option<String>,
+
+    /// Maximum size in bytes of the string bodies of log records, including the truncation
+    /// marker.
+    #[cfg(feature = "logs")]
+    #[allow(dead_code)]
+    pub(crate) max_log_body_bytes: Option<usize>,
+
+    /// Callback receiving the converted `ResourceSpans` once they were exported.
+    #[cfg(all(feature = "testing", feature = "trace"))]
+    pub(crate) span_inspector:
+        Option<Inspector<opentelemetry_proto::tonic::trace::v1::ResourceSpans>>,
+
+    /// Callback receiving the converted `ResourceLogs` once they were exported.
+    #[cfg(all(feature = "testing", feature = "logs"))]
+    pub(crate) log_inspector: Option<Inspector<opentelemetry_proto::tonic::logs::v1::ResourceLogs>>,
+
+    /// Callback receiving the converted `ResourceMetrics` once they were exported.
+    #[cfg(all(feature = "testing", feature = "metrics"))]
+    pub(crate) metric_inspector:
+        Option<Inspector<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics>>,
+}
+
+/// Callback inspecting converted OTLP messages once they were exported, for tests asserting on
+/// what is exported.
+#[cfg(feature = "testing")]
+pub(crate) struct Inspector<T> {
+    callback: Arc<dyn Fn(&T) + Send + Sync>,
+    /// The messages converted since the exporter last took them, waiting for their export.
+    converted: Arc<std::sync::Mutex<Vec<T>>>,
+}
+
+#[cfg(feature = "testing")]
+impl<T: Clone> Inspector<T> {
+    pub(crate) fn new(callback: impl Fn(&T) + Send + Sync + 'static) -> Self {
+        Inspector {
+            callback: Arc::new(callback),
+            converted: Arc::default(),
+        }
+    }
+
+    /// Keep `messages` until the export sending them completed.
+    fn stage<'a>(&self, messages: impl IntoIterator<Item = &'a T>)
+    where
+        T: 'a,
+    {
+        let mut converted = self.converted.lock().unwrap_or_else(|e| e.into_inner());
+        converted.extend(messages.into_iter().cloned());
+    }
+
+    /// The messages converted since the last call, to inspect once their export completed.
+    pub(crate) fn take(&self) -> Inspection<T> {
+        let mut converted = self.converted.lock().unwrap_or_else(|e| e.into_inner());
+        Inspection {
+            callback: Arc::clone(&self.callback),
+            messages: std::mem::take(&mut *converted),
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<T> Clone for Inspector<T> {
+    fn clone(&self) -> Self {
+        Inspector {
+            callback: Arc::clone(&self.callback),
+            converted: Arc::clone(&self.converted),
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<T> std::fmt::Debug for Inspector<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Inspector")
+    }
+}
+
+/// The messages converted for an export, passed to the inspector if the export succeeded.
+#[cfg(feature = "testing")]
+pub(crate) struct Inspection<T> {
+    callback: Arc<dyn Fn(&T) + Send + Sync>,
+    messages: Vec<T>,
+}
+
+#[cfg(feature = "testing")]
+impl<T> Inspection<T> {
+    pub(crate) fn finish(self, exported: bool) {
+        if exported {
+            self.messages
+                .iter()
+                .for_each(|message| (self.callback)(message));
+        }
+    }
+}
+
+/// Precision of the exported timestamps, for backends which don't use sub-millisecond
+/// resolution.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TimestampPrecision {
+    /// Export the timestamps with their full nanosecond precision.
+    #[default]
+    Nanoseconds,
+    /// Truncate the timestamps to whole microseconds.
+    Microseconds,
+    /// Truncate the timestamps to whole milliseconds.
+    Milliseconds,
+}
+
+impl TimestampPrecision {
+    /// Truncate a timestamp in nanoseconds since the epoch to the precision. Unset timestamps
+    /// stay unset.
+    #[allow(dead_code)]
+    pub(crate) fn truncate(self, nanos: u64) -> u64 {
+        let unit = match self {
+            TimestampPrecision::Nanoseconds => return nanos,
+            TimestampPrecision::Microseconds => 1_000,
+            TimestampPrecision::Milliseconds => 1_000_000,
+        };
+        nanos - nanos % unit
+    }
+}
+
+/// How the span exporter handles spans whose end time is before their start time, which
+/// happens when the clock is adjusted while a span is recorded.
+#[cfg(feature = "trace")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SpanDurationPolicy {
+    /// Set the end time to the start time, exporting the span with a zero duration.
+    #[default]
+    Clamp,
+    /// Drop the span.
+    Drop,
+}
+
+/// How the metric exporter handles metrics of the same resource sharing a name but not a type,
+/// e.g. a counter and a gauge both named `requests` in different meters. Backends usually
+/// reject such conflicting streams.
+///
+/// A warning naming the scopes of both metrics is logged for every conflict, whatever the
+/// policy. The metric seen first is always exported unchanged.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MetricConflictPolicy {
+    /// Export both metrics.
+    #[default]
+    Keep,
+    /// Drop the conflicting metric.
+    Drop,
+    /// Append the type of the conflicting metric to its name, e.g. `requests.gauge`.
+    Rename,
+}
+
+/// The start time exported for the data points of cumulative sums and histograms, which backends
+/// use to compute rates. Gauges and delta data points are left as they are.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MetricStartTimeStrategy {
+    /// Keep the start times of the SDK, the time the aggregation of each stream was created.
+    #[default]
+    Sdk,
+    /// Start all streams at the start of the process, approximated by the time the first
+    /// metric exporter of the process was built.
+    ProcessStart,
+    /// Start each stream at the time of the collection it was first exported in, and keep that
+    /// start time for the following collections. Streams the SDK restarts, or missing from a
+    /// collection, are observed anew.
+    FirstObservation,
+}
+
+/// Function remapping trace ids before they leave the process.
+#[cfg(feature = "trace")]
+#[derive(Clone)]
+#[allow(dead_code)]
+pub(crate) struct TraceIdMapper(pub(crate) Arc<dyn Fn(TraceId) -> TraceId + Send + Sync>);
+
+#[cfg(feature = "trace")]
+impl fmt::Debug for TraceIdMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TraceIdMapper")
+    }
+}
+
+/// Filters the exported telemetry by the name of its instrumentation scope.
+#[derive(Debug, Clone)]
+pub(crate) struct ScopeNamePrefixFilter {
+    pub(crate) allow: Vec<String>,
+    pub(crate) deny: Vec<String>,
+}
+
+impl ScopeNamePrefixFilter {
+    /// Whether the telemetry of the scope named `name` is exported. Denied prefixes take
+    /// precedence over allowed ones, and without allowed prefixes every scope is allowed.
+    #[allow(dead_code)]
+    pub(crate) fn admits(&self, name: &str) -> bool {
+        let matches = |prefixes: &[String]| {
+            prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+        };
+        !matches(&self.deny) && (self.allow.is_empty() || matches(&self.allow))
+    }
+}
+
+/// Description and unit applied to a metric during conversion.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+pub(crate) struct MetricMetadataOverride {
+    pub(crate) description: String,
+    pub(crate) unit: String,
+    /// Whether non-empty values of the metric are replaced as well.
+    pub(crate) force: bool,
+}
+
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+impl ConversionConfig {
+    /// Convert `metrics` and apply the configured metric options.
+    pub(crate) fn metrics_request(
+        &self,
+        metrics: &opentelemetry_sdk::metrics::data::ResourceMetrics,
+    ) -> ExportMetricsServiceRequest {
+        let mut request = ExportMetricsServiceRequest::from(metrics);
+        if let Some(filter) = &self.scope_name_prefix_filter {
+            request.resource_metrics.retain_mut(|resource_metrics| {
+                resource_metrics.scope_metrics.retain(|scope_metrics| {
+                    filter.admits(scope_metrics.scope.as_ref().map_or("", |s| &s.name))
+                });
+                !resource_metrics.scope_metrics.is_empty()
+            });
+        }
+        if !self.metric_metadata_overrides.is_empty() {
+            apply_metric_metadata_overrides(&mut request, &self.metric_metadata_overrides);
+        }
+        resolve_metric_conflicts(&mut request, self.metric_conflict_policy);
+        if let Some(prefix) = &self.metric_name_prefix {
+            prefix_metric_names(&mut request, prefix);
+        }
+        if self.suppress_zero_data_points {
+            drop_zero_data_points(&mut request);
+        }
+        if self.has_attribute_options() {
+            self.apply_data_point_attribute_options(&mut request);
+        }
+        if self.timestamp_precision != TimestampPrecision::Nanoseconds {
+            truncate_data_point_timestamps(&mut request, self.timestamp_precision);
+        }
+        #[cfg(feature = "testing")]
+        if let Some(inspector) = &self.metric_inspector {
+            inspector.stage(&request.resource_metrics);
+        }
+        request
+    }
+
+    /// The accumulator keeping the metrics of failed exports, if enabled.
+    #[cfg(any(
+        feature = "http-proto",
+        feature = "http-json",
+        feature = "grpc-tonic",
+        feature = "tcp"
+    ))]
+    pub(crate) fn metric_accumulator(&self) -> Option<MetricAccumulator> {
+        self.metric_accumulation_on_failure
+            .then(MetricAccumulator::default)
+    }
+
+    /// The start times of the exported streams, unless the SDK's start times are kept.
+    pub(crate) fn metric_start_times(&self) -> Option<MetricStartTimes> {
+        MetricStartTimes::new(self.metric_start_time_strategy)
+    }
+}
+
+/// Truncate the timestamps of metric data points and their exemplars to `precision`.
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+fn truncate_data_point_timestamps(
+    request: &mut ExportMetricsServiceRequest,
+    precision: TimestampPrecision,
+) {
+    use opentelemetry_proto::tonic::metrics::v1::{metric::Data, Exemplar};
+
+    let truncate = |start: &mut u64, time: &mut u64, exemplars: &mut [Exemplar]| {
+        *start = precision.truncate(*start);
+        *time = precision.truncate(*time);
+        for exemplar in exemplars {
+            exemplar.time_unix_nano = precision.truncate(exemplar.time_unix_nano);
+        }
+    };
+
+    let metrics = request
+        .resource_metrics
+        .iter_mut()
+        .flat_map(|resource_metrics| resource_metrics.scope_metrics.iter_mut())
+        .flat_map(|scope_metrics| scope_metrics.metrics.iter_mut());
+    for metric in metrics {
+        match &mut metric.data {
+            Some(Data::Gauge(gauge)) => gauge.data_points.iter_mut().for_each(|p| {
+                truncate(
+                    &mut p.start_time_unix_nano,
+                    &mut p.time_unix_nano,
+                    &mut p.exemplars,
+                )
+            }),
+            Some(Data::Sum(sum)) => sum.data_points.iter_mut().for_each(|p| {
+                truncate(
+                    &mut p.start_time_unix_nano,
+                    &mut p.time_unix_nano,
+                    &mut p.exemplars,
+                )
+            }),
+            Some(Data::Histogram(histogram)) => histogram.data_points.iter_mut().for_each(|p| {
+                truncate(
+                    &mut p.start_time_unix_nano,
+                    &mut p.time_unix_nano,
+                    &mut p.exemplars,
+                )
+            }),
+            Some(Data::ExponentialHistogram(histogram)) => {
+                histogram.data_points.iter_mut().for_each(|p| {
+                    truncate(
+                        &mut p.start_time_unix_nano,
+                        &mut p.time_unix_nano,
+                        &mut p.exemplars,
+                    )
+                })
+            }
+            Some(Data::Summary(summary)) => summary.data_points.iter_mut().for_each(|p| {
+                truncate(&mut p.start_time_unix_nano, &mut p.time_unix_nano, &mut [])
+            }),
+            None => {}
+        }
+    }
+}
+
+/// Prepend `prefix` to the names of the metrics not starting with it yet.
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+fn prefix_metric_names(request: &mut ExportMetricsServiceRequest, prefix: &str) {
+    let metrics = request
+        .resource_metrics
+        .iter_mut()
+        .flat_map(|resource_metrics| resource_metrics.scope_metrics.iter_mut())
+        .flat_map(|scope_metrics| scope_metrics.metrics.iter_mut());
+    for metric in metrics {
+        if !metric.name.starts_with(prefix) {
+            metric.name.insert_str(0, prefix);
+        }
+    }
+}
+
+/// Remove the sum and gauge data points with a value of zero, and the metrics left without any.
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+fn drop_zero_data_points(request: &mut ExportMetricsServiceRequest) {
+    use opentelemetry_proto::tonic::metrics::v1::{
+        metric::Data, number_data_point, NumberDataPoint,
+    };
+
+    let is_zero = |point: &NumberDataPoint| match point.value {
+        Some(number_data_point::Value::AsDouble(value)) => value == 0.0,
+        Some(number_data_point::Value::AsInt(value)) => value == 0,
+        None => false,
+    };
+
+    let scope_metrics = request
+        .resource_metrics
+        .iter_mut()
+        .flat_map(|resource_metrics| resource_metrics.scope_metrics.iter_mut());
+    for scope_metrics in scope_metrics {
+        scope_metrics.metrics.retain_mut(|metric| {
+            let data_points = match &mut metric.data {
+                Some(Data::Gauge(gauge)) => &mut gauge.data_points,
+                Some(Data::Sum(sum)) => &mut sum.data_points,
+                _ => return true,
+            };
+            data_points.retain(|point| !is_zero(point));
+            !data_points.is_empty()
+        });
+    }
+}
+
+/// Fill in the description and unit of the metrics with an override. Existing non-empty values
+/// are only replaced by forced overrides, and empty override values are never applied.
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+fn apply_metric_metadata_overrides(
+    request: &mut ExportMetricsServiceRequest,
+    overrides: &HashMap<String, MetricMetadataOverride>,
+) {
+    let metrics = request
+        .resource_metrics
+        .iter_mut()
+        .flat_map(|resource_metrics| resource_metrics.scope_metrics.iter_mut())
+        .flat_map(|scope_metrics| scope_metrics.metrics.iter_mut());
+
+    for metric in metrics {
+        let Some(metadata) = overrides.get(&metric.name) else {
+            continue;
+        };
+        if !metadata.description.is_empty() && (metadata.force || metric.description.is_empty()) {
+            metric.description.clone_from(&metadata.description);
+        }
+        if !metadata.unit.is_empty() && (metadata.force || metric.unit.is_empty()) {
+            metric.unit.clone_from(&metadata.unit);
+        }
+    }
+}
+
+/// Detect metrics of the same resource sharing a name but not a type, and handle the metrics
+/// seen last according to `policy`. Metrics of the same type in different scopes don't
+/// conflict.
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+fn resolve_metric_conflicts(
+    request: &mut ExportMetricsServiceRequest,
+    policy: MetricConflictPolicy,
+) {
+    use opentelemetry_proto::tonic::metrics::v1::metric::Data;
+
+    fn metric_type(data: &Option<Data>) -> &'static str {
+        match data {
+            Some(Data::Gauge(_)) => "gauge",
+            Some(Data::Sum(_)) => "sum",
+            Some(Data::Histogram(_)) => "histogram",
+            Some(Data::ExponentialHistogram(_)) => "exponential_histogram",
+            Some(Data::Summary(_)) => "summary",
+            None => "empty",
+        }
+    }
+
+    for resource_metrics in request.resource_metrics.iter_mut() {
+        // type and scope of the first metric seen with each name
+        let mut seen: HashMap<String, (&'static str, String)> = HashMap::new();
+        for scope_metrics in resource_metrics.scope_metrics.iter_mut() {
+            let scope = scope_metrics
+                .scope
+                .as_ref()
+                .map(|scope| scope.name.clone())
+                .unwrap_or_default();
+            scope_metrics.metrics.retain_mut(|metric| {
+                let kind = metric_type(&metric.data);
+                let (first_kind, first_scope) = match seen.get(&metric.name) {
+                    None => {
+                        seen.insert(metric.name.clone(), (kind, scope.clone()));
+                        return true;
+                    }
+                    Some((first_kind, _)) if *first_kind == kind => return true,
+                    Some(first) => first.clone(),
+                };
+
+                opentelemetry::otel_warn!(
+                    name: "MetricExporter.ConflictingMetric",
+                    metric_name = metric.name.clone(),
+                    first_scope = first_scope,
+                    first_type = first_kind,
+                    conflicting_scope = scope.clone(),
+                    conflicting_type = kind,
+                    policy = format!("{:?}", policy),
+                );
+                match policy {
+                    MetricConflictPolicy::Keep => true,
+                    MetricConflictPolicy::Drop => false,
+                    MetricConflictPolicy::Rename => {
+                        metric.name = format!("{}.{}", metric.name, kind);
+                        seen.entry(metric.name.clone())
+                            .or_insert((kind, scope.clone()));
+                        true
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+impl ConversionConfig {
+    /// Group `batch` by resource and scope and apply the configured span options.
+    pub(crate) fn resource_spans(
+        &self,
+        batch: Vec<opentelemetry_sdk::export::trace::SpanData>,
+        resource: &opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    ) -> Vec<ResourceSpans> {
+        let batch = validate_span_durations(batch, self.span_duration_policy);
+        let mut resource_spans =
+            opentelemetry_proto::transform::trace::tonic::group_spans_by_resource_and_scope(
+                batch, resource,
+            );
+
+        if let Some(mapper) = &self.trace_id_mapper {
+            remap_trace_ids(&mut resource_spans, mapper);
+        }
+        if self.annotate_span_depth {
+            annotate_span_depths(&mut resource_spans);
+        }
+        // after the depths, which count the spans of filtered scopes as well
+        if let Some(filter) = &self.scope_name_prefix_filter {
+            resource_spans.retain_mut(|resource_spans| {
+                resource_spans.scope_spans.retain(|scope_spans| {
+                    filter.admits(scope_spans.scope.as_ref().map_or("", |s| &s.name))
+                });
+                !resource_spans.scope_spans.is_empty()
+            });
+        }
+        // before the attribute options, which may drop the attribute
+        if let Some(key) = &self.status_from_attribute {
+            derive_span_statuses(&mut resource_spans, key);
+        }
+        if self.has_attribute_options() {
+            self.apply_span_attribute_options(&mut resource_spans);
+        }
+        if self.timestamp_precision != TimestampPrecision::Nanoseconds {
+            truncate_span_timestamps(&mut resource_spans, self.timestamp_precision);
+        }
+
+        let resource_spans = match self.max_spans_per_resource {
+            Some(max_spans) => split_resource_spans(resource_spans, max_spans),
+            None => resource_spans,
+        };
+        #[cfg(feature = "testing")]
+        if let Some(inspector) = &self.span_inspector {
+            inspector.stage(&resource_spans);
+        }
+        resource_spans
+    }
+
+    /// Convert `batch` like [`resource_spans`](Self::resource_spans) into the export requests
+    /// to send, holding at most the configured number of `ResourceSpans` entries each.
+    pub(crate) fn trace_requests(
+        &self,
+        batch: Vec<opentelemetry_sdk::export::trace::SpanData>,
+        resource: &opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    ) -> Vec<ExportTraceServiceRequest> {
+        let resource_spans = self.resource_spans(batch, resource);
+        match self.max_resources_per_request {
+            Some(max_resources) => split_trace_requests(resource_spans, max_resources),
+            None => vec![ExportTraceServiceRequest { resource_spans }],
+        }
+    }
+
+    /// Apply the attribute options to spans, their events and links, and to their resources and
+    /// scopes.
+    fn apply_span_attribute_options(&self, resource_spans: &mut [ResourceSpans]) {
+        for resource_spans in resource_spans {
+            self.apply_resource_options(&mut resource_spans.resource);
+            for scope_spans in &mut resource_spans.scope_spans {
+                if let Some(scope) = &mut scope_spans.scope {
+                    scope.dropped_attributes_count +=
+                        self.apply_scope_attribute_options(&mut scope.attributes);
+                }
+                for span in &mut scope_spans.spans {
+                    span.dropped_attributes_count +=
+                        self.apply_attribute_options(&mut span.attributes);
+                    for event in &mut span.events {
+                        let mut dropped = self.apply_attribute_options(&mut event.attributes);
+                        if let Some(max_attributes) = self.max_attributes_per_event {
+                            dropped += truncate_attributes(&mut event.attributes, max_attributes);
+                        }
+                        event.dropped_attributes_count += dropped;
+                    }
+                    for link in &mut span.links {
+                        link.dropped_attributes_count +=
+                            self.apply_attribute_options(&mut link.attributes);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Apply `policy` to the spans of `batch` ending before they start.
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+fn validate_span_durations(
+    mut batch: Vec<opentelemetry_sdk::export::trace::SpanData>,
+    policy: SpanDurationPolicy,
+) -> Vec<opentelemetry_sdk::export::trace::SpanData> {
+    let invalid = batch
+        .iter()
+        .filter(|span| span.end_time < span.start_time)
+        .count();
+    if invalid == 0 {
+        return batch;
+    }
+
+    opentelemetry::otel_warn!(
+        name: "SpanExporter.NegativeSpanDuration",
+        invalid_spans = invalid as i64,
+        policy = format!("{:?}", policy),
+    );
+    match policy {
+        SpanDurationPolicy::Clamp => {
+            for span in batch.iter_mut() {
+                if span.end_time < span.start_time {
+                    span.end_time = span.start_time;
+                }
+            }
+        }
+        SpanDurationPolicy::Drop => batch.retain(|span| span.end_time >= span.start_time),
+    }
+    batch
+}
+
+/// Add the [`SPAN_DEPTH_ATTRIBUTE`] to all spans: the number of ancestors of the span within the
+/// batch, so spans whose parent isn't part of the batch have a depth of zero.
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+fn annotate_span_depths(resource_spans: &mut [ResourceSpans]) {
+    use std::collections::HashMap;
+
+    // the parent of every span of the batch, keyed by trace and span id
+    let parents: HashMap<(Vec<u8>, Vec<u8>), Vec<u8>> = resource_spans
+        .iter()
+        .flat_map(|resource_spans| resource_spans.scope_spans.iter())
+        .flat_map(|scope_spans| scope_spans.spans.iter())
+        .map(|span| {
+            (
+                (span.trace_id.clone(), span.span_id.clone()),
+                span.parent_span_id.clone(),
+            )
+        })
+        .collect();
+
+    let spans = resource_spans
+        .iter_mut()
+        .flat_map(|resource_spans| resource_spans.scope_spans.iter_mut())
+        .flat_map(|scope_spans| scope_spans.spans.iter_mut());
+    for span in spans {
+        let mut depth = 0;
+        let mut parent = &span.parent_span_id;
+        // bounded by the batch size in case span ids form a cycle
+        while depth < parents.len() {
+            match parents.get(&(span.trace_id.clone(), parent.clone())) {
+                Some(grandparent) => {
+                    depth += 1;
+                    parent = grandparent;
+                }
+                None => break,
+            }
+        }
+        span.attributes.push(KeyValue {
+            key: SPAN_DEPTH_ATTRIBUTE.to_string(),
+            value: Some(AnyValue {
+                value: Some(Value::IntValue(depth as i64)),
+            }),
+        });
+    }
+}
+
+/// Set the status of the spans without one to an error if their attribute `key` flags one: a
+/// `true` boolean, or a string reading `true` in any case. Other values are ignored.
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+fn derive_span_statuses(resource_spans: &mut [ResourceSpans], key: &str) {
+    use opentelemetry_proto::tonic::trace::v1::{status::StatusCode, Status};
+
+    let spans = resource_spans
+        .iter_mut()
+        .flat_map(|resource_spans| resource_spans.scope_spans.iter_mut())
+        .flat_map(|scope_spans| scope_spans.spans.iter_mut());
+    for span in spans {
+        if span
+            .status
+            .as_ref()
+            .is_some_and(|status| status.code != StatusCode::Unset as i32)
+        {
+            continue;
+        }
+        let value = span
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key == key)
+            .and_then(|attribute| attribute.value.as_ref())
+            .and_then(|value| value.value.as_ref());
+        let is_error = match value {
+            Some(Value::BoolValue(value)) => *value,
+            Some(Value::StringValue(value)) => value.eq_ignore_ascii_case("true"),
+            _ => false,
+        };
+        if is_error {
+            span.status = Some(Status {
+                message: String::new(),
+                code: StatusCode::Error as i32,
+            });
+        }
+    }
+}
+
+/// Replace the trace ids of all spans and their links using `mapper`. Every trace id is mapped
+/// once per batch, so spans of the same trace keep sharing a trace id after remapping.
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+fn remap_trace_ids(resource_spans: &mut [ResourceSpans], mapper: &TraceIdMapper) {
+    let mut mapped = std::collections::HashMap::<[u8; 16], [u8; 16]>::new();
+    let mut remap = |trace_id: &mut Vec<u8>| {
+        if let Ok(bytes) = <[u8; 16]>::try_from(trace_id.as_slice()) {
+            let new_id = mapped
+                .entry(bytes)
+                .or_insert_with(|| (mapper.0)(TraceId::from_bytes(bytes)).to_bytes());
+            *trace_id = new_id.to_vec();
+        }
+    };
+
+    let spans = resource_spans
+        .iter_mut()
+        .flat_map(|resource_spans| resource_spans.scope_spans.iter_mut())
+        .flat_map(|scope_spans| scope_spans.spans.iter_mut());
+    for span in spans {
+        remap(&mut span.trace_id);
+        for link in &mut span.links {
+            remap(&mut link.trace_id);
+        }
+    }
+}
+
+/// Truncate the timestamps of spans and their events to `precision`. Start and end time are
+/// truncated alike, so spans never end before they start.
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+fn truncate_span_timestamps(resource_spans: &mut [ResourceSpans], precision: TimestampPrecision) {
+    let spans = resource_spans
+        .iter_mut()
+        .flat_map(|resource_spans| resource_spans.scope_spans.iter_mut())
+        .flat_map(|scope_spans| scope_spans.spans.iter_mut());
+    for span in spans {
+        span.start_time_unix_nano = precision.truncate(span.start_time_unix_nano);
+        span.end_time_unix_nano = precision.truncate(span.end_time_unix_nano);
+        for event in &mut span.events {
+            event.time_unix_nano = precision.truncate(event.time_unix_nano);
+        }
+    }
+}
+
+/// Split `resource_spans` into export requests holding at most `max_resources` entries each,
+/// whether the entries are of distinct resources or of one resource split into several.
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+fn split_trace_requests(
+    mut resource_spans: Vec<ResourceSpans>,
+    max_resources: usize,
+) -> Vec<ExportTraceServiceRequest> {
+    let max_resources = max_resources.max(1);
+    let mut requests = Vec::new();
+    while resource_spans.len() > max_resources {
+        let rest = resource_spans.split_off(max_resources);
+        requests.push(ExportTraceServiceRequest { resource_spans });
+        resource_spans = rest;
+    }
+    requests.push(ExportTraceServiceRequest { resource_spans });
+    requests
+}
+
+/// Split every [`ResourceSpans`] holding more than `max_spans` spans into multiple entries
+/// sharing the same resource. Scopes are split as well when needed, keeping the span order.
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+fn split_resource_spans(
+    resource_spans: Vec<ResourceSpans>,
+    max_spans: usize,
+) -> Vec<ResourceSpans> {
+    use opentelemetry_proto::tonic::trace::v1::ScopeSpans;
+
+    let max_spans = max_spans.max(1);
+    let mut result = Vec::with_capacity(resource_spans.len());
+
+    for resource_spans in resource_spans {
+        let span_count: usize = resource_spans
+            .scope_spans
+            .iter()
+            .map(|scope_spans| scope_spans.spans.len())
+            .sum();
+        if span_count <= max_spans {
+            result.push(resource_spans);
+            continue;
+        }
+
+        let ResourceSpans {
+            resource,
+            scope_spans,
+            schema_url,
+        } = resource_spans;
+        let mut current = Vec::new();
+        let mut current_count = 0;
+
+        for scope_spans in scope_spans {
+            let mut spans = scope_spans.spans.into_iter().peekable();
+            while spans.peek().is_some() {
+                let chunk: Vec<_> = spans.by_ref().take(max_spans - current_count).collect();
+                current_count += chunk.len();
+                current.push(ScopeSpans {
+                    scope: scope_spans.scope.clone(),
+                    spans: chunk,
+                    schema_url: scope_spans.schema_url.clone(),
+                });
+
+                if current_count == max_spans {
+                    result.push(ResourceSpans {
+                        resource: resource.clone(),
+                        scope_spans: std::mem::take(&mut current),
+                        schema_url: schema_url.clone(),
+                    });
+                    current_count = 0;
+                }
+            }
+        }
+
+        if !current.is_empty() {
+            result.push(ResourceSpans {
+                resource,
+                scope_spans: current,
+                schema_url,
+            });
+        }
+    }
+
+    result
+}
+
+#[cfg(feature = "logs")]
+#[cfg(otlp_transport)]
+impl ConversionConfig {
+    /// Group `batch` by resource and scope and apply the configured log options.
+    pub(crate) fn resource_logs(
+        &self,
+        batch: opentelemetry_sdk::export::logs::LogBatch<'_>,
+        resource: &opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    ) -> Vec<opentelemetry_proto::tonic::logs::v1::ResourceLogs> {
+        let mut resource_logs =
+            opentelemetry_proto::transform::logs::tonic::group_logs_by_resource_and_scope(
+                batch, resource,
+            );
+
+        if let Some(filter) = &self.scope_name_prefix_filter {
+            resource_logs.retain_mut(|resource_logs| {
+                resource_logs.scope_logs.retain(|scope_logs| {
+                    filter.admits(scope_logs.scope.as_ref().map_or("", |s| &s.name))
+                });
+                !resource_logs.scope_logs.is_empty()
+            });
+        }
+        if self.has_attribute_options() {
+            for resource_logs in &mut resource_logs {
+                self.apply_resource_options(&mut resource_logs.resource);
+                for scope_logs in &mut resource_logs.scope_logs {
+                    if let Some(scope) = &mut scope_logs.scope {
+                        scope.dropped_attributes_count +=
+                            self.apply_scope_attribute_options(&mut scope.attributes);
+                    }
+                    for log_record in &mut scope_logs.log_records {
+                        log_record.dropped_attributes_count +=
+                            self.apply_attribute_options(&mut log_record.attributes);
+                    }
+                }
+            }
+        }
+        if let Some(max_bytes) = self.max_log_body_bytes {
+            let bodies = resource_logs
+                .iter_mut()
+                .flat_map(|resource_logs| resource_logs.scope_logs.iter_mut())
+                .flat_map(|scope_logs| scope_logs.log_records.iter_mut())
+                .filter_map(|log_record| log_record.body.as_mut());
+            for body in bodies {
+                if let Some(Value::StringValue(body)) = &mut body.value {
+                    truncate_log_body(body, max_bytes);
+                }
+            }
+        }
+        if self.timestamp_precision != TimestampPrecision::Nanoseconds {
+            let log_records = resource_logs
+                .iter_mut()
+                .flat_map(|resource_logs| resource_logs.scope_logs.iter_mut())
+                .flat_map(|scope_logs| scope_logs.log_records.iter_mut());
+            for log_record in log_records {
+                log_record.time_unix_nano =
+                    self.timestamp_precision.truncate(log_record.time_unix_nano);
+                log_record.observed_time_unix_nano = self
+                    .timestamp_precision
+                    .truncate(log_record.observed_time_unix_nano);
+            }
+        }
+        #[cfg(feature = "testing")]
+        if let Some(inspector) = &self.log_inspector {
+            inspector.stage(&resource_logs);
+        }
+        resource_logs
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+impl ConversionConfig {
+    /// Apply the attribute options to metric data points, and to their resources and scopes.
+    ///
+    /// Unlike other records, data points have no count of dropped attributes. Their attributes
+    /// also identify their series, so data points which only differ in truncated attributes are
+    /// exported as the same series.
+    fn apply_data_point_attribute_options(&self, request: &mut ExportMetricsServiceRequest) {
+        use opentelemetry_proto::tonic::metrics::v1::metric::Data;
+
+        for resource_metrics in &mut request.resource_metrics {
+            self.apply_resource_options(&mut resource_metrics.resource);
+            for scope_metrics in &mut resource_metrics.scope_metrics {
+                if let Some(scope) = &mut scope_metrics.scope {
+                    scope.dropped_attributes_count +=
+                        self.apply_scope_attribute_options(&mut scope.attributes);
+                }
+                for metric in &mut scope_metrics.metrics {
+                    match &mut metric.data {
+                        Some(Data::Gauge(gauge)) => self
+                            .apply_data_point_options(&mut gauge.data_points, |p| {
+                                &mut p.attributes
+                            }),
+                        Some(Data::Sum(sum)) => self
+                            .apply_data_point_options(&mut sum.data_points, |p| &mut p.attributes),
+                        Some(Data::Histogram(histogram)) => self
+                            .apply_data_point_options(&mut histogram.data_points, |p| {
+                                &mut p.attributes
+                            }),
+                        Some(Data::ExponentialHistogram(histogram)) => self
+                            .apply_data_point_options(&mut histogram.data_points, |p| {
+                                &mut p.attributes
+                            }),
+                        Some(Data::Summary(summary)) => self
+                            .apply_data_point_options(&mut summary.data_points, |p| {
+                                &mut p.attributes
+                            }),
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply the attribute options to the attributes of `points`.
+    fn apply_data_point_options<P>(
+        &self,
+        points: &mut [P],
+        attributes: fn(&mut P) -> &mut Vec<KeyValue>,
+    ) {
+        for point in points {
+            self.apply_attribute_options(attributes(point));
+        }
+    }
+}
+
+#[cfg(otlp_transport)]
+impl ConversionConfig {
+    /// Whether any option applies to the attributes of the exported records.
+    fn has_attribute_options(&self) -> bool {
+        self.max_attributes_per_record.is_some()
+            || self.max_scope_attributes.is_some()
+            || self.max_attributes_per_event.is_some()
+            || self.max_array_attribute_len.is_some()
+            || self.stringify_attributes
+            || self.drops_attribute_keys()
+            || self.sort_resource_attributes
+            || self.require_resource
+            || self.fallback_resource.is_some()
+            || self.service_namespace.is_some()
+    }
+
+    /// Whether attributes are removed by the pattern of their key.
+    fn drops_attribute_keys(&self) -> bool {
+        #[cfg(feature = "attribute-key-patterns")]
+        return !self.attribute_key_drop_patterns.is_empty();
+        #[cfg(not(feature = "attribute-key-patterns"))]
+        false
+    }
+
+    /// Remove the attributes whose key matches any of the drop patterns, returning how many
+    /// were removed.
+    #[cfg_attr(
+        not(feature = "attribute-key-patterns"),
+        allow(unused_variables, clippy::ptr_arg)
+    )]
+    fn drop_matching_keys(&self, attributes: &mut Vec<KeyValue>) -> u32 {
+        #[cfg(feature = "attribute-key-patterns")]
+        if self.drops_attribute_keys() {
+            let patterns = &self.attribute_key_drop_patterns;
+            let before = attributes.len();
+            attributes.retain(|kv| !patterns.iter().any(|pattern| pattern.is_match(&kv.key)));
+            return (before - attributes.len()) as u32;
+        }
+        0
+    }
+
+    /// Apply the attribute options to the attributes of a record, returning how many attributes
+    /// were removed.
+    fn apply_attribute_options(&self, attributes: &mut Vec<KeyValue>) -> u32 {
+        let mut dropped = self.drop_matching_keys(attributes);
+        if let Some(max_attributes) = self.max_attributes_per_record {
+            dropped += truncate_attributes(attributes, max_attributes);
+        }
+        self.apply_value_options(attributes);
+        dropped
+    }
+
+    /// Replace a resource holding nothing but the defaults of the SDK with the fallback resource,
+    /// apply the attribute options to it, inject the default `service.name` if the resource is
+    /// required but has no attributes left and the configured `service.namespace` if it has none,
+    /// then sort its attributes by key if configured. The sort is stable, so attributes sharing a
+    /// key keep their order.
+    fn apply_resource_options(
+        &self,
+        resource: &mut Option<opentelemetry_proto::tonic::resource::v1::Resource>,
+    ) {
+        let resource = match resource {
+            Some(resource) => resource,
+            None if self.require_resource
+                || self.fallback_resource.is_some()
+                || self.service_namespace.is_some() =>
+            {
+                resource.insert(Default::default())
+            }
+            None => return,
+        };
+        if let Some(fallback) = &self.fallback_resource {
+            if is_undetected(&resource.attributes) {
+                let fallback =
+                    Attributes::from(fallback.iter().map(|(key, value)| {
+                        opentelemetry::KeyValue::new(key.clone(), value.clone())
+                    }))
+                    .0;
+                // the attributes describing the SDK stay accurate, unless the fallback sets them
+                let sdk_attributes = resource.attributes.drain(..).filter(|attribute| {
+                    attribute.key.starts_with(TELEMETRY_SDK_PREFIX)
+                        && !fallback.iter().any(|set| set.key == attribute.key)
+                });
+                resource.attributes = fallback.iter().cloned().chain(sdk_attributes).collect();
+            }
+        }
+        resource.dropped_attributes_count +=
+            self.apply_resource_attribute_options(&mut resource.attributes);
+        if self.require_resource && resource.attributes.is_empty() {
+            resource.attributes.push(KeyValue {
+                key: "service.name".to_string(),
+                value: Some(AnyValue {
+                    value: Some(Value::StringValue(DEFAULT_SERVICE_NAME.to_string())),
+                }),
+            });
+        }
+        if let Some(namespace) = &self.service_namespace {
+            if !resource
+                .attributes
+                .iter()
+                .any(|attribute| attribute.key == SERVICE_NAMESPACE)
+            {
+                resource.attributes.push(KeyValue {
+                    key: SERVICE_NAMESPACE.to_string(),
+                    value: Some(AnyValue {
+                        value: Some(Value::StringValue(namespace.clone())),
+                    }),
+                });
+            }
+        }
+        if self.sort_resource_attributes {
+            resource.attributes.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+    }
+
+    /// Apply the attribute options to the attributes of a resource or scope, which aren't capped,
+    /// returning how many attributes were removed.
+    fn apply_resource_attribute_options(&self, attributes: &mut Vec<KeyValue>) -> u32 {
+        let dropped = self.drop_matching_keys(attributes);
+        self.apply_value_options(attributes);
+        dropped
+    }
+
+    /// Apply the attribute options to the attributes of a scope, which are capped separately from
+    /// records, returning how many attributes were removed.
+    fn apply_scope_attribute_options(&self, attributes: &mut Vec<KeyValue>) -> u32 {
+        let mut dropped = self.drop_matching_keys(attributes);
+        if let Some(max_attributes) = self.max_scope_attributes {
+            dropped += truncate_attributes(attributes, max_attributes);
+        }
+        self.apply_value_options(attributes);
+        dropped
+    }
+
+    /// Apply the options to the attribute values. Arrays are capped before they are stringified.
+    fn apply_value_options(&self, attributes: &mut [KeyValue]) {
+        if let Some(max_len) = self.max_array_attribute_len {
+            for attribute in attributes.iter_mut() {
+                truncate_array_value(attribute, max_len);
+            }
+        }
+        if self.stringify_attributes {
+            for value in attributes.iter_mut().filter_map(|kv| kv.value.as_mut()) {
+                value.value = value.value.take().map(|v| Value::StringValue(stringify(v)));
+            }
+        }
+    }
+}
+
+/// Whether the resource `attributes` are only those the SDK sets without any resource detected,
+/// i.e. the `telemetry.sdk.*` attributes and the `unknown_service` default of `service.name`.
+#[cfg(otlp_transport)]
+fn is_undetected(attributes: &[KeyValue]) -> bool {
+    attributes.iter().all(|attribute| {
+        attribute.key.starts_with(TELEMETRY_SDK_PREFIX)
+            || (attribute.key == "service.name"
+                && matches!(
+                    attribute.value.as_ref().and_then(|value| value.value.as_ref()),
+                    Some(Value::StringValue(name)) if name.starts_with(DEFAULT_SERVICE_NAME)
+                ))
+    })
+}
+
+/// Keep the first `max_attributes` attributes, returning how many were removed.
+#[cfg(otlp_transport)]
+fn truncate_attributes(attributes: &mut Vec<KeyValue>, max_attributes: usize) -> u32 {
+    let dropped = attributes.len().saturating_sub(max_attributes);
+    attributes.truncate(max_attributes);
+    dropped as u32
+}
+
+/// Keep the first `max_len` elements of an array attribute value. The OTLP attributes have no
+/// count of dropped elements, so the truncation is only logged.
+#[cfg(otlp_transport)]
+fn truncate_array_value(attribute: &mut KeyValue, max_len: usize) {
+    if let Some(AnyValue {
+        value: Some(Value::ArrayValue(array)),
+    }) = &mut attribute.value
+    {
+        if array.values.len() > max_len {
+            opentelemetry::otel_debug!(
+                name: "Exporter.ArrayAttributeTruncated",
+                key = attribute.key.clone(),
+                length = array.values.len() as u64,
+                max_length = max_len as u64,
+            );
+            array.values.truncate(max_len);
+        }
+    }
+}
+
+/// The string representation of an attribute value. Arrays and maps are rendered like JSON,
+/// bytes as lowercase hex.
+#[cfg(otlp_transport)]
+fn stringify(value: Value) -> String {
+    // strings nested in arrays and maps are quoted
+    let nested = |value: AnyValue| match value.value {
+        Some(Value::StringValue(s)) => format!("{s:?}"),
+        Some(value) => stringify(value),
+        None => "null".to_string(),
+    };
+    match value {
+        Value::StringValue(s) => s,
+        Value::BoolValue(b) => b.to_string(),
+        Value::IntValue(i) => i.to_string(),
+        Value::DoubleValue(d) => d.to_string(),
+        Value::BytesValue(bytes) => bytes.iter().map(|b| format!("{b:02x}")).collect(),
+        Value::ArrayValue(array) => {
+            let values: Vec<_> = array.values.into_iter().map(nested).collect();
+            format!("[{}]", values.join(","))
+        }
+        Value::KvlistValue(list) => {
+            let entries: Vec<_> = list
+                .values
+                .into_iter()
+                .map(|kv| format!("{:?}:{}", kv.key, nested(kv.value.unwrap_or_default())))
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+    }
+}
+
+/// Appended to the log bodies cut at the maximum size.
+#[cfg(feature = "logs")]
+#[cfg(otlp_transport)]
+const LOG_BODY_TRUNCATION_MARKER: &str = "...[truncated]";
+
+/// Cut `body` to `max_bytes` at a character boundary, including the truncation marker, if it
+/// is longer. Limits below the length of the marker still fit the marker.
+#[cfg(feature = "logs")]
+#[cfg(otlp_transport)]
+fn truncate_log_body(body: &mut String, max_bytes: usize) {
+    if body.len() <= max_bytes {
+        return;
+    }
+    let mut end = max_bytes.saturating_sub(LOG_BODY_TRUNCATION_MARKER.len());
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    body.truncate(end);
+    body.push_str(LOG_BODY_TRUNCATION_MARKER);
+}
+
+#[cfg(test)]
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+mod metrics_tests {
+    use std::borrow::Cow;
+
+    use opentelemetry::InstrumentationScope;
+    use opentelemetry_sdk::metrics::data::{
+        Gauge, GaugeDataPoint, Metric, ResourceMetrics, ScopeMetrics, Sum, SumDataPoint,
+    };
+    use opentelemetry_sdk::metrics::Temporality;
+    use opentelemetry_sdk::Resource;
+
+    use super::{ConversionConfig, MetricConflictPolicy, MetricMetadataOverride};
+
+    fn resource_metrics(name: &'static str, description: &'static str) -> ResourceMetrics {
+        ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: vec![ScopeMetrics {
+                scope: InstrumentationScope::builder("test").build(),
+                metrics: vec![Metric {
+                    name: Cow::Borrowed(name),
+                    description: Cow::Borrowed(description),
+                    unit: Cow::Borrowed(""),
+                    data: Box::new(Gauge {
+                        data_points: vec![GaugeDataPoint {
+                            attributes: vec![],
+                            start_time: None,
+                            time: std::time::SystemTime::now(),
+                            value: 1u64,
+                            exemplars: vec![],
+                        }],
+                    }),
+                }],
+            }],
+        }
+    }
+
+    fn conversion(force: bool) -> ConversionConfig {
+        let mut conversion = ConversionConfig::default();
+        conversion.metric_metadata_overrides.insert(
+            "queue.size".to_string(),
+            MetricMetadataOverride {
+                description: "Items in the queue".to_string(),
+                unit: "{item}".to_string(),
+                force,
+            },
+        );
+        conversion
+    }
+
+    #[test]
+    fn test_metric_metadata_overrides_fill_missing_values() {
+        let request =
+            conversion(false).metrics_request(&resource_metrics("queue.size", "Queue size"));
+        let metric = &request.resource_metrics[0].scope_metrics[0].metrics[0];
+
+        assert_eq!(metric.unit, "{item}");
+        // existing values are kept
+        assert_eq!(metric.description, "Queue size");
+
+        // other metrics are not touched
+        let request = conversion(false).metrics_request(&resource_metrics("other", ""));
+        let metric = &request.resource_metrics[0].scope_metrics[0].metrics[0];
+        assert_eq!(metric.unit, "");
+        assert_eq!(metric.description, "");
+    }
+
+    #[test]
+    fn test_forced_metric_metadata_overrides_replace_values() {
+        let request =
+            conversion(true).metrics_request(&resource_metrics("queue.size", "Queue size"));
+        let metric = &request.resource_metrics[0].scope_metrics[0].metrics[0];
+
+        assert_eq!(metric.unit, "{item}");
+        assert_eq!(metric.description, "Items in the queue");
+    }
+
+    /// A `requests` counter in the `http` scope and a `requests` gauge in the `pool` scope.
+    fn conflicting_metrics() -> ResourceMetrics {
+        let now = std::time::SystemTime::now();
+        let mut metrics = resource_metrics("requests", "");
+        metrics.scope_metrics[0].scope = InstrumentationScope::builder("http").build();
+        metrics.scope_metrics[0].metrics[0].data = Box::new(Sum {
+            data_points: vec![SumDataPoint {
+                attributes: vec![],
+                start_time: now,
+                time: now,
+                value: 3u64,
+                exemplars: vec![],
+            }],
+            temporality: Temporality::Cumulative,
+            is_monotonic: true,
+        });
+        let mut gauge = resource_metrics("requests", "").scope_metrics.remove(0);
+        gauge.scope = InstrumentationScope::builder("pool").build();
+        metrics.scope_metrics.push(gauge);
+        metrics
+    }
+
+    fn exported_names(
+        policy: MetricConflictPolicy,
+        metrics: &ResourceMetrics,
+    ) -> Vec<(String, String)> {
+        let conversion = ConversionConfig {
+            metric_conflict_policy: policy,
+            ..Default::default()
+        };
+        let request = conversion.metrics_request(metrics);
+        request.resource_metrics[0]
+            .scope_metrics
+            .iter()
+            .flat_map(|scope_metrics| {
+                let scope = scope_metrics.scope.as_ref().unwrap().name.clone();
+                scope_metrics
+                    .metrics
+                    .iter()
+                    .map(move |metric| (scope.clone(), metric.name.clone()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_conflicting_metrics_are_resolved_by_policy() {
+        let metrics = conflicting_metrics();
+        let expected = |names: &[(&str, &str)]| {
+            names
+                .iter()
+                .map(|(scope, name)| (scope.to_string(), name.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            exported_names(MetricConflictPolicy::Keep, &metrics),
+            expected(&[("http", "requests"), ("pool", "requests")])
+        );
+        assert_eq!(
+            exported_names(MetricConflictPolicy::Drop, &metrics),
+            expected(&[("http", "requests")])
+        );
+        assert_eq!(
+            exported_names(MetricConflictPolicy::Rename, &metrics),
+            expected(&[("http", "requests"), ("pool", "requests.gauge")])
+        );
+
+        // metrics of the same type in different scopes don't conflict
+        let mut metrics = resource_metrics("requests", "");
+        let mut other = resource_metrics("requests", "").scope_metrics.remove(0);
+        other.scope = InstrumentationScope::builder("pool").build();
+        metrics.scope_metrics.push(other);
+        assert_eq!(
+            exported_names(MetricConflictPolicy::Drop, &metrics),
+            expected(&[("test", "requests"), ("pool", "requests")])
+        );
+    }
+
+    #[test]
+    fn test_metric_names_are_prefixed() {
+        let mut metrics = resource_metrics("requests", "");
+        let prefixed = resource_metrics("tenant_a.requests", "");
+        metrics.scope_metrics[0]
+            .metrics
+            .extend(prefixed.scope_metrics.into_iter().flat_map(|s| s.metrics));
+        let names = |conversion: ConversionConfig| {
+            conversion.metrics_request(&metrics).resource_metrics[0].scope_metrics[0]
+                .metrics
+                .iter()
+                .map(|metric| metric.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(ConversionConfig::default()),
+            vec!["requests", "tenant_a.requests"]
+        );
+        // names already carrying the prefix are left alone
+        assert_eq!(
+            names(ConversionConfig {
+                metric_name_prefix: Some("tenant_a.".to_string()),
+                ..Default::default()
+            }),
+            vec!["tenant_a.requests", "tenant_a.requests"]
+        );
+    }
+
+    #[test]
+    fn test_zero_data_points_are_suppressed() {
+        use opentelemetry_proto::tonic::metrics::v1::metric::Data;
+
+        let now = std::time::SystemTime::now();
+        let point = |value: f64| SumDataPoint {
+            attributes: vec![opentelemetry::KeyValue::new("value", value)],
+            start_time: now,
+            time: now,
+            value,
+            exemplars: vec![],
+        };
+        let mut metrics = resource_metrics("requests", "");
+        metrics.scope_metrics[0].metrics[0].data = Box::new(Sum {
+            data_points: vec![point(0.0), point(2.5), point(-0.0)],
+            temporality: Temporality::Delta,
+            is_monotonic: false,
+        });
+        let mut idle = resource_metrics("idle", "");
+        idle.scope_metrics[0].metrics[0].data = Box::new(Gauge {
+            data_points: vec![GaugeDataPoint {
+                attributes: vec![],
+                start_time: None,
+                time: now,
+                value: 0i64,
+                exemplars: vec![],
+            }],
+        });
+        metrics.scope_metrics[0]
+            .metrics
+            .push(idle.scope_metrics[0].metrics.remove(0));
+
+        let values = |conversion: ConversionConfig| {
+            let request = conversion.metrics_request(&metrics);
+            request.resource_metrics[0].scope_metrics[0]
+                .metrics
+                .iter()
+                .map(|metric| {
+                    let points = match &metric.data {
+                        Some(Data::Sum(sum)) => sum.data_points.len(),
+                        Some(Data::Gauge(gauge)) => gauge.data_points.len(),
+                        other => panic!("unexpected data {other:?}"),
+                    };
+                    (metric.name.clone(), points)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // kept by default
+        assert_eq!(
+            values(ConversionConfig::default()),
+            vec![("requests".to_string(), 3), ("idle".to_string(), 1)]
+        );
+        // the gauge without a nonzero data point is left out entirely
+        assert_eq!(
+            values(ConversionConfig {
+                suppress_zero_data_points: true,
+                ..Default::default()
+            }),
+            vec![("requests".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_data_point_attributes_are_truncated() {
+        use opentelemetry_proto::tonic::metrics::v1::metric::Data;
+
+        let now = std::time::SystemTime::now();
+        let point = |attributes: usize| SumDataPoint {
+            attributes: (0..attributes)
+                .map(|i| opentelemetry::KeyValue::new(format!("k{i}"), i as i64))
+                .collect(),
+            start_time: now,
+            time: now,
+            value: 1.0,
+            exemplars: vec![],
+        };
+        let mut metrics = resource_metrics("requests", "");
+        metrics.scope_metrics[0].metrics[0].data = Box::new(Sum {
+            data_points: vec![point(1), point(3), point(4)],
+            temporality: Temporality::Delta,
+            is_monotonic: false,
+        });
+
+        let request = ConversionConfig {
+            max_attributes_per_record: Some(2),
+            ..Default::default()
+        }
+        .metrics_request(&metrics);
+        let metrics = &request.resource_metrics[0].scope_metrics[0].metrics;
+        match &metrics[0].data {
+            Some(Data::Sum(sum)) => {
+                // no data point is dropped, though the last two now share their series
+                let lens: Vec<_> = sum.data_points.iter().map(|p| p.attributes.len()).collect();
+                assert_eq!(lens, [1, 2, 2]);
+                assert_eq!(sum.data_points[1].attributes, sum.data_points[2].attributes);
+            }
+            other => panic!("unexpected data {other:?}"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_exponential_histogram_is_fully_encoded() {
+        use std::sync::{Arc, Mutex};
+
+        use opentelemetry::metrics::MeterProvider;
+        use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+        use opentelemetry_proto::tonic::metrics::v1::metric::Data;
+        use opentelemetry_sdk::metrics::data::{
+            ExponentialHistogram, ExponentialHistogramDataPoint,
+        };
+        use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+        use opentelemetry_sdk::metrics::{
+            new_view, Aggregation, Instrument, MetricResult, PeriodicReader, SdkMeterProvider,
+            Stream, Temporality,
+        };
+        use opentelemetry_sdk::runtime;
+
+        type Exported = (
+            ExponentialHistogramDataPoint<f64>,
+            ExportMetricsServiceRequest,
+        );
+
+        /// Keeps the recorded data point along with the request it is converted to.
+        #[derive(Clone, Default)]
+        struct ConvertingExporter(Arc<Mutex<Vec<Exported>>>);
+
+        #[async_trait::async_trait]
+        impl PushMetricExporter for ConvertingExporter {
+            async fn export(&self, metrics: &mut ResourceMetrics) -> MetricResult<()> {
+                let data_point = metrics.scope_metrics[0].metrics[0]
+                    .data
+                    .as_any()
+                    .downcast_ref::<ExponentialHistogram<f64>>()
+                    .unwrap()
+                    .data_points[0]
+                    .clone();
+                let request = ConversionConfig::default().metrics_request(metrics);
+                self.0.lock().unwrap().push((data_point, request));
+                Ok(())
+            }
+
+            async fn force_flush(&self) -> MetricResult<()> {
+                Ok(())
+            }
+
+            fn shutdown(&self) -> MetricResult<()> {
+                Ok(())
+            }
+
+            fn temporality(&self) -> Temporality {
+                Temporality::Cumulative
+            }
+        }
+
+        let exporter = ConvertingExporter::default();
+        let view = new_view(
+            Instrument::new().name("latency"),
+            Stream::new().aggregation(Aggregation::Base2ExponentialHistogram {
+                max_size: 160,
+                max_scale: 20,
+                record_min_max: true,
+            }),
+        )
+        .unwrap();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone(), runtime::Tokio).build())
+            .with_view(view)
+            .build();
+        let histogram = provider.meter("test").f64_histogram("latency").build();
+        for value in [-4.0, -1.0, -1.0, 0.0, 2.0, 3.0] {
+            histogram.record(value, &[]);
+        }
+        provider.force_flush().unwrap();
+
+        let exported = exporter.0.lock().unwrap();
+        let (expected, request) = &exported[0];
+        let data_point = match &request.resource_metrics[0].scope_metrics[0].metrics[0].data {
+            Some(Data::ExponentialHistogram(histogram)) => &histogram.data_points[0],
+            other => panic!("expected an exponential histogram, got {other:?}"),
+        };
+
+        assert_eq!(data_point.count, 6);
+        assert_eq!(data_point.sum, Some(-1.0));
+        assert_eq!(data_point.min, Some(-4.0));
+        assert_eq!(data_point.max, Some(3.0));
+        assert_eq!(data_point.zero_count, 1);
+        assert_eq!(data_point.zero_threshold, 0.0);
+        assert_eq!(data_point.scale, i32::from(expected.scale));
+
+        let positive = data_point.positive.as_ref().unwrap();
+        assert_eq!(positive.offset, expected.positive_bucket.offset);
+        assert_eq!(positive.bucket_counts, expected.positive_bucket.counts);
+        assert_eq!(positive.bucket_counts.iter().sum::<u64>(), 2);
+
+        let negative = data_point.negative.as_ref().unwrap();
+        assert_eq!(negative.offset, expected.negative_bucket.offset);
+        assert_eq!(negative.bucket_counts, expected.negative_bucket.counts);
+        assert_eq!(negative.bucket_counts.iter().sum::<u64>(), 3);
+        // -1.0 was recorded twice into the same bucket
+        assert!(negative.bucket_counts.contains(&2));
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "logs")]
+#[cfg(otlp_transport)]
+mod logs_tests {
+    use opentelemetry::InstrumentationScope;
+    use opentelemetry_proto::tonic::common::v1::any_value::Value;
+    use opentelemetry_sdk::export::logs::LogBatch;
+    use opentelemetry_sdk::logs::LogRecord;
+
+    use super::{truncate_log_body, ConversionConfig, LOG_BODY_TRUNCATION_MARKER};
+
+    #[test]
+    fn test_oversized_log_body_is_truncated() {
+        let scope = InstrumentationScope::builder("test").build();
+        let mut stack_trace = LogRecord::default();
+        stack_trace.body = Some("frame\n".repeat(1000).into());
+        let mut short = LogRecord::default();
+        short.body = Some("started".into());
+        let mut structured = LogRecord::default();
+        structured.body = Some(opentelemetry::logs::AnyValue::Bytes(Box::new(vec![
+            0;
+            1000
+        ])));
+        let records = [
+            (&stack_trace, &scope),
+            (&short, &scope),
+            (&structured, &scope),
+        ];
+
+        let conversion = ConversionConfig {
+            max_log_body_bytes: Some(100),
+            ..Default::default()
+        };
+        let resource_logs = conversion.resource_logs(LogBatch::new(&records), &Default::default());
+        let bodies = resource_logs[0].scope_logs[0]
+            .log_records
+            .iter()
+            .map(|log_record| log_record.body.clone().unwrap().value.unwrap())
+            .collect::<Vec<_>>();
+
+        let Value::StringValue(truncated) = &bodies[0] else {
+            panic!("unexpected body {:?}", bodies[0]);
+        };
+        assert_eq!(truncated.len(), 100);
+        assert!(truncated.starts_with("frame\nframe\n"));
+        assert!(truncated.ends_with(LOG_BODY_TRUNCATION_MARKER));
+        assert_eq!(bodies[1], Value::StringValue("started".to_string()));
+        // only string bodies are truncated
+        assert_eq!(bodies[2], Value::BytesValue(vec![0; 1000]));
+    }
+
+    #[test]
+    fn test_log_attributes_exceeding_cap_are_counted_as_dropped() {
+        use opentelemetry::logs::LogRecord as _;
+
+        let scope = InstrumentationScope::builder("test").build();
+        let mut over = LogRecord::default();
+        over.add_attributes((0..5).map(|i| (format!("k{i}"), i)));
+        let mut within = LogRecord::default();
+        within.add_attribute("k0", 0);
+        let records = [(&over, &scope), (&within, &scope)];
+
+        let conversion = ConversionConfig {
+            max_attributes_per_record: Some(3),
+            ..Default::default()
+        };
+        let resource_logs = conversion.resource_logs(LogBatch::new(&records), &Default::default());
+        let log_records = &resource_logs[0].scope_logs[0].log_records;
+
+        let keys: Vec<_> = log_records[0]
+            .attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["k0", "k1", "k2"]);
+        assert_eq!(log_records[0].dropped_attributes_count, 2);
+        assert_eq!(log_records[1].attributes.len(), 1);
+        assert_eq!(log_records[1].dropped_attributes_count, 0);
+
+        // without options, the records are exported as recorded by the SDK
+        let resource_logs =
+            ConversionConfig::default().resource_logs(LogBatch::new(&records), &Default::default());
+        let log_record = &resource_logs[0].scope_logs[0].log_records[0];
+        assert_eq!(log_record.attributes.len(), 5);
+        assert_eq!(log_record.dropped_attributes_count, 0);
+    }
+
+    #[test]
+    fn test_truncation_at_character_boundary() {
+        let mut body = "é".repeat(20);
+        truncate_log_body(&mut body, LOG_BODY_TRUNCATION_MARKER.len() + 5);
+        assert_eq!(body, format!("éé{LOG_BODY_TRUNCATION_MARKER}"));
+
+        let mut body = "x".repeat(20);
+        truncate_log_body(&mut body, 3);
+        assert_eq!(body, LOG_BODY_TRUNCATION_MARKER);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "trace")]
+#[cfg(otlp_transport)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use opentelemetry::trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceId, TraceState};
+    use opentelemetry_proto::tonic::common::v1::InstrumentationScope;
+    use opentelemetry_proto::tonic::resource::v1::Resource;
+    use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span};
+    use opentelemetry_sdk::export::trace::SpanData;
+    use opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+
+    use super::{ConversionConfig, SpanDurationPolicy, TimestampPrecision};
+
+    fn span(trace_id: u128, span_id: u64, parent_span_id: u64) -> SpanData {
+        let now = SystemTime::now();
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(trace_id),
+                SpanId::from(span_id),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::from(parent_span_id),
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Internal,
+            name: "span".into(),
+            start_time: now,
+            end_time: now + Duration::from_millis(1),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status: Default::default(),
+            instrumentation_scope: opentelemetry::InstrumentationScope::builder("test").build(),
+        }
+    }
+
+    fn resource_spans(scopes: &[(&str, usize)]) -> ResourceSpans {
+        ResourceSpans {
+            resource: Some(Resource::default()),
+            scope_spans: scopes
+                .iter()
+                .map(|(name, count)| ScopeSpans {
+                    scope: Some(InstrumentationScope {
+                        name: name.to_string(),
+                        ..Default::default()
+                    }),
+                    spans: (0..*count)
+                        .map(|i| Span {
+                            name: format!("{name}-{i}"),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    schema_url: String::new(),
+                })
+                .collect(),
+            schema_url: "https://opentelemetry.io/schemas/1.0.0".to_string(),
+        }
+    }
+
+    fn span_names(resource_spans: &ResourceSpans) -> Vec<String> {
+        resource_spans
+            .scope_spans
+            .iter()
+            .flat_map(|scope_spans| scope_spans.spans.iter().map(|span| span.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_resource_spans_at_boundary() {
+        // exactly at the limit, nothing is split
+        let split = super::split_resource_spans(vec![resource_spans(&[("lib", 2)])], 2);
+        assert_eq!(split.len(), 1);
+        assert_eq!(span_names(&split[0]), vec!["lib-0", "lib-1"]);
+
+        // one over the limit, the last span moves into a new entry
+        let split = super::split_resource_spans(vec![resource_spans(&[("lib", 3)])], 2);
+        assert_eq!(split.len(), 2);
+        assert_eq!(span_names(&split[0]), vec!["lib-0", "lib-1"]);
+        assert_eq!(span_names(&split[1]), vec!["lib-2"]);
+        for entry in &split {
+            assert_eq!(entry.schema_url, "https://opentelemetry.io/schemas/1.0.0");
+            assert!(entry.resource.is_some());
+        }
+    }
+
+    #[test]
+    fn test_remap_trace_ids_consistently() {
+        use std::sync::atomic::{AtomicU8, Ordering};
+        use std::sync::Arc;
+
+        use super::TraceIdMapper;
+
+        // hands out a new trace id on every call, consistency must come from the conversion
+        let next_id = Arc::new(AtomicU8::new(1));
+        let conversion = ConversionConfig {
+            trace_id_mapper: Some(TraceIdMapper(Arc::new(move |_| {
+                TraceId::from_bytes([next_id.fetch_add(1, Ordering::SeqCst); 16])
+            }))),
+            ..Default::default()
+        };
+
+        let resource_spans = conversion.resource_spans(
+            vec![span(7, 2, 1), span(7, 1, 0), span(8, 3, 0)],
+            &Default::default(),
+        );
+        let spans = &resource_spans[0].scope_spans[0].spans;
+
+        // parent and child still share a trace id, which differs from the original one
+        assert_eq!(spans[0].trace_id, spans[1].trace_id);
+        assert_ne!(spans[0].trace_id, TraceId::from(7).to_bytes().to_vec());
+        assert_eq!(spans[0].parent_span_id, spans[1].span_id);
+        // other traces get a different trace id
+        assert_ne!(spans[0].trace_id, spans[2].trace_id);
+    }
+
+    #[test]
+    fn test_span_ending_before_start() {
+        let mut backwards = span(1, 2, 0);
+        backwards.end_time = backwards.start_time - Duration::from_secs(1);
+        let batch = vec![span(1, 1, 0), backwards];
+
+        let clamped =
+            ConversionConfig::default().resource_spans(batch.clone(), &Default::default());
+        let spans = &clamped[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[1].end_time_unix_nano, spans[1].start_time_unix_nano);
+        assert!(spans[0].end_time_unix_nano > spans[0].start_time_unix_nano);
+
+        let conversion = ConversionConfig {
+            span_duration_policy: SpanDurationPolicy::Drop,
+            ..Default::default()
+        };
+        let dropped = conversion.resource_spans(batch, &Default::default());
+        let spans = &dropped[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].span_id, SpanId::from(1).to_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_trace_state_is_exported() {
+        use std::str::FromStr;
+        use std::sync::Arc;
+
+        use super::TraceIdMapper;
+
+        let trace_state = TraceState::from_str("vendor=routing:eu,other=1").unwrap();
+        let mut traced = span(1, 1, 0);
+        traced.span_context = SpanContext::new(
+            TraceId::from(1),
+            SpanId::from(1),
+            TraceFlags::SAMPLED,
+            false,
+            trace_state.clone(),
+        );
+        let mut links = SpanLinks::default();
+        links.links.push(opentelemetry::trace::Link::new(
+            traced.span_context.clone(),
+            vec![],
+            0,
+        ));
+        traced.links = links;
+
+        // the trace state is kept whatever the options
+        let conversions = [
+            ConversionConfig::default(),
+            ConversionConfig {
+                trace_id_mapper: Some(TraceIdMapper(Arc::new(|_| TraceId::from(2)))),
+                annotate_span_depth: true,
+                max_attributes_per_record: Some(1),
+                stringify_attributes: true,
+                ..Default::default()
+            },
+        ];
+        for conversion in conversions {
+            let resource_spans =
+                conversion.resource_spans(vec![traced.clone()], &Default::default());
+            let exported = &resource_spans[0].scope_spans[0].spans[0];
+            assert_eq!(exported.trace_state, "vendor=routing:eu,other=1");
+            assert_eq!(exported.links[0].trace_state, "vendor=routing:eu,other=1");
+        }
+
+        // spans without trace state export an empty one
+        let resource_spans =
+            ConversionConfig::default().resource_spans(vec![span(1, 1, 0)], &Default::default());
+        assert_eq!(resource_spans[0].scope_spans[0].spans[0].trace_state, "");
+    }
+
+    #[test]
+    fn test_scope_dropped_attributes_count() {
+        let mut scoped = span(1, 1, 0);
+        scoped.instrumentation_scope = opentelemetry::InstrumentationScope::builder("test")
+            .with_attributes([
+                opentelemetry::KeyValue::new("a", 1),
+                opentelemetry::KeyValue::new("b", 2),
+                opentelemetry::KeyValue::new("internal.c", 3),
+            ])
+            .build();
+
+        let conversion = ConversionConfig {
+            max_scope_attributes: Some(1),
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![scoped.clone()], &Default::default());
+        let scope = resource_spans[0].scope_spans[0].scope.as_ref().unwrap();
+        assert_eq!(scope.attributes.len(), 1);
+        assert_eq!(scope.attributes[0].key, "a");
+        assert_eq!(scope.dropped_attributes_count, 2);
+
+        // keys dropped by pattern count as well, and the remaining ones are capped
+        #[cfg(feature = "attribute-key-patterns")]
+        {
+            let conversion = ConversionConfig {
+                max_scope_attributes: Some(1),
+                attribute_key_drop_patterns: vec![regex::Regex::new("^internal\\.").unwrap()],
+                ..Default::default()
+            };
+            let resource_spans =
+                conversion.resource_spans(vec![scoped.clone()], &Default::default());
+            let scope = resource_spans[0].scope_spans[0].scope.as_ref().unwrap();
+            assert_eq!(scope.dropped_attributes_count, 2);
+        }
+
+        // the limit of records doesn't apply to scopes
+        let conversion = ConversionConfig {
+            max_attributes_per_record: Some(1),
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![scoped], &Default::default());
+        let scope = resource_spans[0].scope_spans[0].scope.as_ref().unwrap();
+        assert_eq!(scope.attributes.len(), 3);
+        assert_eq!(scope.dropped_attributes_count, 0);
+    }
+
+    #[test]
+    fn test_status_derived_from_error_attribute() {
+        use opentelemetry::trace::Status;
+        use opentelemetry_proto::tonic::trace::v1::status::StatusCode;
+
+        let with_error = |value: opentelemetry::Value, status: Status| {
+            let mut span = span(1, 1, 0);
+            span.attributes = vec![opentelemetry::KeyValue::new("error", value)];
+            span.status = status;
+            span
+        };
+        let batch = vec![
+            with_error(true.into(), Status::Unset),
+            with_error("True".into(), Status::Unset),
+            with_error(false.into(), Status::Unset),
+            with_error("FALSE".into(), Status::Unset),
+            // values other than booleans don't flag an error
+            with_error("connection reset".into(), Status::Unset),
+            with_error("0".into(), Status::Unset),
+            with_error("no".into(), Status::Unset),
+            with_error(1.into(), Status::Unset),
+            with_error(true.into(), Status::Ok),
+            span(1, 2, 0),
+        ];
+        let conversion = ConversionConfig {
+            status_from_attribute: Some("error".into()),
+            ..Default::default()
+        };
+
+        let resource_spans = conversion.resource_spans(batch, &Default::default());
+        let statuses: Vec<_> = resource_spans[0].scope_spans[0]
+            .spans
+            .iter()
+            .map(|span| {
+                span.status
+                    .as_ref()
+                    .map(|status| (status.code, status.message.as_str()))
+            })
+            .collect();
+        let error = StatusCode::Error as i32;
+        let unset = Some((StatusCode::Unset as i32, ""));
+        assert_eq!(
+            statuses,
+            vec![
+                Some((error, "")),
+                Some((error, "")),
+                unset,
+                unset,
+                unset,
+                unset,
+                unset,
+                unset,
+                Some((StatusCode::Ok as i32, "")),
+                unset,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_span_depth_annotation() {
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+        // 1 <- 2 <- 3 in one trace, 5 <- 6 with 5's parent 4 outside of the batch, and a span
+        // of another trace with the same parent id as 2
+        let batch = vec![
+            span(1, 3, 2),
+            span(1, 1, 0),
+            span(1, 2, 1),
+            span(1, 6, 5),
+            span(1, 5, 4),
+            span(2, 7, 1),
+        ];
+        let conversion = ConversionConfig {
+            annotate_span_depth: true,
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(batch.clone(), &Default::default());
+        let depths: Vec<_> = resource_spans[0].scope_spans[0]
+            .spans
+            .iter()
+            .map(|span| {
+                let depth = span.attributes.iter().find(|kv| kv.key == "span.depth");
+                match depth.and_then(|kv| kv.value.as_ref()?.value.as_ref()) {
+                    Some(Value::IntValue(depth)) => *depth,
+                    other => panic!("unexpected depth {other:?}"),
+                }
+            })
+            .collect();
+        assert_eq!(depths, vec![2, 0, 1, 1, 0, 0]);
+
+        // not annotated by default
+        let resource_spans = ConversionConfig::default().resource_spans(batch, &Default::default());
+        assert!(resource_spans[0].scope_spans[0]
+            .spans
+            .iter()
+            .all(|span| span.attributes.is_empty()));
+    }
+
+    #[test]
+    fn test_scopes_filtered_by_name_prefix() {
+        use super::ScopeNamePrefixFilter;
+
+        let mut batch = vec![span(1, 1, 0), span(1, 2, 1), span(1, 3, 1)];
+        batch[1].instrumentation_scope =
+            opentelemetry::InstrumentationScope::builder("hyper::client").build();
+        batch[2].instrumentation_scope =
+            opentelemetry::InstrumentationScope::builder("app::db").build();
+        let scopes = |allow: &[&str], deny: &[&str]| {
+            let conversion = ConversionConfig {
+                scope_name_prefix_filter: Some(ScopeNamePrefixFilter {
+                    allow: allow.iter().map(|prefix| prefix.to_string()).collect(),
+                    deny: deny.iter().map(|prefix| prefix.to_string()).collect(),
+                }),
+                ..Default::default()
+            };
+            let mut scopes: Vec<_> = conversion
+                .resource_spans(batch.clone(), &Default::default())
+                .into_iter()
+                .flat_map(|resource_spans| resource_spans.scope_spans)
+                .map(|scope_spans| scope_spans.scope.unwrap().name)
+                .collect();
+            scopes.sort();
+            scopes
+        };
+
+        assert_eq!(scopes(&[], &["hyper"]), vec!["app::db", "test"]);
+        assert_eq!(
+            scopes(&["app::", "hyper"], &[]),
+            vec!["app::db", "hyper::client"]
+        );
+        // denied prefixes take precedence over allowed ones
+        assert_eq!(scopes(&["app::", "hyper"], &["hyper::"]), vec!["app::db"]);
+        // resources left without scopes aren't exported
+        assert!(ConversionConfig {
+            scope_name_prefix_filter: Some(ScopeNamePrefixFilter {
+                allow: vec![],
+                deny: vec![String::new()],
+            }),
+            ..Default::default()
+        }
+        .resource_spans(batch, &Default::default())
+        .is_empty());
+    }
+
+    #[test]
+    fn test_attributes_exceeding_cap_are_truncated() {
+        use opentelemetry::KeyValue;
+
+        let mut over = span(1, 1, 0);
+        over.attributes = (0..5).map(|i| KeyValue::new(format!("k{i}"), i)).collect();
+        over.dropped_attributes_count = 1;
+        let mut links = SpanLinks::default();
+        links.links.push(opentelemetry::trace::Link::new(
+            over.span_context.clone(),
+            vec![KeyValue::new("a", 1), KeyValue::new("b", 2)],
+            0,
+        ));
+        over.links = links;
+
+        let conversion = ConversionConfig {
+            max_attributes_per_record: Some(3),
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![over], &Default::default());
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+
+        let keys: Vec<_> = span.attributes.iter().map(|kv| kv.key.as_str()).collect();
+        assert_eq!(keys, vec!["k0", "k1", "k2"]);
+        // the attributes dropped by the SDK are still counted
+        assert_eq!(span.dropped_attributes_count, 3);
+        // records within the cap are left alone
+        assert_eq!(span.links[0].attributes.len(), 2);
+        assert_eq!(span.links[0].dropped_attributes_count, 0);
+    }
+
+    #[test]
+    fn test_event_attributes_exceeding_cap_are_truncated() {
+        use opentelemetry::trace::Event;
+        use opentelemetry::KeyValue;
+
+        let mut with_events = span(1, 1, 0);
+        with_events.attributes = (0..3).map(|i| KeyValue::new(format!("k{i}"), i)).collect();
+        with_events.events.events = vec![
+            Event::new(
+                "over",
+                SystemTime::UNIX_EPOCH,
+                (0..4).map(|i| KeyValue::new(format!("e{i}"), i)).collect(),
+                1,
+            ),
+            Event::new(
+                "within",
+                SystemTime::UNIX_EPOCH,
+                vec![KeyValue::new("a", 1)],
+                0,
+            ),
+        ];
+
+        let conversion = ConversionConfig {
+            max_attributes_per_event: Some(2),
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![with_events], &Default::default());
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+
+        let keys: Vec<_> = span.events[0]
+            .attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["e0", "e1"]);
+        // the attributes dropped by the SDK are still counted
+        assert_eq!(span.events[0].dropped_attributes_count, 3);
+        assert_eq!(span.events[1].attributes.len(), 1);
+        assert_eq!(span.events[1].dropped_attributes_count, 0);
+        // the span itself isn't capped
+        assert_eq!(span.attributes.len(), 3);
+        assert_eq!(span.dropped_attributes_count, 0);
+    }
+
+    #[test]
+    fn test_array_attributes_exceeding_cap_are_truncated() {
+        use opentelemetry::{Array, KeyValue, Value as AttributeValue};
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+        let mut with_arrays = span(1, 1, 0);
+        with_arrays.attributes = vec![
+            KeyValue::new("ids", AttributeValue::Array(Array::I64((0..100).collect()))),
+            KeyValue::new(
+                "tags",
+                AttributeValue::Array(Array::String(vec!["a".into(), "b".into()])),
+            ),
+            KeyValue::new("scalar", 42),
+        ];
+
+        let conversion = ConversionConfig {
+            max_array_attribute_len: Some(3),
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![with_arrays], &Default::default());
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+        let lengths: Vec<_> = span
+            .attributes
+            .iter()
+            .map(
+                |kv| match kv.value.as_ref().and_then(|v| v.value.as_ref()) {
+                    Some(Value::ArrayValue(array)) => Some(array.values.len()),
+                    _ => None,
+                },
+            )
+            .collect();
+
+        assert_eq!(lengths, vec![Some(3), Some(2), None]);
+        // the attributes themselves are kept
+        assert_eq!(span.dropped_attributes_count, 0);
+    }
+
+    #[test]
+    fn test_stringify_attributes() {
+        use opentelemetry::{Array, KeyValue, Value as AttributeValue};
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+        let mut with_attributes = span(1, 1, 0);
+        with_attributes.attributes = vec![
+            KeyValue::new("int", 42),
+            KeyValue::new("bool", true),
+            KeyValue::new("string", "value"),
+            KeyValue::new(
+                "array",
+                AttributeValue::Array(Array::String(vec!["a".into(), "b".into()])),
+            ),
+        ];
+
+        let conversion = ConversionConfig {
+            stringify_attributes: true,
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![with_attributes], &Default::default());
+        let values: Vec<_> = resource_spans[0].scope_spans[0].spans[0]
+            .attributes
+            .iter()
+            .map(|kv| kv.value.as_ref().and_then(|v| v.value.clone()))
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                Some(Value::StringValue("42".into())),
+                Some(Value::StringValue("true".into())),
+                Some(Value::StringValue("value".into())),
+                Some(Value::StringValue(r#"["a","b"]"#.into())),
+            ]
+        );
+    }
+
+    #[cfg(feature = "attribute-key-patterns")]
+    #[test]
+    fn test_attribute_keys_matching_pattern_are_dropped() {
+        use opentelemetry::KeyValue;
+        use regex::Regex;
+
+        let mut with_attributes = span(1, 1, 0);
+        with_attributes.attributes = vec![
+            KeyValue::new("k8s.pod.name", "pod-1"),
+            KeyValue::new("http.method", "GET"),
+            KeyValue::new("k8s.pod.uid", "1234"),
+            KeyValue::new("k8s.namespace.name", "default"),
+        ];
+
+        let conversion = ConversionConfig {
+            attribute_key_drop_patterns: vec![Regex::new(r"^k8s\.pod\.").unwrap()],
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![with_attributes], &Default::default());
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+
+        let keys: Vec<_> = span.attributes.iter().map(|kv| kv.key.as_str()).collect();
+        assert_eq!(keys, vec!["http.method", "k8s.namespace.name"]);
+        assert_eq!(span.dropped_attributes_count, 2);
+    }
+
+    #[test]
+    fn test_sorted_resource_attributes_encode_identically() {
+        use opentelemetry::KeyValue;
+        use opentelemetry_proto::transform::common::tonic::{
+            Attributes, ResourceAttributesWithSchema,
+        };
+        use prost::Message;
+
+        let resource = |keys: &[&str]| ResourceAttributesWithSchema {
+            attributes: Attributes::from(
+                keys.iter()
+                    .map(|key| KeyValue::new(key.to_string(), "value"))
+                    .collect::<Vec<_>>(),
+            ),
+            schema_url: None,
+        };
+        let conversion = ConversionConfig {
+            sort_resource_attributes: true,
+            ..Default::default()
+        };
+
+        let first = conversion.resource_spans(
+            vec![span(1, 1, 0)],
+            &resource(&["service.name", "host.name", "deployment.environment"]),
+        );
+        let second = conversion.resource_spans(
+            vec![span(2, 2, 0)],
+            &resource(&["host.name", "deployment.environment", "service.name"]),
+        );
+
+        let first = first[0].resource.as_ref().unwrap().encode_to_vec();
+        let second = second[0].resource.as_ref().unwrap().encode_to_vec();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_default_service_name_injected_into_empty_resource() {
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+        let resource = ConversionConfig::default()
+            .resource_spans(vec![span(1, 1, 0)], &Default::default())[0]
+            .resource
+            .clone()
+            .unwrap();
+        assert!(resource.attributes.is_empty());
+
+        let conversion = ConversionConfig {
+            require_resource: true,
+            ..Default::default()
+        };
+        let resource = conversion.resource_spans(vec![span(1, 1, 0)], &Default::default())[0]
+            .resource
+            .clone()
+            .unwrap();
+        assert_eq!(resource.attributes.len(), 1);
+        assert_eq!(resource.attributes[0].key, "service.name");
+        assert_eq!(
+            resource.attributes[0].value.as_ref().unwrap().value,
+            Some(Value::StringValue("unknown_service".into()))
+        );
+    }
+
+    #[test]
+    fn test_fallback_resource_replaces_undetected_resource() {
+        use opentelemetry::KeyValue;
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+        use opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema;
+
+        let conversion = ConversionConfig {
+            fallback_resource: Some(opentelemetry_sdk::Resource::new([KeyValue::new(
+                "service.name",
+                "checkout",
+            )])),
+            require_resource: true,
+            ..Default::default()
+        };
+        let exported = |resource: &opentelemetry_sdk::Resource| {
+            let resource = ResourceAttributesWithSchema::from(resource);
+            let resource = conversion.resource_spans(vec![span(1, 1, 0)], &resource)[0]
+                .resource
+                .clone()
+                .unwrap();
+            resource
+                .attributes
+                .into_iter()
+                .map(|attribute| (attribute.key, attribute.value.unwrap().value))
+                .collect::<Vec<_>>()
+        };
+        let service_name = |exported: &[(String, Option<Value>)]| {
+            exported
+                .iter()
+                .find(|(key, _)| key == "service.name")
+                .and_then(|(_, value)| value.clone())
+        };
+
+        temp_env::with_vars_unset(["OTEL_SERVICE_NAME", "OTEL_RESOURCE_ATTRIBUTES"], || {
+            // the resource of a provider whose detection yielded nothing holds the SDK defaults
+            let undetected = exported(&opentelemetry_sdk::Resource::default());
+            assert_eq!(
+                service_name(&undetected),
+                Some(Value::StringValue("checkout".into()))
+            );
+            assert!(undetected
+                .iter()
+                .any(|(key, _)| key == "telemetry.sdk.language"));
+
+            let detected = exported(&opentelemetry_sdk::Resource::default().merge(
+                &opentelemetry_sdk::Resource::new([KeyValue::new("host.name", "web-1")]),
+            ));
+            assert!(detected.iter().any(|(key, _)| key == "host.name"));
+            assert!(matches!(
+                service_name(&detected),
+                Some(Value::StringValue(name)) if name.starts_with("unknown_service")
+            ));
+        });
+    }
+
+    #[test]
+    fn test_service_namespace_injected_unless_set() {
+        use opentelemetry::KeyValue;
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+        use opentelemetry_proto::transform::common::tonic::{
+            Attributes, ResourceAttributesWithSchema,
+        };
+
+        let conversion = ConversionConfig {
+            service_namespace: Some("checkout".into()),
+            ..Default::default()
+        };
+        let namespace = |attributes: Vec<KeyValue>| {
+            let resource = ResourceAttributesWithSchema {
+                attributes: Attributes::from(attributes),
+                schema_url: None,
+            };
+            let resource = conversion.resource_spans(vec![span(1, 1, 0)], &resource)[0]
+                .resource
+                .clone()
+                .unwrap();
+            let namespaces: Vec<_> = resource
+                .attributes
+                .into_iter()
+                .filter(|attribute| attribute.key == "service.namespace")
+                .map(|attribute| attribute.value.unwrap().value)
+                .collect();
+            assert_eq!(namespaces.len(), 1);
+            namespaces[0].clone()
+        };
+
+        assert_eq!(
+            namespace(vec![KeyValue::new("service.name", "cart")]),
+            Some(Value::StringValue("checkout".into()))
+        );
+        assert_eq!(
+            namespace(vec![KeyValue::new("service.namespace", "shop")]),
+            Some(Value::StringValue("shop".into()))
+        );
+    }
+
+    #[test]
+    fn test_split_resource_spans_across_scopes() {
+        let split =
+            super::split_resource_spans(vec![resource_spans(&[("lib1", 3), ("lib2", 2)])], 2);
+
+        assert_eq!(split.len(), 3);
+        assert_eq!(span_names(&split[0]), vec!["lib1-0", "lib1-1"]);
+        assert_eq!(span_names(&split[1]), vec!["lib1-2", "lib2-0"]);
+        assert_eq!(split[1].scope_spans.len(), 2);
+        assert_eq!(span_names(&split[2]), vec!["lib2-1"]);
+    }
+
+    #[test]
+    fn test_timestamps_truncated_to_milliseconds() {
+        use opentelemetry::trace::Event;
+
+        let start = SystemTime::UNIX_EPOCH + Duration::from_nanos(1_234_567_891);
+        let mut precise = span(1, 1, 0);
+        precise.start_time = start;
+        precise.end_time = start + Duration::from_nanos(999_999);
+        let mut events = SpanEvents::default();
+        events.events.push(Event::new(
+            "event",
+            start + Duration::from_micros(2_500),
+            vec![],
+            0,
+        ));
+        precise.events = events;
+
+        let unchanged =
+            ConversionConfig::default().resource_spans(vec![precise.clone()], &Default::default());
+        assert_eq!(
+            unchanged[0].scope_spans[0].spans[0].start_time_unix_nano,
+            1_234_567_891
+        );
+
+        let conversion = ConversionConfig {
+            timestamp_precision: TimestampPrecision::Milliseconds,
+            ..Default::default()
+        };
+        let resource_spans = conversion.resource_spans(vec![precise], &Default::default());
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.start_time_unix_nano, 1_234_000_000);
+        assert_eq!(span.end_time_unix_nano, 1_235_000_000);
+        assert_eq!(span.events[0].time_unix_nano, 1_237_000_000);
+    }
+
+    #[test]
+    fn test_resources_exceeding_limit_are_split_into_requests() {
+        let batch: Vec<_> = (1..=5).map(|id| span(1, id, 0)).collect();
+
+        // unbounded, a single request holds every entry
+        let conversion = ConversionConfig {
+            max_spans_per_resource: Some(1),
+            ..Default::default()
+        };
+        let requests = conversion.trace_requests(batch.clone(), &Default::default());
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].resource_spans.len(), 5);
+
+        let conversion = ConversionConfig {
+            max_spans_per_resource: Some(1),
+            max_resources_per_request: Some(2),
+            ..Default::default()
+        };
+        let requests = conversion.trace_requests(batch, &Default::default());
+        let sizes: Vec<_> = requests
+            .iter()
+            .map(|request| request.resource_spans.len())
+            .collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        // the spans keep their order across the requests
+        let span_ids: Vec<_> = requests
+            .iter()
+            .flat_map(|request| &request.resource_spans)
+            .map(|resource_spans| resource_spans.scope_spans[0].spans[0].span_id.clone())
+            .collect();
+        let expected: Vec<_> = (1..=5)
+            .map(|id| SpanId::from(id).to_bytes().to_vec())
+            .collect();
+        assert_eq!(span_ids, expected);
+    }
+
+    #[test]
+    fn test_distinct_resources_are_split_into_requests() {
+        use opentelemetry_proto::tonic::common::v1::{any_value::Value, AnyValue, KeyValue};
+
+        let tenant = |name: &str| ResourceSpans {
+            resource: Some(Resource {
+                attributes: vec![KeyValue {
+                    key: "service.name".into(),
+                    value: Some(AnyValue {
+                        value: Some(Value::StringValue(name.into())),
+                    }),
+                }],
+                ..Default::default()
+            }),
+            ..resource_spans(&[("lib", 1)])
+        };
+        let tenants: Vec<_> = ["a", "b", "c"].into_iter().map(tenant).collect();
+
+        // the resource limit applies without a span limit
+        let requests = super::split_trace_requests(tenants.clone(), 2);
+        let split: Vec<_> = requests
+            .iter()
+            .map(|request| request.resource_spans.clone())
+            .collect();
+        assert_eq!(split, vec![tenants[..2].to_vec(), tenants[2..].to_vec()]);
+
+        assert_eq!(super::split_trace_requests(tenants, 3).len(), 1);
+    }
+
+    #[test]
+    fn test_remote_parent_sets_span_flags() {
+        use opentelemetry::trace::{Link, TraceContextExt, Tracer, TracerProvider as _};
+        use opentelemetry::Context;
+        use opentelemetry_proto::tonic::trace::v1::SpanFlags;
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use opentelemetry_sdk::trace::TracerProvider;
+
+        const HAS_IS_REMOTE: u32 = SpanFlags::ContextHasIsRemoteMask as u32;
+        const IS_REMOTE: u32 = SpanFlags::ContextIsRemoteMask as u32;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("test");
+
+        let remote_parent = SpanContext::new(
+            TraceId::from(1),
+            SpanId::from(1),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let parent = Context::new().with_remote_span_context(remote_parent.clone());
+        tracer
+            .span_builder("remote-parent")
+            .with_links(vec![Link::with_context(remote_parent)])
+            .start_with_context(&tracer, &parent);
+        tracer.start("root");
+
+        let spans = ConversionConfig::default()
+            .resource_spans(exporter.get_finished_spans().unwrap(), &Default::default())
+            .into_iter()
+            .flat_map(|resource_spans| resource_spans.scope_spans)
+            .flat_map(|scope_spans| scope_spans.spans)
+            .collect::<Vec<_>>();
+        let remote = spans.iter().find(|s| s.name == "remote-parent").unwrap();
+        let root = spans.iter().find(|s| s.name == "root").unwrap();
+
+        assert_eq!(
+            remote.flags & (HAS_IS_REMOTE | IS_REMOTE),
+            HAS_IS_REMOTE | IS_REMOTE
+        );
+        assert_eq!(remote.flags & 0xff, TraceFlags::SAMPLED.to_u8() as u32);
+        assert_eq!(remote.links[0].flags & IS_REMOTE, IS_REMOTE);
+        // known not to be remote
+        assert_eq!(root.flags & (HAS_IS_REMOTE | IS_REMOTE), HAS_IS_REMOTE);
+    }
+}
@@ -0,0 +1,126 @@
+//! Bound the rate at which identical export failures are logged.
+//!
+//! During an outage every export fails the same way. Logging each failure floods the logs
+//! without telling more than the first few did, so once an error was logged
+//! `max_per_interval` times within an interval, it is suppressed until the next interval.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Length of the intervals the logged failures are counted in.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of distinct errors above which the counts of past intervals are discarded.
+const MAX_TRACKED_ERRORS: usize = 64;
+
+/// Decides which export failures are logged.
+#[derive(Default)]
+pub(crate) struct ErrorLog {
+    /// Maximum number of times an error is logged per interval, unbounded if `None`.
+    max_per_interval: Option<usize>,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+/// The occurrences of an error within the current interval.
+struct Window {
+    start: Instant,
+    logged: usize,
+    suppressed: u64,
+}
+
+impl fmt::Debug for ErrorLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ErrorLog")
+            .field("max_per_interval", &self.max_per_interval)
+            .finish()
+    }
+}
+
+impl ErrorLog {
+    pub(crate) fn new(max_per_interval: Option<usize>) -> Self {
+        ErrorLog {
+            max_per_interval,
+            windows: Mutex::default(),
+        }
+    }
+
+    /// Whether `error` is logged now. If so, returns the number of identical failures
+    /// suppressed since it was last logged, to be logged along with it.
+    pub(crate) fn admit(&self, error: &str) -> Option<u64> {
+        self.admit_at(error, Instant::now())
+    }
+
+    fn admit_at(&self, error: &str, now: Instant) -> Option<u64> {
+        let Some(max_per_interval) = self.max_per_interval else {
+            return Some(0);
+        };
+
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() >= MAX_TRACKED_ERRORS && !windows.contains_key(error) {
+            windows.retain(|_, window| now.duration_since(window.start) < INTERVAL);
+        }
+        let window = windows.entry(error.to_string()).or_insert(Window {
+            start: now,
+            logged: 0,
+            suppressed: 0,
+        });
+
+        if now.duration_since(window.start) >= INTERVAL {
+            let suppressed = window.suppressed;
+            *window = Window {
+                start: now,
+                logged: 1,
+                suppressed: 0,
+            };
+            Some(suppressed)
+        } else if window.logged < max_per_interval {
+            window.logged += 1;
+            Some(0)
+        } else {
+            window.suppressed += 1;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{ErrorLog, INTERVAL};
+
+    #[test]
+    fn test_flooding_failures_are_logged_at_bounded_rate() {
+        let log = ErrorLog::new(Some(3));
+        let start = Instant::now();
+
+        // 100 identical failures within one interval, interleaved with another failure
+        let mut logged = 0;
+        for i in 0..100 {
+            let now = start + Duration::from_millis(i * 10);
+            if log.admit_at("unavailable", now).is_some() {
+                logged += 1;
+            }
+            if i == 50 {
+                // other errors are counted on their own
+                assert_eq!(log.admit_at("invalid argument", now), Some(0));
+            }
+        }
+        assert_eq!(logged, 3);
+
+        // the first failure of the next interval reports the suppressed ones
+        let next = start + INTERVAL + Duration::from_secs(1);
+        assert_eq!(log.admit_at("unavailable", next), Some(97));
+        assert_eq!(log.admit_at("unavailable", next), Some(0));
+        assert_eq!(log.admit_at("unavailable", next), Some(0));
+        assert_eq!(log.admit_at("unavailable", next), None);
+    }
+
+    #[test]
+    fn test_unbounded_without_sampling() {
+        let log = ErrorLog::new(None);
+        assert!((0..1000).all(|_| log.admit("unavailable") == Some(0)));
+    }
+}
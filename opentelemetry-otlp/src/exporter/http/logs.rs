@@ -1,11 +1,21 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use http::{header::CONTENT_TYPE, Method};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Method,
+};
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceResponse;
 use opentelemetry_sdk::export::logs::{LogBatch, LogExporter};
 use opentelemetry_sdk::logs::{LogError, LogResult};
 
-use super::OtlpHttpClient;
+use super::{
+    check_partial_success, check_version, handle_export_response, log_rejected_status,
+    OtlpHttpClient,
+};
+use crate::exporter::auth::authorization;
+use crate::exporter::{inflight, rate};
+use crate::Signal;
 
 #[async_trait]
 impl LogExporter for OtlpHttpClient {
@@ -18,8 +28,10 @@ impl LogExporter for OtlpHttpClient {
                 Some(client) => Ok(Arc::clone(client)),
                 _ => Err(LogError::Other("exporter is already shut down".into())),
             })?;
+        self.throttle.check()?;
 
         let (body, content_type) = { self.build_logs_export_body(batch)? };
+        let (body, signature) = self.prepare_payload(body);
         let mut request = http::Request::builder()
             .method(Method::POST)
             .uri(&self.collector_endpoint)
@@ -30,11 +42,25 @@ impl LogExporter for OtlpHttpClient {
         for (k, v) in &self.headers {
             request.headers_mut().insert(k.clone(), v.clone());
         }
+        if let Some((name, value)) = signature {
+            request.headers_mut().insert(name, value);
+        }
+        if let Some(value) = authorization(self.auth_provider.as_ref()).await? {
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
 
         let request_uri = request.uri().to_string();
+        let payload = self
+            .payload_recorder
+            .as_ref()
+            .map(|recorder| (recorder, request.body().clone()));
+        let _permit = inflight::acquire(self.inflight.as_ref(), request.body().len()).await;
+        rate::acquire(self.export_rate.as_ref()).await;
         let response = client.send(request).await?;
+        self.throttle.update(&response, &self.errors);
 
         if !response.status().is_success() {
+            log_rejected_status(&self.errors, response.status());
             let error = format!(
                 "OpenTelemetry logs export failed. Url: {}, Status Code: {}, Response: {:?}",
                 response.status().as_u16(),
@@ -44,6 +70,19 @@ impl LogExporter for OtlpHttpClient {
             return Err(LogError::Other(error.into()));
         }
 
+        let partial_success = handle_export_response::<ExportLogsServiceResponse>(
+            self.protocol,
+            response.body(),
+            &self.errors,
+        );
+        check_version(self.version_check.as_ref(), response.headers());
+        if let Some((recorder, payload)) = payload {
+            recorder.record(&payload, Signal::Logs);
+        }
+        if let Some((rejected, message)) = partial_success {
+            check_partial_success(rejected, &message, self.fail_on_partial_success)?;
+        }
+
         Ok(())
     }
 
@@ -1,13 +1,24 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use http::{header::CONTENT_TYPE, Method};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Method,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceResponse;
 use opentelemetry_sdk::metrics::data::ResourceMetrics;
 use opentelemetry_sdk::metrics::{MetricError, MetricResult};
 
-use crate::{metric::MetricsClient, Error};
+use crate::{
+    exporter::{auth::authorization, inflight, rate},
+    metric::MetricsClient,
+    Error, Signal,
+};
 
-use super::OtlpHttpClient;
+use super::{
+    check_partial_success, check_version, handle_export_response, is_retryable_status,
+    log_rejected_status, OtlpHttpClient,
+};
 
 #[async_trait]
 impl MetricsClient for OtlpHttpClient {
@@ -21,7 +32,32 @@ impl MetricsClient for OtlpHttpClient {
                 _ => Err(MetricError::Other("exporter is already shut down".into())),
             })?;
 
-        let (body, content_type) = self.build_metrics_export_body(metrics)?;
+        let mut export_request = self.conversion.metrics_request(metrics);
+        if let Some(start_times) = &self.metric_start_times {
+            start_times.apply(&mut export_request);
+        }
+        let retained = self.metric_accumulator.as_ref().map(|accumulator| {
+            accumulator.merge_pending(&mut export_request);
+            (accumulator, export_request.clone())
+        });
+        if let Err(e) = self.throttle.check() {
+            if let Some((accumulator, export_request)) = retained {
+                accumulator.keep(export_request);
+            }
+            return Err(MetricError::ExportErr(Box::new(e)));
+        }
+        let authorization = match authorization(self.auth_provider.as_ref()).await {
+            Ok(authorization) => authorization,
+            Err(e) => {
+                if let Some((accumulator, export_request)) = retained {
+                    accumulator.keep(export_request);
+                }
+                return Err(MetricError::ExportErr(Box::new(e)));
+            }
+        };
+
+        let (body, content_type) = self.build_metrics_export_body(&export_request)?;
+        let (body, signature) = self.prepare_payload(body);
         let mut request = http::Request::builder()
             .method(Method::POST)
             .uri(&self.collector_endpoint)
@@ -32,11 +68,53 @@ impl MetricsClient for OtlpHttpClient {
         for (k, v) in &self.headers {
             request.headers_mut().insert(k.clone(), v.clone());
         }
+        if let Some((name, value)) = signature {
+            request.headers_mut().insert(name, value);
+        }
+        if let Some(value) = authorization {
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+
+        let payload = self
+            .payload_recorder
+            .as_ref()
+            .map(|recorder| (recorder, request.body().clone()));
+        let _permit = inflight::acquire(self.inflight.as_ref(), request.body().len()).await;
+        rate::acquire(self.export_rate.as_ref()).await;
+        let response = match client.send(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                if let Some((accumulator, export_request)) = retained {
+                    accumulator.keep(export_request);
+                }
+                return Err(MetricError::ExportErr(Box::new(Error::RequestFailed(e))));
+            }
+        };
 
-        client
-            .send(request)
-            .await
-            .map_err(|e| MetricError::ExportErr(Box::new(Error::RequestFailed(e))))?;
+        self.throttle.update(&response, &self.errors);
+
+        if !response.status().is_success() {
+            log_rejected_status(&self.errors, response.status());
+            if let Some((accumulator, export_request)) = retained {
+                if is_retryable_status(response.status(), &self.retryable_statuses) {
+                    accumulator.keep(export_request);
+                }
+            }
+            return Ok(());
+        }
+
+        let partial_success = handle_export_response::<ExportMetricsServiceResponse>(
+            self.protocol,
+            response.body(),
+            &self.errors,
+        );
+        check_version(self.version_check.as_ref(), response.headers());
+        if let Some((recorder, payload)) = payload {
+            recorder.record(&payload, Signal::Metrics);
+        }
+        if let Some((rejected, message)) = partial_success {
+            check_partial_success(rejected, &message, self.fail_on_partial_success)?;
+        }
 
         Ok(())
     }
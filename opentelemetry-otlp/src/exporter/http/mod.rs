@@ -1,25 +1,40 @@
+#[cfg(feature = "request-signing")]
+use self::signing::{RequestSigner, SignedPayload};
+use self::throttle::Throttle;
+#[cfg(feature = "metrics")]
+use super::accumulation::MetricAccumulator;
+#[cfg(feature = "reqwest-rustls")]
+use super::identity::ClientIdentity;
+#[cfg(feature = "metrics")]
+use super::start_time::MetricStartTimes;
 use super::{
-    default_headers, default_protocol, parse_header_string,
-    OTEL_EXPORTER_OTLP_HTTP_ENDPOINT_DEFAULT,
+    auth::AuthProvider, check_partial_success, conversion::ConversionConfig, default_headers,
+    default_protocol, error_log::ErrorLog, inflight::InflightBytes, parse_header_string,
+    rate::ExportRate, resolve_signal_endpoint, version::VersionCheck, PayloadRecorder,
+    OTEL_EXPORTER_OTLP_COMPRESSION, OTEL_EXPORTER_OTLP_HTTP_ENDPOINT_DEFAULT,
 };
+#[cfg(feature = "metrics")]
+use crate::self_metrics::SelfMetrics;
+use crate::stats::StatsRecorder;
 use crate::{
-    ExportConfig, Protocol, OTEL_EXPORTER_OTLP_ENDPOINT, OTEL_EXPORTER_OTLP_HEADERS,
+    Compression, ExportConfig, Protocol, Signal, OTEL_EXPORTER_OTLP_HEADERS,
     OTEL_EXPORTER_OTLP_TIMEOUT,
 };
-use http::{HeaderName, HeaderValue, Uri};
+use http::{header::CONTENT_ENCODING, HeaderName, HeaderValue, Uri};
+use opentelemetry::{otel_debug, otel_warn};
 use opentelemetry_http::HttpClient;
+#[cfg(feature = "metrics")]
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
 use opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema;
 #[cfg(feature = "logs")]
-use opentelemetry_proto::transform::logs::tonic::group_logs_by_resource_and_scope;
-#[cfg(feature = "trace")]
-use opentelemetry_proto::transform::trace::tonic::group_spans_by_resource_and_scope;
-#[cfg(feature = "logs")]
 use opentelemetry_sdk::export::logs::LogBatch;
 #[cfg(feature = "trace")]
 use opentelemetry_sdk::export::trace::SpanData;
 use prost::Message;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -30,6 +45,9 @@ mod metrics;
 #[cfg(feature = "logs")]
 mod logs;
 
+#[cfg(feature = "request-signing")]
+pub(crate) mod signing;
+mod throttle;
 #[cfg(feature = "trace")]
 mod trace;
 
@@ -56,6 +74,54 @@ pub struct HttpConfig {
 
     /// Additional headers to send to the collector.
     headers: Option<HashMap<String, String>>,
+
+    /// Additional headers of single signals, layered on top of `headers`.
+    signal_headers: HashMap<Signal, HashMap<String, String>>,
+
+    /// Transform applied to the serialized payload before it is sent.
+    payload_transform: Option<PayloadTransform>,
+
+    /// Status codes after which accumulated metrics are kept, in addition to the retryable ones
+    /// of the OTLP specification.
+    retryable_statuses: HashSet<u16>,
+
+    /// The compression algorithm of the request bodies.
+    compression: Option<Compression>,
+
+    /// The level the request bodies are compressed at, instead of the default of the algorithm.
+    compression_level: Option<i32>,
+
+    /// Provides the bearer token sent with every export.
+    auth_provider: Option<AuthProvider>,
+
+    /// Signs the body of the export requests.
+    #[cfg(feature = "request-signing")]
+    request_signer: Option<RequestSigner>,
+
+    /// Client certificate and key presented to the collector for mutual TLS.
+    #[cfg(feature = "reqwest-rustls")]
+    client_identity: Option<ClientIdentity>,
+
+    /// Domain name the certificate of the collector is verified for and sent as SNI.
+    #[cfg(feature = "reqwest-rustls")]
+    tls_domain_name: Option<String>,
+
+    /// Whether any certificate of the collector is accepted.
+    #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+    tls_insecure_skip_verify: bool,
+
+    /// Proxy the requests are sent through.
+    #[cfg(feature = "reqwest")]
+    proxy: Option<String>,
+}
+
+/// A transform of the serialized payload of the export requests.
+struct PayloadTransform(Arc<dyn Fn(Vec<u8>) -> Vec<u8> + Send + Sync>);
+
+impl Debug for PayloadTransform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PayloadTransform(..)")
+    }
 }
 
 #[cfg(any(
@@ -65,9 +131,9 @@ pub struct HttpConfig {
 ))]
 impl Default for HttpConfig {
     fn default() -> Self {
+        // The blocking client is created when the exporter is built, see `default_blocking_client`.
         #[cfg(feature = "reqwest-blocking-client")]
-        let default_client =
-            Some(Arc::new(reqwest::blocking::Client::new()) as Arc<dyn HttpClient>);
+        let default_client = None;
         #[cfg(all(not(feature = "reqwest-blocking-client"), feature = "reqwest-client"))]
         let default_client = Some(Arc::new(reqwest::Client::new()) as Arc<dyn HttpClient>);
         #[cfg(all(
@@ -89,6 +155,22 @@ impl Default for HttpConfig {
         HttpConfig {
             client: default_client,
             headers: None,
+            signal_headers: HashMap::new(),
+            payload_transform: None,
+            retryable_statuses: HashSet::new(),
+            compression: None,
+            compression_level: None,
+            auth_provider: None,
+            #[cfg(feature = "request-signing")]
+            request_signer: None,
+            #[cfg(feature = "reqwest-rustls")]
+            client_identity: None,
+            #[cfg(feature = "reqwest-rustls")]
+            tls_domain_name: None,
+            #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+            tls_insecure_skip_verify: false,
+            #[cfg(feature = "reqwest")]
+            proxy: None,
         }
     }
 }
@@ -142,18 +224,37 @@ impl Default for HttpExporterBuilder {
 }
 
 impl HttpExporterBuilder {
+    /// Whether a reqwest client is created for the options of the exporter, replacing the
+    /// configured HTTP client.
+    #[cfg(feature = "reqwest")]
+    fn creates_reqwest_client(&self) -> bool {
+        #[cfg(feature = "reqwest-rustls")]
+        let tls = self.http_config.client_identity.is_some()
+            || self.http_config.tls_domain_name.is_some();
+        #[cfg(not(feature = "reqwest-rustls"))]
+        let tls = false;
+        #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+        let tls = tls || self.http_config.tls_insecure_skip_verify;
+        tls || self.http_config.proxy.is_some()
+    }
+
     fn build_client(
         &mut self,
+        signal: Signal,
         signal_endpoint_var: &str,
         signal_endpoint_path: &str,
         signal_timeout_var: &str,
         signal_http_headers_var: &str,
+        signal_compression_var: &str,
     ) -> Result<OtlpHttpClient, crate::Error> {
-        let endpoint = resolve_http_endpoint(
+        #[cfg_attr(not(feature = "reqwest-rustls"), allow(unused_mut))]
+        let mut endpoint = resolve_http_endpoint(
             signal_endpoint_var,
             signal_endpoint_path,
             self.exporter_config.endpoint.clone(),
         )?;
+        self.exporter_config
+            .validate_endpoint_port(&endpoint, self.exporter_config.protocol)?;
 
         let timeout = match env::var(signal_timeout_var)
             .ok()
@@ -165,11 +266,6 @@ impl HttpExporterBuilder {
             },
             None => self.exporter_config.timeout,
         };
-        let http_client = self
-            .http_config
-            .client
-            .take()
-            .ok_or(crate::Error::NoHttpClient)?;
         #[allow(clippy::mutable_key_type)] // http headers are not mutated
         let mut headers: HashMap<HeaderName, HeaderValue> = self
             .http_config
@@ -177,6 +273,13 @@ impl HttpExporterBuilder {
             .take()
             .unwrap_or_default()
             .into_iter()
+            // headers of the signal override the shared ones
+            .chain(
+                self.http_config
+                    .signal_headers
+                    .remove(&signal)
+                    .unwrap_or_default(),
+            )
             .filter_map(|(k, v)| {
                 Some((
                     HeaderName::from_str(&k).ok()?,
@@ -191,14 +294,147 @@ impl HttpExporterBuilder {
         {
             add_header_from_string(&input, &mut headers);
         }
+        let compression = self.resolve_compression(signal_compression_var)?;
+        if let Some(compression) = compression {
+            headers.insert(
+                CONTENT_ENCODING,
+                HeaderValue::from_static(match compression {
+                    Compression::Gzip => "gzip",
+                    Compression::Zstd => "zstd",
+                }),
+            );
+        }
+        self.exporter_config.validate_header_count(headers.len())?;
 
-        Ok(OtlpHttpClient::new(
+        #[cfg(feature = "reqwest")]
+        if self.creates_reqwest_client() {
+            let mut options = ReqwestOptions::default();
+            if let Some(proxy) = self.http_config.proxy.take() {
+                options.proxy = Some(proxy_with_env_exclusions(&proxy)?);
+            }
+            #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+            if self.http_config.tls_insecure_skip_verify {
+                otel_warn!(
+                    name: "HttpExporter.TlsVerificationDisabled",
+                    endpoint = endpoint.to_string(),
+                    message = "the certificate of the collector is not verified, never do this in production",
+                );
+                options.accept_invalid_certs = true;
+            }
+            #[cfg(feature = "reqwest-rustls")]
+            if let Some(identity) = self.http_config.client_identity.take() {
+                identity.validate()?;
+                options.identity = Some(identity.reqwest()?);
+            }
+            #[cfg(feature = "reqwest-rustls")]
+            if let Some(domain_name) = self.http_config.tls_domain_name.take() {
+                let (overridden, addrs) = override_domain_name(endpoint, &domain_name)?;
+                endpoint = overridden;
+                options.domain_name = Some((domain_name, addrs));
+            }
+            self.http_config.client = Some(reqwest_client(options, &self.exporter_config)?);
+        }
+
+        let http_client = match self.http_config.client.take() {
+            Some(client) => client,
+            #[cfg(feature = "reqwest-blocking-client")]
+            None => default_blocking_client(&self.exporter_config)?,
+            #[cfg(not(feature = "reqwest-blocking-client"))]
+            None => return Err(crate::Error::NoHttpClient),
+        };
+
+        let mut client = OtlpHttpClient::new(
             http_client,
             endpoint,
             headers,
             self.exporter_config.protocol,
             timeout,
-        ))
+            self.exporter_config.conversion.clone(),
+            self.exporter_config
+                .max_inflight_bytes
+                .map(InflightBytes::new),
+            self.http_config.payload_transform.take(),
+        );
+        client.version_check = self
+            .exporter_config
+            .version_check
+            .as_deref()
+            .map(VersionCheck::new);
+        client
+            .payload_recorder
+            .clone_from(&self.exporter_config.payload_recorder);
+        client.stats = self.exporter_config.stats.clone();
+        client.export_rate = self
+            .exporter_config
+            .max_export_rate
+            .map(|rate| ExportRate::new(rate, &self.exporter_config.thread_name_prefix))
+            .transpose()?
+            .flatten();
+        client.fail_on_partial_success = self.exporter_config.fail_on_partial_success;
+        client.errors = Arc::new(ErrorLog::new(self.exporter_config.error_log_sampling));
+        client.auth_provider = self.http_config.auth_provider.take();
+        #[cfg(feature = "gzip-http")]
+        {
+            client.compression = compression;
+            client.compression_level = match (compression, self.http_config.compression_level) {
+                (Some(compression), Some(level)) => {
+                    compression.validate_level(level)?;
+                    // levels of gzip aren't negative
+                    Some(level as u32)
+                }
+                _ => None,
+            };
+        }
+        #[cfg(feature = "request-signing")]
+        {
+            client.request_signer = self.http_config.request_signer.take();
+        }
+        #[cfg(feature = "metrics")]
+        client
+            .retryable_statuses
+            .clone_from(&self.http_config.retryable_statuses);
+        Ok(client)
+    }
+
+    /// The compression set in the builder, else the one of the signal's or the shared environment
+    /// variable.
+    fn resolve_compression(
+        &self,
+        signal_compression_var: &str,
+    ) -> Result<Option<Compression>, crate::Error> {
+        #[cfg_attr(feature = "gzip-http", allow(unused_variables))]
+        let (compression, from_env) = match self.http_config.compression {
+            Some(compression) => (compression, false),
+            None => match env::var(signal_compression_var)
+                .or_else(|_| env::var(OTEL_EXPORTER_OTLP_COMPRESSION))
+            {
+                Ok(compression) => (compression.parse()?, true),
+                Err(_) => return Ok(None),
+            },
+        };
+        match compression {
+            #[cfg(feature = "gzip-http")]
+            Compression::Gzip => Ok(Some(compression)),
+            // the environment may be shared with exporters built with gzip, so it doesn't fail
+            // the exporter like an explicit compression does
+            #[cfg(not(feature = "gzip-http"))]
+            Compression::Gzip if from_env => {
+                otel_warn!(
+                    name: "HttpExporter.CompressionUnavailable",
+                    compression = compression.to_string(),
+                    message = "the gzip-http feature is disabled, exporting uncompressed",
+                );
+                Ok(None)
+            }
+            #[cfg(not(feature = "gzip-http"))]
+            Compression::Gzip => Err(crate::Error::FeatureRequiredForCompressionAlgorithm(
+                "gzip-http",
+                Compression::Gzip,
+            )),
+            Compression::Zstd => Err(crate::Error::UnsupportedCompressionAlgorithm(
+                compression.to_string(),
+            )),
+        }
     }
 
     /// Create a log exporter with the current configuration
@@ -207,16 +443,20 @@ impl HttpExporterBuilder {
         mut self,
     ) -> Result<crate::SpanExporter, opentelemetry::trace::TraceError> {
         use crate::{
-            OTEL_EXPORTER_OTLP_TRACES_ENDPOINT, OTEL_EXPORTER_OTLP_TRACES_HEADERS,
-            OTEL_EXPORTER_OTLP_TRACES_TIMEOUT,
+            OTEL_EXPORTER_OTLP_TRACES_COMPRESSION, OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+            OTEL_EXPORTER_OTLP_TRACES_HEADERS, OTEL_EXPORTER_OTLP_TRACES_TIMEOUT,
         };
 
         let client = self.build_client(
+            Signal::Traces,
             OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
             "/v1/traces",
             OTEL_EXPORTER_OTLP_TRACES_TIMEOUT,
             OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+            OTEL_EXPORTER_OTLP_TRACES_COMPRESSION,
         )?;
+        #[cfg(feature = "metrics")]
+        let client = client.with_self_metrics(self.exporter_config.self_metrics, "traces");
 
         Ok(crate::SpanExporter::new(client))
     }
@@ -225,16 +465,20 @@ impl HttpExporterBuilder {
     #[cfg(feature = "logs")]
     pub fn build_log_exporter(mut self) -> opentelemetry_sdk::logs::LogResult<crate::LogExporter> {
         use crate::{
-            OTEL_EXPORTER_OTLP_LOGS_ENDPOINT, OTEL_EXPORTER_OTLP_LOGS_HEADERS,
-            OTEL_EXPORTER_OTLP_LOGS_TIMEOUT,
+            OTEL_EXPORTER_OTLP_LOGS_COMPRESSION, OTEL_EXPORTER_OTLP_LOGS_ENDPOINT,
+            OTEL_EXPORTER_OTLP_LOGS_HEADERS, OTEL_EXPORTER_OTLP_LOGS_TIMEOUT,
         };
 
         let client = self.build_client(
+            Signal::Logs,
             OTEL_EXPORTER_OTLP_LOGS_ENDPOINT,
             "/v1/logs",
             OTEL_EXPORTER_OTLP_LOGS_TIMEOUT,
             OTEL_EXPORTER_OTLP_LOGS_HEADERS,
+            OTEL_EXPORTER_OTLP_LOGS_COMPRESSION,
         )?;
+        #[cfg(feature = "metrics")]
+        let client = client.with_self_metrics(self.exporter_config.self_metrics, "logs");
 
         Ok(crate::LogExporter::new(client))
     }
@@ -246,16 +490,20 @@ impl HttpExporterBuilder {
         temporality: opentelemetry_sdk::metrics::Temporality,
     ) -> opentelemetry_sdk::metrics::MetricResult<crate::MetricExporter> {
         use crate::{
-            OTEL_EXPORTER_OTLP_METRICS_ENDPOINT, OTEL_EXPORTER_OTLP_METRICS_HEADERS,
-            OTEL_EXPORTER_OTLP_METRICS_TIMEOUT,
+            OTEL_EXPORTER_OTLP_METRICS_COMPRESSION, OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
+            OTEL_EXPORTER_OTLP_METRICS_HEADERS, OTEL_EXPORTER_OTLP_METRICS_TIMEOUT,
         };
 
         let client = self.build_client(
+            Signal::Metrics,
             OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
             "/v1/metrics",
             OTEL_EXPORTER_OTLP_METRICS_TIMEOUT,
             OTEL_EXPORTER_OTLP_METRICS_HEADERS,
+            OTEL_EXPORTER_OTLP_METRICS_COMPRESSION,
         )?;
+        #[cfg(feature = "metrics")]
+        let client = client.with_self_metrics(self.exporter_config.self_metrics, "metrics");
 
         Ok(crate::MetricExporter::new(client, temporality))
     }
@@ -271,16 +519,51 @@ struct OtlpHttpClient {
     #[allow(dead_code)]
     // <allow dead> would be removed once we support set_resource for metrics and traces.
     resource: opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    #[allow(dead_code)]
+    // <allow dead> only read by the enabled signals.
+    conversion: ConversionConfig,
+    inflight: Option<Arc<InflightBytes>>,
+    export_rate: Option<Arc<ExportRate>>,
+    payload_transform: Option<PayloadTransform>,
+    #[cfg(feature = "gzip-http")]
+    compression: Option<Compression>,
+    #[cfg(feature = "gzip-http")]
+    compression_level: Option<u32>,
+    #[cfg(feature = "request-signing")]
+    request_signer: Option<RequestSigner>,
+    auth_provider: Option<AuthProvider>,
+    version_check: Option<VersionCheck>,
+    payload_recorder: Option<PayloadRecorder>,
+    stats: StatsRecorder,
+    /// Pauses the exports while the collector asked not to receive any.
+    throttle: Arc<Throttle>,
+    /// Decides which export failures are logged.
+    errors: Arc<ErrorLog>,
+    /// Whether exports the collector rejected items of fail.
+    fail_on_partial_success: bool,
+    /// Status codes treated as retryable in addition to the ones of the OTLP specification.
+    #[cfg(feature = "metrics")]
+    retryable_statuses: HashSet<u16>,
+    #[cfg(feature = "metrics")]
+    metric_accumulator: Option<MetricAccumulator>,
+    #[cfg(feature = "metrics")]
+    metric_start_times: Option<MetricStartTimes>,
+    #[cfg(feature = "metrics")]
+    self_metrics: Option<SelfMetrics>,
 }
 
 impl OtlpHttpClient {
     #[allow(clippy::mutable_key_type)] // http headers are not mutated
+    #[allow(clippy::too_many_arguments)]
     fn new(
         client: Arc<dyn HttpClient>,
         collector_endpoint: Uri,
         headers: HashMap<HeaderName, HeaderValue>,
         protocol: Protocol,
         timeout: Duration,
+        conversion: ConversionConfig,
+        inflight: Option<Arc<InflightBytes>>,
+        payload_transform: Option<PayloadTransform>,
     ) -> Self {
         OtlpHttpClient {
             client: Mutex::new(Some(client)),
@@ -289,24 +572,122 @@ impl OtlpHttpClient {
             protocol,
             _timeout: timeout,
             resource: ResourceAttributesWithSchema::default(),
+            #[cfg(feature = "metrics")]
+            retryable_statuses: HashSet::new(),
+            #[cfg(feature = "metrics")]
+            metric_accumulator: conversion.metric_accumulator(),
+            #[cfg(feature = "metrics")]
+            metric_start_times: conversion.metric_start_times(),
+            conversion,
+            inflight,
+            export_rate: None,
+            payload_transform,
+            #[cfg(feature = "gzip-http")]
+            compression: None,
+            #[cfg(feature = "gzip-http")]
+            compression_level: None,
+            #[cfg(feature = "request-signing")]
+            request_signer: None,
+            auth_provider: None,
+            version_check: None,
+            payload_recorder: None,
+            stats: StatsRecorder::default(),
+            throttle: Arc::default(),
+            errors: Arc::default(),
+            fail_on_partial_success: false,
+            #[cfg(feature = "metrics")]
+            self_metrics: None,
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn with_self_metrics(mut self, enabled: bool, signal: &'static str) -> Self {
+        self.self_metrics = enabled.then(|| SelfMetrics::global(signal));
+        self
+    }
+
+    /// Report the items skipped because they failed to serialize.
+    #[cfg(feature = "http-json")]
+    fn record_serialization_failures(&self, skipped: usize) {
+        if skipped == 0 {
+            return;
+        }
+        otel_warn!(
+            name: "HttpExporter.ItemsSkipped",
+            skipped_items = skipped as i64,
+        );
+        #[cfg(feature = "metrics")]
+        if let Some(self_metrics) = &self.self_metrics {
+            self_metrics.record_serialization_failures(skipped);
+        }
+    }
+
+    /// Apply the configured payload transform to a serialized request body, then compress it.
+    fn transform_payload(&self, body: Vec<u8>) -> Vec<u8> {
+        let body = match &self.payload_transform {
+            Some(transform) => (transform.0)(body),
+            None => body,
+        };
+        #[cfg(feature = "gzip-http")]
+        if self.compression == Some(Compression::Gzip) {
+            return gzip(&body, self.compression_level);
         }
+        body
     }
 
+    /// Transform a serialized request body and sign it if configured, returning the body to
+    /// send and the header carrying its signature.
+    fn prepare_payload(&self, body: Vec<u8>) -> (Vec<u8>, Option<(HeaderName, HeaderValue)>) {
+        #[cfg(feature = "request-signing")]
+        if let Some(signer) = &self.request_signer {
+            let serialized = signer
+                .signs(SignedPayload::Serialized)
+                .then(|| signer.sign(&body));
+            let body = self.transform_payload(body);
+            self.stats.record_bytes_sent(body.len());
+            let signature = serialized.unwrap_or_else(|| signer.sign(&body));
+            return (body, Some(signature));
+        }
+        let body = self.transform_payload(body);
+        self.stats.record_bytes_sent(body.len());
+        (body, None)
+    }
+
+    /// Encode the bodies of the requests exporting `spans`, one per request.
     #[cfg(feature = "trace")]
-    fn build_trace_export_body(
+    fn build_trace_export_bodies(
         &self,
         spans: Vec<SpanData>,
-    ) -> opentelemetry::trace::TraceResult<(Vec<u8>, &'static str)> {
-        use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
-        let resource_spans = group_spans_by_resource_and_scope(spans, &self.resource);
+    ) -> opentelemetry::trace::TraceResult<(Vec<Vec<u8>>, &'static str)> {
+        let mut bodies = Vec::new();
+        let mut content_type = "application/x-protobuf";
+        for req in self.conversion.trace_requests(spans, &self.resource) {
+            let (body, request_content_type) = self.build_trace_export_body(req)?;
+            bodies.push(body);
+            content_type = request_content_type;
+        }
+        Ok((bodies, content_type))
+    }
 
-        let req = ExportTraceServiceRequest { resource_spans };
+    #[cfg(feature = "trace")]
+    fn build_trace_export_body(
+        &self,
+        req: opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest,
+    ) -> opentelemetry::trace::TraceResult<(Vec<u8>, &'static str)> {
         match self.protocol {
             #[cfg(feature = "http-json")]
-            Protocol::HttpJson => match serde_json::to_string_pretty(&req) {
-                Ok(json) => Ok((json.into(), "application/json")),
-                Err(e) => Err(opentelemetry::trace::TraceError::from(e.to_string())),
-            },
+            Protocol::HttpJson => {
+                let (json, skipped) = to_json_skipping_failures(&req, |req| {
+                    req.resource_spans
+                        .iter_mut()
+                        .flat_map(|resource_spans| resource_spans.scope_spans.iter_mut())
+                        .map(|scope_spans| &mut scope_spans.spans)
+                        .collect()
+                })
+                .map_err(|e| opentelemetry::trace::TraceError::from(e.to_string()))?;
+                self.record_serialization_failures(skipped);
+                Ok((json, "application/json"))
+            }
             _ => Ok((req.encode_to_vec(), "application/x-protobuf")),
         }
     }
@@ -317,15 +698,23 @@ impl OtlpHttpClient {
         logs: LogBatch<'_>,
     ) -> opentelemetry_sdk::logs::LogResult<(Vec<u8>, &'static str)> {
         use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
-        let resource_logs = group_logs_by_resource_and_scope(logs, &self.resource);
+        let resource_logs = self.conversion.resource_logs(logs, &self.resource);
         let req = ExportLogsServiceRequest { resource_logs };
 
         match self.protocol {
             #[cfg(feature = "http-json")]
-            Protocol::HttpJson => match serde_json::to_string_pretty(&req) {
-                Ok(json) => Ok((json.into(), "application/json")),
-                Err(e) => Err(opentelemetry_sdk::logs::LogError::from(e.to_string())),
-            },
+            Protocol::HttpJson => {
+                let (json, skipped) = to_json_skipping_failures(&req, |req| {
+                    req.resource_logs
+                        .iter_mut()
+                        .flat_map(|resource_logs| resource_logs.scope_logs.iter_mut())
+                        .map(|scope_logs| &mut scope_logs.log_records)
+                        .collect()
+                })
+                .map_err(|e| opentelemetry_sdk::logs::LogError::from(e.to_string()))?;
+                self.record_serialization_failures(skipped);
+                Ok((json, "application/json"))
+            }
             _ => Ok((req.encode_to_vec(), "application/x-protobuf")),
         }
     }
@@ -333,64 +722,315 @@ impl OtlpHttpClient {
     #[cfg(feature = "metrics")]
     fn build_metrics_export_body(
         &self,
-        metrics: &mut opentelemetry_sdk::metrics::data::ResourceMetrics,
+        req: &ExportMetricsServiceRequest,
     ) -> opentelemetry_sdk::metrics::MetricResult<(Vec<u8>, &'static str)> {
-        use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
-
-        let req: ExportMetricsServiceRequest = (&*metrics).into();
-
         match self.protocol {
             #[cfg(feature = "http-json")]
-            Protocol::HttpJson => match serde_json::to_string_pretty(&req) {
-                Ok(json) => Ok((json.into(), "application/json")),
-                Err(e) => Err(opentelemetry_sdk::metrics::MetricError::Other(
-                    e.to_string(),
-                )),
-            },
+            Protocol::HttpJson => {
+                let (json, skipped) = to_json_skipping_failures(req, |req| {
+                    req.resource_metrics
+                        .iter_mut()
+                        .flat_map(|resource_metrics| resource_metrics.scope_metrics.iter_mut())
+                        .map(|scope_metrics| &mut scope_metrics.metrics)
+                        .collect()
+                })
+                .map_err(|e| opentelemetry_sdk::metrics::MetricError::Other(e.to_string()))?;
+                self.record_serialization_failures(skipped);
+                Ok((json, "application/json"))
+            }
             _ => Ok((req.encode_to_vec(), "application/x-protobuf")),
         }
     }
 }
 
-fn build_endpoint_uri(endpoint: &str, path: &str) -> Result<Uri, crate::Error> {
-    let path = if endpoint.ends_with('/') && path.starts_with('/') {
-        path.strip_prefix('/').unwrap()
-    } else {
-        path
+/// Serialize `request` as JSON. If that fails, the `items` of the request are serialized one
+/// by one and the failing ones are skipped, so pathological data doesn't fail the export of the
+/// whole batch. Returns the JSON and the number of skipped items.
+#[cfg(feature = "http-json")]
+fn to_json_skipping_failures<R, T>(
+    request: &R,
+    items: impl FnOnce(&mut R) -> Vec<&mut Vec<T>>,
+) -> serde_json::Result<(Vec<u8>, usize)>
+where
+    R: serde::Serialize + Clone,
+    T: serde::Serialize,
+{
+    if let Ok(json) = serde_json::to_vec_pretty(request) {
+        return Ok((json, 0));
+    }
+
+    let mut request = request.clone();
+    let mut skipped = 0;
+    for items in items(&mut request) {
+        let len = items.len();
+        items.retain(|item| match serde_json::to_vec(item) {
+            Ok(_) => true,
+            Err(error) => {
+                otel_warn!(
+                    name: "HttpExporter.ItemSerializationFailed",
+                    error = error.to_string(),
+                );
+                false
+            }
+        });
+        skipped += len - items.len();
+    }
+    serde_json::to_vec_pretty(&request).map(|json| (json, skipped))
+}
+
+/// An OTLP export service response, which may report a partial success.
+trait ExportResponse: Message + Default {
+    #[cfg(feature = "http-json")]
+    fn from_json(body: &[u8]) -> serde_json::Result<Self>;
+
+    /// The number of rejected items and the error message of the reported partial success.
+    fn partial_success(&self) -> Option<(i64, &str)>;
+}
+
+macro_rules! impl_export_response {
+    ($response:ty, $rejected:ident) => {
+        impl ExportResponse for $response {
+            #[cfg(feature = "http-json")]
+            fn from_json(body: &[u8]) -> serde_json::Result<Self> {
+                serde_json::from_slice(body)
+            }
+
+            fn partial_success(&self) -> Option<(i64, &str)> {
+                self.partial_success.as_ref().map(|partial_success| {
+                    (
+                        partial_success.$rejected,
+                        partial_success.error_message.as_str(),
+                    )
+                })
+            }
+        }
     };
-    format!("{endpoint}{path}").parse().map_err(From::from)
 }
 
-// see https://github.com/open-telemetry/opentelemetry-specification/blob/main/specification/protocol/exporter.md#endpoint-urls-for-otlphttp
+#[cfg(feature = "trace")]
+impl_export_response!(
+    opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceResponse,
+    rejected_spans
+);
+#[cfg(feature = "metrics")]
+impl_export_response!(
+    opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceResponse,
+    rejected_data_points
+);
+#[cfg(feature = "logs")]
+impl_export_response!(
+    opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceResponse,
+    rejected_log_records
+);
+
+/// Check the OTLP version the collector advertised in the headers of a response, if enabled.
+fn check_version(version_check: Option<&VersionCheck>, headers: &http::HeaderMap) {
+    if let Some(version_check) = version_check {
+        version_check.check(
+            headers
+                .get(version_check.header())
+                .and_then(|value| value.to_str().ok()),
+        );
+    }
+}
+
+/// Log the status of an export the collector rejected.
+fn log_rejected_status(errors: &ErrorLog, status: http::StatusCode) {
+    let status = status.as_u16();
+    if let Some(suppressed) = errors.admit(&format!("HTTP status {status}")) {
+        otel_debug!(
+            name: "HttpExporter.ExportFailedWithStatus",
+            status = status,
+            suppressed = suppressed as i64,
+        );
+    }
+}
+
+/// Decode the body of a successful export response and report the partial success or warning
+/// it carries, if any. The reported rejected item count and error message are returned.
+fn handle_export_response<R: ExportResponse>(
+    protocol: Protocol,
+    body: &[u8],
+    errors: &ErrorLog,
+) -> Option<(i64, String)> {
+    // collectors may omit the body entirely on full success
+    if body.is_empty() {
+        return None;
+    }
+
+    let response = match protocol {
+        #[cfg(feature = "http-json")]
+        Protocol::HttpJson => R::from_json(body).map_err(|e| e.to_string()),
+        _ => R::decode(body).map_err(|e| e.to_string()),
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(error) => {
+            otel_debug!(name: "HttpExporter.ResponseDecodeFailed", error = error);
+            return None;
+        }
+    };
+
+    match response.partial_success() {
+        Some((rejected, message)) if rejected != 0 || !message.is_empty() => {
+            if let Some(suppressed) = errors.admit(message) {
+                otel_warn!(
+                    name: "HttpExporter.PartialSuccess",
+                    rejected_items = rejected,
+                    message = message.to_string(),
+                    suppressed = suppressed as i64,
+                );
+            }
+            Some((rejected, message.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Whether an export rejected with `status` may succeed if it is attempted again, as the OTLP
+/// specification requires or as configured with `retryable`.
+#[cfg(feature = "metrics")]
+fn is_retryable_status(status: http::StatusCode, retryable: &HashSet<u16>) -> bool {
+    matches!(status.as_u16(), 429 | 502 | 503 | 504) || retryable.contains(&status.as_u16())
+}
+
+/// Create the default blocking client on a thread of its own, as the client starts a runtime,
+/// which panics when done from within an async context.
+#[cfg(feature = "reqwest-blocking-client")]
+fn default_blocking_client(config: &ExportConfig) -> Result<Arc<dyn HttpClient>, crate::Error> {
+    let client = config
+        .spawn_thread("client", reqwest::blocking::Client::new)?
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+    Ok(Arc::new(client))
+}
+
+/// The options a reqwest client is created for, replacing the configured HTTP client.
+#[cfg(feature = "reqwest")]
+#[derive(Default)]
+struct ReqwestOptions {
+    proxy: Option<reqwest::Proxy>,
+    #[cfg(feature = "reqwest-rustls")]
+    identity: Option<reqwest::Identity>,
+    /// Domain name verified and sent as SNI, resolved to the addresses of the endpoint.
+    #[cfg(feature = "reqwest-rustls")]
+    domain_name: Option<(String, Vec<std::net::SocketAddr>)>,
+    #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+    accept_invalid_certs: bool,
+}
+
+/// Replace the host of `endpoint` with `domain_name`, so it is verified and sent as SNI, and
+/// return the addresses of the original host the domain name is resolved to.
+#[cfg(feature = "reqwest-rustls")]
+fn override_domain_name(
+    endpoint: Uri,
+    domain_name: &str,
+) -> Result<(Uri, Vec<std::net::SocketAddr>), crate::Error> {
+    use std::net::ToSocketAddrs;
+
+    let host = endpoint.host().unwrap_or_default().to_string();
+    let port = endpoint
+        .port_u16()
+        .unwrap_or(if endpoint.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+    let addrs = (host.trim_matches(['[', ']']), port)
+        .to_socket_addrs()
+        .map_err(|source| crate::Error::DnsResolutionFailed {
+            host: host.clone(),
+            source,
+        })?
+        .collect();
+
+    let authority = match endpoint.port_u16() {
+        Some(port) => format!("{domain_name}:{port}"),
+        None => domain_name.to_string(),
+    };
+    let path = endpoint.path_and_query().map_or("", |path| path.as_str());
+    let scheme = endpoint.scheme_str().unwrap_or("http");
+    Ok((format!("{scheme}://{authority}{path}").parse()?, addrs))
+}
+
+/// The proxy at `url` all requests are sent through, except those to the hosts excluded by the
+/// `NO_PROXY` environment variable.
+#[cfg(feature = "reqwest")]
+fn proxy_with_env_exclusions(url: &str) -> Result<reqwest::Proxy, crate::Error> {
+    let proxy = reqwest::Proxy::all(url)
+        .map_err(|error| crate::Error::InvalidProxy(format!("{url}: {error}")))?;
+    Ok(proxy.no_proxy(reqwest::NoProxy::from_env()))
+}
+
+/// Create a reqwest client with the `options`, blocking if the default client is.
+#[cfg(feature = "reqwest")]
+#[cfg_attr(not(feature = "reqwest-blocking-client"), allow(unused_variables))]
+fn reqwest_client(
+    options: ReqwestOptions,
+    config: &ExportConfig,
+) -> Result<Arc<dyn HttpClient>, crate::Error> {
+    #[cfg(feature = "reqwest-rustls")]
+    let has_identity = options.identity.is_some();
+    let failed = move |error: reqwest::Error| {
+        #[cfg(feature = "reqwest-rustls")]
+        if has_identity {
+            return crate::Error::InvalidClientIdentity(error.to_string());
+        }
+        crate::Error::RequestFailed(Box::new(error))
+    };
+    macro_rules! configure {
+        ($builder:expr) => {{
+            let mut builder = $builder;
+            if let Some(proxy) = options.proxy {
+                builder = builder.proxy(proxy);
+            }
+            #[cfg(feature = "reqwest-rustls")]
+            if let Some(identity) = options.identity {
+                builder = builder.identity(identity);
+            }
+            #[cfg(feature = "reqwest-rustls")]
+            if let Some((domain_name, addrs)) = options.domain_name {
+                builder = builder.resolve_to_addrs(&domain_name, &addrs);
+            }
+            #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+            if options.accept_invalid_certs {
+                builder = builder.danger_accept_invalid_certs(true);
+            }
+            builder.build()
+        }};
+    }
+
+    // like the default blocking client, it must not be created within an async context
+    #[cfg(feature = "reqwest-blocking-client")]
+    let client: Arc<dyn HttpClient> = Arc::new(
+        config
+            .spawn_thread("client", move || {
+                configure!(reqwest::blocking::Client::builder())
+            })?
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            .map_err(failed)?,
+    );
+    #[cfg(not(feature = "reqwest-blocking-client"))]
+    let client: Arc<dyn HttpClient> =
+        Arc::new(configure!(reqwest::Client::builder()).map_err(failed)?);
+    Ok(client)
+}
+
+/// The endpoint of a signal, with the signal path appended to the generic and default endpoints.
 fn resolve_http_endpoint(
     signal_endpoint_var: &str,
     signal_endpoint_path: &str,
     provided_endpoint: Option<String>,
 ) -> Result<Uri, crate::Error> {
-    // per signal env var is not modified
-    if let Some(endpoint) = env::var(signal_endpoint_var)
-        .ok()
-        .and_then(|s| s.parse().ok())
-    {
-        return Ok(endpoint);
-    }
-
-    // if signal env var is not set, then we check if the OTEL_EXPORTER_OTLP_ENDPOINT is set
-    if let Some(endpoint) = env::var(OTEL_EXPORTER_OTLP_ENDPOINT)
-        .ok()
-        .and_then(|s| build_endpoint_uri(&s, signal_endpoint_path).ok())
-    {
-        return Ok(endpoint);
-    }
-
-    provided_endpoint
-        .map(|e| e.parse().map_err(From::from))
-        .unwrap_or_else(|| {
-            build_endpoint_uri(
-                OTEL_EXPORTER_OTLP_HTTP_ENDPOINT_DEFAULT,
-                signal_endpoint_path,
-            )
-        })
+    resolve_signal_endpoint(
+        signal_endpoint_var,
+        signal_endpoint_path,
+        provided_endpoint,
+        OTEL_EXPORTER_OTLP_HTTP_ENDPOINT_DEFAULT,
+    )
+    .parse()
+    .map_err(From::from)
 }
 
 #[allow(clippy::mutable_key_type)] // http headers are not mutated
@@ -434,6 +1074,136 @@ pub trait WithHttpConfig {
 
     /// Set additional headers to send to the collector.
     fn with_headers(self, headers: HashMap<String, String>) -> Self;
+
+    /// Set additional headers sent with the exports of spans only, e.g. to route them to
+    /// another tenant of the same backend.
+    ///
+    /// The headers are layered on top of the ones set with
+    /// [`with_headers`](WithHttpConfig::with_headers), overriding headers with the same name.
+    /// Headers read from the environment still take precedence.
+    fn with_trace_headers(self, headers: HashMap<String, String>) -> Self;
+
+    /// Set additional headers sent with the exports of metrics only, see
+    /// [`with_trace_headers`](WithHttpConfig::with_trace_headers).
+    fn with_metrics_headers(self, headers: HashMap<String, String>) -> Self;
+
+    /// Set additional headers sent with the exports of logs only, see
+    /// [`with_trace_headers`](WithHttpConfig::with_trace_headers).
+    fn with_logs_headers(self, headers: HashMap<String, String>) -> Self;
+
+    /// Keep the metrics of exports rejected with one of the `statuses` for the next export, with
+    /// `with_metric_accumulation_on_failure` enabled, in addition to those rejected with `429`,
+    /// `502`, `503` or `504`, the retryable statuses of the OTLP specification. Meant for
+    /// gateways rejecting exports with nonstandard codes during transient conditions.
+    ///
+    /// Only the metric accumulation uses the statuses: the exporter doesn't resend rejected
+    /// requests itself, and the exports of spans and logs are unaffected.
+    fn with_metric_accumulation_http_status(self, statuses: HashSet<u16>) -> Self;
+
+    /// Transform the serialized payload of every export request before it is sent, e.g. to
+    /// encrypt it or to wrap it in the envelope expected by a gateway.
+    ///
+    /// The transform is applied to the protobuf or JSON encoded request. The `Content-Type`
+    /// header is left untouched, so the collector, or a proxy in front of it, must reverse the
+    /// transform before it can decode the request.
+    fn with_payload_transform<F>(self, transform: F) -> Self
+    where
+        F: Fn(Vec<u8>) -> Vec<u8> + Send + Sync + 'static;
+
+    /// Compress the body of every export request with `compression`, sent with the matching
+    /// `Content-Encoding` header. Takes precedence over the `OTEL_EXPORTER_OTLP_COMPRESSION`
+    /// environment variable and the variables of the single signals.
+    ///
+    /// Only gzip is supported, with the `gzip-http` feature; building the exporter fails
+    /// without it, while gzip set in the environment only warns and exports uncompressed. The
+    /// body is compressed after the
+    /// [payload transform](WithHttpConfig::with_payload_transform) is applied.
+    fn with_compression(self, compression: Compression) -> Self;
+
+    /// Set the level the request bodies are compressed at, from `0` to `9` for gzip, with
+    /// higher levels compressing better but slower. Building the exporter fails with
+    /// [`Error::UnsupportedCompressionLevel`](crate::Error::UnsupportedCompressionLevel) if the
+    /// level is out of range. By default the bodies are compressed at the default level of
+    /// gzip, `6`, as they are when the compression is only set by the environment. The level
+    /// has no effect on uncompressed bodies.
+    fn with_compression_level(self, level: i32) -> Self;
+
+    /// Call `provider` before every export for the bearer token sent in the `authorization`
+    /// header, e.g. to refresh short-lived OAuth tokens. The token replaces an `authorization`
+    /// header of the [configured headers](WithHttpConfig::with_headers).
+    ///
+    /// The provider is called once per export and should cache the token until it expires. The
+    /// export fails with [`Error::AuthProviderFailed`](crate::Error::AuthProviderFailed),
+    /// without being sent, if the provider fails.
+    fn with_auth_provider<F, Fut, E>(self, provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, E>> + Send + 'static,
+        E: Display;
+
+    /// Sign the body of every export request with `signer`, for gateways authenticating
+    /// requests by an HMAC over their body.
+    ///
+    /// The signature header is added after the [configured headers](WithHttpConfig::with_headers),
+    /// replacing any header of the same name.
+    #[cfg(feature = "request-signing")]
+    fn with_request_signer(self, signer: RequestSigner) -> Self;
+
+    /// Authenticate with the PEM encoded client certificate chain `cert_pem` and its private
+    /// key `key_pem`, for collectors requiring mutual TLS. PKCS#8 and RSA keys are supported.
+    ///
+    /// The exporter sends the requests with a reqwest client presenting the certificate, which
+    /// replaces the [HTTP client](WithHttpConfig::with_http_client). Other clients must be
+    /// configured with the certificate themselves. Building the exporter fails with
+    /// [`Error::InvalidClientIdentity`](crate::Error::InvalidClientIdentity) if the certificate
+    /// or the key doesn't parse, or the key doesn't match the certificate.
+    #[cfg(feature = "reqwest-rustls")]
+    fn with_tls_client_auth(
+        self,
+        cert_pem: impl Into<Vec<u8>>,
+        key_pem: impl Into<Vec<u8>>,
+    ) -> Self;
+
+    /// Verify the certificate of the collector for `domain_name`, also sent as SNI, while still
+    /// connecting to the host of the endpoint, e.g. an IP address.
+    ///
+    /// The host of the endpoint is resolved when the exporter is built, and the requests are
+    /// sent to `domain_name` resolved to its addresses, so the `Host` header carries the domain
+    /// name too. Like [`with_tls_client_auth`](WithHttpConfig::with_tls_client_auth), the
+    /// exporter sends the requests with a reqwest client replacing the
+    /// [HTTP client](WithHttpConfig::with_http_client).
+    #[cfg(feature = "reqwest-rustls")]
+    fn with_tls_domain_name<T: Into<String>>(self, domain_name: T) -> Self;
+
+    /// **Dangerous:** accept any certificate of the collector, without verifying it was issued
+    /// for the collector by a trusted authority, e.g. for local development against a collector
+    /// with a self-signed certificate.
+    ///
+    /// Anyone able to intercept the connection can read and alter the exported telemetry, so
+    /// never enable this in production. A warning is logged when an exporter skipping the
+    /// verification is built. Like
+    /// [`with_tls_client_auth`](WithHttpConfig::with_tls_client_auth), the exporter sends the
+    /// requests with a reqwest client replacing the
+    /// [HTTP client](WithHttpConfig::with_http_client). Deliberately not configurable with an
+    /// environment variable, and only available with the `dangerous-configuration` feature.
+    #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+    fn with_tls_insecure_skip_verify(self, skip: bool) -> Self;
+
+    /// Send the requests through the HTTP proxy at `url`, e.g. `http://proxy.internal:3128`,
+    /// tunneling `https` requests with `CONNECT`. Requests to the hosts listed in the `NO_PROXY`
+    /// environment variable still bypass the proxy, so traffic within the cluster isn't
+    /// proxied.
+    ///
+    /// Without an explicit proxy, the reqwest clients of the exporter already honor the
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment variables. Like
+    /// [`with_tls_client_auth`](WithHttpConfig::with_tls_client_auth), the exporter sends the
+    /// requests with a reqwest client replacing the
+    /// [HTTP client](WithHttpConfig::with_http_client). Other clients must be configured with
+    /// the proxy themselves, e.g. with [`reqwest::ClientBuilder::proxy`]. Building the exporter
+    /// fails with [`Error::InvalidProxy`](crate::Error::InvalidProxy) if `url` isn't a valid
+    /// proxy URL.
+    #[cfg(feature = "reqwest")]
+    fn with_proxy<T: Into<String>>(self, url: T) -> Self;
 }
 
 impl<B: HasHttpConfig> WithHttpConfig for B {
@@ -453,6 +1223,120 @@ impl<B: HasHttpConfig> WithHttpConfig for B {
             });
         self
     }
+
+    fn with_trace_headers(self, headers: HashMap<String, String>) -> Self {
+        with_signal_headers(self, Signal::Traces, headers)
+    }
+
+    fn with_metrics_headers(self, headers: HashMap<String, String>) -> Self {
+        with_signal_headers(self, Signal::Metrics, headers)
+    }
+
+    fn with_logs_headers(self, headers: HashMap<String, String>) -> Self {
+        with_signal_headers(self, Signal::Logs, headers)
+    }
+
+    fn with_payload_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(Vec<u8>) -> Vec<u8> + Send + Sync + 'static,
+    {
+        self.http_client_config().payload_transform = Some(PayloadTransform(Arc::new(transform)));
+        self
+    }
+
+    fn with_metric_accumulation_http_status(mut self, statuses: HashSet<u16>) -> Self {
+        self.http_client_config()
+            .retryable_statuses
+            .extend(statuses);
+        self
+    }
+
+    fn with_compression(mut self, compression: Compression) -> Self {
+        self.http_client_config().compression = Some(compression);
+        self
+    }
+
+    fn with_compression_level(mut self, level: i32) -> Self {
+        self.http_client_config().compression_level = Some(level);
+        self
+    }
+
+    fn with_auth_provider<F, Fut, E>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, E>> + Send + 'static,
+        E: Display,
+    {
+        self.http_client_config().auth_provider = Some(AuthProvider::new(provider));
+        self
+    }
+
+    #[cfg(feature = "request-signing")]
+    fn with_request_signer(mut self, signer: RequestSigner) -> Self {
+        self.http_client_config().request_signer = Some(signer);
+        self
+    }
+
+    #[cfg(feature = "reqwest-rustls")]
+    fn with_tls_client_auth(
+        mut self,
+        cert_pem: impl Into<Vec<u8>>,
+        key_pem: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.http_client_config().client_identity =
+            Some(ClientIdentity::new(cert_pem.into(), key_pem.into()));
+        self
+    }
+
+    #[cfg(feature = "reqwest-rustls")]
+    fn with_tls_domain_name<T: Into<String>>(mut self, domain_name: T) -> Self {
+        self.http_client_config().tls_domain_name = Some(domain_name.into());
+        self
+    }
+
+    #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+    fn with_tls_insecure_skip_verify(mut self, skip: bool) -> Self {
+        self.http_client_config().tls_insecure_skip_verify = skip;
+        self
+    }
+
+    #[cfg(feature = "reqwest")]
+    fn with_proxy<T: Into<String>>(mut self, url: T) -> Self {
+        self.http_client_config().proxy = Some(url.into());
+        self
+    }
+}
+
+/// Compress a request body with gzip.
+#[cfg(feature = "gzip-http")]
+fn gzip(body: &[u8], level: Option<u32>) -> Vec<u8> {
+    use std::io::Write;
+
+    let level = level.map_or_else(flate2::Compression::default, flate2::Compression::new);
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+    encoder
+        .write_all(body)
+        .and_then(|()| encoder.finish())
+        .expect("writing to a Vec doesn't fail")
+}
+
+/// Add `headers` to the headers of `signal`, decoding their values like
+/// [`with_headers`](WithHttpConfig::with_headers).
+fn with_signal_headers<B: HasHttpConfig>(
+    mut builder: B,
+    signal: Signal,
+    headers: HashMap<String, String>,
+) -> B {
+    builder
+        .http_client_config()
+        .signal_headers
+        .entry(signal)
+        .or_default()
+        .extend(headers.into_iter().map(|(key, value)| {
+            let value = super::url_decode(&value).unwrap_or(value);
+            (key, value)
+        }));
+    builder
 }
 
 #[cfg(test)]
@@ -461,10 +1345,113 @@ mod tests {
     use crate::exporter::tests::run_env_test;
     use crate::{
         HttpExporterBuilder, WithExportConfig, WithHttpConfig, OTEL_EXPORTER_OTLP_ENDPOINT,
-        OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+        OTEL_EXPORTER_OTLP_HEADERS, OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+        OTEL_EXPORTER_OTLP_TRACES_HEADERS,
     };
+    use std::collections::HashMap;
+
+    use super::resolve_http_endpoint;
+
+    #[test]
+    #[cfg(not(feature = "gzip-http"))]
+    fn test_gzip_from_env_without_feature_exports_uncompressed() {
+        use crate::{Compression, OTEL_EXPORTER_OTLP_COMPRESSION};
+
+        run_env_test(vec![(OTEL_EXPORTER_OTLP_COMPRESSION, "gzip")], || {
+            let compression = HttpExporterBuilder::default()
+                .resolve_compression("OTEL_EXPORTER_OTLP_TRACES_COMPRESSION")
+                .unwrap();
+            assert_eq!(compression, None);
+
+            // set explicitly, the missing feature still fails the exporter
+            let explicit = HttpExporterBuilder::default()
+                .with_compression(Compression::Gzip)
+                .resolve_compression("OTEL_EXPORTER_OTLP_TRACES_COMPRESSION");
+            assert!(matches!(
+                explicit,
+                Err(crate::Error::FeatureRequiredForCompressionAlgorithm(..))
+            ));
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "reqwest-rustls")]
+    fn test_override_domain_name() {
+        let endpoint = "https://127.0.0.1:4318/v1/traces".parse().unwrap();
+        let (endpoint, addrs) =
+            super::override_domain_name(endpoint, "collector.internal").unwrap();
+        assert_eq!(endpoint, "https://collector.internal:4318/v1/traces");
+        assert_eq!(addrs, vec!["127.0.0.1:4318".parse().unwrap()]);
+    }
+
+    #[cfg(all(
+        feature = "trace",
+        any(feature = "reqwest-client", feature = "reqwest-blocking-client")
+    ))]
+    #[test]
+    fn test_requests_are_sent_through_proxy() {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        use crate::{WithExportConfig, WithHttpConfig};
+
+        // answers a single request, returning its request line
+        let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = proxy.local_addr().unwrap();
+        let request_line = std::thread::spawn(move || {
+            let (stream, _) = proxy.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+            request_line
+        });
+
+        let mut exporter = crate::SpanExporter::builder()
+            .with_http()
+            .with_endpoint("http://collector.invalid:4318/v1/traces")
+            .with_proxy(format!("http://{addr}"))
+            .build()
+            .unwrap();
+        // the blocking client sends the request within `export`, the async one needs a runtime
+        #[cfg(feature = "reqwest-blocking-client")]
+        let result = futures_util::FutureExt::now_or_never(exporter.export(vec![]))
+            .expect("the blocking client completes the export");
+        #[cfg(not(feature = "reqwest-blocking-client"))]
+        let result = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(exporter.export(vec![]));
+        result.unwrap();
+        assert_eq!(
+            request_line.join().unwrap(),
+            "POST http://collector.invalid:4318/v1/traces HTTP/1.1\r\n"
+        );
 
-    use super::{build_endpoint_uri, resolve_http_endpoint};
+        let invalid = crate::SpanExporter::builder()
+            .with_http()
+            .with_proxy("not a proxy")
+            .build();
+        assert!(matches!(invalid, Err(error) if error.to_string().contains("invalid proxy")));
+    }
 
     #[test]
     fn test_append_signal_path_to_generic_env() {
@@ -530,20 +1517,6 @@ mod tests {
         });
     }
 
-    #[test]
-    fn test_build_endpoint_uri() {
-        let uri = build_endpoint_uri("https://example.com", "/v1/traces").unwrap();
-        assert_eq!(uri, "https://example.com/v1/traces");
-
-        // Should be no duplicate slahes:
-        let uri = build_endpoint_uri("https://example.com/", "/v1/traces").unwrap();
-        assert_eq!(uri, "https://example.com/v1/traces");
-
-        // Append paths properly:
-        let uri = build_endpoint_uri("https://example.com/additional/path/", "/v1/traces").unwrap();
-        assert_eq!(uri, "https://example.com/additional/path/v1/traces");
-    }
-
     #[test]
     fn test_invalid_uri_in_signal_env_falls_back_to_generic_env() {
         run_env_test(
@@ -682,6 +1655,22 @@ mod tests {
             http_config: HttpConfig {
                 client: None,
                 headers: Some(initial_headers),
+                signal_headers: HashMap::new(),
+                payload_transform: None,
+                retryable_statuses: Default::default(),
+                compression: None,
+                compression_level: None,
+                auth_provider: None,
+                #[cfg(feature = "request-signing")]
+                request_signer: None,
+                #[cfg(feature = "reqwest-rustls")]
+                client_identity: None,
+                #[cfg(feature = "reqwest-rustls")]
+                tls_domain_name: None,
+                #[cfg(all(feature = "reqwest-rustls", feature = "dangerous-configuration"))]
+                tls_insecure_skip_verify: false,
+                #[cfg(feature = "reqwest")]
+                proxy: None,
             },
             exporter_config: crate::ExportConfig::default(),
         };
@@ -700,6 +1689,1037 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_signal_headers_apply_to_their_signal_only() {
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+
+        #[derive(Debug)]
+        struct NoopCollector;
+
+        #[async_trait::async_trait]
+        impl HttpClient for NoopCollector {
+            async fn send(&self, _request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        run_env_test(vec![], || {
+            let builder = || {
+                HttpExporterBuilder::default()
+                    .with_headers(HashMap::from([(
+                        "tenant".to_string(),
+                        "shared".to_string(),
+                    )]))
+                    .with_trace_headers(HashMap::from([(
+                        "tenant".to_string(),
+                        "traces".to_string(),
+                    )]))
+                    .with_http_client(NoopCollector)
+            };
+            let header = |client: &super::OtlpHttpClient, name: &'static str| {
+                client
+                    .headers
+                    .get(&http::HeaderName::from_static(name))
+                    .map(|value| value.to_str().unwrap().to_string())
+            };
+
+            let traces = builder()
+                .build_client(
+                    crate::Signal::Traces,
+                    OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+                    "/v1/traces",
+                    "",
+                    OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+                    "",
+                )
+                .unwrap();
+            assert_eq!(header(&traces, "tenant").as_deref(), Some("traces"));
+            assert!(header(&traces, "user-agent").is_some());
+
+            let metrics = builder()
+                .build_client(
+                    crate::Signal::Metrics,
+                    crate::OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
+                    "/v1/metrics",
+                    "",
+                    crate::OTEL_EXPORTER_OTLP_METRICS_HEADERS,
+                    "",
+                )
+                .unwrap();
+            assert_eq!(header(&metrics, "tenant").as_deref(), Some("shared"));
+            assert!(header(&metrics, "user-agent").is_some());
+        });
+    }
+
+    #[test]
+    fn test_too_many_headers() {
+        run_env_test(vec![(OTEL_EXPORTER_OTLP_HEADERS, "k1=v1,k2=v2")], || {
+            // the default user agent, one configured header and two headers from the env
+            let mut builder = HttpExporterBuilder::default()
+                .with_headers(HashMap::from([("k3".to_string(), "v3".to_string())]))
+                .with_max_headers(3);
+
+            let result = builder.build_client(
+                crate::Signal::Traces,
+                OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+                "/v1/traces",
+                "",
+                OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+                "",
+            );
+
+            assert!(matches!(
+                result.err(),
+                Some(crate::Error::TooManyHeaders { count: 4, max: 3 })
+            ));
+        });
+    }
+
+    #[test]
+    fn test_success_response_with_warning_is_surfaced() {
+        use opentelemetry_proto::tonic::collector::trace::v1::{
+            ExportTracePartialSuccess, ExportTraceServiceResponse,
+        };
+        use prost::Message;
+
+        let response = ExportTraceServiceResponse {
+            partial_success: Some(ExportTracePartialSuccess {
+                rejected_spans: 0,
+                error_message: "attribute limit exceeded".to_string(),
+            }),
+        };
+
+        assert_eq!(
+            super::handle_export_response::<ExportTraceServiceResponse>(
+                crate::Protocol::HttpBinary,
+                &response.encode_to_vec(),
+                &super::ErrorLog::default(),
+            ),
+            Some((0, "attribute limit exceeded".to_string()))
+        );
+
+        // an empty partial success or body is a full success
+        let response = ExportTraceServiceResponse {
+            partial_success: Some(ExportTracePartialSuccess::default()),
+        };
+        assert_eq!(
+            super::handle_export_response::<ExportTraceServiceResponse>(
+                crate::Protocol::HttpBinary,
+                &response.encode_to_vec(),
+                &super::ErrorLog::default(),
+            ),
+            None
+        );
+        assert_eq!(
+            super::handle_export_response::<ExportTraceServiceResponse>(
+                crate::Protocol::HttpBinary,
+                &[],
+                &super::ErrorLog::default(),
+            ),
+            None
+        );
+    }
+
+    #[cfg(feature = "http-json")]
+    #[test]
+    fn test_success_json_response_with_warning_is_surfaced() {
+        use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceResponse;
+
+        let body = br#"{"partialSuccess":{"rejectedSpans":2,"errorMessage":"invalid span id"}}"#;
+
+        assert_eq!(
+            super::handle_export_response::<ExportTraceServiceResponse>(
+                crate::Protocol::HttpJson,
+                body,
+                &super::ErrorLog::default(),
+            ),
+            Some((2, "invalid span id".to_string()))
+        );
+    }
+
+    #[cfg(feature = "trace")]
+    #[tokio::test]
+    async fn test_payload_transform() {
+        use opentelemetry::trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceId};
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+        use opentelemetry_sdk::export::trace::{SpanData, SpanExporter};
+        use prost::Message;
+        use std::sync::{Arc, Mutex};
+        use std::time::SystemTime;
+
+        fn xor(body: Vec<u8>) -> Vec<u8> {
+            body.into_iter().map(|byte| byte ^ 0x5a).collect()
+        }
+
+        /// Reverses the transform and decodes the request, like a collector would.
+        #[derive(Clone, Debug, Default)]
+        struct DecodingCollector(Arc<Mutex<Vec<ExportTraceServiceRequest>>>);
+
+        #[async_trait::async_trait]
+        impl HttpClient for DecodingCollector {
+            async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                let decoded = ExportTraceServiceRequest::decode(&*xor(request.into_body()))?;
+                self.0.lock().unwrap().push(decoded);
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        let collector = DecodingCollector::default();
+        let mut exporter = HttpExporterBuilder::default()
+            .with_protocol(crate::Protocol::HttpBinary)
+            .with_http_client(collector.clone())
+            .with_payload_transform(xor)
+            .build_client(
+                crate::Signal::Traces,
+                OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+                "/v1/traces",
+                "",
+                OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+                "",
+            )
+            .unwrap();
+
+        let now = SystemTime::now();
+        let span = SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(1),
+                SpanId::from(1),
+                TraceFlags::SAMPLED,
+                false,
+                Default::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Internal,
+            name: "transformed".into(),
+            start_time: now,
+            end_time: now,
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: Default::default(),
+            instrumentation_scope: Default::default(),
+        };
+        exporter.export(vec![span]).await.unwrap();
+
+        let requests = collector.0.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let span = &requests[0].resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.name, "transformed");
+    }
+
+    /// Records the URI, content type and body of every request.
+    #[cfg(feature = "http-json")]
+    #[derive(Clone, Debug, Default)]
+    struct JsonCollector(
+        std::sync::Arc<std::sync::Mutex<Vec<(String, String, serde_json::Value)>>>,
+    );
+
+    #[cfg(feature = "http-json")]
+    #[async_trait::async_trait]
+    impl opentelemetry_http::HttpClient for JsonCollector {
+        async fn send(
+            &self,
+            request: opentelemetry_http::Request<Vec<u8>>,
+        ) -> Result<
+            opentelemetry_http::Response<opentelemetry_http::Bytes>,
+            opentelemetry_http::HttpError,
+        > {
+            let uri = request.uri().to_string();
+            let content_type = request.headers()[http::header::CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = serde_json::from_slice(request.body())?;
+            self.0.lock().unwrap().push((uri, content_type, body));
+            Ok(opentelemetry_http::Response::new(Default::default()))
+        }
+    }
+
+    #[cfg(feature = "http-json")]
+    #[tokio::test]
+    async fn test_json_trace_export() {
+        use crate::SpanExporter;
+        use opentelemetry::trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceId};
+        use opentelemetry::KeyValue;
+        use opentelemetry_sdk::export::trace::{SpanData, SpanExporter as _};
+        use std::time::SystemTime;
+
+        let collector = JsonCollector::default();
+        let mut exporter = SpanExporter::builder()
+            .with_http()
+            .with_protocol(crate::Protocol::HttpJson)
+            .with_http_client(collector.clone())
+            .build()
+            .unwrap();
+
+        let now = SystemTime::now();
+        let span = SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(0x5b8efff798038103d269b633813fc60c),
+                SpanId::from(0xeee19b7ec3c1b174),
+                TraceFlags::SAMPLED,
+                false,
+                Default::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Server,
+            name: "json".into(),
+            start_time: now,
+            end_time: now,
+            attributes: vec![KeyValue::new("retries", 6)],
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: Default::default(),
+            instrumentation_scope: Default::default(),
+        };
+        exporter.export(vec![span]).await.unwrap();
+
+        let requests = collector.0.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (uri, content_type, body) = &requests[0];
+        assert!(uri.ends_with("/v1/traces"), "{uri}");
+        assert_eq!(content_type, "application/json");
+        let span = &body["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], "5b8efff798038103d269b633813fc60c");
+        assert_eq!(span["spanId"], "eee19b7ec3c1b174");
+        assert_eq!(span["kind"], 2);
+        assert_eq!(span["attributes"][0]["value"]["intValue"], "6");
+        assert!(span["startTimeUnixNano"].is_string());
+    }
+
+    #[cfg(feature = "http-json")]
+    #[tokio::test]
+    async fn test_json_metrics_export() {
+        use crate::MetricExporter;
+        use opentelemetry::{InstrumentationScope, KeyValue};
+        use opentelemetry_sdk::metrics::data::{
+            Metric, ResourceMetrics, ScopeMetrics, Sum, SumDataPoint,
+        };
+        use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+        use opentelemetry_sdk::metrics::Temporality;
+        use opentelemetry_sdk::Resource;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let collector = JsonCollector::default();
+        let exporter = MetricExporter::builder()
+            .with_http()
+            .with_protocol(crate::Protocol::HttpJson)
+            .with_http_client(collector.clone())
+            .build()
+            .unwrap();
+
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: vec![ScopeMetrics {
+                scope: InstrumentationScope::builder("test").build(),
+                metrics: vec![Metric {
+                    name: "requests".into(),
+                    description: "".into(),
+                    unit: "".into(),
+                    data: Box::new(Sum {
+                        data_points: vec![SumDataPoint {
+                            attributes: vec![KeyValue::new("route", "/")],
+                            start_time: UNIX_EPOCH,
+                            time: UNIX_EPOCH + Duration::from_secs(1),
+                            value: 6u64,
+                            exemplars: vec![],
+                        }],
+                        temporality: Temporality::Cumulative,
+                        is_monotonic: true,
+                    }),
+                }],
+            }],
+        };
+        exporter.export(&mut metrics).await.unwrap();
+
+        let requests = collector.0.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (uri, content_type, body) = &requests[0];
+        assert!(uri.ends_with("/v1/metrics"), "{uri}");
+        assert_eq!(content_type, "application/json");
+        let metric = &body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"][0];
+        assert_eq!(metric["name"], "requests");
+        assert_eq!(metric["sum"]["aggregationTemporality"], 2);
+        let point = &metric["sum"]["dataPoints"][0];
+        assert_eq!(point["attributes"][0]["value"]["stringValue"], "/");
+        assert_eq!(point["timeUnixNano"], "1000000000");
+    }
+
+    #[cfg(all(feature = "http-json", feature = "logs"))]
+    #[tokio::test]
+    async fn test_json_logs_export() {
+        use crate::LogExporter;
+        use opentelemetry::logs::AnyValue;
+        use opentelemetry::InstrumentationScope;
+        use opentelemetry_sdk::export::logs::{LogBatch, LogExporter as _};
+        use opentelemetry_sdk::logs::LogRecord;
+
+        let collector = JsonCollector::default();
+        let exporter = LogExporter::builder()
+            .with_http()
+            .with_protocol(crate::Protocol::HttpJson)
+            .with_http_client(collector.clone())
+            .build()
+            .unwrap();
+
+        let scope = InstrumentationScope::builder("test").build();
+        let mut record = LogRecord::default();
+        record.body = Some(AnyValue::Bytes(Box::new(b"otel".to_vec())));
+        exporter
+            .export(LogBatch::new(&[(&record, &scope)]))
+            .await
+            .unwrap();
+
+        let requests = collector.0.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (uri, content_type, body) = &requests[0];
+        assert!(uri.ends_with("/v1/logs"), "{uri}");
+        assert_eq!(content_type, "application/json");
+        let record = &body["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        // bytes are base64 encoded
+        assert_eq!(record["body"]["bytesValue"], "b3RlbA==");
+    }
+
+    #[cfg(feature = "request-signing")]
+    #[tokio::test]
+    async fn test_request_signature_header() {
+        use crate::{HmacAlgorithm, MetricExporter, RequestSigner, SignedPayload};
+        use hmac::{Hmac, Mac};
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_sdk::metrics::data::ResourceMetrics;
+        use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+        use opentelemetry_sdk::Resource;
+        use sha2::Sha256;
+        use std::sync::{Arc, Mutex};
+
+        fn xor(body: Vec<u8>) -> Vec<u8> {
+            body.into_iter().map(|byte| byte ^ 0x5a).collect()
+        }
+
+        fn hmac_hex(body: &[u8]) -> String {
+            let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+            mac.update(body);
+            mac.finalize()
+                .into_bytes()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect()
+        }
+
+        /// The bodies of requests and their signature headers.
+        type SignedRequests = Arc<Mutex<Vec<(Vec<u8>, String)>>>;
+
+        /// Records the body and signature header of every request.
+        #[derive(Clone, Debug, Default)]
+        struct SigningGateway(SignedRequests);
+
+        #[async_trait::async_trait]
+        impl HttpClient for SigningGateway {
+            async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                let signature = request.headers()["x-gateway-signature"]
+                    .to_str()?
+                    .to_string();
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((request.into_body(), signature));
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        async fn export(payload: SignedPayload) -> (Vec<u8>, String) {
+            let gateway = SigningGateway::default();
+            let exporter = MetricExporter::builder()
+                .with_http()
+                .with_protocol(crate::Protocol::HttpBinary)
+                .with_http_client(gateway.clone())
+                .with_headers(HashMap::from([(
+                    "x-gateway-signature".to_string(),
+                    "overridden".to_string(),
+                )]))
+                .with_payload_transform(xor)
+                .with_request_signer(
+                    RequestSigner::new("secret", HmacAlgorithm::Sha256)
+                        .with_header(http::HeaderName::from_static("x-gateway-signature"))
+                        .with_signed_payload(payload),
+                )
+                .build()
+                .unwrap();
+            let mut metrics = ResourceMetrics {
+                resource: Resource::empty(),
+                scope_metrics: vec![],
+            };
+            exporter.export(&mut metrics).await.unwrap();
+            let mut requests = gateway.0.lock().unwrap();
+            assert_eq!(requests.len(), 1);
+            requests.remove(0)
+        }
+
+        let (transmitted, signature) = export(SignedPayload::Serialized).await;
+        assert_eq!(signature, hmac_hex(&xor(transmitted)));
+
+        let (transmitted, signature) = export(SignedPayload::Transmitted).await;
+        assert_eq!(signature, hmac_hex(&transmitted));
+    }
+
+    #[cfg(feature = "gzip-http")]
+    #[tokio::test]
+    async fn test_gzip_compressed_body() {
+        use crate::{Compression, MetricExporter};
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_sdk::metrics::data::ResourceMetrics;
+        use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+        use opentelemetry_sdk::Resource;
+        use std::io::Read;
+        use std::sync::{Arc, Mutex};
+
+        /// The bodies of requests and their content encodings.
+        type EncodedRequests = Arc<Mutex<Vec<(Vec<u8>, Option<String>)>>>;
+
+        /// Records the body and content encoding of every request.
+        #[derive(Clone, Debug, Default)]
+        struct Collector(EncodedRequests);
+
+        #[async_trait::async_trait]
+        impl HttpClient for Collector {
+            async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                let encoding = request
+                    .headers()
+                    .get(http::header::CONTENT_ENCODING)
+                    .map(|value| value.to_str().unwrap().to_string());
+                self.0.lock().unwrap().push((request.into_body(), encoding));
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        async fn export(compression: Option<Compression>) -> (Vec<u8>, Option<String>) {
+            let collector = Collector::default();
+            let mut builder = MetricExporter::builder()
+                .with_http()
+                .with_protocol(crate::Protocol::HttpBinary)
+                .with_http_client(collector.clone());
+            if let Some(compression) = compression {
+                builder = builder.with_compression(compression);
+            }
+            let exporter = builder.build().unwrap();
+            let mut metrics = ResourceMetrics {
+                resource: Resource::new([opentelemetry::KeyValue::new("service.name", "test")]),
+                scope_metrics: vec![],
+            };
+            exporter.export(&mut metrics).await.unwrap();
+            let mut requests = collector.0.lock().unwrap();
+            assert_eq!(requests.len(), 1);
+            requests.remove(0)
+        }
+
+        let (plain, encoding) = export(None).await;
+        assert_eq!(encoding, None);
+
+        let (compressed, encoding) = export(Some(Compression::Gzip)).await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, plain);
+
+        let unsupported = MetricExporter::builder()
+            .with_http()
+            .with_http_client(Collector::default())
+            .with_compression(Compression::Zstd)
+            .build();
+        assert!(unsupported.is_err());
+
+        let out_of_range = MetricExporter::builder()
+            .with_http()
+            .with_http_client(Collector::default())
+            .with_compression(Compression::Gzip)
+            .with_compression_level(10)
+            .build();
+        assert!(out_of_range.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_auth_provider_token_per_export() {
+        use crate::MetricExporter;
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_sdk::metrics::data::ResourceMetrics;
+        use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+        use opentelemetry_sdk::Resource;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        /// Records the `authorization` header of every request.
+        #[derive(Clone, Debug, Default)]
+        struct AuthorizingCollector(Arc<Mutex<Vec<String>>>);
+
+        #[async_trait::async_trait]
+        impl HttpClient for AuthorizingCollector {
+            async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                let authorization = request.headers()[http::header::AUTHORIZATION].to_str()?;
+                self.0.lock().unwrap().push(authorization.to_string());
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        let collector = AuthorizingCollector::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider_calls = calls.clone();
+        let exporter = MetricExporter::builder()
+            .with_http()
+            .with_protocol(crate::Protocol::HttpBinary)
+            .with_http_client(collector.clone())
+            .with_headers(HashMap::from([(
+                "authorization".to_string(),
+                "Bearer static".to_string(),
+            )]))
+            .with_auth_provider(move || {
+                let call = provider_calls.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    match call {
+                        2 => Err("token endpoint unavailable"),
+                        _ => Ok(format!("token-{call}")),
+                    }
+                }
+            })
+            .build()
+            .unwrap();
+
+        let mut metrics = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: vec![],
+        };
+        exporter.export(&mut metrics).await.unwrap();
+        let error = exporter.export(&mut metrics).await.unwrap_err();
+        assert!(error.to_string().contains("token endpoint unavailable"));
+        exporter.export(&mut metrics).await.unwrap();
+
+        // the token replaces the configured header, and failed exports aren't sent
+        assert_eq!(
+            *collector.0.lock().unwrap(),
+            vec!["Bearer token-1".to_string(), "Bearer token-3".to_string()]
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[cfg(feature = "trace")]
+    #[tokio::test]
+    async fn test_partial_success_fails_export_if_configured() {
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_proto::tonic::collector::trace::v1::{
+            ExportTracePartialSuccess, ExportTraceServiceResponse,
+        };
+        use opentelemetry_sdk::export::trace::SpanExporter;
+        use prost::Message;
+
+        /// Rejects some spans of every export.
+        #[derive(Debug)]
+        struct RejectingCollector;
+
+        #[async_trait::async_trait]
+        impl HttpClient for RejectingCollector {
+            async fn send(&self, _request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                let response = ExportTraceServiceResponse {
+                    partial_success: Some(ExportTracePartialSuccess {
+                        rejected_spans: 2,
+                        error_message: "spans too old".to_string(),
+                    }),
+                };
+                Ok(Response::new(response.encode_to_vec().into()))
+            }
+        }
+
+        let export = |fail| {
+            let mut exporter = HttpExporterBuilder::default()
+                .with_protocol(crate::Protocol::HttpBinary)
+                .with_http_client(RejectingCollector)
+                .with_fail_on_partial_success(fail)
+                .build_client(
+                    crate::Signal::Traces,
+                    OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+                    "/v1/traces",
+                    "",
+                    OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+                    "",
+                )
+                .unwrap();
+            exporter.export(vec![])
+        };
+
+        export(false).await.unwrap();
+        let error = export(true).await.unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("the collector rejected 2 items: spans too old"),
+            "{error}"
+        );
+    }
+
+    #[cfg(feature = "trace")]
+    #[tokio::test]
+    async fn test_retry_after_pauses_exports() {
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_sdk::export::trace::SpanExporter;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Rate-limits every export for a minute.
+        #[derive(Clone, Debug, Default)]
+        struct RateLimitingCollector(Arc<AtomicUsize>);
+
+        #[async_trait::async_trait]
+        impl HttpClient for RateLimitingCollector {
+            async fn send(&self, _request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(Response::builder()
+                    .status(http::StatusCode::TOO_MANY_REQUESTS)
+                    .header(http::header::RETRY_AFTER, "60")
+                    .body(Bytes::new())?)
+            }
+        }
+
+        let collector = RateLimitingCollector::default();
+        let mut exporter = HttpExporterBuilder::default()
+            .with_protocol(crate::Protocol::HttpBinary)
+            .with_http_client(collector.clone())
+            .build_client(
+                crate::Signal::Traces,
+                OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+                "/v1/traces",
+                "",
+                OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+                "",
+            )
+            .unwrap();
+
+        assert!(exporter.export(vec![]).await.is_err());
+        assert_eq!(collector.0.load(Ordering::SeqCst), 1);
+
+        // the collector isn't sent any export until the minute passed
+        let error = exporter.export(vec![]).await.unwrap_err();
+        assert!(
+            error.to_string().contains("asked not to send exports"),
+            "{error}"
+        );
+        assert_eq!(collector.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "trace")]
+    #[tokio::test]
+    async fn test_sent_payload_recorder() {
+        use opentelemetry::trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceId};
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+        use opentelemetry_sdk::export::trace::{SpanData, SpanExporter};
+        use prost::Message;
+        use std::sync::{Arc, Mutex};
+        use std::time::SystemTime;
+
+        #[derive(Debug)]
+        struct AcceptingCollector;
+
+        #[async_trait::async_trait]
+        impl HttpClient for AcceptingCollector {
+            async fn send(&self, _request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&recorded);
+        let mut exporter = HttpExporterBuilder::default()
+            .with_protocol(crate::Protocol::HttpBinary)
+            .with_http_client(AcceptingCollector)
+            .with_sent_payload_recorder(move |payload, signal| {
+                sink.lock().unwrap().push((payload.to_vec(), signal));
+            })
+            .build_client(
+                crate::Signal::Traces,
+                OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+                "/v1/traces",
+                "",
+                OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+                "",
+            )
+            .unwrap();
+
+        let now = SystemTime::now();
+        let span = SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(1),
+                SpanId::from(1),
+                TraceFlags::SAMPLED,
+                false,
+                Default::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Internal,
+            name: "recorded".into(),
+            start_time: now,
+            end_time: now,
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: Default::default(),
+            instrumentation_scope: Default::default(),
+        };
+        exporter.export(vec![span]).await.unwrap();
+
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(recorded.len(), 1);
+        let (payload, signal) = &recorded[0];
+        assert_eq!(*signal, crate::Signal::Traces);
+        let request = ExportTraceServiceRequest::decode(payload.as_slice()).unwrap();
+        let span = &request.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.name, "recorded");
+    }
+
+    #[cfg(all(feature = "trace", feature = "exporter-stats"))]
+    #[tokio::test]
+    async fn test_exporter_stats() {
+        use crate::{SpanExporter, WithHttpConfig};
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Accepts the first export, then fails the others.
+        #[derive(Clone, Debug, Default)]
+        struct FailingCollector {
+            requests: Arc<AtomicUsize>,
+            received_bytes: Arc<AtomicUsize>,
+        }
+
+        #[async_trait::async_trait]
+        impl HttpClient for FailingCollector {
+            async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                self.received_bytes
+                    .fetch_add(request.body().len(), Ordering::SeqCst);
+                let status = match self.requests.fetch_add(1, Ordering::SeqCst) {
+                    0 => http::StatusCode::OK,
+                    _ => http::StatusCode::BAD_REQUEST,
+                };
+                Ok(Response::builder().status(status).body(Bytes::new())?)
+            }
+        }
+
+        let collector = FailingCollector::default();
+        let mut exporter = SpanExporter::builder()
+            .with_http()
+            .with_protocol(crate::Protocol::HttpBinary)
+            .with_http_client(collector.clone())
+            .build()
+            .unwrap();
+
+        exporter.export(vec![]).await.unwrap();
+        assert!(exporter.export(vec![]).await.is_err());
+
+        let stats = exporter.exporter_stats();
+        assert_eq!(stats.exports, 2);
+        assert_eq!(stats.failed_exports, 1);
+        assert_eq!(stats.retries, 0);
+        assert_eq!(
+            stats.bytes_sent,
+            collector.received_bytes.load(Ordering::SeqCst) as u64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_metrics_of_failed_exports_are_accumulated() {
+        use crate::MetricExporter;
+        use opentelemetry::InstrumentationScope;
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+        use opentelemetry_proto::tonic::metrics::v1::{metric::Data, number_data_point::Value};
+        use opentelemetry_sdk::metrics::data::{
+            Metric, ResourceMetrics, ScopeMetrics, Sum, SumDataPoint,
+        };
+        use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+        use opentelemetry_sdk::metrics::Temporality;
+        use opentelemetry_sdk::Resource;
+        use prost::Message;
+        use std::borrow::Cow;
+        use std::sync::{Arc, Mutex};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        /// Rejects the first two exports as unavailable and keeps the accepted ones.
+        #[derive(Clone, Debug, Default)]
+        struct FlakyCollector(Arc<Mutex<(usize, Vec<ExportMetricsServiceRequest>)>>);
+
+        #[async_trait::async_trait]
+        impl HttpClient for FlakyCollector {
+            async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                let mut state = self.0.lock().unwrap();
+                state.0 += 1;
+                if state.0 <= 2 {
+                    let mut response = Response::new(Bytes::new());
+                    *response.status_mut() = http::StatusCode::SERVICE_UNAVAILABLE;
+                    return Ok(response);
+                }
+                state
+                    .1
+                    .push(ExportMetricsServiceRequest::decode(&**request.body())?);
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        fn sum(name: &'static str, temporality: Temporality, cycle: u64, value: u64) -> Metric {
+            Metric {
+                name: Cow::Borrowed(name),
+                description: Cow::Borrowed(""),
+                unit: Cow::Borrowed(""),
+                data: Box::new(Sum {
+                    data_points: vec![SumDataPoint {
+                        attributes: vec![],
+                        start_time: UNIX_EPOCH + Duration::from_secs(cycle),
+                        time: UNIX_EPOCH + Duration::from_secs(cycle + 1),
+                        value,
+                        exemplars: vec![],
+                    }],
+                    temporality,
+                    is_monotonic: true,
+                }),
+            }
+        }
+
+        let collector = FlakyCollector::default();
+        let exporter = MetricExporter::builder()
+            .with_http()
+            .with_protocol(crate::Protocol::HttpBinary)
+            .with_http_client(collector.clone())
+            .with_temporality(Temporality::Delta)
+            .with_metric_accumulation_on_failure(true)
+            .build()
+            .unwrap();
+
+        for (cycle, value) in [(0, 1), (1, 2), (2, 3)] {
+            let mut metrics = ResourceMetrics {
+                resource: Resource::empty(),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: InstrumentationScope::builder("test").build(),
+                    metrics: vec![
+                        sum("requests", Temporality::Delta, cycle, value),
+                        sum("uptime", Temporality::Cumulative, 0, value * 10),
+                    ],
+                }],
+            };
+            // responses with a non-success status don't fail the export
+            exporter.export(&mut metrics).await.unwrap();
+        }
+
+        let state = collector.0.lock().unwrap();
+        assert_eq!(state.1.len(), 1);
+        let metrics = &state.1[0].resource_metrics[0].scope_metrics[0].metrics;
+        let value = |name: &str| match &metrics.iter().find(|m| m.name == name).unwrap().data {
+            Some(Data::Sum(sum)) => {
+                assert_eq!(sum.data_points.len(), 1);
+                let point = &sum.data_points[0];
+                (point.start_time_unix_nano, point.value)
+            }
+            other => panic!("expected a sum, got {other:?}"),
+        };
+
+        // deltas of all three cycles are added up, covering all intervals
+        assert_eq!(value("requests"), (0, Some(Value::AsInt(6))));
+        // the latest cumulative value is kept
+        assert_eq!(value("uptime"), (0, Some(Value::AsInt(30))));
+    }
+
+    #[tokio::test]
+    async fn test_custom_metric_accumulation_http_status() {
+        use crate::MetricExporter;
+        use opentelemetry::InstrumentationScope;
+        use opentelemetry_http::{Bytes, HttpClient, HttpError, Request, Response};
+        use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+        use opentelemetry_sdk::metrics::data::{
+            Metric, ResourceMetrics, ScopeMetrics, Sum, SumDataPoint,
+        };
+        use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+        use opentelemetry_sdk::metrics::Temporality;
+        use opentelemetry_sdk::Resource;
+        use prost::Message;
+        use std::borrow::Cow;
+        use std::collections::HashSet;
+        use std::sync::{Arc, Mutex};
+        use std::time::UNIX_EPOCH;
+
+        /// Rejects the first export with the nonstandard code 520 and counts the data points of
+        /// the accepted ones.
+        #[derive(Clone, Debug, Default)]
+        struct QuirkyGateway(Arc<Mutex<(usize, Vec<usize>)>>);
+
+        #[async_trait::async_trait]
+        impl HttpClient for QuirkyGateway {
+            async fn send(&self, request: Request<Vec<u8>>) -> Result<Response<Bytes>, HttpError> {
+                let mut state = self.0.lock().unwrap();
+                state.0 += 1;
+                if state.0 == 1 {
+                    let mut response = Response::new(Bytes::new());
+                    *response.status_mut() = http::StatusCode::from_u16(520)?;
+                    return Ok(response);
+                }
+                let request = ExportMetricsServiceRequest::decode(&**request.body())?;
+                let points = request.resource_metrics[0].scope_metrics[0].metrics.len();
+                state.1.push(points);
+                Ok(Response::new(Bytes::new()))
+            }
+        }
+
+        async fn export_twice(retryable: HashSet<u16>) -> Vec<usize> {
+            let gateway = QuirkyGateway::default();
+            let exporter = MetricExporter::builder()
+                .with_http()
+                .with_protocol(crate::Protocol::HttpBinary)
+                .with_http_client(gateway.clone())
+                .with_metric_accumulation_http_status(retryable)
+                .with_temporality(Temporality::Delta)
+                .with_metric_accumulation_on_failure(true)
+                .build()
+                .unwrap();
+
+            for name in ["first", "second"] {
+                let mut metrics = ResourceMetrics {
+                    resource: Resource::empty(),
+                    scope_metrics: vec![ScopeMetrics {
+                        scope: InstrumentationScope::builder("test").build(),
+                        metrics: vec![Metric {
+                            name: Cow::Borrowed(name),
+                            description: Cow::Borrowed(""),
+                            unit: Cow::Borrowed(""),
+                            data: Box::new(Sum {
+                                data_points: vec![SumDataPoint {
+                                    attributes: vec![],
+                                    start_time: UNIX_EPOCH,
+                                    time: UNIX_EPOCH,
+                                    value: 1u64,
+                                    exemplars: vec![],
+                                }],
+                                temporality: Temporality::Delta,
+                                is_monotonic: true,
+                            }),
+                        }],
+                    }],
+                };
+                let _ = exporter.export(&mut metrics).await;
+            }
+
+            let state = gateway.0.lock().unwrap();
+            state.1.clone()
+        }
+
+        // the rejected metric is only sent again if the code is retryable
+        assert_eq!(export_twice(HashSet::from([520])).await, vec![2]);
+        assert_eq!(export_twice(HashSet::new()).await, vec![1]);
+    }
+
     #[test]
     fn test_http_exporter_endpoint() {
         // default endpoint should add signal path
@@ -731,4 +2751,40 @@ mod tests {
             assert_eq!(url, "http://localhost:4318/v1/tracesbutnotreally");
         });
     }
+
+    #[cfg(feature = "http-json")]
+    #[test]
+    fn test_items_failing_to_serialize_are_skipped() {
+        use serde::ser::{Error, Serialize, Serializer};
+
+        /// An item failing to serialize if it is corrupt, i.e. has no value.
+        #[derive(Clone)]
+        struct Item(Option<u32>);
+
+        impl Serialize for Item {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self.0 {
+                    Some(value) => serializer.serialize_u32(value),
+                    None => Err(S::Error::custom("corrupt item")),
+                }
+            }
+        }
+
+        #[derive(Clone, serde::Serialize)]
+        struct Request {
+            items: Vec<Item>,
+        }
+
+        let request = Request {
+            items: vec![Item(Some(1)), Item(None), Item(Some(3))],
+        };
+        let (json, skipped) =
+            super::to_json_skipping_failures(&request, |request| vec![&mut request.items]).unwrap();
+
+        assert_eq!(skipped, 1);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({ "items": [1, 3] })
+        );
+    }
 }
@@ -0,0 +1,129 @@
+//! Sign the body of the export requests with an HMAC, for gateways authenticating requests by
+//! their signature.
+
+use std::fmt;
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use http::{HeaderName, HeaderValue};
+use sha2::{Sha256, Sha512};
+
+/// The header the signature is sent in unless configured otherwise.
+pub const DEFAULT_SIGNATURE_HEADER: &str = "x-otlp-signature";
+
+/// The hash function of the HMAC signing the export requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HmacAlgorithm {
+    /// HMAC-SHA256
+    Sha256,
+    /// HMAC-SHA512
+    Sha512,
+}
+
+/// The body of the export requests the signature is computed over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignedPayload {
+    /// The protobuf or JSON encoded request, before the
+    /// [payload transform](crate::WithHttpConfig::with_payload_transform) is applied.
+    #[default]
+    Serialized,
+    /// The body as sent to the collector, after the payload transform compressed or otherwise
+    /// transformed it.
+    Transmitted,
+}
+
+/// Signs the body of every export request with an HMAC, sent as the lowercase hex encoded
+/// signature in the [`DEFAULT_SIGNATURE_HEADER`] header unless configured otherwise.
+#[derive(Clone)]
+pub struct RequestSigner {
+    key: Arc<[u8]>,
+    algorithm: HmacAlgorithm,
+    header: HeaderName,
+    payload: SignedPayload,
+}
+
+impl fmt::Debug for RequestSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the key is a secret
+        f.debug_struct("RequestSigner")
+            .field("algorithm", &self.algorithm)
+            .field("header", &self.header)
+            .field("payload", &self.payload)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RequestSigner {
+    /// Sign the serialized requests with `key`.
+    pub fn new(key: impl Into<Vec<u8>>, algorithm: HmacAlgorithm) -> Self {
+        RequestSigner {
+            key: key.into().into(),
+            algorithm,
+            header: HeaderName::from_static(DEFAULT_SIGNATURE_HEADER),
+            payload: SignedPayload::default(),
+        }
+    }
+
+    /// Send the signature in `header`.
+    pub fn with_header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Compute the signature over `payload`, e.g. the transmitted body for gateways signing the
+    /// body as received. Defaults to [`SignedPayload::Serialized`].
+    pub fn with_signed_payload(mut self, payload: SignedPayload) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub(crate) fn signs(&self, payload: SignedPayload) -> bool {
+        self.payload == payload
+    }
+
+    /// The header carrying the signature of `body`.
+    pub(crate) fn sign(&self, body: &[u8]) -> (HeaderName, HeaderValue) {
+        let signature = match self.algorithm {
+            HmacAlgorithm::Sha256 => mac::<Hmac<Sha256>>(&self.key, body),
+            HmacAlgorithm::Sha512 => mac::<Hmac<Sha512>>(&self.key, body),
+        };
+        let hex: String = signature.iter().map(|byte| format!("{byte:02x}")).collect();
+        let value = HeaderValue::from_str(&hex).expect("hex digits are a valid header value");
+        (self.header.clone(), value)
+    }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HmacAlgorithm, RequestSigner};
+
+    #[test]
+    fn test_known_signatures() {
+        // test case 2 of RFC 4231
+        let signer = RequestSigner::new("Jefe", HmacAlgorithm::Sha256);
+        let (header, value) = signer.sign(b"what do ya want for nothing?");
+        assert_eq!(header, "x-otlp-signature");
+        assert_eq!(
+            value,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let signer = RequestSigner::new("Jefe", HmacAlgorithm::Sha512)
+            .with_header(http::HeaderName::from_static("x-signature"));
+        let (header, value) = signer.sign(b"what do ya want for nothing?");
+        assert_eq!(header, "x-signature");
+        assert_eq!(
+            value,
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+             9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Pausing the exports while the collector asked to be left alone with a `Retry-After` header.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use http::header::RETRY_AFTER;
+use http::StatusCode;
+use opentelemetry::otel_warn;
+
+use crate::exporter::error_log::ErrorLog;
+
+/// The longest the exports are paused for, however long the collector asked for.
+const MAX_PAUSE: Duration = Duration::from_secs(60 * 60);
+
+/// The instant before which no export is sent, as the collector asked for.
+#[derive(Debug, Default)]
+pub(crate) struct Throttle {
+    until: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    /// Fail with [`Error::Throttled`](crate::Error::Throttled) if the collector asked not to
+    /// send exports yet.
+    pub(crate) fn check(&self) -> Result<(), crate::Error> {
+        let until = self.until.lock().unwrap_or_else(|e| e.into_inner());
+        match until.map(|until| until.saturating_duration_since(Instant::now())) {
+            Some(remaining) if !remaining.is_zero() => Err(crate::Error::Throttled { remaining }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pause the exports for the `Retry-After` of a response rejecting an export as too many
+    /// requests or unavailable, for at most an hour.
+    pub(crate) fn update<T>(&self, response: &http::Response<T>, errors: &ErrorLog) {
+        if !matches!(
+            response.status(),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        ) {
+            return;
+        }
+        let delay = match response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, SystemTime::now()))
+        {
+            Some(delay) if !delay.is_zero() => delay.min(MAX_PAUSE),
+            _ => return,
+        };
+        let Some(until) = Instant::now().checked_add(delay) else {
+            return;
+        };
+
+        let status = response.status().as_u16();
+        if let Some(suppressed) = errors.admit(&format!("HTTP status {status} throttled")) {
+            otel_warn!(
+                name: "HttpExporter.Throttled",
+                status = status,
+                retry_after_secs = delay.as_secs() as i64,
+                suppressed = suppressed as i64,
+            );
+        }
+        *self.until.lock().unwrap_or_else(|e| e.into_inner()) = Some(until);
+    }
+}
+
+/// The delay of a `Retry-After` header, either in seconds or an HTTP date relative to `now`.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    // dates in the past don't delay the exports
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{parse_retry_after, Throttle};
+    use crate::exporter::error_log::ErrorLog;
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        // 2015-10-21T07:28:00Z is `now`
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:29:30 GMT", now),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("-1", now), None);
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_throttle_window() {
+        let response = |status: u16, retry_after: &str| {
+            http::Response::builder()
+                .status(status)
+                .header("retry-after", retry_after)
+                .body(())
+                .unwrap()
+        };
+
+        let errors = ErrorLog::default();
+        let throttle = Throttle::default();
+        assert!(throttle.check().is_ok());
+        // only rejections for load are throttled
+        throttle.update(&response(500, "60"), &errors);
+        assert!(throttle.check().is_ok());
+
+        throttle.update(&response(429, "60"), &errors);
+        match throttle.check() {
+            Err(crate::Error::Throttled { remaining }) => {
+                assert!(remaining > Duration::from_secs(59), "{remaining:?}")
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        // a huge delay is capped rather than overflowing
+        let throttle = Throttle::default();
+        throttle.update(&response(429, &u64::MAX.to_string()), &errors);
+        match throttle.check() {
+            Err(crate::Error::Throttled { remaining }) => {
+                assert!(remaining <= super::MAX_PAUSE, "{remaining:?}")
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let throttle = Throttle::default();
+        let past = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(10));
+        throttle.update(&response(503, &past), &errors);
+        assert!(throttle.check().is_ok());
+    }
+
+    #[test]
+    fn test_throttling_is_logged_through_error_log() {
+        let response = http::Response::builder()
+            .status(429)
+            .header("retry-after", "60")
+            .body(())
+            .unwrap();
+
+        let errors = ErrorLog::new(Some(1));
+        Throttle::default().update(&response, &errors);
+        // the throttling used up the allowance of its interval
+        assert_eq!(errors.admit("HTTP status 429 throttled"), None);
+    }
+}
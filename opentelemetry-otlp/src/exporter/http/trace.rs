@@ -1,11 +1,21 @@
 use std::sync::Arc;
 
 use futures_core::future::BoxFuture;
-use http::{header::CONTENT_TYPE, Method};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Method,
+};
 use opentelemetry::trace::TraceError;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceResponse;
 use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
 
-use super::OtlpHttpClient;
+use super::{
+    check_partial_success, check_version, handle_export_response, log_rejected_status,
+    OtlpHttpClient,
+};
+use crate::exporter::auth::authorization;
+use crate::exporter::{inflight, rate};
+use crate::Signal;
 
 impl SpanExporter for OtlpHttpClient {
     fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
@@ -20,43 +30,90 @@ impl SpanExporter for OtlpHttpClient {
             Ok(client) => client,
             Err(err) => return Box::pin(std::future::ready(Err(err))),
         };
+        if let Err(err) = self.throttle.check() {
+            return Box::pin(std::future::ready(Err(err.into())));
+        }
 
-        let (body, content_type) = match self.build_trace_export_body(batch) {
-            Ok(body) => body,
+        let (bodies, content_type) = match self.build_trace_export_bodies(batch) {
+            Ok(bodies) => bodies,
             Err(e) => return Box::pin(std::future::ready(Err(e))),
         };
 
-        let mut request = match http::Request::builder()
-            .method(Method::POST)
-            .uri(&self.collector_endpoint)
-            .header(CONTENT_TYPE, content_type)
-            .body(body)
-        {
-            Ok(req) => req,
-            Err(e) => {
-                return Box::pin(std::future::ready(Err(crate::Error::RequestFailed(
-                    Box::new(e),
-                )
-                .into())))
-            }
-        };
+        let mut requests = Vec::with_capacity(bodies.len());
+        for body in bodies {
+            let (body, signature) = self.prepare_payload(body);
+            let mut request = match http::Request::builder()
+                .method(Method::POST)
+                .uri(&self.collector_endpoint)
+                .header(CONTENT_TYPE, content_type)
+                .body(body)
+            {
+                Ok(req) => req,
+                Err(e) => {
+                    return Box::pin(std::future::ready(Err(crate::Error::RequestFailed(
+                        Box::new(e),
+                    )
+                    .into())))
+                }
+            };
 
-        for (k, v) in &self.headers {
-            request.headers_mut().insert(k.clone(), v.clone());
+            for (k, v) in &self.headers {
+                request.headers_mut().insert(k.clone(), v.clone());
+            }
+            if let Some((name, value)) = signature {
+                request.headers_mut().insert(name, value);
+            }
+            requests.push(request);
         }
 
+        let protocol = self.protocol;
+        let inflight = self.inflight.clone();
+        let export_rate = self.export_rate.clone();
+        let version_check = self.version_check.clone();
+        let payload_recorder = self.payload_recorder.clone();
+        let throttle = self.throttle.clone();
+        let errors = self.errors.clone();
+        let fail_on_partial_success = self.fail_on_partial_success;
+        let auth_provider = self.auth_provider.clone();
         Box::pin(async move {
-            let request_uri = request.uri().to_string();
-            let response = client.send(request).await?;
+            let authorization = authorization(auth_provider.as_ref()).await?;
+            // the requests are sent one after the other, stopping at the first failure
+            for mut request in requests {
+                if let Some(value) = &authorization {
+                    request.headers_mut().insert(AUTHORIZATION, value.clone());
+                }
+                let request_uri = request.uri().to_string();
+                let payload = payload_recorder
+                    .as_ref()
+                    .map(|recorder| (recorder, request.body().clone()));
+                let _permit = inflight::acquire(inflight.as_ref(), request.body().len()).await;
+                rate::acquire(export_rate.as_ref()).await;
+                let response = client.send(request).await?;
+                throttle.update(&response, &errors);
+
+                if !response.status().is_success() {
+                    log_rejected_status(&errors, response.status());
+                    let error = format!(
+                        "OpenTelemetry trace export failed. Url: {}, Status Code: {}, Response: {:?}",
+                        response.status().as_u16(),
+                        request_uri,
+                        response.body()
+                    );
+                    return Err(TraceError::Other(error.into()));
+                }
 
-            if !response.status().is_success() {
-                let error = format!(
-                    "OpenTelemetry trace export failed. Url: {}, Status Code: {}, Response: {:?}",
-                    response.status().as_u16(),
-                    request_uri,
-                    response.body()
+                let partial_success = handle_export_response::<ExportTraceServiceResponse>(
+                    protocol,
+                    response.body(),
+                    &errors,
                 );
-                return Err(TraceError::Other(error.into()));
+                check_version(version_check.as_ref(), response.headers());
+                if let Some((recorder, payload)) = payload {
+                    recorder.record(&payload, Signal::Traces);
+                }
+                if let Some((rejected, message)) = partial_success {
+                    check_partial_success(rejected, &message, fail_on_partial_success)?;
+                }
             }
 
             Ok(())
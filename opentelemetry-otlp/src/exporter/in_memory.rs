@@ -0,0 +1,272 @@
+//! Export into a shared vector instead of a collector, to assert in tests on exactly what would
+//! have been sent.
+//!
+//! The telemetry goes through the same conversion as with the other transports, so the
+//! resource, attribute limits and other conversion options apply to the captured messages.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::conversion::ConversionConfig;
+use crate::ExportConfig;
+
+/// Configuration for the in-memory exporter, capturing the exported `ResourceSpans`,
+/// `ResourceMetrics` or `ResourceLogs` into a shared vector.
+///
+/// ## Examples
+///
+/// ```
+/// # #[cfg(feature="trace")]
+/// # {
+/// use std::sync::{Arc, Mutex};
+///
+/// let exported = Arc::new(Mutex::new(Vec::new()));
+/// let span_exporter = opentelemetry_otlp::SpanExporter::builder()
+///     .with_in_memory(Arc::clone(&exported))
+///     .build()
+///     .expect("valid configuration");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct InMemoryExporterBuilder<T> {
+    pub(crate) exporter_config: ExportConfig,
+    exported: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T: fmt::Debug + Send + 'static> InMemoryExporterBuilder<T> {
+    pub(crate) fn new(exported: Arc<Mutex<Vec<T>>>) -> Self {
+        InMemoryExporterBuilder {
+            exporter_config: ExportConfig::default(),
+            exported,
+        }
+    }
+
+    fn build_client(self) -> InMemoryClient<T> {
+        let conversion = self.exporter_config.conversion;
+        InMemoryClient {
+            exported: self.exported,
+            #[cfg(any(feature = "trace", feature = "logs"))]
+            resource: Default::default(),
+            #[cfg(feature = "metrics")]
+            metric_start_times: conversion.metric_start_times(),
+            conversion,
+            shut_down: AtomicBool::new(false),
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+impl InMemoryExporterBuilder<opentelemetry_proto::tonic::trace::v1::ResourceSpans> {
+    /// Create a span exporter with the current configuration
+    pub fn build_span_exporter(
+        self,
+    ) -> Result<crate::SpanExporter, opentelemetry::trace::TraceError> {
+        Ok(crate::SpanExporter::new(self.build_client()))
+    }
+}
+
+#[cfg(feature = "logs")]
+impl InMemoryExporterBuilder<opentelemetry_proto::tonic::logs::v1::ResourceLogs> {
+    /// Create a log exporter with the current configuration
+    pub fn build_log_exporter(self) -> opentelemetry_sdk::logs::LogResult<crate::LogExporter> {
+        Ok(crate::LogExporter::new(self.build_client()))
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl InMemoryExporterBuilder<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics> {
+    /// Create a metrics exporter with the current configuration
+    pub fn build_metrics_exporter(
+        self,
+        temporality: opentelemetry_sdk::metrics::Temporality,
+    ) -> opentelemetry_sdk::metrics::MetricResult<crate::MetricExporter> {
+        Ok(crate::MetricExporter::new(self.build_client(), temporality))
+    }
+}
+
+/// Converts the exports of a signal and appends them to the shared vector.
+#[derive(Debug)]
+struct InMemoryClient<T> {
+    exported: Arc<Mutex<Vec<T>>>,
+    #[cfg(any(feature = "trace", feature = "logs"))]
+    resource: opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    #[allow(dead_code)]
+    // <allow dead> only read by the clients of enabled signals.
+    conversion: ConversionConfig,
+    #[cfg(feature = "metrics")]
+    metric_start_times: Option<crate::exporter::start_time::MetricStartTimes>,
+    shut_down: AtomicBool,
+}
+
+#[allow(dead_code)]
+// <allow dead> only called by the clients of enabled signals.
+impl<T> InMemoryClient<T> {
+    fn store(&self, items: impl IntoIterator<Item = T>) -> Result<(), &'static str> {
+        if self.shut_down.load(Ordering::Acquire) {
+            return Err("exporter is already shut down");
+        }
+        self.exported
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(items);
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        self.shut_down.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(feature = "trace")]
+mod trace {
+    use futures_core::future::BoxFuture;
+    use opentelemetry::trace::TraceError;
+    use opentelemetry_proto::tonic::trace::v1::ResourceSpans;
+    use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+
+    use super::InMemoryClient;
+
+    impl SpanExporter for InMemoryClient<ResourceSpans> {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            let resource_spans = self
+                .conversion
+                .trace_requests(batch, &self.resource)
+                .into_iter()
+                .flat_map(|request| request.resource_spans);
+            let result = self
+                .store(resource_spans)
+                .map_err(|e| TraceError::Other(e.into()));
+            Box::pin(std::future::ready(result))
+        }
+
+        fn shutdown(&mut self) {
+            InMemoryClient::shutdown(self);
+        }
+
+        fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+            self.resource = resource.into();
+        }
+    }
+}
+
+#[cfg(feature = "logs")]
+mod logs {
+    use async_trait::async_trait;
+    use opentelemetry_proto::tonic::logs::v1::ResourceLogs;
+    use opentelemetry_sdk::export::logs::{LogBatch, LogExporter};
+    use opentelemetry_sdk::logs::{LogError, LogResult};
+
+    use super::InMemoryClient;
+
+    #[async_trait]
+    impl LogExporter for InMemoryClient<ResourceLogs> {
+        async fn export(&self, batch: LogBatch<'_>) -> LogResult<()> {
+            let resource_logs = self.conversion.resource_logs(batch, &self.resource);
+            self.store(resource_logs)
+                .map_err(|e| LogError::Other(e.into()))
+        }
+
+        fn shutdown(&mut self) {
+            InMemoryClient::shutdown(self);
+        }
+
+        fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+            self.resource = resource.into();
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod metrics {
+    use async_trait::async_trait;
+    use opentelemetry_proto::tonic::metrics::v1::ResourceMetrics;
+    use opentelemetry_sdk::metrics::{MetricError, MetricResult};
+
+    use super::InMemoryClient;
+    use crate::metric::MetricsClient;
+
+    #[async_trait]
+    impl MetricsClient for InMemoryClient<ResourceMetrics> {
+        async fn export(
+            &self,
+            metrics: &mut opentelemetry_sdk::metrics::data::ResourceMetrics,
+        ) -> MetricResult<()> {
+            let mut request = self.conversion.metrics_request(metrics);
+            if let Some(start_times) = &self.metric_start_times {
+                start_times.apply(&mut request);
+            }
+            self.store(request.resource_metrics)
+                .map_err(|e| MetricError::Other(e.into()))
+        }
+
+        fn shutdown(&self) -> MetricResult<()> {
+            InMemoryClient::shutdown(self);
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "trace")]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::SystemTime;
+
+    use opentelemetry::trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceId, TraceState};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::export::trace::{SpanData, SpanExporter as _};
+    use opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+    use opentelemetry_sdk::Resource;
+
+    use crate::SpanExporter;
+
+    fn span(name: &'static str) -> SpanData {
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(1),
+                SpanId::from(1),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Internal,
+            name: name.into(),
+            start_time: SystemTime::now(),
+            end_time: SystemTime::now(),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status: Default::default(),
+            instrumentation_scope: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spans_are_captured() {
+        let exported = Arc::new(Mutex::new(Vec::new()));
+        let mut exporter = SpanExporter::builder()
+            .with_in_memory(Arc::clone(&exported))
+            .build()
+            .unwrap();
+        exporter.set_resource(&Resource::new([KeyValue::new("service.name", "checkout")]));
+
+        exporter.export(vec![span("first")]).await.unwrap();
+        exporter.export(vec![span("second")]).await.unwrap();
+
+        let exported = exported.lock().unwrap();
+        let names: Vec<_> = exported
+            .iter()
+            .flat_map(|resource_spans| &resource_spans.scope_spans)
+            .flat_map(|scope_spans| &scope_spans.spans)
+            .map(|span| span.name.as_str())
+            .collect();
+        assert_eq!(names, ["first", "second"]);
+        let resource = exported[0].resource.as_ref().unwrap();
+        assert_eq!(resource.attributes[0].key, "service.name");
+    }
+}
@@ -0,0 +1,137 @@
+//! Bound the total size of the export requests in flight.
+
+use std::future::poll_fn;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+
+/// Limits the sum of the sizes of the export requests in flight.
+///
+/// Exports wait until enough bytes are released by the exports before them. A request larger
+/// than the limit is let through once no other request is in flight, so it can't block the
+/// exporter forever.
+#[derive(Debug)]
+pub(crate) struct InflightBytes {
+    max_bytes: usize,
+    state: Mutex<InflightState>,
+}
+
+#[derive(Debug, Default)]
+struct InflightState {
+    bytes: usize,
+    waiters: Vec<Waker>,
+}
+
+impl InflightBytes {
+    pub(crate) fn new(max_bytes: usize) -> Arc<Self> {
+        Arc::new(InflightBytes {
+            max_bytes,
+            state: Mutex::default(),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, InflightState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait until `bytes` more bytes can be in flight. The bytes are released when the returned
+    /// permit is dropped.
+    pub(crate) async fn acquire(self: &Arc<Self>, bytes: usize) -> InflightPermit {
+        let bytes = bytes.min(self.max_bytes);
+        poll_fn(|cx| {
+            let mut state = self.state();
+            if state.bytes == 0 || state.bytes + bytes <= self.max_bytes {
+                state.bytes += bytes;
+                Poll::Ready(())
+            } else {
+                state.waiters.push(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await;
+
+        InflightPermit {
+            limiter: Arc::clone(self),
+            bytes,
+        }
+    }
+
+    #[cfg(test)]
+    fn inflight(&self) -> usize {
+        self.state().bytes
+    }
+}
+
+/// Bytes of an export request in flight, released on drop.
+#[derive(Debug)]
+pub(crate) struct InflightPermit {
+    limiter: Arc<InflightBytes>,
+    bytes: usize,
+}
+
+impl Drop for InflightPermit {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.limiter.state();
+            state.bytes -= self.bytes;
+            std::mem::take(&mut state.waiters)
+        };
+        waiters.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// Acquire `bytes` from `limiter`, if there is one.
+pub(crate) async fn acquire(
+    limiter: Option<&Arc<InflightBytes>>,
+    bytes: usize,
+) -> Option<InflightPermit> {
+    match limiter {
+        Some(limiter) => Some(limiter.acquire(bytes).await),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::InflightBytes;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_inflight_bytes_stay_bounded() {
+        let limiter = InflightBytes::new(1000);
+        let max_seen = Arc::new(AtomicUsize::new(0));
+
+        let exports = (0..50).map(|_| {
+            let limiter = Arc::clone(&limiter);
+            let max_seen = Arc::clone(&max_seen);
+            tokio::spawn(async move {
+                let _permit = limiter.acquire(400).await;
+                max_seen.fetch_max(limiter.inflight(), Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            })
+        });
+        for export in exports.collect::<Vec<_>>() {
+            export.await.unwrap();
+        }
+
+        assert!(max_seen.load(Ordering::SeqCst) <= 1000);
+        // two payloads fit, so exports did run concurrently
+        assert_eq!(max_seen.load(Ordering::SeqCst), 800);
+        assert_eq!(limiter.inflight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_payload_is_let_through_alone() {
+        let limiter = InflightBytes::new(1000);
+
+        let permit = limiter.acquire(5000).await;
+        assert_eq!(limiter.inflight(), 1000);
+        drop(permit);
+
+        let _small = limiter.acquire(10).await;
+        let oversized = tokio::time::timeout(Duration::from_millis(50), limiter.acquire(5000));
+        assert!(oversized.await.is_err(), "oversized payload must wait");
+    }
+}
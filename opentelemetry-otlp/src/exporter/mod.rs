@@ -4,13 +4,22 @@
 
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 use crate::exporter::http::HttpExporterBuilder;
+#[cfg(feature = "testing")]
+use crate::exporter::in_memory::InMemoryExporterBuilder;
+#[cfg(feature = "tcp")]
+use crate::exporter::tcp::TcpExporterBuilder;
 #[cfg(feature = "grpc-tonic")]
 use crate::exporter::tonic::TonicExporterBuilder;
-use crate::{Error, Protocol};
+use crate::stats::StatsRecorder;
+use crate::{Error, Protocol, TimestampPrecision};
+use conversion::{ConversionConfig, ScopeNamePrefixFilter};
 #[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
-use std::fmt::{Display, Formatter};
+use std::fmt::{self, Display, Formatter};
+#[cfg(any(feature = "grpc-tonic", feature = "gzip-http"))]
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Target to which the exporter is going to send signals, defaults to https://localhost:4317.
@@ -53,19 +62,67 @@ const OTEL_EXPORTER_OTLP_PROTOCOL_HTTP_JSON: &str = "http/json";
 pub const OTEL_EXPORTER_OTLP_TIMEOUT: &str = "OTEL_EXPORTER_OTLP_TIMEOUT";
 /// Default max waiting time for the backend to process each signal batch.
 pub const OTEL_EXPORTER_OTLP_TIMEOUT_DEFAULT: u64 = 10;
+/// Default maximum number of headers or metadata entries sent with each export request.
+pub const OTEL_EXPORTER_OTLP_MAX_HEADERS_DEFAULT: usize = 100;
+/// Default prefix of the names of the threads spawned by the exporters.
+pub const OTEL_EXPORTER_OTLP_THREAD_NAME_PREFIX_DEFAULT: &str = "otlp-export";
+/// Default time in seconds the span exporter waits on shutdown for its enqueued exports to be
+/// sent.
+pub const OTEL_EXPORTER_OTLP_SHUTDOWN_TIMEOUT_DEFAULT: u64 = 5;
 
 // Endpoints per protocol https://github.com/open-telemetry/opentelemetry-specification/blob/main/specification/protocol/exporter.md
 #[cfg(feature = "grpc-tonic")]
 const OTEL_EXPORTER_OTLP_GRPC_ENDPOINT_DEFAULT: &str = "http://localhost:4317";
 const OTEL_EXPORTER_OTLP_HTTP_ENDPOINT_DEFAULT: &str = "http://localhost:4318";
 
+#[cfg(feature = "metrics")]
+#[cfg(any(
+    feature = "grpc-tonic",
+    feature = "http-proto",
+    feature = "http-json",
+    feature = "tcp"
+))]
+pub(crate) mod accumulation;
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+pub(crate) mod auth;
+pub(crate) mod conversion;
+#[cfg(any(
+    feature = "grpc-tonic",
+    feature = "http-proto",
+    feature = "http-json",
+    all(feature = "trace", any(feature = "tcp", feature = "testing"))
+))]
+pub(crate) mod error_log;
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 pub(crate) mod http;
+#[cfg(any(
+    all(feature = "grpc-tonic", feature = "tls"),
+    all(
+        feature = "reqwest-rustls",
+        any(feature = "http-proto", feature = "http-json")
+    )
+))]
+pub(crate) mod identity;
+#[cfg(feature = "testing")]
+pub(crate) mod in_memory;
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+pub(crate) mod inflight;
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+pub(crate) mod rate;
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+pub(crate) mod start_time;
+#[cfg(feature = "trace")]
+pub(crate) mod submission;
+#[cfg(feature = "tcp")]
+pub(crate) mod tcp;
 #[cfg(feature = "grpc-tonic")]
 pub(crate) mod tonic;
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+pub(crate) mod version;
 
 /// Configuration for the OTLP exporter.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExportConfig {
     /// The address of the OTLP collector. If it's not provided via builder or environment variables.
     /// Default address will be used based on the protocol.
@@ -76,6 +133,103 @@ pub struct ExportConfig {
 
     /// The timeout to the collector.
     pub timeout: Duration,
+
+    /// Options applied while converting telemetry into OTLP messages.
+    #[allow(dead_code)]
+    // <allow dead> only read by the clients of the enabled signals.
+    pub(crate) conversion: ConversionConfig,
+
+    /// The maximum number of headers or metadata entries sent with each request.
+    #[allow(dead_code)]
+    // <allow dead> only read by the enabled transports.
+    pub(crate) max_headers: usize,
+
+    /// The maximum total size of the export requests in flight, in bytes.
+    #[allow(dead_code)]
+    // <allow dead> only read by the enabled transports.
+    pub(crate) max_inflight_bytes: Option<usize>,
+
+    /// The maximum number of export requests sent per second.
+    #[allow(dead_code)]
+    // <allow dead> only read by the enabled transports.
+    pub(crate) max_export_rate: Option<u32>,
+
+    /// The prefix of the names of the threads spawned by the exporter.
+    #[allow(dead_code)]
+    // <allow dead> only read by the HTTP clients running on their own threads.
+    pub(crate) thread_name_prefix: String,
+
+    /// The response header the collector advertises its OTLP version in, if the version is
+    /// compared with the exporter's.
+    #[allow(dead_code)]
+    // <allow dead> only read by the gRPC and HTTP transports.
+    pub(crate) version_check: Option<String>,
+
+    /// Callback receiving the payload of every export the collector accepted.
+    #[allow(dead_code)]
+    // <allow dead> only read by the enabled transports.
+    pub(crate) payload_recorder: Option<PayloadRecorder>,
+
+    /// How the span exporter submits its exports.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    // <allow dead> only read by the span exporter builders of enabled transports.
+    pub(crate) submission_mode: submission::SubmissionMode,
+
+    /// Number of pending exports above which the submission queue warns.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    // <allow dead> only read by the span exporter builders of enabled transports.
+    pub(crate) queue_highwater_threshold: Option<usize>,
+
+    /// Maximum number of enqueued exports the submission worker sends at the same time.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    // <allow dead> only read by the span exporter builders of enabled transports.
+    pub(crate) max_concurrent_exports: usize,
+
+    /// How long the span exporter waits on shutdown for the enqueued exports to be sent.
+    #[cfg(feature = "trace")]
+    #[allow(dead_code)]
+    // <allow dead> only read by the span exporter builders of enabled transports.
+    pub(crate) shutdown_timeout: Duration,
+
+    /// Whether an endpoint on the conventional port of the other transport fails building the
+    /// exporter, instead of only being warned about.
+    #[allow(dead_code)]
+    // <allow dead> only read by the gRPC and HTTP transports.
+    pub(crate) strict_endpoint_validation: bool,
+
+    /// Maximum number of times an identical export failure is logged per minute.
+    #[allow(dead_code)]
+    // <allow dead> only read by the exporters of enabled transports.
+    pub(crate) error_log_sampling: Option<usize>,
+
+    /// Whether exports the collector rejected items of fail, instead of only being warned about.
+    #[allow(dead_code)]
+    // <allow dead> only read by the gRPC and HTTP transports.
+    pub(crate) fail_on_partial_success: bool,
+
+    /// Whether the telemetry of exports failing repeatedly is logged to stderr.
+    #[allow(dead_code)]
+    // <allow dead> only read by the exporters of enabled signals.
+    pub(crate) fallback_to_logging: bool,
+
+    /// What the exporter does when exporting a batch panics.
+    #[allow(dead_code)]
+    // <allow dead> only read by the exporters of enabled signals.
+    pub(crate) panic_policy: ExportPanicPolicy,
+
+    /// Whether the exporter records metrics about its own exports.
+    #[cfg(feature = "metrics")]
+    #[allow(dead_code)]
+    // <allow dead> only read by the signal builders of enabled transports.
+    pub(crate) self_metrics: bool,
+
+    /// Counters of the exports, shared by the exporter and its transport.
+    #[allow(dead_code)]
+    // <allow dead> only read by the signal builders of enabled transports.
+    pub(crate) stats: StatsRecorder,
 }
 
 impl Default for ExportConfig {
@@ -88,10 +242,123 @@ impl Default for ExportConfig {
             // won't know if user provided a value
             protocol,
             timeout: Duration::from_secs(OTEL_EXPORTER_OTLP_TIMEOUT_DEFAULT),
+            conversion: ConversionConfig::default(),
+            max_headers: OTEL_EXPORTER_OTLP_MAX_HEADERS_DEFAULT,
+            max_inflight_bytes: None,
+            max_export_rate: None,
+            thread_name_prefix: OTEL_EXPORTER_OTLP_THREAD_NAME_PREFIX_DEFAULT.to_string(),
+            version_check: None,
+            payload_recorder: None,
+            strict_endpoint_validation: false,
+            error_log_sampling: None,
+            fail_on_partial_success: false,
+            fallback_to_logging: false,
+            panic_policy: ExportPanicPolicy::default(),
+            #[cfg(feature = "trace")]
+            submission_mode: submission::SubmissionMode::default(),
+            #[cfg(feature = "trace")]
+            queue_highwater_threshold: None,
+            #[cfg(feature = "trace")]
+            max_concurrent_exports: 1,
+            #[cfg(feature = "trace")]
+            shutdown_timeout: Duration::from_secs(OTEL_EXPORTER_OTLP_SHUTDOWN_TIMEOUT_DEFAULT),
+            #[cfg(feature = "metrics")]
+            self_metrics: false,
+            stats: StatsRecorder::default(),
         }
     }
 }
 
+/// The port collectors conventionally serve OTLP over gRPC on.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+const OTLP_GRPC_PORT: u16 = 4317;
+
+/// The port collectors conventionally serve OTLP over HTTP on.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+const OTLP_HTTP_PORT: u16 = 4318;
+
+impl ExportConfig {
+    /// Fail if `count` headers exceed the configured maximum.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    pub(crate) fn validate_header_count(&self, count: usize) -> Result<(), Error> {
+        if count > self.max_headers {
+            return Err(Error::TooManyHeaders {
+                count,
+                max: self.max_headers,
+            });
+        }
+        Ok(())
+    }
+
+    /// Warn if `endpoint` is on the port collectors conventionally serve OTLP over another
+    /// transport than `protocol` on, e.g. 4318 for gRPC, failing with strict endpoint
+    /// validation.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    pub(crate) fn validate_endpoint_port(
+        &self,
+        endpoint: &::http::Uri,
+        protocol: Protocol,
+    ) -> Result<(), Error> {
+        let conventional_port = match endpoint.port_u16() {
+            Some(OTLP_GRPC_PORT) if protocol != Protocol::Grpc => OTLP_GRPC_PORT,
+            Some(OTLP_HTTP_PORT) if protocol == Protocol::Grpc => OTLP_HTTP_PORT,
+            _ => return Ok(()),
+        };
+        if self.strict_endpoint_validation {
+            return Err(Error::EndpointPortMismatch {
+                endpoint: endpoint.to_string(),
+                protocol,
+            });
+        }
+        opentelemetry::otel_warn!(
+            name: "Exporter.EndpointPortMismatch",
+            endpoint = endpoint.to_string(),
+            protocol = format!("{:?}", protocol),
+            message = format!(
+                "port {} conventionally serves OTLP over {}",
+                conventional_port,
+                if conventional_port == OTLP_GRPC_PORT { "gRPC" } else { "HTTP" }
+            ),
+        );
+        Ok(())
+    }
+
+    /// Spawn a thread named `<thread name prefix>-<name>` running `f`.
+    #[cfg(any(
+        all(feature = "trace", otlp_transport),
+        feature = "reqwest-blocking-client"
+    ))]
+    pub(crate) fn spawn_thread<F, T>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> Result<std::thread::JoinHandle<T>, Error>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let name = format!("{}-{}", self.thread_name_prefix, name);
+        std::thread::Builder::new()
+            .name(name.clone())
+            .spawn(f)
+            .map_err(|source| Error::ThreadSpawn { name, source })
+    }
+}
+
+/// What the exporters do when exporting a batch panics, e.g. because of a bug converting an
+/// attribute of the batch.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ExportPanicPolicy {
+    /// Log the panic and drop the batch, failing its export. The exporter keeps exporting the
+    /// next batches.
+    #[default]
+    DropBatch,
+    /// Let the panic unwind into the caller of `export`, e.g. the export task of the batch
+    /// processor.
+    Propagate,
+}
+
 /// The compression algorithm to use when sending data.
 #[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -111,6 +378,35 @@ impl Display for Compression {
     }
 }
 
+impl Compression {
+    /// The levels of the algorithm, from the fastest to the smallest output.
+    #[cfg(any(feature = "grpc-tonic", feature = "gzip-http"))]
+    fn levels(self) -> RangeInclusive<i32> {
+        match self {
+            Compression::Gzip => 0..=9,
+            Compression::Zstd => 1..=22,
+        }
+    }
+
+    /// Fail with [`Error::UnsupportedCompressionLevel`] if `level` isn't a level of the
+    /// algorithm.
+    #[cfg(any(feature = "grpc-tonic", feature = "gzip-http"))]
+    pub(crate) fn validate_level(self, level: i32) -> Result<(), Error> {
+        if self.levels().contains(&level) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedCompressionLevel(self, level))
+        }
+    }
+
+    /// The level of the algorithm closest to `level`.
+    #[cfg(feature = "grpc-tonic")]
+    pub(crate) fn clamp_level(self, level: i32) -> i32 {
+        let levels = self.levels();
+        level.clamp(*levels.start(), *levels.end())
+    }
+}
+
 impl FromStr for Compression {
     type Err = Error;
 
@@ -123,6 +419,37 @@ impl FromStr for Compression {
     }
 }
 
+/// Callback receiving the payload of every export the collector accepted, with the signal it
+/// belongs to.
+#[derive(Clone)]
+pub(crate) struct PayloadRecorder(Arc<RecordFn>);
+
+type RecordFn = dyn Fn(&[u8], Signal) + Send + Sync;
+
+impl PayloadRecorder {
+    #[allow(dead_code)]
+    pub(crate) fn record(&self, payload: &[u8], signal: Signal) {
+        (self.0)(payload, signal)
+    }
+}
+
+impl fmt::Debug for PayloadRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PayloadRecorder")
+    }
+}
+
+/// The signals exported over OTLP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Signal {
+    /// Spans.
+    Traces,
+    /// Metric data points.
+    Metrics,
+    /// Log records.
+    Logs,
+}
+
 /// default protocol based on enabled features
 fn default_protocol() -> Protocol {
     match OTEL_EXPORTER_OTLP_PROTOCOL_DEFAULT {
@@ -158,6 +485,22 @@ impl HasExportConfig for TonicExporterBuilder {
     }
 }
 
+/// Provide [ExportConfig] access to the [TcpExporterBuilder].
+#[cfg(feature = "tcp")]
+impl HasExportConfig for TcpExporterBuilder {
+    fn export_config(&mut self) -> &mut ExportConfig {
+        &mut self.exporter_config
+    }
+}
+
+/// Provide [ExportConfig] access to the [InMemoryExporterBuilder].
+#[cfg(feature = "testing")]
+impl<T> HasExportConfig for InMemoryExporterBuilder<T> {
+    fn export_config(&mut self) -> &mut ExportConfig {
+        &mut self.exporter_config
+    }
+}
+
 /// Provide [ExportConfig] access to the [HttpExporterBuilder].
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 impl HasExportConfig for HttpExporterBuilder {
@@ -182,6 +525,9 @@ impl HasExportConfig for HttpExporterBuilder {
 /// ```
 pub trait WithExportConfig {
     /// Set the address of the OTLP collector. If not set or set to empty string, the default address is used.
+    ///
+    /// The gRPC exporter also connects to a collector listening on a Unix domain socket, with the
+    /// path of the socket as a `unix://` endpoint such as `unix:///var/run/otel-collector.sock`.
     fn with_endpoint<T: Into<String>>(self, endpoint: T) -> Self;
     /// Set the protocol to use when communicating with the collector.
     ///
@@ -192,9 +538,193 @@ pub trait WithExportConfig {
     /// All exporters in this crate only support one protocol, thus choosing the protocol is an no-op at the moment.
     fn with_protocol(self, protocol: Protocol) -> Self;
     /// Set the timeout to the collector.
+    ///
+    /// The gRPC exporter sends the timeout in the `grpc-timeout` header of every request, so the
+    /// collector can give up on exports the exporter no longer waits for. Retries send the time
+    /// remaining after the backoff already waited.
     fn with_timeout(self, timeout: Duration) -> Self;
     /// Set export config. This will override all previous configuration.
     fn with_export_config(self, export_config: ExportConfig) -> Self;
+    /// Set the maximum number of headers or metadata entries sent with each request, including
+    /// the ones read from the environment. Defaults to [`OTEL_EXPORTER_OTLP_MAX_HEADERS_DEFAULT`].
+    ///
+    /// Building the exporter fails if more headers are configured, instead of the collector
+    /// rejecting every request later on.
+    fn with_max_headers(self, max_headers: usize) -> Self;
+    /// Set the maximum total size, in bytes, of the encoded export requests in flight.
+    ///
+    /// Exports wait for earlier exports to complete when the limit would be exceeded, which
+    /// bounds the memory held by requests to a slow collector. A single request larger than the
+    /// limit is sent once no other request is in flight. Unbounded by default.
+    fn with_max_inflight_bytes(self, max_inflight_bytes: usize) -> Self;
+    /// Set the maximum number of export requests sent per second, to protect a collector too
+    /// small for the bursts of exports of the application.
+    ///
+    /// Requests exceeding the rate wait, spaced evenly apart, until they may be sent, which
+    /// holds back the exports behind them, up to the queue limits of the processors. Retries
+    /// aren't counted as requests of their own. A rate of zero leaves the requests unlimited,
+    /// which is the default.
+    ///
+    /// The waiting requests are woken by a thread named `<thread name prefix>-export-rate`;
+    /// building the exporter fails with [`Error::ThreadSpawn`](crate::Error::ThreadSpawn) if it
+    /// can't be spawned.
+    fn with_max_export_rate(self, requests_per_sec: u32) -> Self;
+    /// Set the prefix of the names of the threads spawned by the exporter: the worker sending the
+    /// exports enqueued by the span submission mode, the timer of the export rate and the thread
+    /// creating the blocking HTTP client. Defaults to
+    /// [`OTEL_EXPORTER_OTLP_THREAD_NAME_PREFIX_DEFAULT`].
+    fn with_thread_name_prefix<T: Into<String>>(self, prefix: T) -> Self;
+    /// Set the maximum number of attributes exported per span, span event, span link, log record
+    /// and metric data point, regardless of the limits configured in the SDK.
+    ///
+    /// Excess attributes are removed when the records are exported and counted in the
+    /// `dropped_attributes_count` of spans, events, links and log records. Metric data points
+    /// have no such count, and their attributes identify their series: data points only
+    /// differing in truncated attributes are exported as the same series. Unbounded by default.
+    fn with_max_attributes_per_record(self, max_attributes: usize) -> Self;
+    /// Set the maximum number of attributes exported per instrumentation scope.
+    ///
+    /// Excess attributes are removed when the records are exported and counted in the
+    /// `dropped_attributes_count` of the scope, which also counts the attributes removed by
+    /// [key patterns](WithExportConfig::with_attribute_key_drop_patterns). Unbounded by default.
+    fn with_max_scope_attributes(self, max_attributes: usize) -> Self;
+    /// Set the maximum number of attributes exported per span event, on top of the
+    /// [maximum per record](WithExportConfig::with_max_attributes_per_record).
+    ///
+    /// Excess attributes are removed when the spans are exported and counted in the
+    /// `dropped_attributes_count` of the event. Unbounded by default.
+    fn with_max_attributes_per_event(self, max_attributes: usize) -> Self;
+    /// Set the maximum number of elements exported per array attribute value, e.g. to bound a
+    /// list of ids recorded on a span.
+    ///
+    /// Excess elements are removed when the records are exported, keeping the first elements.
+    /// Applies to the attributes of records, resources and scopes. OTLP has no count of dropped
+    /// elements, so the truncation is only logged at debug level. Unbounded by default.
+    fn with_max_array_attribute_len(self, max_len: usize) -> Self;
+    /// Export non-string attribute values as their string representation, for backends which
+    /// only accept string attributes. Disabled by default.
+    ///
+    /// Applies to the attributes of records, resources and scopes. The type of the values is
+    /// lost: `42` and `"42"` can't be told apart by the backend, numeric and boolean queries on
+    /// the attributes no longer match, and arrays and maps are rendered like JSON. Bytes are
+    /// rendered as lowercase hex.
+    fn with_stringify_attributes(self, enabled: bool) -> Self;
+    /// Remove the attributes whose key matches any of `patterns` from the exported records,
+    /// resources and scopes of all signals, e.g. `^k8s\.pod\.` to drop all pod labels.
+    ///
+    /// Removed attributes are counted in the `dropped_attributes_count` of the records,
+    /// resources and scopes which have one. Replaces earlier patterns, and no attribute is
+    /// removed by default.
+    #[cfg(feature = "attribute-key-patterns")]
+    fn with_attribute_key_drop_patterns(self, patterns: Vec<regex::Regex>) -> Self;
+    /// Export the attributes of resources sorted by key, so identical resources are encoded
+    /// into identical bytes whatever order their attributes were set in. Helps collectors and
+    /// backends deduplicating by resource hash. Disabled by default.
+    ///
+    /// Applies to the resources of all signals, after the other attribute options. The
+    /// attributes of records and scopes keep their order.
+    fn with_sorted_resource_attributes(self, enabled: bool) -> Self;
+    /// Inject `service.name=unknown_service`, the default of the SDK, into resources without
+    /// attributes, for backends rejecting exports with an empty resource. Disabled by default,
+    /// exporting the resource as configured in the SDK.
+    ///
+    /// Applies to the resources of all signals, after the other attribute options, so resources
+    /// whose attributes were all dropped get the default as well.
+    fn with_require_resource(self, required: bool) -> Self;
+    /// Export `resource` in place of resources holding nothing but the defaults of the SDK, i.e.
+    /// the `telemetry.sdk.*` attributes and an `unknown_service` name, e.g. when resource
+    /// detection timed out querying the cloud metadata, so telemetry is always attributed to a
+    /// service. The `telemetry.sdk.*` attributes are kept unless `resource` sets them. Resources
+    /// with any other attribute are exported unchanged. Disabled by default.
+    ///
+    /// Applies to the resources of all signals, before the other attribute options, and takes
+    /// precedence over [`with_require_resource`](WithExportConfig::with_require_resource).
+    fn with_fallback_resource(self, resource: opentelemetry_sdk::Resource) -> Self;
+    /// Inject `service.namespace=<namespace>` into resources without a `service.namespace`, for
+    /// backends grouping services by namespace. Resources setting one keep theirs. Disabled by
+    /// default.
+    ///
+    /// Applies to the resources of all signals, after the other attribute options.
+    fn with_service_namespace<T: Into<String>>(self, namespace: T) -> Self;
+    /// Truncate the timestamps of the exported spans, span events, log records and metric data
+    /// points to `precision`, for backends which don't use sub-millisecond resolution. Defaults
+    /// to [`TimestampPrecision::Nanoseconds`], exporting the timestamps unchanged.
+    fn with_timestamp_precision(self, precision: TimestampPrecision) -> Self;
+    /// Export only the spans, log records and metrics of instrumentation scopes whose name
+    /// starts with one of the `allow` prefixes and none of the `deny` prefixes, e.g. to deny
+    /// `hyper` to drop the telemetry of a chatty dependency.
+    ///
+    /// A scope matching both lists is denied, and every scope not denied is allowed if `allow`
+    /// is empty. Telemetry without a scope is matched by its empty name. Resources left without
+    /// scopes aren't exported. Replaces an earlier filter, and every scope is exported by
+    /// default.
+    fn with_scope_name_prefix_filter(self, allow: Vec<String>, deny: Vec<String>) -> Self;
+    /// Warn when the collector advertises an older OTLP version than the exporter implements,
+    /// which makes the collector silently drop the fields it doesn't know. Disabled by default.
+    ///
+    /// OTLP doesn't specify how a collector advertises its version, so the version is read
+    /// from the `header` of the gRPC and HTTP responses, e.g. one added by a gateway in front
+    /// of the collector. Responses without the header are skipped. The warning is logged once
+    /// per exporter.
+    fn with_version_compatibility_check<T: Into<String>>(self, header: T) -> Self;
+    /// Call `recorder` with the payload of every export the collector accepted, e.g. to keep an
+    /// audit trail of exactly what left the process. No payload is recorded by default.
+    ///
+    /// The payload is the protobuf or JSON encoded request as sent over HTTP, after the payload
+    /// transform set with `with_payload_transform`, and the protobuf
+    /// encoded request for gRPC and TCP, without the gRPC or TCP framing and before gRPC
+    /// compression. The recorder runs on the exporting task and is called once per request.
+    ///
+    /// Recording is not free: HTTP payloads are copied to outlive the request, and gRPC
+    /// requests are encoded a second time, as tonic encodes the requests internally. Keep the
+    /// recorder fast, e.g. by handing the payload to another thread.
+    fn with_sent_payload_recorder<F>(self, recorder: F) -> Self
+    where
+        F: Fn(&[u8], Signal) + Send + Sync + 'static;
+    /// Log identical export failures at most `max_per_interval` times per minute, so a
+    /// sustained outage doesn't flood the logs. Every failure is logged by default.
+    ///
+    /// Failures are identical if their errors read the same. Once an error was logged
+    /// `max_per_interval` times within a minute, it is suppressed for the rest of the minute,
+    /// and the number of suppressed failures is logged with the next logged one. Applies to
+    /// the failures the exporter logs itself, i.e. the gRPC or HTTP status of failed exports,
+    /// the partial successes and throttling responses of HTTP collectors, and the failures of
+    /// span exports submitted in the background, see `with_submission_mode`.
+    /// Failures returned to the SDK are logged by its processors.
+    fn with_error_log_sampling(self, max_per_interval: usize) -> Self;
+    /// Fail building the exporter if the endpoint is on the port collectors conventionally
+    /// serve OTLP over the other transport on, i.e. 4318 for gRPC or 4317 for HTTP, with
+    /// [`Error::EndpointPortMismatch`](crate::Error::EndpointPortMismatch). Such endpoints are
+    /// only warned about by default, as the collector may serve any transport on any port.
+    ///
+    /// Applies to the gRPC and HTTP transports, and to endpoints from the environment as well.
+    fn with_strict_endpoint_validation(self, strict: bool) -> Self;
+    /// Fail exports the collector reported as partially successful with rejected items with
+    /// [`Error::PartialSuccess`](crate::Error::PartialSuccess), carrying the number of rejected
+    /// items and the message of the collector.
+    ///
+    /// Partial successes are always logged as warnings, but count as successful exports by
+    /// default, as retrying them would resend the accepted items as well. Applies to the gRPC
+    /// and HTTP transports.
+    fn with_fail_on_partial_success(self, fail: bool) -> Self;
+    /// Log the spans, log records and metrics of failing exports to stderr once three exports
+    /// in a row failed, e.g. because no collector runs during local development, instead of
+    /// only dropping them. Logging stops when an export succeeds again. Disabled by default.
+    fn with_fallback_to_logging(self, enabled: bool) -> Self;
+    /// Record metrics about the exports, such as exported and dropped items and export
+    /// durations, on the global meter provider. Disabled by default.
+    ///
+    /// The metrics are exported through the same pipeline as the application metrics. Failed
+    /// exports of the metrics exporter itself are not recorded to avoid feeding a failing
+    /// pipeline with its own failures.
+    #[cfg(feature = "metrics")]
+    fn with_self_metrics(self, enabled: bool) -> Self;
+    /// Set what the exporter does when exporting a batch panics, e.g. because of a bug
+    /// converting an attribute of the batch. Defaults to [`ExportPanicPolicy::DropBatch`],
+    /// logging the panic and dropping the batch so the exporter keeps running.
+    ///
+    /// The dropped batches are counted by the `otel_sdk_exporter_export_panics` self-metric.
+    fn with_export_panic_policy(self, policy: ExportPanicPolicy) -> Self;
 }
 
 impl<B: HasExportConfig> WithExportConfig for B {
@@ -219,6 +749,239 @@ impl<B: HasExportConfig> WithExportConfig for B {
         self.export_config().timeout = exporter_config.timeout;
         self
     }
+
+    fn with_max_headers(mut self, max_headers: usize) -> Self {
+        self.export_config().max_headers = max_headers;
+        self
+    }
+
+    fn with_max_inflight_bytes(mut self, max_inflight_bytes: usize) -> Self {
+        self.export_config().max_inflight_bytes = Some(max_inflight_bytes);
+        self
+    }
+
+    fn with_max_export_rate(mut self, requests_per_sec: u32) -> Self {
+        self.export_config().max_export_rate = Some(requests_per_sec);
+        self
+    }
+
+    fn with_thread_name_prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.export_config().thread_name_prefix = prefix.into();
+        self
+    }
+
+    fn with_max_attributes_per_record(mut self, max_attributes: usize) -> Self {
+        self.export_config().conversion.max_attributes_per_record = Some(max_attributes);
+        self
+    }
+
+    fn with_max_scope_attributes(mut self, max_attributes: usize) -> Self {
+        self.export_config().conversion.max_scope_attributes = Some(max_attributes);
+        self
+    }
+
+    fn with_max_attributes_per_event(mut self, max_attributes: usize) -> Self {
+        self.export_config().conversion.max_attributes_per_event = Some(max_attributes);
+        self
+    }
+
+    fn with_max_array_attribute_len(mut self, max_len: usize) -> Self {
+        self.export_config().conversion.max_array_attribute_len = Some(max_len);
+        self
+    }
+
+    fn with_stringify_attributes(mut self, enabled: bool) -> Self {
+        self.export_config().conversion.stringify_attributes = enabled;
+        self
+    }
+
+    #[cfg(feature = "attribute-key-patterns")]
+    fn with_attribute_key_drop_patterns(mut self, patterns: Vec<regex::Regex>) -> Self {
+        self.export_config().conversion.attribute_key_drop_patterns = patterns;
+        self
+    }
+
+    fn with_sorted_resource_attributes(mut self, enabled: bool) -> Self {
+        self.export_config().conversion.sort_resource_attributes = enabled;
+        self
+    }
+
+    fn with_require_resource(mut self, required: bool) -> Self {
+        self.export_config().conversion.require_resource = required;
+        self
+    }
+
+    fn with_fallback_resource(mut self, resource: opentelemetry_sdk::Resource) -> Self {
+        self.export_config().conversion.fallback_resource = Some(resource);
+        self
+    }
+
+    fn with_service_namespace<T: Into<String>>(mut self, namespace: T) -> Self {
+        self.export_config().conversion.service_namespace = Some(namespace.into());
+        self
+    }
+
+    fn with_timestamp_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.export_config().conversion.timestamp_precision = precision;
+        self
+    }
+
+    fn with_scope_name_prefix_filter(mut self, allow: Vec<String>, deny: Vec<String>) -> Self {
+        self.export_config().conversion.scope_name_prefix_filter =
+            Some(ScopeNamePrefixFilter { allow, deny });
+        self
+    }
+
+    fn with_version_compatibility_check<T: Into<String>>(mut self, header: T) -> Self {
+        self.export_config().version_check = Some(header.into());
+        self
+    }
+
+    fn with_sent_payload_recorder<F>(mut self, recorder: F) -> Self
+    where
+        F: Fn(&[u8], Signal) + Send + Sync + 'static,
+    {
+        self.export_config().payload_recorder = Some(PayloadRecorder(Arc::new(recorder)));
+        self
+    }
+
+    fn with_strict_endpoint_validation(mut self, strict: bool) -> Self {
+        self.export_config().strict_endpoint_validation = strict;
+        self
+    }
+
+    fn with_fail_on_partial_success(mut self, fail: bool) -> Self {
+        self.export_config().fail_on_partial_success = fail;
+        self
+    }
+
+    fn with_fallback_to_logging(mut self, enabled: bool) -> Self {
+        self.export_config().fallback_to_logging = enabled;
+        self
+    }
+
+    fn with_error_log_sampling(mut self, max_per_interval: usize) -> Self {
+        self.export_config().error_log_sampling = Some(max_per_interval);
+        self
+    }
+
+    #[cfg(feature = "metrics")]
+    fn with_self_metrics(mut self, enabled: bool) -> Self {
+        self.export_config().self_metrics = enabled;
+        self
+    }
+
+    fn with_export_panic_policy(mut self, policy: ExportPanicPolicy) -> Self {
+        self.export_config().panic_policy = policy;
+        self
+    }
+}
+
+/// Fail an export the collector rejected `rejected` items of, if partial successes fail exports.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+fn check_partial_success(rejected: i64, message: &str, fail: bool) -> Result<(), Error> {
+    if fail && rejected > 0 {
+        return Err(Error::PartialSuccess {
+            rejected: rejected as u64,
+            message: message.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Resolve the endpoint of a signal with the precedence the specification requires, see
+/// <https://github.com/open-telemetry/opentelemetry-specification/blob/main/specification/protocol/exporter.md#endpoint-urls-for-otlphttp>:
+///
+/// 1. the signal endpoint variable, e.g. `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`, used verbatim,
+/// 2. `OTEL_EXPORTER_OTLP_ENDPOINT`, with `signal_path` appended,
+/// 3. the endpoint provided to the builder, used verbatim,
+/// 4. `default_endpoint`, with `signal_path` appended.
+///
+/// Variables which aren't valid endpoints are skipped. The gRPC transport has no signal path.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+pub(crate) fn resolve_signal_endpoint(
+    signal_endpoint_var: &str,
+    signal_path: &str,
+    provided_endpoint: Option<String>,
+    default_endpoint: &str,
+) -> String {
+    endpoint_from_env(signal_endpoint_var, signal_path)
+        .or(provided_endpoint)
+        .unwrap_or_else(|| append_signal_path(default_endpoint, signal_path))
+}
+
+/// The endpoint of a signal set by the signal endpoint variable or `OTEL_EXPORTER_OTLP_ENDPOINT`,
+/// as [resolved](resolve_signal_endpoint) if any.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+pub(crate) fn endpoint_from_env(signal_endpoint_var: &str, signal_path: &str) -> Option<String> {
+    // a Unix domain socket isn't a valid URI, but a valid endpoint of the gRPC transport
+    let is_valid = |endpoint: &String| {
+        endpoint.starts_with("unix://") || endpoint.parse::<::http::Uri>().is_ok()
+    };
+    std::env::var(signal_endpoint_var)
+        .ok()
+        .filter(is_valid)
+        .or_else(|| {
+            std::env::var(OTEL_EXPORTER_OTLP_ENDPOINT)
+                .ok()
+                .map(|endpoint| append_signal_path(&endpoint, signal_path))
+                .filter(is_valid)
+        })
+}
+
+/// `endpoint` with `path` appended, without doubling the slash between them.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+fn append_signal_path(endpoint: &str, path: &str) -> String {
+    let path = match path.strip_prefix('/') {
+        Some(stripped) if endpoint.ends_with('/') => stripped,
+        _ => path,
+    };
+    format!("{endpoint}{path}")
+}
+
+/// Build with the first transport of `preference` whose feature is enabled and which builds,
+/// logging the transports passed over and the one chosen. `build` receives `config` with the
+/// protocol of the transport.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+pub(crate) fn build_with_preference<T, E: Display>(
+    preference: &[Protocol],
+    config: ExportConfig,
+    mut build: impl FnMut(ExportConfig) -> Result<T, E>,
+) -> Result<T, Error> {
+    for &protocol in preference {
+        let enabled = match protocol {
+            Protocol::Grpc => cfg!(feature = "grpc-tonic"),
+            Protocol::HttpBinary => cfg!(feature = "http-proto"),
+            Protocol::HttpJson => cfg!(feature = "http-json"),
+        };
+        if !enabled {
+            opentelemetry::otel_debug!(
+                name: "Exporter.TransportNotEnabled",
+                protocol = format!("{:?}", protocol),
+            );
+            continue;
+        }
+        match build(ExportConfig {
+            protocol,
+            ..config.clone()
+        }) {
+            Ok(exporter) => {
+                opentelemetry::otel_info!(
+                    name: "Exporter.TransportSelected",
+                    protocol = format!("{:?}", protocol),
+                );
+                return Ok(exporter);
+            }
+            Err(error) => {
+                opentelemetry::otel_warn!(
+                    name: "Exporter.TransportNotViable",
+                    protocol = format!("{:?}", protocol),
+                    error = error.to_string(),
+                );
+            }
+        }
+    }
+    Err(Error::NoViableTransport(preference.to_vec()))
 }
 
 #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
@@ -290,6 +1053,65 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_endpoint_port_mismatch() {
+        use crate::{Error, ExportConfig, Protocol};
+
+        let validate = |endpoint: &str, protocol, strict| {
+            let config = ExportConfig {
+                strict_endpoint_validation: strict,
+                ..Default::default()
+            };
+            config.validate_endpoint_port(&endpoint.parse().unwrap(), protocol)
+        };
+
+        // mismatched ports are only warned about by default
+        assert!(validate("https://collector:4318", Protocol::Grpc, false).is_ok());
+        assert!(matches!(
+            validate("https://collector:4318", Protocol::Grpc, true),
+            Err(Error::EndpointPortMismatch {
+                protocol: Protocol::Grpc,
+                ..
+            })
+        ));
+        assert!(matches!(
+            validate("http://collector:4317/v1/traces", Protocol::HttpJson, true),
+            Err(Error::EndpointPortMismatch {
+                protocol: Protocol::HttpJson,
+                ..
+            })
+        ));
+
+        // matching, custom and default ports are fine
+        for (endpoint, protocol) in [
+            ("http://collector:4317", Protocol::Grpc),
+            ("http://collector:4318/v1/traces", Protocol::HttpBinary),
+            ("http://collector:4318/v1/traces", Protocol::HttpJson),
+            ("https://collector:443", Protocol::Grpc),
+            ("https://collector/v1/traces", Protocol::HttpBinary),
+        ] {
+            assert!(validate(endpoint, protocol, true).is_ok(), "{endpoint}");
+        }
+    }
+
+    #[cfg(all(feature = "grpc-tonic", feature = "trace"))]
+    #[tokio::test]
+    async fn test_strict_endpoint_validation_fails_build() {
+        use crate::WithExportConfig;
+
+        let built = |strict| {
+            crate::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint("http://localhost:4318")
+                .with_strict_endpoint_validation(strict)
+                .build()
+        };
+        run_env_test(vec![], || {
+            assert!(built(false).is_ok());
+            assert!(built(true).is_err());
+        });
+    }
+
     #[cfg(any(feature = "http-proto", feature = "http-json"))]
     #[test]
     fn test_default_http_endpoint() {
@@ -298,6 +1120,31 @@ mod tests {
         assert_eq!(exporter_builder.exporter_config.endpoint, None);
     }
 
+    #[cfg(feature = "reqwest-blocking-client")]
+    #[test]
+    fn test_thread_name_prefix() {
+        use crate::WithExportConfig;
+
+        let exporter_builder = crate::HttpExporterBuilder::default();
+        let name = exporter_builder
+            .exporter_config
+            .spawn_thread("test", || std::thread::current().name().map(String::from))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("otlp-export-test"));
+
+        let exporter_builder =
+            crate::HttpExporterBuilder::default().with_thread_name_prefix("my-service");
+        let name = exporter_builder
+            .exporter_config
+            .spawn_thread("test", || std::thread::current().name().map(String::from))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("my-service-test"));
+    }
+
     #[cfg(feature = "grpc-tonic")]
     #[test]
     fn test_default_tonic_endpoint() {
@@ -404,4 +1251,116 @@ mod tests {
             )
         }
     }
+
+    #[test]
+    fn test_resolve_signal_endpoint() {
+        use super::resolve_signal_endpoint;
+
+        let signals = [
+            ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "/v1/traces"),
+            ("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT", "/v1/metrics"),
+            ("OTEL_EXPORTER_OTLP_LOGS_ENDPOINT", "/v1/logs"),
+        ];
+        // the http transports append the signal path, gRPC doesn't have one
+        let transports = [
+            ("http://localhost:4318", true),
+            ("http://localhost:4317", false),
+        ];
+        for (signal_var, signal_path) in signals {
+            for (default_endpoint, http) in transports {
+                let path = if http { signal_path } else { "" };
+                let resolve = |provided: Option<&str>| {
+                    resolve_signal_endpoint(
+                        signal_var,
+                        path,
+                        provided.map(str::to_string),
+                        default_endpoint,
+                    )
+                };
+
+                run_env_test(vec![], || {
+                    assert_eq!(resolve(None), format!("{default_endpoint}{path}"));
+                    // the builder endpoint is used verbatim
+                    assert_eq!(resolve(Some("http://builder:1234")), "http://builder:1234");
+                });
+                run_env_test(
+                    vec![(super::OTEL_EXPORTER_OTLP_ENDPOINT, "http://generic:1234/")],
+                    || {
+                        assert_eq!(
+                            resolve(Some("http://builder:1234")),
+                            format!("http://generic:1234/{}", path.trim_start_matches('/'))
+                        );
+                    },
+                );
+                // the signal endpoint takes precedence and is used verbatim
+                run_env_test(
+                    vec![
+                        (super::OTEL_EXPORTER_OTLP_ENDPOINT, "http://generic:1234"),
+                        (signal_var, "http://signal:1234"),
+                    ],
+                    || assert_eq!(resolve(None), "http://signal:1234"),
+                );
+                // invalid endpoints in the environment are skipped
+                run_env_test(
+                    vec![
+                        (super::OTEL_EXPORTER_OTLP_ENDPOINT, "http://generic:1234"),
+                        (signal_var, "-*/*-/invalid-uri"),
+                    ],
+                    || assert_eq!(resolve(None), format!("http://generic:1234{path}")),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_append_signal_path() {
+        use super::append_signal_path;
+
+        assert_eq!(
+            append_signal_path("https://example.com", "/v1/traces"),
+            "https://example.com/v1/traces"
+        );
+        // no duplicate slashes
+        assert_eq!(
+            append_signal_path("https://example.com/", "/v1/traces"),
+            "https://example.com/v1/traces"
+        );
+        assert_eq!(
+            append_signal_path("https://example.com/additional/path/", "/v1/traces"),
+            "https://example.com/additional/path/v1/traces"
+        );
+        assert_eq!(
+            append_signal_path("https://example.com/", ""),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "grpc-tonic", feature = "http-proto"))]
+    fn test_build_with_preference_falls_back() {
+        use crate::{Error, ExportConfig, Protocol};
+
+        let mut attempted = Vec::new();
+        // the collector can't be reached over gRPC
+        let chosen = super::build_with_preference(
+            &[Protocol::Grpc, Protocol::HttpBinary, Protocol::HttpJson],
+            ExportConfig::default(),
+            |config| {
+                attempted.push(config.protocol);
+                match config.protocol {
+                    Protocol::Grpc => Err("gRPC is blocked"),
+                    protocol => Ok(protocol),
+                }
+            },
+        );
+        assert_eq!(chosen.unwrap(), Protocol::HttpBinary);
+        assert_eq!(attempted, vec![Protocol::Grpc, Protocol::HttpBinary]);
+
+        let none = super::build_with_preference(&[Protocol::Grpc], ExportConfig::default(), |_| {
+            Err::<(), _>("gRPC is blocked")
+        });
+        assert!(
+            matches!(none, Err(Error::NoViableTransport(preference)) if preference == [Protocol::Grpc])
+        );
+    }
 }
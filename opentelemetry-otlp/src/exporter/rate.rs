@@ -0,0 +1,192 @@
+//! Limit the rate of the export requests, to protect collectors too small for bursts of exports.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A token bucket holding a single token, refilled at the maximum rate of export requests.
+///
+/// Every request reserves the next token, so requests exceeding the rate wait in the order they
+/// were made, spaced evenly apart. Waiting doesn't depend on an async runtime, since the HTTP
+/// clients may run on any: the waiting requests are woken by a timer thread of the limiter.
+#[derive(Debug)]
+pub(crate) struct ExportRate {
+    interval: Duration,
+    /// When the next token is refilled, or `None` before the first request.
+    next: Mutex<Option<Instant>>,
+    timer: Arc<Timer>,
+}
+
+impl ExportRate {
+    /// The rate of `requests_per_sec`, with `None` if it is zero and the rate isn't limited.
+    /// Fails if the timer thread, named `<thread_name_prefix>-export-rate`, can't be spawned.
+    pub(crate) fn new(
+        requests_per_sec: u32,
+        thread_name_prefix: &str,
+    ) -> Result<Option<Arc<Self>>, crate::Error> {
+        if requests_per_sec == 0 {
+            return Ok(None);
+        }
+        let timer = Arc::new(Timer::default());
+        let name = format!("{thread_name_prefix}-export-rate");
+        let running = Arc::clone(&timer);
+        thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || running.run())
+            .map_err(|source| crate::Error::ThreadSpawn { name, source })?;
+        Ok(Some(Arc::new(ExportRate {
+            interval: Duration::from_secs(1) / requests_per_sec,
+            next: Mutex::new(None),
+            timer,
+        })))
+    }
+
+    /// Wait until the request may be sent.
+    pub(crate) async fn acquire(&self) {
+        let due = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let due = next.map_or(now, |next| next.max(now));
+            *next = Some(due + self.interval);
+            due
+        };
+        SleepUntil {
+            due,
+            timer: &self.timer,
+        }
+        .await
+    }
+}
+
+impl Drop for ExportRate {
+    fn drop(&mut self) {
+        // lets the timer thread exit
+        self.timer.lock().closed = true;
+        self.timer.changed.notify_one();
+    }
+}
+
+/// Wait for the rate of `limiter` to allow another request, if there is one.
+pub(crate) async fn acquire(limiter: Option<&Arc<ExportRate>>) {
+    if let Some(limiter) = limiter {
+        limiter.acquire().await;
+    }
+}
+
+/// The requests waiting for their token, woken by the timer thread once it is due.
+#[derive(Debug, Default)]
+struct Timer {
+    state: Mutex<TimerState>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct TimerState {
+    waiting: Vec<(Instant, Waker)>,
+    closed: bool,
+}
+
+impl Timer {
+    fn lock(&self) -> MutexGuard<'_, TimerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wake `waker` once `due` passed.
+    fn wake_at(&self, due: Instant, waker: &Waker) {
+        let mut state = self.lock();
+        match state
+            .waiting
+            .iter_mut()
+            .find(|(waiting, registered)| *waiting == due && registered.will_wake(waker))
+        {
+            Some((_, registered)) => registered.clone_from(waker),
+            None => state.waiting.push((due, waker.clone())),
+        }
+        drop(state);
+        self.changed.notify_one();
+    }
+
+    /// Wake the waiting requests when they are due, until the limiter is dropped.
+    fn run(&self) {
+        let mut state = self.lock();
+        while !state.closed {
+            let now = Instant::now();
+            state.waiting.retain(|(due, waker)| {
+                let waiting = *due > now;
+                if !waiting {
+                    waker.wake_by_ref();
+                }
+                waiting
+            });
+            state = match state.waiting.iter().map(|(due, _)| *due).min() {
+                Some(next) => {
+                    self.changed
+                        .wait_timeout(state, next - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self.changed.wait(state).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+/// Completes once `due` passed, woken by the timer.
+struct SleepUntil<'a> {
+    due: Instant,
+    timer: &'a Timer,
+}
+
+impl Future for SleepUntil<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.due {
+            return Poll::Ready(());
+        }
+        self.timer.wake_at(self.due, cx.waker());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::ExportRate;
+
+    #[tokio::test]
+    async fn test_requests_are_spaced_by_rate() {
+        let limiter = ExportRate::new(50, "test").unwrap().unwrap();
+
+        let start = Instant::now();
+        for _ in 0..11 {
+            limiter.acquire().await;
+        }
+        // the first request is sent right away, the others 20ms after each other
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_share_the_rate() {
+        let limiter = ExportRate::new(50, "test").unwrap().unwrap();
+
+        let start = Instant::now();
+        let requests = (0..11).map(|_| {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await })
+        });
+        for request in requests.collect::<Vec<_>>() {
+            request.await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_zero_rate_is_unlimited() {
+        assert!(ExportRate::new(0, "test").unwrap().is_none());
+    }
+}
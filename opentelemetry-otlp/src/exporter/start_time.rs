@@ -0,0 +1,355 @@
+//! Rewrite the start times of cumulative metric data points to the configured convention.
+//!
+//! The SDK starts cumulative streams when their aggregation is created. Backends computing rates
+//! expect either the start of the process or the first observation of each stream instead.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+use opentelemetry_proto::tonic::common::v1::KeyValue;
+use opentelemetry_proto::tonic::metrics::v1::{metric::Data, AggregationTemporality};
+use prost::Message;
+
+use super::conversion::MetricStartTimeStrategy;
+
+/// Approximation of the process start, the time the first metric exporter was built.
+static PROCESS_START: OnceLock<u64> = OnceLock::new();
+
+fn process_start() -> u64 {
+    *PROCESS_START.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_nanos() as u64)
+            .unwrap_or_default()
+    })
+}
+
+/// The start times of the cumulative streams exported so far.
+#[derive(Debug)]
+pub(crate) struct MetricStartTimes {
+    strategy: MetricStartTimeStrategy,
+    /// Start times of the streams reported by the last collection.
+    streams: Mutex<HashMap<StreamKey, StreamStart>>,
+}
+
+/// Identifies a stream by its scope, metric name and attributes.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct StreamKey {
+    scope: String,
+    scope_version: String,
+    metric: String,
+    /// The keys and encoded values of the attributes, sorted by key so the order the attributes
+    /// were recorded in doesn't matter.
+    attributes: Vec<(String, Vec<u8>)>,
+}
+
+impl StreamKey {
+    fn new(scope: (&str, &str), metric: &str, attributes: &[KeyValue]) -> Self {
+        let mut attributes: Vec<_> = attributes
+            .iter()
+            .map(|kv| {
+                let value = kv.value.as_ref().map(Message::encode_to_vec);
+                (kv.key.clone(), value.unwrap_or_default())
+            })
+            .collect();
+        attributes.sort();
+        StreamKey {
+            scope: scope.0.to_string(),
+            scope_version: scope.1.to_string(),
+            metric: metric.to_string(),
+            attributes,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StreamStart {
+    /// The start time the SDK reported, which changes when the stream is reset.
+    sdk: u64,
+    exported: u64,
+}
+
+impl MetricStartTimes {
+    /// Track the start times for `strategy`, or `None` if the SDK's start times are kept.
+    pub(crate) fn new(strategy: MetricStartTimeStrategy) -> Option<Self> {
+        match strategy {
+            MetricStartTimeStrategy::Sdk => return None,
+            MetricStartTimeStrategy::ProcessStart => {
+                process_start();
+            }
+            MetricStartTimeStrategy::FirstObservation => {}
+        }
+        Some(MetricStartTimes {
+            strategy,
+            streams: Mutex::default(),
+        })
+    }
+
+    /// Rewrite the start times of the cumulative data points of `request`, a whole collection.
+    ///
+    /// The SDK reports each cumulative stream in every collection, so the streams missing from
+    /// `request` are forgotten, and are observed again if they are reported later.
+    pub(crate) fn apply(&self, request: &mut ExportMetricsServiceRequest) {
+        let mut streams = self.streams.lock().unwrap_or_else(|e| e.into_inner());
+        let mut previous = std::mem::take(&mut *streams);
+        let cumulative = AggregationTemporality::Cumulative as i32;
+
+        for scope_metrics in request
+            .resource_metrics
+            .iter_mut()
+            .flat_map(|resource_metrics| resource_metrics.scope_metrics.iter_mut())
+        {
+            let scope = scope_metrics
+                .scope
+                .as_ref()
+                .map(|scope| (scope.name.as_str(), scope.version.as_str()))
+                .unwrap_or_default();
+            for metric in scope_metrics.metrics.iter_mut() {
+                let mut restart = |attributes: &[KeyValue], start: &mut u64, time: u64| {
+                    *start = match self.strategy {
+                        MetricStartTimeStrategy::Sdk => *start,
+                        MetricStartTimeStrategy::ProcessStart => process_start().min(time),
+                        MetricStartTimeStrategy::FirstObservation => {
+                            let key = StreamKey::new(scope, &metric.name, attributes);
+                            let stream = match previous.remove(&key) {
+                                Some(stream) => streams.entry(key).or_insert(stream),
+                                None => streams.entry(key).or_insert(StreamStart {
+                                    sdk: *start,
+                                    exported: time,
+                                }),
+                            };
+                            if stream.sdk != *start {
+                                // reset by the SDK, observed again from now on
+                                *stream = StreamStart {
+                                    sdk: *start,
+                                    exported: time,
+                                };
+                            }
+                            stream.exported
+                        }
+                    };
+                };
+
+                match &mut metric.data {
+                    Some(Data::Sum(sum)) if sum.aggregation_temporality == cumulative => {
+                        for point in sum.data_points.iter_mut() {
+                            restart(
+                                &point.attributes,
+                                &mut point.start_time_unix_nano,
+                                point.time_unix_nano,
+                            );
+                        }
+                    }
+                    Some(Data::Histogram(histogram))
+                        if histogram.aggregation_temporality == cumulative =>
+                    {
+                        for point in histogram.data_points.iter_mut() {
+                            restart(
+                                &point.attributes,
+                                &mut point.start_time_unix_nano,
+                                point.time_unix_nano,
+                            );
+                        }
+                    }
+                    Some(Data::ExponentialHistogram(histogram))
+                        if histogram.aggregation_temporality == cumulative =>
+                    {
+                        for point in histogram.data_points.iter_mut() {
+                            restart(
+                                &point.attributes,
+                                &mut point.start_time_unix_nano,
+                                point.time_unix_nano,
+                            );
+                        }
+                    }
+                    // gauges have no start, and delta points start at the previous collection
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+    use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, KeyValue};
+    use opentelemetry_proto::tonic::metrics::v1::{
+        metric::Data, number_data_point::Value, AggregationTemporality, Metric, NumberDataPoint,
+        ResourceMetrics, ScopeMetrics, Sum,
+    };
+
+    use super::{process_start, MetricStartTimes};
+    use crate::MetricStartTimeStrategy;
+
+    /// The SDK's start time of all streams.
+    const SDK_START: u64 = 1_000;
+
+    /// A collection of `streams` at `time`, as cumulative and as delta sums.
+    fn collection(time: u64, streams: &[&str]) -> ExportMetricsServiceRequest {
+        let sum = |temporality: AggregationTemporality| Metric {
+            name: format!("{temporality:?}"),
+            data: Some(Data::Sum(Sum {
+                data_points: streams
+                    .iter()
+                    .map(|stream| NumberDataPoint {
+                        attributes: vec![KeyValue {
+                            key: "stream".to_string(),
+                            value: Some(AnyValue {
+                                value: Some(any_value::Value::StringValue(stream.to_string())),
+                            }),
+                        }],
+                        start_time_unix_nano: SDK_START,
+                        time_unix_nano: time,
+                        value: Some(Value::AsInt(1)),
+                        ..Default::default()
+                    })
+                    .collect(),
+                aggregation_temporality: temporality as i32,
+                is_monotonic: true,
+            })),
+            ..Default::default()
+        };
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![
+                        sum(AggregationTemporality::Cumulative),
+                        sum(AggregationTemporality::Delta),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    /// The start times of the cumulative and delta points of `request`.
+    fn start_times(request: &ExportMetricsServiceRequest) -> (Vec<u64>, Vec<u64>) {
+        let metrics = &request.resource_metrics[0].scope_metrics[0].metrics;
+        let starts = |metric: &Metric| match &metric.data {
+            Some(Data::Sum(sum)) => sum
+                .data_points
+                .iter()
+                .map(|point| point.start_time_unix_nano)
+                .collect(),
+            _ => unreachable!(),
+        };
+        (starts(&metrics[0]), starts(&metrics[1]))
+    }
+
+    /// Apply `strategy` to three collections `base` + 1000, 2000 and 3000 ns, with stream `b`
+    /// first appearing in the second.
+    fn collect(strategy: MetricStartTimeStrategy, base: u64) -> Vec<(Vec<u64>, Vec<u64>)> {
+        let tracker = MetricStartTimes::new(strategy);
+        [
+            collection(base + 1_000, &["a"]),
+            collection(base + 2_000, &["a", "b"]),
+            collection(base + 3_000, &["a", "b"]),
+        ]
+        .into_iter()
+        .map(|mut request| {
+            if let Some(tracker) = &tracker {
+                tracker.apply(&mut request);
+            }
+            start_times(&request)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_start_times_follow_strategy_across_collections() {
+        let base = process_start() + 1_000_000;
+
+        assert_eq!(
+            collect(MetricStartTimeStrategy::Sdk, base),
+            vec![
+                (vec![SDK_START], vec![SDK_START]),
+                (vec![SDK_START; 2], vec![SDK_START; 2]),
+                (vec![SDK_START; 2], vec![SDK_START; 2]),
+            ]
+        );
+
+        // each stream starts with the collection it was first exported in
+        assert_eq!(
+            collect(MetricStartTimeStrategy::FirstObservation, base),
+            vec![
+                (vec![base + 1_000], vec![SDK_START]),
+                (vec![base + 1_000, base + 2_000], vec![SDK_START; 2]),
+                (vec![base + 1_000, base + 2_000], vec![SDK_START; 2]),
+            ]
+        );
+
+        let process = process_start();
+        assert_eq!(
+            collect(MetricStartTimeStrategy::ProcessStart, base),
+            vec![
+                (vec![process], vec![SDK_START]),
+                (vec![process; 2], vec![SDK_START; 2]),
+                (vec![process; 2], vec![SDK_START; 2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reset_stream_is_observed_again() {
+        let tracker = MetricStartTimes::new(MetricStartTimeStrategy::FirstObservation).unwrap();
+        let restarted = |time: u64, sdk_start: u64| {
+            let mut request = collection(time, &["a"]);
+            if let Some(Data::Sum(sum)) =
+                &mut request.resource_metrics[0].scope_metrics[0].metrics[0].data
+            {
+                sum.data_points[0].start_time_unix_nano = sdk_start;
+            }
+            tracker.apply(&mut request);
+            start_times(&request).0[0]
+        };
+
+        assert_eq!(restarted(2_000, SDK_START), 2_000);
+        // the SDK restarted the stream at 4500
+        assert_eq!(restarted(5_000, 4_500), 5_000);
+        assert_eq!(restarted(6_000, 4_500), 5_000);
+    }
+
+    #[test]
+    fn test_unreported_streams_are_forgotten() {
+        let tracker = MetricStartTimes::new(MetricStartTimeStrategy::FirstObservation).unwrap();
+        let apply = |time: u64, streams: &[&str]| {
+            let mut request = collection(time, streams);
+            tracker.apply(&mut request);
+            start_times(&request).0
+        };
+
+        assert_eq!(apply(1_000, &["a", "b"]), vec![1_000, 1_000]);
+        assert_eq!(apply(2_000, &["a"]), vec![1_000]);
+        assert_eq!(tracker.streams.lock().unwrap().len(), 1);
+        // `b` is observed again once reported again
+        assert_eq!(apply(3_000, &["a", "b"]), vec![1_000, 3_000]);
+    }
+
+    #[test]
+    fn test_streams_are_keyed_by_sorted_attributes() {
+        let tracker = MetricStartTimes::new(MetricStartTimeStrategy::FirstObservation).unwrap();
+        let attribute = |key: &str| KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue("value".to_string())),
+            }),
+        };
+        let apply = |time: u64, keys: [&str; 2]| {
+            let mut request = collection(time, &["a"]);
+            if let Some(Data::Sum(sum)) =
+                &mut request.resource_metrics[0].scope_metrics[0].metrics[0].data
+            {
+                sum.data_points[0].attributes = keys.map(attribute).to_vec();
+            }
+            tracker.apply(&mut request);
+            start_times(&request).0[0]
+        };
+
+        assert_eq!(apply(1_000, ["x", "y"]), 1_000);
+        assert_eq!(apply(2_000, ["y", "x"]), 1_000);
+    }
+}
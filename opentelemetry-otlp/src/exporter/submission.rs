@@ -0,0 +1,435 @@
+//! Hand span exports over to a worker thread instead of waiting for the collector.
+//!
+//! Span exports own their data, so they can outlive the call to `export`. Log and metric
+//! exports borrow theirs from the SDK and are always sent before `export` returns.
+
+use std::time::Duration;
+
+/// How the span exporter submits its exports, independently of the span processor.
+///
+/// The batch span processor already exports off the application threads, while the simple
+/// span processor exports on the thread ending the span. Enqueuing the exports decouples the
+/// processor from the collector's latency, at the cost of losing the enqueued exports if the
+/// process exits before they are sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SubmissionMode {
+    /// Complete each export once the collector responded.
+    #[default]
+    Blocking,
+    /// Enqueue the export and complete it right away. Exports failing later are only logged.
+    ///
+    /// If `capacity` exports are already enqueued or being sent, the export is dropped and
+    /// fails immediately.
+    FireAndForget {
+        /// The maximum number of exports enqueued or being sent.
+        capacity: usize,
+    },
+    /// Like [`FireAndForget`](SubmissionMode::FireAndForget), but the export waits for up to
+    /// `timeout` for room in the queue before being dropped. The wait doesn't block the thread
+    /// polling the export.
+    BoundedWait {
+        /// The maximum number of exports enqueued or being sent.
+        capacity: usize,
+        /// How long an export waits for room in the queue.
+        timeout: Duration,
+    },
+}
+
+#[cfg(otlp_transport)]
+pub(crate) use self::queue::ExportQueue;
+
+#[cfg(otlp_transport)]
+mod queue {
+    use std::collections::VecDeque;
+    use std::fmt;
+    use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+    use std::time::{Duration, Instant};
+
+    use futures_core::future::BoxFuture;
+    use opentelemetry::otel_warn;
+    use opentelemetry::trace::TraceError;
+    use opentelemetry_sdk::export::trace::ExportResult;
+
+    use crate::exporter::error_log::ErrorLog;
+    #[cfg(feature = "metrics")]
+    use crate::self_metrics::SelfMetrics;
+    use crate::ExportConfig;
+
+    /// Exports waiting for, or being sent by, the worker thread.
+    pub(crate) struct ExportQueue {
+        shared: Arc<Shared>,
+        /// Unparked when an export is enqueued while the worker is sending others.
+        worker: Thread,
+        capacity: usize,
+        timeout: Option<Duration>,
+        shutdown_timeout: Duration,
+    }
+
+    struct Shared {
+        state: Mutex<State>,
+        changed: Condvar,
+        errors: ErrorLog,
+        #[cfg(feature = "metrics")]
+        self_metrics: Option<SelfMetrics>,
+    }
+
+    #[derive(Default)]
+    struct State {
+        queue: VecDeque<BoxFuture<'static, ExportResult>>,
+        /// Number of exports enqueued or being sent.
+        pending: usize,
+        closed: bool,
+        highwater: HighWater,
+        /// Exports waiting for room in the queue, woken once an export completes, the queue is
+        /// closed, or their deadline passed.
+        waiting: Vec<Waiting>,
+    }
+
+    struct Waiting {
+        /// `None` if the wait never times out.
+        deadline: Option<Instant>,
+        waker: Waker,
+    }
+
+    /// Tracks the pending exports crossing the high-water threshold.
+    #[derive(Default)]
+    struct HighWater {
+        threshold: Option<usize>,
+        /// Whether the pending exports are above the threshold.
+        above: bool,
+        crossings: u64,
+    }
+
+    impl HighWater {
+        /// Update with the number of `pending` exports, returning whether they just crossed
+        /// the threshold.
+        fn update(&mut self, pending: usize) -> bool {
+            let Some(threshold) = self.threshold else {
+                return false;
+            };
+            let crossed = pending > threshold && !self.above;
+            self.above = pending > threshold;
+            if crossed {
+                self.crossings += 1;
+            }
+            crossed
+        }
+    }
+
+    impl fmt::Debug for ExportQueue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ExportQueue")
+                .field("capacity", &self.capacity)
+                .field("timeout", &self.timeout)
+                .field("shutdown_timeout", &self.shutdown_timeout)
+                .finish()
+        }
+    }
+
+    impl ExportQueue {
+        /// Start the worker thread sending the exports, waiting for up to `timeout` for room
+        /// when `capacity` exports are pending. Fails if the thread can't be spawned.
+        pub(crate) fn start(
+            capacity: usize,
+            timeout: Option<Duration>,
+            config: &ExportConfig,
+        ) -> Result<Self, crate::Error> {
+            let shared = Arc::new(Shared {
+                state: Mutex::new(State {
+                    highwater: HighWater {
+                        threshold: config.queue_highwater_threshold,
+                        ..HighWater::default()
+                    },
+                    ..State::default()
+                }),
+                changed: Condvar::new(),
+                errors: ErrorLog::new(config.error_log_sampling),
+                #[cfg(feature = "metrics")]
+                self_metrics: config.self_metrics.then(|| SelfMetrics::global("traces")),
+            });
+            #[cfg(feature = "metrics")]
+            if config.self_metrics {
+                let queue = Arc::downgrade(&shared);
+                SelfMetrics::observe_queue_size("traces", move || {
+                    Some(queue.upgrade()?.lock().pending)
+                });
+            }
+            // tonic and the TCP transport need the runtime the exporter was built in
+            #[cfg(any(feature = "grpc-tonic", feature = "tcp"))]
+            let runtime = tokio::runtime::Handle::try_current().ok();
+
+            let worker = Arc::clone(&shared);
+            let max_concurrent = config.max_concurrent_exports.max(1);
+            let worker = config
+                .spawn_thread("span-submission", move || {
+                    #[cfg(any(feature = "grpc-tonic", feature = "tcp"))]
+                    let _runtime = runtime.as_ref().map(|runtime| runtime.enter());
+                    worker.run(max_concurrent);
+                })?
+                .thread()
+                .clone();
+
+            Ok(ExportQueue {
+                shared,
+                worker,
+                capacity: capacity.max(1),
+                timeout,
+                shutdown_timeout: config.shutdown_timeout,
+            })
+        }
+
+        /// Enqueue `export`, failing if the queue stays full. The returned future waits for room
+        /// in the queue without blocking the thread polling it, so the runtime driving the
+        /// exports being sent keeps running.
+        pub(crate) fn submit(
+            &self,
+            export: BoxFuture<'static, ExportResult>,
+        ) -> BoxFuture<'static, ExportResult> {
+            let shared = Arc::clone(&self.shared);
+            let worker = self.worker.clone();
+            let capacity = self.capacity;
+            // a timeout too long to be represented never elapses
+            let deadline = self
+                .timeout
+                .map(|timeout| Instant::now().checked_add(timeout));
+            let mut export = Some(export);
+            Box::pin(std::future::poll_fn(move |cx| {
+                let mut state = shared.lock();
+                if state.closed {
+                    return Poll::Ready(Err(TraceError::Other(
+                        "exporter is already shut down".into(),
+                    )));
+                }
+                if state.pending >= capacity {
+                    return match deadline {
+                        Some(deadline) if deadline.map_or(true, |d| Instant::now() < d) => {
+                            state.waiting.push(Waiting {
+                                deadline,
+                                waker: cx.waker().clone(),
+                            });
+                            drop(state);
+                            // the worker wakes the export once its deadline passed
+                            worker.unpark();
+                            Poll::Pending
+                        }
+                        _ => Poll::Ready(Err(TraceError::Other(
+                            "the export queue is full, dropping the export".into(),
+                        ))),
+                    };
+                }
+                let Some(export) = export.take() else {
+                    return Poll::Ready(Ok(()));
+                };
+
+                state.pending += 1;
+                state.queue.push_back(export);
+                let pending = state.pending;
+                let crossed = state.highwater.update(pending);
+                let crossings = state.highwater.crossings;
+                drop(state);
+                shared.changed.notify_all();
+                worker.unpark();
+
+                if crossed {
+                    otel_warn!(
+                        name: "SpanExporter.QueueHighWater",
+                        pending_exports = pending as i64,
+                        capacity = capacity as i64,
+                        crossings = crossings as i64,
+                    );
+                    #[cfg(feature = "metrics")]
+                    if let Some(self_metrics) = &shared.self_metrics {
+                        self_metrics.record_queue_highwater();
+                    }
+                }
+                Poll::Ready(Ok(()))
+            }))
+        }
+
+        /// Number of exports enqueued or being sent.
+        #[cfg(test)]
+        pub(crate) fn pending(&self) -> usize {
+            self.shared.lock().pending
+        }
+
+        /// Number of times the pending exports crossed the high-water threshold.
+        #[cfg(test)]
+        pub(crate) fn highwater_crossings(&self) -> u64 {
+            self.shared.lock().highwater.crossings
+        }
+
+        /// Stop accepting exports and wait for up to the shutdown timeout for the enqueued
+        /// exports to be sent. Exports still enqueued afterwards are sent in the background, and
+        /// the shutdown fails with [`ExportTimedOut`](TraceError::ExportTimedOut).
+        ///
+        /// On the thread of a current-thread runtime, the shutdown doesn't wait: blocking the
+        /// thread would stop the runtime from driving the exports being sent.
+        pub(crate) fn shutdown(&self) -> ExportResult {
+            // a timeout too long to be represented never elapses
+            #[cfg_attr(not(any(feature = "grpc-tonic", feature = "tcp")), allow(unused_mut))]
+            let mut deadline = Instant::now().checked_add(self.shutdown_timeout);
+            #[cfg(any(feature = "grpc-tonic", feature = "tcp"))]
+            if tokio::runtime::Handle::try_current().is_ok_and(|runtime| {
+                runtime.runtime_flavor() == tokio::runtime::RuntimeFlavor::CurrentThread
+            }) {
+                deadline = Some(Instant::now());
+            }
+            let mut state = self.shared.lock();
+            state.closed = true;
+            state.wake_waiting();
+            self.shared.changed.notify_all();
+            while state.pending > 0 {
+                state = self
+                    .shared
+                    .wait(state, deadline)
+                    .map_err(|_| TraceError::ExportTimedOut(self.shutdown_timeout))?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for ExportQueue {
+        fn drop(&mut self) {
+            // lets the worker exit once the enqueued exports are sent
+            let mut state = self.shared.lock();
+            state.closed = true;
+            state.wake_waiting();
+            drop(state);
+            self.shared.changed.notify_all();
+        }
+    }
+
+    impl State {
+        /// Wake the exports waiting for room in the queue.
+        fn wake_waiting(&mut self) {
+            self.waiting
+                .drain(..)
+                .for_each(|waiting| waiting.waker.wake());
+        }
+
+        /// Wake the exports whose deadline passed, returning the earliest deadline of the
+        /// exports still waiting.
+        fn wake_timed_out(&mut self) -> Option<Instant> {
+            let now = Instant::now();
+            let mut earliest = None;
+            self.waiting.retain(|waiting| match waiting.deadline {
+                Some(deadline) if deadline <= now => {
+                    waiting.waker.wake_by_ref();
+                    false
+                }
+                Some(deadline) => {
+                    earliest = Some(earliest.map_or(deadline, |e: Instant| e.min(deadline)));
+                    true
+                }
+                None => true,
+            });
+            earliest
+        }
+    }
+
+    impl Shared {
+        fn lock(&self) -> MutexGuard<'_, State> {
+            self.state.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        /// Wait for the state to change, for up to `deadline` if any. Fails with the state once
+        /// the deadline passed.
+        fn wait<'a>(
+            &self,
+            state: MutexGuard<'a, State>,
+            deadline: Option<Instant>,
+        ) -> Result<MutexGuard<'a, State>, MutexGuard<'a, State>> {
+            let Some(deadline) = deadline else {
+                return Ok(self.changed.wait(state).unwrap_or_else(|e| e.into_inner()));
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(state);
+            }
+            Ok(self
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0)
+        }
+
+        /// Send the enqueued exports, up to `max_concurrent` at the same time, until the queue
+        /// is closed and drained. Each export carries its own timeout and retries.
+        fn run(&self, max_concurrent: usize) {
+            let waker = Waker::from(Arc::new(Unpark(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            let mut sending: Vec<BoxFuture<'static, ExportResult>> = Vec::new();
+            loop {
+                {
+                    let mut state = self.lock();
+                    while sending.is_empty() && state.queue.is_empty() {
+                        if state.closed {
+                            return;
+                        }
+                        state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                    while sending.len() < max_concurrent {
+                        match state.queue.pop_front() {
+                            Some(export) => sending.push(export),
+                            None => break,
+                        }
+                    }
+                }
+
+                let before = sending.len();
+                sending.retain_mut(|export| match export.as_mut().poll(&mut cx) {
+                    Poll::Ready(result) => {
+                        self.complete(result);
+                        false
+                    }
+                    Poll::Pending => true,
+                });
+                // woken by the exports being sent, by an export being enqueued, or by the
+                // deadline of an export waiting for room
+                if sending.len() == before {
+                    let deadline = self.lock().wake_timed_out();
+                    match deadline {
+                        Some(deadline) => {
+                            thread::park_timeout(deadline.saturating_duration_since(Instant::now()))
+                        }
+                        None => thread::park(),
+                    }
+                }
+            }
+        }
+
+        /// Log the failure of a sent export and make room for another one.
+        fn complete(&self, result: ExportResult) {
+            if let Err(error) = result {
+                let error = error.to_string();
+                if let Some(suppressed) = self.errors.admit(&error) {
+                    otel_warn!(
+                        name: "SpanExporter.SubmittedExportFailed",
+                        error = error,
+                        suppressed = suppressed as i64,
+                    );
+                }
+            }
+
+            let mut state = self.lock();
+            state.pending -= 1;
+            let pending = state.pending;
+            state.highwater.update(pending);
+            state.wake_waiting();
+            drop(state);
+            self.changed.notify_all();
+        }
+    }
+
+    /// Wakes the worker thread parked while sending exports.
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_sdk::export::logs::{LogBatch, LogExporter};
+use opentelemetry_sdk::logs::LogResult;
+use prost::Message;
+
+use super::TcpClient;
+use crate::Signal;
+
+#[async_trait]
+impl LogExporter for TcpClient {
+    async fn export(&self, batch: LogBatch<'_>) -> LogResult<()> {
+        let resource_logs = self.conversion.resource_logs(batch, &self.resource);
+        let payload = ExportLogsServiceRequest { resource_logs }.encode_to_vec();
+
+        self.connection.send(&payload).await?;
+        if let Some(recorder) = &self.payload_recorder {
+            recorder.record(&payload, Signal::Logs);
+        }
+
+        Ok(())
+    }
+
+    fn shutdown(&mut self) {
+        self.connection.shutdown();
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.resource = resource.into();
+    }
+}
@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::MetricResult;
+use prost::Message;
+
+use super::TcpClient;
+use crate::metric::MetricsClient;
+use crate::Signal;
+
+#[async_trait]
+impl MetricsClient for TcpClient {
+    async fn export(&self, metrics: &mut ResourceMetrics) -> MetricResult<()> {
+        let mut request = self.conversion.metrics_request(metrics);
+        if let Some(start_times) = &self.metric_start_times {
+            start_times.apply(&mut request);
+        }
+        let retained = self.metric_accumulator.as_ref().map(|accumulator| {
+            accumulator.merge_pending(&mut request);
+            (accumulator, request.clone())
+        });
+
+        let payload = request.encode_to_vec();
+        let result = self.connection.send(&payload).await;
+        if let (Err(_), Some((accumulator, request))) = (&result, retained) {
+            // the collector never rejects a request, so every failure may be retried
+            accumulator.keep(request);
+        }
+        result?;
+        if let Some(recorder) = &self.payload_recorder {
+            recorder.record(&payload, Signal::Metrics);
+        }
+
+        Ok(())
+    }
+
+    fn shutdown(&self) -> MetricResult<()> {
+        self.connection.shutdown();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+//! Export OTLP over a plain TCP connection, for collectors which can't run a gRPC or HTTP stack.
+//!
+//! Each export request is sent as a frame of a 4-byte big-endian length followed by the
+//! protobuf encoded request. An exporter sends a single signal, so collectors usually receive
+//! each signal on its own port.
+
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::conversion::ConversionConfig;
+use super::PayloadRecorder;
+use crate::stats::StatsRecorder;
+use crate::ExportConfig;
+
+#[cfg(feature = "logs")]
+mod logs;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "trace")]
+mod trace;
+
+/// Default address of the collector receiving OTLP over TCP.
+const OTEL_EXPORTER_OTLP_TCP_ENDPOINT_DEFAULT: &str = "localhost:4319";
+
+/// Configuration for the length-prefixed TCP OTLP exporter.
+///
+/// The endpoint is the `host:port` of the collector, optionally prefixed with `tcp://`. It
+/// defaults to `localhost:4319`.
+///
+/// ## Examples
+///
+/// ```no_run
+/// # #[cfg(feature="trace")]
+/// # {
+/// use opentelemetry_otlp::WithExportConfig;
+///
+/// let span_exporter = opentelemetry_otlp::SpanExporter::builder()
+///     .with_tcp()
+///     .with_endpoint("tcp://collector.local:4319")
+///     .build()
+///     .expect("valid configuration");
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct TcpExporterBuilder {
+    pub(crate) exporter_config: ExportConfig,
+}
+
+impl TcpExporterBuilder {
+    fn build_client(self) -> TcpClient {
+        let endpoint = self
+            .exporter_config
+            .endpoint
+            .as_deref()
+            .map(|endpoint| endpoint.trim_start_matches("tcp://"))
+            .unwrap_or(OTEL_EXPORTER_OTLP_TCP_ENDPOINT_DEFAULT)
+            .to_string();
+
+        TcpClient::new(
+            TcpConnection::new(
+                endpoint,
+                self.exporter_config.timeout,
+                self.exporter_config.stats,
+            ),
+            self.exporter_config.conversion,
+            self.exporter_config.payload_recorder,
+        )
+    }
+
+    /// Create a span exporter with the current configuration
+    #[cfg(feature = "trace")]
+    pub fn build_span_exporter(
+        self,
+    ) -> Result<crate::SpanExporter, opentelemetry::trace::TraceError> {
+        Ok(crate::SpanExporter::new(self.build_client()))
+    }
+
+    /// Create a log exporter with the current configuration
+    #[cfg(feature = "logs")]
+    pub fn build_log_exporter(self) -> opentelemetry_sdk::logs::LogResult<crate::LogExporter> {
+        Ok(crate::LogExporter::new(self.build_client()))
+    }
+
+    /// Create a metrics exporter with the current configuration
+    #[cfg(feature = "metrics")]
+    pub fn build_metrics_exporter(
+        self,
+        temporality: opentelemetry_sdk::metrics::Temporality,
+    ) -> opentelemetry_sdk::metrics::MetricResult<crate::MetricExporter> {
+        Ok(crate::MetricExporter::new(self.build_client(), temporality))
+    }
+}
+
+/// Encodes the export requests of a signal and sends them over a [`TcpConnection`].
+#[derive(Debug)]
+struct TcpClient {
+    connection: Arc<TcpConnection>,
+    #[allow(dead_code)]
+    // <allow dead> would be removed once we support set_resource for metrics.
+    resource: opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    #[allow(dead_code)]
+    // <allow dead> only read by the clients of enabled signals.
+    conversion: ConversionConfig,
+    payload_recorder: Option<PayloadRecorder>,
+    #[cfg(feature = "metrics")]
+    metric_accumulator: Option<crate::exporter::accumulation::MetricAccumulator>,
+    #[cfg(feature = "metrics")]
+    metric_start_times: Option<crate::exporter::start_time::MetricStartTimes>,
+}
+
+impl TcpClient {
+    fn new(
+        connection: TcpConnection,
+        conversion: ConversionConfig,
+        payload_recorder: Option<PayloadRecorder>,
+    ) -> Self {
+        TcpClient {
+            connection: Arc::new(connection),
+            resource: Default::default(),
+            payload_recorder,
+            #[cfg(feature = "metrics")]
+            metric_accumulator: conversion.metric_accumulator(),
+            #[cfg(feature = "metrics")]
+            metric_start_times: conversion.metric_start_times(),
+            conversion,
+        }
+    }
+}
+
+/// A connection to the collector, established on the first export and re-established once the
+/// collector closed it.
+pub(crate) struct TcpConnection {
+    endpoint: String,
+    timeout: Duration,
+    stream: Mutex<Option<TcpStream>>,
+    shut_down: AtomicBool,
+    stats: StatsRecorder,
+}
+
+impl fmt::Debug for TcpConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpConnection")
+            .field("endpoint", &self.endpoint)
+            .finish()
+    }
+}
+
+impl TcpConnection {
+    pub(crate) fn new(endpoint: String, timeout: Duration, stats: StatsRecorder) -> Self {
+        TcpConnection {
+            endpoint,
+            timeout,
+            stream: Mutex::new(None),
+            shut_down: AtomicBool::new(false),
+            stats,
+        }
+    }
+
+    /// Send `payload` as a single length-prefixed frame.
+    pub(crate) async fn send(&self, payload: &[u8]) -> Result<(), crate::Error> {
+        if self.shut_down.load(Ordering::Acquire) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "exporter is already shut down",
+            )
+            .into());
+        }
+        let length = u32::try_from(payload.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "payload exceeds the maximum frame length",
+            )
+        })?;
+        let mut frame = Vec::with_capacity(payload.len() + 4);
+        frame.extend_from_slice(&length.to_be_bytes());
+        frame.extend_from_slice(payload);
+        self.stats.record_bytes_sent(frame.len());
+
+        let mut stream = self.stream.lock().await;
+        let result = tokio::time::timeout(self.timeout, self.write_frame(&mut stream, &frame))
+            .await
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "export timed out")));
+        if result.is_err() {
+            // the frame may have been written partially, so the connection can't be reused
+            *stream = None;
+        }
+        Ok(result?)
+    }
+
+    async fn write_frame(&self, stream: &mut Option<TcpStream>, frame: &[u8]) -> io::Result<()> {
+        // a connection kept from an earlier export is dropped if the collector closed it, or
+        // retried once on a new connection if writing to it fails
+        if let Some(connected) = stream.as_mut().filter(|connected| is_open(connected)) {
+            if connected.write_all(frame).await.is_ok() {
+                return Ok(());
+            }
+        }
+
+        let mut connected = TcpStream::connect(&self.endpoint).await?;
+        connected.set_nodelay(true)?;
+        connected.write_all(frame).await?;
+        *stream = Some(connected);
+        Ok(())
+    }
+
+    pub(crate) fn shutdown(&self) {
+        self.shut_down.store(true, Ordering::Release);
+        if let Ok(mut stream) = self.stream.try_lock() {
+            *stream = None;
+        }
+    }
+}
+
+/// Whether the collector hasn't closed `stream`. The collector never sends data, so a readable
+/// stream signals the end of the connection.
+fn is_open(stream: &TcpStream) -> bool {
+    matches!(
+        stream.try_read(&mut [0; 1]),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock
+    )
+}
+
+#[cfg(test)]
+#[cfg(feature = "trace")]
+mod tests {
+    use std::time::SystemTime;
+
+    use opentelemetry::trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceId, TraceState};
+    use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+    use opentelemetry_sdk::export::trace::{SpanData, SpanExporter as _};
+    use opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+    use prost::Message;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use crate::{SpanExporter, WithExportConfig};
+
+    fn span(name: &'static str) -> SpanData {
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(1),
+                SpanId::from(1),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Internal,
+            name: name.into(),
+            start_time: SystemTime::now(),
+            end_time: SystemTime::now(),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status: Default::default(),
+            instrumentation_scope: Default::default(),
+        }
+    }
+
+    /// Decodes the frames of each connection, closing every connection after its first frame.
+    async fn start_collector() -> (String, mpsc::UnboundedReceiver<(usize, Vec<String>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for connection in 0.. {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let length = stream.read_u32().await.unwrap();
+                let mut payload = vec![0; length as usize];
+                stream.read_exact(&mut payload).await.unwrap();
+                drop(stream);
+
+                let request = ExportTraceServiceRequest::decode(payload.as_slice()).unwrap();
+                let names = request.resource_spans[0].scope_spans[0]
+                    .spans
+                    .iter()
+                    .map(|span| span.name.clone())
+                    .collect();
+                tx.send((connection, names)).unwrap();
+            }
+        });
+        (format!("tcp://{addr}"), rx)
+    }
+
+    #[tokio::test]
+    async fn test_frames_are_decoded_and_connection_is_reestablished() {
+        let (endpoint, mut requests) = start_collector().await;
+        let mut exporter = SpanExporter::builder()
+            .with_tcp()
+            .with_endpoint(endpoint)
+            .build()
+            .unwrap();
+
+        exporter.export(vec![span("a"), span("b")]).await.unwrap();
+        assert_eq!(
+            requests.recv().await.unwrap(),
+            (0, vec!["a".to_string(), "b".to_string()])
+        );
+
+        // the collector closed the first connection
+        exporter.export(vec![span("c")]).await.unwrap();
+        assert_eq!(requests.recv().await.unwrap(), (1, vec!["c".to_string()]));
+    }
+}
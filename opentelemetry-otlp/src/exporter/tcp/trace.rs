@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use futures_core::future::BoxFuture;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use prost::Message;
+
+use super::TcpClient;
+use crate::Signal;
+
+impl SpanExporter for TcpClient {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        let payloads: Vec<_> = self
+            .conversion
+            .trace_requests(batch, &self.resource)
+            .iter()
+            .map(Message::encode_to_vec)
+            .collect();
+
+        let connection = Arc::clone(&self.connection);
+        let payload_recorder = self.payload_recorder.clone();
+        Box::pin(async move {
+            for payload in payloads {
+                connection.send(&payload).await?;
+                if let Some(recorder) = &payload_recorder {
+                    recorder.record(&payload, Signal::Traces);
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn shutdown(&mut self) {
+        self.connection.shutdown();
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.resource = resource.into();
+    }
+}
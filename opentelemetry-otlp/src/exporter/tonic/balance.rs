@@ -0,0 +1,512 @@
+//! The channels the exports are sent on, e.g. spread over several collector endpoints.
+
+use std::fmt;
+use std::future::poll_fn;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_core::future::BoxFuture;
+use http_body_util::{BodyExt, Full};
+use opentelemetry::otel_warn;
+use tonic::body::BoxBody;
+use tonic::codegen::{Bytes, Service, StdError};
+use tonic::transport::Channel;
+
+use super::compression::{compress_request, LeveledCompression};
+use super::service_path::ServicePathPrefix;
+
+/// The channel the requests of an exporter are sent on.
+#[derive(Clone, Debug)]
+pub(crate) enum ExportChannel {
+    Single(Channel),
+    Weighted(WeightedChannels),
+    /// A channel replaced whenever the client certificate changed.
+    #[cfg(feature = "tls")]
+    Reloading(super::reload::ReloadingChannel),
+    /// A channel periodically replaced by a new connection.
+    Reconnecting(super::reconnect::ReconnectingChannel),
+}
+
+impl ExportChannel {
+    /// Spread the requests over the channels to the endpoints in proportion to their weight,
+    /// which must be above zero, picking them as `selection` says. With `failover`, requests
+    /// failing on one endpoint are sent to the next one, as long as the `timeout` of the export
+    /// isn't over.
+    pub(crate) fn weighted(
+        channels: Vec<(String, ExportChannel, u32)>,
+        selection: EndpointSelection,
+        failover: bool,
+        timeout: Duration,
+    ) -> Result<Self, crate::Error> {
+        if channels.is_empty() {
+            return Err(crate::Error::NoWeightedEndpoint);
+        }
+
+        let weights: Vec<u32> = channels.iter().map(|(_, _, weight)| *weight).collect();
+        let health = match selection {
+            EndpointSelection::WeightedRoundRobin => None,
+            EndpointSelection::HealthAware => {
+                Some(Arc::new(Mutex::new(EndpointHealth::new(&weights))))
+            }
+        };
+        let (endpoints, channels): (Vec<String>, Vec<ExportChannel>) = channels
+            .into_iter()
+            .map(|(endpoint, channel, _)| (endpoint, channel))
+            .unzip();
+        Ok(ExportChannel::Weighted(WeightedChannels {
+            schedule: Arc::new(Mutex::new(Schedule::new(weights))),
+            health,
+            failover: failover.then(|| Arc::new(Mutex::new(Failover::new(endpoints)))),
+            timeout,
+            channels: channels.into(),
+        }))
+    }
+}
+
+impl Service<http::Request<BoxBody>> for ExportChannel {
+    type Response = http::Response<BoxBody>;
+    type Error = StdError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            ExportChannel::Single(channel) => channel.poll_ready(cx).map_err(Into::into),
+            // the channel is only picked once the request is sent, so readiness is awaited then
+            ExportChannel::Weighted(_) => Poll::Ready(Ok(())),
+            #[cfg(feature = "tls")]
+            ExportChannel::Reloading(_) => Poll::Ready(Ok(())),
+            ExportChannel::Reconnecting(_) => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, mut request: http::Request<BoxBody>) -> Self::Future {
+        ServicePathPrefix::apply(&mut request);
+        if request.extensions().get::<LeveledCompression>().is_some() {
+            // the channel polled ready is the one called
+            let clone = self.clone();
+            let mut channel = std::mem::replace(self, clone);
+            return Box::pin(async move {
+                let request = compress_request(request).await?;
+                channel.dispatch(request).await
+            });
+        }
+        self.dispatch(request)
+    }
+}
+
+impl ExportChannel {
+    /// Send `request` on the channel it is spread to.
+    fn dispatch(
+        &mut self,
+        request: http::Request<BoxBody>,
+    ) -> BoxFuture<'static, Result<http::Response<BoxBody>, StdError>> {
+        match self {
+            ExportChannel::Single(channel) => {
+                let future = channel.call(request);
+                Box::pin(async move { Ok(future.await?) })
+            }
+            ExportChannel::Weighted(channels) if channels.failover.is_some() => {
+                let channels = channels.clone();
+                Box::pin(async move { channels.send_with_failover(request).await })
+            }
+            ExportChannel::Weighted(channels) => {
+                let (index, channel) = channels.next();
+                let channels = channels.clone();
+                Box::pin(async move { channels.send(index, channel, request).await })
+            }
+            #[cfg(feature = "tls")]
+            ExportChannel::Reloading(channel) => {
+                let mut channel = channel.current();
+                Box::pin(async move {
+                    poll_fn(|cx| channel.poll_ready(cx)).await?;
+                    Ok(channel.call(request).await?)
+                })
+            }
+            ExportChannel::Reconnecting(channel) => {
+                let mut channel = channel.current();
+                Box::pin(async move {
+                    poll_fn(|cx| channel.poll_ready(cx)).await?;
+                    channel.call(request).await
+                })
+            }
+        }
+    }
+}
+
+/// How the exports are spread over the
+/// [weighted endpoints](crate::WithTonicConfig::with_weighted_endpoints).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EndpointSelection {
+    /// Pick the endpoints by weighted round-robin, regardless of how they perform.
+    #[default]
+    WeightedRoundRobin,
+    /// Pick the endpoint with the lowest recent latency and error rate, relative to its weight.
+    ///
+    /// Every tenth export is sent to the endpoint which went without exports the longest
+    /// instead, so endpoints which recovered receive exports again.
+    HealthAware,
+}
+
+/// Whether the collector accepted the request, as far as the response headers tell. The status
+/// of a successful gRPC response is only sent in its trailers.
+fn is_success(response: &http::Response<BoxBody>) -> bool {
+    let status = response.headers().get("grpc-status");
+    response.status().is_success() && status.filter(|status| *status != "0").is_none()
+}
+
+/// Channels to several endpoints, picked by smooth weighted round-robin, or by their health.
+#[derive(Clone)]
+pub(crate) struct WeightedChannels {
+    channels: Arc<[ExportChannel]>,
+    schedule: Arc<Mutex<Schedule>>,
+    health: Option<Arc<Mutex<EndpointHealth>>>,
+    failover: Option<Arc<Mutex<Failover>>>,
+    /// How long sending a request may take, across all the endpoints it fails over to.
+    timeout: Duration,
+}
+
+impl fmt::Debug for WeightedChannels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeightedChannels")
+            .field("channels", &self.channels.len())
+            .finish()
+    }
+}
+
+impl WeightedChannels {
+    /// The channel of the next request, and its index.
+    fn next(&self) -> (usize, ExportChannel) {
+        let mut schedule = self.schedule.lock().unwrap_or_else(|e| e.into_inner());
+        let scheduled = schedule.next();
+        let index = match &self.health {
+            Some(health) => health
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .pick(scheduled, schedule.picks),
+            None => scheduled,
+        };
+        (index, self.channels[index].clone())
+    }
+
+    /// The index of the next endpoint which isn't in `tried`, skipping ejected endpoints as
+    /// long as any other endpoint is left.
+    fn next_available(&self, failover: &Mutex<Failover>, tried: &[usize]) -> Option<usize> {
+        let mut fallback = None;
+        // every endpoint is scheduled within a cycle of the total weight
+        let cycle = self
+            .schedule
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .total;
+        for _ in 0..cycle {
+            let (index, _) = self.next();
+            if tried.contains(&index) {
+                continue;
+            }
+            if !failover
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .is_ejected(index)
+            {
+                return Some(index);
+            }
+            fallback = fallback.or(Some(index));
+        }
+        fallback.or_else(|| (0..self.channels.len()).find(|index| !tried.contains(index)))
+    }
+
+    /// Send `request` on the channel to the endpoint at `index`, recording how it went.
+    async fn send(
+        &self,
+        index: usize,
+        mut channel: ExportChannel,
+        request: http::Request<BoxBody>,
+    ) -> Result<http::Response<BoxBody>, StdError> {
+        let start = Instant::now();
+        let response = match poll_fn(|cx| channel.poll_ready(cx)).await {
+            Ok(()) => channel.call(request).await,
+            Err(error) => Err(error),
+        };
+        if let Some(health) = &self.health {
+            let succeeded = response.as_ref().is_ok_and(is_success);
+            health.lock().unwrap_or_else(|e| e.into_inner()).record(
+                index,
+                start.elapsed(),
+                succeeded,
+            );
+        }
+        if let Some(failover) = &self.failover {
+            let mut failover = failover.lock().unwrap_or_else(|e| e.into_inner());
+            failover.record(index, response.is_ok());
+        }
+        response
+    }
+
+    /// Send `request` to the next available endpoint, and to the following ones while it can't
+    /// be sent, until every endpoint was tried once or the timeout is over. The body is buffered
+    /// to be sent again.
+    async fn send_with_failover(
+        &self,
+        request: http::Request<BoxBody>,
+    ) -> Result<http::Response<BoxBody>, StdError> {
+        let Some(failover) = &self.failover else {
+            let (index, channel) = self.next();
+            return self.send(index, channel, request).await;
+        };
+        let (parts, body) = request.into_parts();
+        // a timeout too long to be represented never ends
+        let deadline = tokio::time::Instant::now().checked_add(self.timeout);
+        let body = body.collect().await?.to_bytes();
+
+        let mut tried = Vec::with_capacity(self.channels.len());
+        loop {
+            let index = self
+                .next_available(failover, &tried)
+                .expect("an endpoint is left untried");
+            tried.push(index);
+            let request = rebuild(&parts, &body);
+            let send = self.send(index, self.channels[index].clone(), request);
+            // the endpoints share the timeout, rather than each one getting all of it
+            let response = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, send).await {
+                    Ok(response) => response,
+                    Err(_) => {
+                        return Err(tonic::Status::deadline_exceeded("export timed out").into())
+                    }
+                },
+                None => send.await,
+            };
+            match response {
+                Err(error) if tried.len() < self.channels.len() => {
+                    otel_warn!(
+                        name: "TonicExporter.FailingOver",
+                        endpoint = failover
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .endpoints[index]
+                            .clone(),
+                        error = format!("{error}"),
+                    );
+                }
+                response => return response,
+            }
+        }
+    }
+}
+
+/// A copy of the request with the `parts` and buffered `body` of another one.
+fn rebuild(parts: &http::request::Parts, body: &Bytes) -> http::Request<BoxBody> {
+    let mut request = http::Request::new(tonic::body::boxed(Full::new(body.clone())));
+    *request.method_mut() = parts.method.clone();
+    *request.uri_mut() = parts.uri.clone();
+    *request.version_mut() = parts.version;
+    *request.headers_mut() = parts.headers.clone();
+    *request.extensions_mut() = parts.extensions.clone();
+    request
+}
+
+/// How many requests in a row must fail to be sent to an endpoint to remove it from rotation.
+const EJECTION_THRESHOLD: u32 = 3;
+
+/// How long an endpoint is removed from rotation, after which it receives a single request to
+/// find out whether it recovered.
+const EJECTION_DURATION: Duration = Duration::from_secs(30);
+
+/// The endpoints which requests failed to be sent to recently, removed from rotation for a while.
+struct Failover {
+    endpoints: Vec<String>,
+    failures: Vec<u32>,
+    ejected_until: Vec<Option<Instant>>,
+}
+
+impl Failover {
+    fn new(endpoints: Vec<String>) -> Self {
+        Failover {
+            failures: vec![0; endpoints.len()],
+            ejected_until: vec![None; endpoints.len()],
+            endpoints,
+        }
+    }
+
+    fn is_ejected(&self, index: usize) -> bool {
+        self.ejected_until[index].is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Record whether a request could be sent to the endpoint at `index`. An endpoint keeps
+    /// being ejected on every failure until a request to it succeeds again.
+    fn record(&mut self, index: usize, sent: bool) {
+        if sent {
+            self.failures[index] = 0;
+            self.ejected_until[index] = None;
+            return;
+        }
+        self.failures[index] = self.failures[index].saturating_add(1);
+        if self.failures[index] >= EJECTION_THRESHOLD {
+            if self.ejected_until[index].is_none() {
+                otel_warn!(
+                    name: "TonicExporter.EndpointEjected",
+                    endpoint = self.endpoints[index].clone(),
+                    failures = i64::from(self.failures[index]),
+                );
+            }
+            self.ejected_until[index] = Some(Instant::now() + EJECTION_DURATION);
+        }
+    }
+}
+
+/// Every how many picks the health-aware selection probes the endpoint picked least recently.
+const PROBE_INTERVAL: u64 = 10;
+
+/// Weight of the latest request in the moving averages of the endpoints.
+const EWMA_ALPHA: f64 = 0.3;
+
+/// Latency a failed request is counted as on top of its actual latency, so endpoints failing
+/// fast aren't preferred over slow endpoints which succeed.
+const ERROR_PENALTY: Duration = Duration::from_secs(1);
+
+/// Exponentially weighted moving averages of the latency and error rate of every endpoint.
+struct EndpointHealth {
+    weights: Vec<f64>,
+    latencies: Vec<f64>,
+    error_rates: Vec<f64>,
+    last_picks: Vec<u64>,
+}
+
+impl EndpointHealth {
+    fn new(weights: &[u32]) -> Self {
+        EndpointHealth {
+            weights: weights.iter().map(|weight| f64::from(*weight)).collect(),
+            latencies: vec![0.0; weights.len()],
+            error_rates: vec![0.0; weights.len()],
+            last_picks: vec![0; weights.len()],
+        }
+    }
+
+    /// Pick the endpoint of the `picks`th request, `scheduled` by round-robin.
+    fn pick(&mut self, scheduled: usize, picks: u64) -> usize {
+        let index = if picks % PROBE_INTERVAL == 0 {
+            (0..self.weights.len())
+                .min_by_key(|index| self.last_picks[*index])
+                .unwrap_or(scheduled)
+        } else {
+            self.healthiest(scheduled)
+        };
+        self.last_picks[index] = picks;
+        index
+    }
+
+    fn record(&mut self, index: usize, latency: Duration, succeeded: bool) {
+        let error = if succeeded { 0.0 } else { 1.0 };
+        self.latencies[index] += EWMA_ALPHA * (latency.as_secs_f64() - self.latencies[index]);
+        self.error_rates[index] += EWMA_ALPHA * (error - self.error_rates[index]);
+    }
+
+    fn score(&self, index: usize) -> f64 {
+        (self.latencies[index] + self.error_rates[index] * ERROR_PENALTY.as_secs_f64())
+            / self.weights[index]
+    }
+
+    /// The endpoint with the lowest score, `scheduled` on ties, so endpoints without requests
+    /// yet are tried in the round-robin order.
+    fn healthiest(&self, scheduled: usize) -> usize {
+        (0..self.weights.len()).fold(scheduled, |best, index| {
+            if self.score(index) < self.score(best) {
+                index
+            } else {
+                best
+            }
+        })
+    }
+}
+
+/// Smooth weighted round-robin, as in nginx: every pick adds the weights to the current scores
+/// and picks the highest score, which then drops by the total weight. Within each cycle of the
+/// total weight, every index is picked as often as its weight, interleaved with the others.
+struct Schedule {
+    weights: Vec<i64>,
+    scores: Vec<i64>,
+    total: i64,
+    picks: u64,
+}
+
+impl Schedule {
+    fn new(weights: Vec<u32>) -> Self {
+        let weights: Vec<i64> = weights.into_iter().map(i64::from).collect();
+        Schedule {
+            scores: vec![0; weights.len()],
+            total: weights.iter().sum(),
+            weights,
+            picks: 0,
+        }
+    }
+
+    fn next(&mut self) -> usize {
+        let mut picked = 0;
+        for (index, weight) in self.weights.iter().enumerate() {
+            self.scores[index] += weight;
+            if self.scores[index] > self.scores[picked] {
+                picked = index;
+            }
+        }
+        self.scores[picked] -= self.total;
+        self.picks += 1;
+        picked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{EndpointHealth, Failover, Schedule, EJECTION_THRESHOLD};
+
+    #[test]
+    fn test_schedule_interleaves_by_weight() {
+        let mut schedule = Schedule::new(vec![3, 1]);
+        let picks: Vec<_> = (0..8).map(|_| schedule.next()).collect();
+        assert_eq!(picks, vec![0, 0, 1, 0, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_health_prefers_fast_and_succeeding_endpoints() {
+        let mut health = EndpointHealth::new(&[1, 1, 1]);
+        assert_eq!(health.healthiest(1), 1);
+
+        health.record(0, Duration::from_millis(500), true);
+        health.record(1, Duration::from_millis(10), false);
+        health.record(2, Duration::from_millis(50), true);
+        assert_eq!(health.healthiest(0), 2);
+    }
+
+    #[test]
+    fn test_health_probes_least_recently_picked_endpoint() {
+        let mut health = EndpointHealth::new(&[1, 1]);
+        health.record(0, Duration::from_millis(500), true);
+        health.record(1, Duration::from_millis(10), true);
+
+        let picks: Vec<_> = (1..=10).map(|pick| health.pick(0, pick)).collect();
+        assert_eq!(picks, vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_failover_ejects_endpoint_failing_in_a_row() {
+        let mut failover = Failover::new(vec!["a".to_string(), "b".to_string()]);
+        for _ in 1..EJECTION_THRESHOLD {
+            failover.record(0, false);
+        }
+        failover.record(0, true);
+        for _ in 1..EJECTION_THRESHOLD {
+            failover.record(0, false);
+        }
+        assert!(!failover.is_ejected(0));
+
+        failover.record(0, false);
+        assert!(failover.is_ejected(0));
+        assert!(!failover.is_ejected(1));
+
+        failover.record(0, true);
+        assert!(!failover.is_ejected(0));
+    }
+}
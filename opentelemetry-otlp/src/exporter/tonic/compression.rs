@@ -0,0 +1,353 @@
+//! Compress the requests of a signal statically or as negotiated with the collector.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use http::HeaderValue;
+use http_body_util::{BodyExt, Full};
+use opentelemetry::otel_debug;
+use tonic::body::BoxBody;
+use tonic::codec::CompressionEncoding;
+use tonic::codegen::StdError;
+use tonic::metadata::MetadataMap;
+
+use crate::exporter::Compression;
+
+const GRPC_ACCEPT_ENCODING: &str = "grpc-accept-encoding";
+
+const GRPC_ENCODING: &str = "grpc-encoding";
+
+/// Length of the prefix of every gRPC message: the compressed flag and the message length.
+const MESSAGE_PREFIX_LEN: usize = 5;
+
+/// How the exporter of a signal compresses its requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompressionStrategy {
+    /// Always compress with the given algorithm.
+    Static(Compression),
+    /// Compress with the first algorithm the collector lists in the `grpc-accept-encoding`
+    /// header of its first successful response, if the algorithm's feature is enabled.
+    ///
+    /// Requests are sent uncompressed until then, and for good if the collector doesn't list
+    /// a supported algorithm.
+    Negotiate,
+    /// Never compress, regardless of the compression configured for all signals.
+    None,
+}
+
+/// The compression of the requests of a single signal.
+#[derive(Clone, Debug)]
+pub(crate) struct SignalCompression {
+    strategy: Strategy,
+    hint: Option<CompressionHint>,
+    /// The level the requests are compressed at, instead of the default of the algorithm.
+    level: Option<i32>,
+}
+
+#[derive(Clone, Debug)]
+enum Strategy {
+    Static(Option<CompressionEncoding>),
+    Negotiate(Arc<OnceLock<Option<CompressionEncoding>>>),
+}
+
+/// The compression the collector last hinted at in a response header, overriding the strategy.
+#[derive(Clone, Debug)]
+struct CompressionHint {
+    header: String,
+    /// `Some(None)` once the collector hinted at sending requests uncompressed.
+    hinted: Arc<Mutex<Option<Option<CompressionEncoding>>>>,
+}
+
+impl SignalCompression {
+    /// Apply `strategy` of the signal, falling back to the compression configured for all
+    /// signals.
+    pub(crate) fn new(
+        strategy: Option<CompressionStrategy>,
+        configured: Option<CompressionEncoding>,
+    ) -> Result<Self, crate::Error> {
+        let strategy = match strategy {
+            Some(CompressionStrategy::Static(compression)) => {
+                Strategy::Static(Some(compression.try_into()?))
+            }
+            Some(CompressionStrategy::Negotiate) => Strategy::Negotiate(Arc::default()),
+            Some(CompressionStrategy::None) => Strategy::Static(None),
+            None => Strategy::Static(configured),
+        };
+        Ok(SignalCompression {
+            strategy,
+            hint: None,
+            level: None,
+        })
+    }
+
+    /// Compress the requests at `level`, which must be in the range of the algorithm the signal
+    /// is configured with. Algorithms negotiated with or hinted at by the collector use the
+    /// closest level in their range.
+    pub(crate) fn with_level(mut self, level: Option<i32>) -> Result<Self, crate::Error> {
+        if let (Some(level), Strategy::Static(Some(encoding))) = (level, &self.strategy) {
+            compression(*encoding).validate_level(level)?;
+        }
+        self.level = level;
+        Ok(self)
+    }
+
+    /// Switch to the compression the collector hints at in the response header `header`.
+    pub(crate) fn with_hint_header(mut self, header: Option<String>) -> Self {
+        self.hint = header.map(|header| CompressionHint {
+            header: header.to_ascii_lowercase(),
+            hinted: Arc::default(),
+        });
+        self
+    }
+
+    /// The encodings of responses the client accepts.
+    pub(crate) fn accepted(&self) -> Vec<CompressionEncoding> {
+        match &self.strategy {
+            Strategy::Static(encoding) => encoding.iter().copied().collect(),
+            Strategy::Negotiate(_) => supported_encodings().to_vec(),
+        }
+    }
+
+    /// The encoding of the next request, if it is compressed.
+    pub(crate) fn encoding(&self) -> Option<CompressionEncoding> {
+        if let Some(hinted) = self.hint.as_ref().and_then(CompressionHint::hinted) {
+            return hinted;
+        }
+        match &self.strategy {
+            Strategy::Static(encoding) => *encoding,
+            Strategy::Negotiate(negotiated) => negotiated.get().copied().flatten(),
+        }
+    }
+
+    /// The compression of the next request at the configured level, if it is compressed at a
+    /// level other than the default. The channel compresses such requests instead of tonic,
+    /// which only compresses at the default level.
+    pub(crate) fn leveled(&self) -> Option<LeveledCompression> {
+        let level = self.level?;
+        let encoding = self.encoding()?;
+        Some(LeveledCompression {
+            encoding,
+            level: compression(encoding).clamp_level(level),
+        })
+    }
+
+    /// Settle the negotiation and follow the hint of the collector with the metadata of a
+    /// successful response.
+    pub(crate) fn negotiate(&self, metadata: &MetadataMap) {
+        if let Some(hint) = &self.hint {
+            hint.follow(metadata);
+        }
+        let Strategy::Negotiate(negotiated) = &self.strategy else {
+            return;
+        };
+        negotiated.get_or_init(|| {
+            let accepted = metadata
+                .get(GRPC_ACCEPT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            accepted.split(',').find_map(|encoding| {
+                supported_encodings()
+                    .iter()
+                    .copied()
+                    .find(|supported| supported.to_string() == encoding.trim())
+            })
+        });
+    }
+}
+
+impl CompressionHint {
+    fn hinted(&self) -> Option<Option<CompressionEncoding>> {
+        *self.hinted.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Remember the compression hinted at in `metadata`. `identity` and `none` hint at sending
+    /// uncompressed requests, while responses without the header, or hinting at an algorithm
+    /// whose feature isn't enabled, keep the current compression.
+    fn follow(&self, metadata: &MetadataMap) {
+        let Some(hint) = metadata
+            .get(self.header.as_str())
+            .and_then(|value| value.to_str().ok())
+        else {
+            return;
+        };
+        let hint = hint.trim().to_ascii_lowercase();
+        let encoding = match hint.as_str() {
+            "identity" | "none" => None,
+            hint => match supported_encodings()
+                .iter()
+                .find(|supported| supported.to_string() == hint)
+            {
+                Some(encoding) => Some(*encoding),
+                None => {
+                    otel_debug!(
+                        name: "TonicExporter.UnsupportedCompressionHint",
+                        hint = hint.to_string(),
+                    );
+                    return;
+                }
+            },
+        };
+        *self.hinted.lock().unwrap_or_else(|e| e.into_inner()) = Some(encoding);
+    }
+}
+
+/// The algorithm of a request compressed at a configured level, attached to the request as an
+/// extension for the channel to compress it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct LeveledCompression {
+    encoding: CompressionEncoding,
+    level: i32,
+}
+
+impl LeveledCompression {
+    #[cfg_attr(
+        not(any(feature = "gzip-tonic", feature = "zstd-tonic")),
+        allow(unused_variables)
+    )]
+    fn compress(&self, message: &[u8]) -> Result<Vec<u8>, StdError> {
+        match self.encoding {
+            #[cfg(feature = "gzip-tonic")]
+            CompressionEncoding::Gzip => {
+                use std::io::Write;
+
+                // the level was clamped to the range of gzip
+                let level = flate2::Compression::new(self.level as u32);
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+                encoder.write_all(message)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd-tonic")]
+            CompressionEncoding::Zstd => Ok(zstd::bulk::compress(message, self.level)?),
+            #[allow(unreachable_patterns)]
+            encoding => Err(format!("unsupported compression encoding {encoding}").into()),
+        }
+    }
+}
+
+/// Compress the messages in the body of `request` if it carries a [`LeveledCompression`],
+/// setting the `grpc-encoding` header and the compressed flag of the messages, as tonic does.
+/// The extension is removed, so channels nested in the one compressing don't compress again.
+pub(crate) async fn compress_request(
+    mut request: http::Request<BoxBody>,
+) -> Result<http::Request<BoxBody>, StdError> {
+    let Some(compression) = request.extensions_mut().remove::<LeveledCompression>() else {
+        return Ok(request);
+    };
+    let (mut parts, body) = request.into_parts();
+    let body = body.collect().await?.to_bytes();
+
+    let mut compressed = Vec::with_capacity(body.len());
+    let mut messages = &body[..];
+    while !messages.is_empty() {
+        if messages.len() < MESSAGE_PREFIX_LEN {
+            return Err("truncated gRPC message prefix".into());
+        }
+        let len = u32::from_be_bytes([messages[1], messages[2], messages[3], messages[4]]);
+        let end = MESSAGE_PREFIX_LEN + len as usize;
+        let message = messages
+            .get(MESSAGE_PREFIX_LEN..end)
+            .ok_or("truncated gRPC message")?;
+        let message = compression.compress(message)?;
+        compressed.push(1);
+        compressed.extend_from_slice(&u32::try_from(message.len())?.to_be_bytes());
+        compressed.extend_from_slice(&message);
+        messages = &messages[end..];
+    }
+
+    parts.headers.insert(
+        GRPC_ENCODING,
+        HeaderValue::from_str(&compression.encoding.to_string())?,
+    );
+    Ok(http::Request::from_parts(
+        parts,
+        tonic::body::boxed(Full::new(compressed.into())),
+    ))
+}
+
+/// The algorithm of `encoding`.
+fn compression(encoding: CompressionEncoding) -> Compression {
+    match encoding {
+        #[cfg(feature = "zstd-tonic")]
+        CompressionEncoding::Zstd => Compression::Zstd,
+        // gzip is the only other algorithm of tonic
+        _ => Compression::Gzip,
+    }
+}
+
+/// The encodings enabled by the features of this crate.
+fn supported_encodings() -> &'static [CompressionEncoding] {
+    &[
+        #[cfg(feature = "gzip-tonic")]
+        CompressionEncoding::Gzip,
+        #[cfg(feature = "zstd-tonic")]
+        CompressionEncoding::Zstd,
+    ]
+}
+
+#[cfg(test)]
+#[cfg(feature = "gzip-tonic")]
+mod tests {
+    use tonic::codec::CompressionEncoding;
+    use tonic::metadata::MetadataMap;
+
+    use super::{CompressionStrategy, SignalCompression};
+    use crate::exporter::tonic::TonicExporterBuilder;
+    use crate::{Compression, Signal, WithTonicConfig};
+
+    #[test]
+    fn test_static_traces_and_negotiated_metrics() {
+        let builder = TonicExporterBuilder::default()
+            .with_compression_strategy(
+                Signal::Traces,
+                CompressionStrategy::Static(Compression::Gzip),
+            )
+            .with_compression_strategy(Signal::Metrics, CompressionStrategy::Negotiate);
+
+        let traces =
+            SignalCompression::new(builder.compression_strategy(Signal::Traces), None).unwrap();
+        assert_eq!(traces.encoding(), Some(CompressionEncoding::Gzip));
+
+        let metrics =
+            SignalCompression::new(builder.compression_strategy(Signal::Metrics), None).unwrap();
+        assert!(metrics.accepted().contains(&CompressionEncoding::Gzip));
+        // uncompressed until the collector responded
+        assert_eq!(metrics.encoding(), None);
+        let mut metadata = MetadataMap::new();
+        metadata.insert("grpc-accept-encoding", "identity, gzip".parse().unwrap());
+        metrics.negotiate(&metadata);
+        assert_eq!(metrics.encoding(), Some(CompressionEncoding::Gzip));
+        // clones of the client share the outcome
+        assert_eq!(metrics.clone().encoding(), Some(CompressionEncoding::Gzip));
+
+        // signals without a strategy use the compression configured for all signals
+        let logs =
+            SignalCompression::new(builder.compression_strategy(Signal::Logs), None).unwrap();
+        assert_eq!(logs.encoding(), None);
+    }
+
+    #[test]
+    fn test_negotiation_without_supported_encoding() {
+        let compression =
+            SignalCompression::new(Some(CompressionStrategy::Negotiate), None).unwrap();
+
+        compression.negotiate(&MetadataMap::new());
+        assert_eq!(compression.encoding(), None);
+
+        // the first response settles the negotiation
+        let mut metadata = MetadataMap::new();
+        metadata.insert("grpc-accept-encoding", "gzip".parse().unwrap());
+        compression.negotiate(&metadata);
+        assert_eq!(compression.encoding(), None);
+    }
+
+    #[test]
+    fn test_strategy_none_overrides_configured_compression() {
+        let compression = SignalCompression::new(
+            Some(CompressionStrategy::None),
+            Some(CompressionEncoding::Gzip),
+        )
+        .unwrap();
+        assert_eq!(compression.encoding(), None);
+        assert!(compression.accepted().is_empty());
+    }
+}
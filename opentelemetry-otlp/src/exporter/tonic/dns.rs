@@ -0,0 +1,315 @@
+//! Resolve the collector endpoint and cache its DNS resolution.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use hyper_util::client::legacy::connect::dns::Name;
+use hyper_util::client::legacy::connect::HttpConnector;
+use opentelemetry::otel_warn;
+use tonic::codegen::Service;
+
+/// Resolves a host name to its addresses, blocking the calling thread.
+type ResolveFn = dyn Fn(&str) -> io::Result<Vec<IpAddr>> + Send + Sync;
+
+/// Resolves host names with a blocking function, run on the blocking thread pool when the
+/// channel connects.
+#[derive(Clone)]
+pub(crate) struct Resolver {
+    resolve: Arc<ResolveFn>,
+    /// How a failure of the first resolution is handled, if configured.
+    first_resolution: Option<Arc<FirstResolution>>,
+}
+
+/// Handles a failure of the first resolution of the endpoint, configured by
+/// [`with_defer_on_dns_failure`](crate::WithTonicConfig::with_defer_on_dns_failure).
+#[derive(Debug)]
+struct FirstResolution {
+    /// Whether the endpoint is resolved again on the next connect rather than failing it.
+    defer: bool,
+    outcome: Mutex<Outcome>,
+}
+
+#[derive(Debug, Default)]
+enum Outcome {
+    #[default]
+    Pending,
+    Settled,
+    /// The first resolution failed and isn't retried.
+    Failed(io::ErrorKind, String),
+}
+
+impl Default for Resolver {
+    /// The resolver of the system.
+    fn default() -> Self {
+        Resolver {
+            resolve: Arc::new(|host| {
+                Ok((host, 0).to_socket_addrs()?.map(|addr| addr.ip()).collect())
+            }),
+            first_resolution: None,
+        }
+    }
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Resolver")
+    }
+}
+
+impl Resolver {
+    #[cfg(test)]
+    pub(crate) fn new(
+        resolve: impl Fn(&str) -> io::Result<Vec<IpAddr>> + Send + Sync + 'static,
+    ) -> Self {
+        Resolver {
+            resolve: Arc::new(resolve),
+            first_resolution: None,
+        }
+    }
+
+    /// The resolver failing every connect after its first resolution failed, or only logging
+    /// the failure and resolving again on the next connect if `defer` is `true`.
+    pub(crate) fn with_first_resolution(self, defer: bool) -> Self {
+        Resolver {
+            first_resolution: Some(Arc::new(FirstResolution {
+                defer,
+                outcome: Mutex::default(),
+            })),
+            ..self
+        }
+    }
+
+    /// Resolve `host` on the calling thread. IP addresses are returned as is.
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        match host.parse() {
+            Ok(ip) => Ok(vec![ip]),
+            Err(_) => (self.resolve)(host),
+        }
+    }
+
+    /// The error of a failed first resolution which isn't retried.
+    fn first_failure(&self) -> Option<io::Error> {
+        let first = self.first_resolution.as_ref()?;
+        match &*first.outcome.lock().unwrap_or_else(|e| e.into_inner()) {
+            Outcome::Failed(kind, message) => Some(io::Error::new(*kind, message.clone())),
+            Outcome::Pending | Outcome::Settled => None,
+        }
+    }
+
+    /// Record the outcome of resolving `host`, if it is the first resolution.
+    fn record(&self, host: &str, resolved: &io::Result<Vec<IpAddr>>) {
+        let Some(first) = &self.first_resolution else {
+            return;
+        };
+        let mut outcome = first.outcome.lock().unwrap_or_else(|e| e.into_inner());
+        if !matches!(*outcome, Outcome::Pending) {
+            return;
+        }
+        *outcome = match resolved {
+            Ok(_) => Outcome::Settled,
+            Err(error) if first.defer => {
+                otel_warn!(
+                    name: "TonicExporter.DnsResolutionDeferred",
+                    host = host.to_string(),
+                    error = error.to_string(),
+                );
+                Outcome::Settled
+            }
+            Err(error) => {
+                otel_warn!(
+                    name: "TonicExporter.DnsResolutionFailed",
+                    host = host.to_string(),
+                    error = error.to_string(),
+                );
+                Outcome::Failed(
+                    error.kind(),
+                    format!("failed to resolve the collector host {host}: {error}"),
+                )
+            }
+        };
+    }
+}
+
+impl Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolver = self.clone();
+        Box::pin(async move {
+            if let Some(error) = resolver.first_failure() {
+                return Err(error);
+            }
+            let resolved = tokio::task::spawn_blocking({
+                let resolver = resolver.clone();
+                let name = name.clone();
+                move || resolver.resolve(name.as_str())
+            })
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            resolver.record(name.as_str(), &resolved);
+            // the connector sets the port of the endpoint
+            let addrs: Vec<_> = resolved?
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, 0))
+                .collect();
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+/// A resolver which reuses the addresses it resolved a name to for `ttl`.
+///
+/// The channel resolves the endpoint each time it (re)connects. Caching the addresses avoids
+/// resolving them on every reconnect, while the ttl still picks up endpoints which moved.
+#[derive(Clone)]
+pub(crate) struct DnsCache<R = Resolver> {
+    resolver: R,
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<Name, CachedAddrs>>>,
+}
+
+#[derive(Debug)]
+struct CachedAddrs {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+impl<R> DnsCache<R> {
+    pub(crate) fn new(resolver: R, ttl: Duration) -> Self {
+        DnsCache {
+            resolver,
+            ttl,
+            entries: Arc::default(),
+        }
+    }
+
+    fn cached(&self, name: &Name) -> Option<Vec<SocketAddr>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(name)
+            .filter(|cached| cached.resolved_at.elapsed() < self.ttl)
+            .map(|cached| cached.addrs.clone())
+    }
+}
+
+impl<R> Service<Name> for DnsCache<R>
+where
+    R: Service<Name, Error = io::Error> + Clone + Send + 'static,
+    R::Response: Iterator<Item = SocketAddr>,
+    R::Future: Send,
+{
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        if let Some(addrs) = self.cached(&name) {
+            return Box::pin(std::future::ready(Ok(addrs.into_iter())));
+        }
+
+        let mut resolver = self.resolver.clone();
+        let entries = Arc::clone(&self.entries);
+        Box::pin(async move {
+            poll_fn(|cx| resolver.poll_ready(cx)).await?;
+            let addrs: Vec<SocketAddr> = resolver.call(name.clone()).await?.collect();
+            entries.lock().unwrap_or_else(|e| e.into_inner()).insert(
+                name,
+                CachedAddrs {
+                    addrs: addrs.clone(),
+                    resolved_at: Instant::now(),
+                },
+            );
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+/// The connector tonic uses by default, resolving the endpoint with `resolver`.
+pub(crate) fn connector<R>(resolver: R) -> HttpConnector<R> {
+    let mut connector = HttpConnector::new_with_resolver(resolver);
+    connector.enforce_http(false);
+    connector.set_nodelay(true);
+    connector
+}
+
+/// The connector tonic uses by default, resolving the endpoint through a [`DnsCache`].
+pub(crate) fn caching_connector(resolver: Resolver, ttl: Duration) -> HttpConnector<DnsCache> {
+    connector(DnsCache::new(resolver, ttl))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::SocketAddr;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use hyper_util::client::legacy::connect::dns::Name;
+    use tonic::codegen::Service;
+
+    use super::DnsCache;
+
+    /// Resolves every name to a new port, counting the resolutions.
+    #[derive(Clone, Default)]
+    struct CountingResolver(Arc<AtomicUsize>);
+
+    impl Service<Name> for CountingResolver {
+        type Response = std::vec::IntoIter<SocketAddr>;
+        type Error = io::Error;
+        type Future = std::future::Ready<io::Result<Self::Response>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _name: Name) -> Self::Future {
+            let port = 4317 + self.0.fetch_add(1, Ordering::SeqCst) as u16;
+            std::future::ready(Ok(vec![SocketAddr::from(([10, 0, 0, 1], port))].into_iter()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolution_is_cached_within_ttl() {
+        let resolutions = CountingResolver::default();
+        let mut cache = DnsCache::new(resolutions.clone(), Duration::from_millis(100));
+        let name = Name::from_str("collector.example.com").unwrap();
+
+        let first: Vec<_> = cache.call(name.clone()).await.unwrap().collect();
+        let second: Vec<_> = cache.call(name.clone()).await.unwrap().collect();
+        assert_eq!(first, second);
+        assert_eq!(resolutions.0.load(Ordering::SeqCst), 1);
+
+        // other names are resolved on their own
+        cache
+            .call(Name::from_str("other.example.com").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resolutions.0.load(Ordering::SeqCst), 2);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let refreshed: Vec<_> = cache.call(name).await.unwrap().collect();
+        assert_ne!(first, refreshed);
+        assert_eq!(resolutions.0.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,153 @@
+//! Limit the connections establishing at the same time, so a burst of TLS handshakes when many
+//! channels connect at startup doesn't spike the CPU.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+
+use http::Uri;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tonic::codegen::Service;
+
+/// The semaphore shared by all channels of the process limited to `limit` concurrent
+/// handshakes. The limit is clamped to the range the semaphore supports.
+pub(crate) fn semaphore(limit: usize) -> Arc<Semaphore> {
+    static SEMAPHORES: OnceLock<Mutex<HashMap<usize, Arc<Semaphore>>>> = OnceLock::new();
+    let limit = limit.clamp(1, Semaphore::MAX_PERMITS);
+    let mut semaphores = SEMAPHORES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    semaphores
+        .entry(limit)
+        .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+        .clone()
+}
+
+/// Connects with `C` once a permit of the semaphore is acquired, holding it until the
+/// connection completed its handshake.
+#[derive(Clone, Debug)]
+pub(crate) struct Limited<C> {
+    connector: C,
+    semaphore: Arc<Semaphore>,
+}
+
+impl<C> Limited<C> {
+    pub(crate) fn new(connector: C, semaphore: Arc<Semaphore>) -> Self {
+        Limited {
+            connector,
+            semaphore,
+        }
+    }
+}
+
+impl<C, T> Service<Uri> for Limited<C>
+where
+    C: Service<Uri, Response = TokioIo<T>> + Clone + Send + 'static,
+    C::Error: std::error::Error + Send + Sync + 'static,
+    C::Future: Send,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    type Response = TokioIo<Handshake<T>>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.connector.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        // the connector polled ready is the one called
+        let clone = self.connector.clone();
+        let mut connector = std::mem::replace(&mut self.connector, clone);
+        let semaphore = self.semaphore.clone();
+        Box::pin(async move {
+            let permit = semaphore.acquire_owned().await?;
+            let io = connector.call(uri).await?;
+            Ok(TokioIo::new(Handshake {
+                io: io.into_inner(),
+                permit: Some(permit),
+                received: false,
+            }))
+        })
+    }
+}
+
+/// A connection holding its handshake permit until the client completed its side of the
+/// handshake.
+///
+/// The client is done once it writes again after receiving the response of the server, e.g.
+/// after verifying the certificate of the server and sending its TLS `Finished` message, or
+/// after acknowledging the HTTP/2 settings of a plaintext connection.
+#[derive(Debug)]
+pub(crate) struct Handshake<T> {
+    io: T,
+    permit: Option<OwnedSemaphorePermit>,
+    received: bool,
+}
+
+impl<T> Handshake<T> {
+    fn written(&mut self) {
+        if self.received {
+            self.permit = None;
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Handshake<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.io).poll_read(cx, buf);
+        if buf.filled().len() > filled {
+            self.received = true;
+        }
+        poll
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Handshake<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.io).poll_write(cx, buf);
+        if let Poll::Ready(Ok(_)) = poll {
+            self.written();
+        }
+        poll
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.io).poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(_)) = poll {
+            self.written();
+        }
+        poll
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,245 @@
+//! Query the collector with the [gRPC health checking protocol].
+//!
+//! [gRPC health checking protocol]: https://github.com/grpc/grpc/blob/master/doc/health-checking.md
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use http::uri::PathAndQuery;
+use tonic::codec::ProstCodec;
+use tonic::service::Interceptor;
+
+use super::balance::ExportChannel;
+use super::BoxInterceptor;
+
+const HEALTH_CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+
+/// The serving status of the collector, as reported by its health service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HealthStatus {
+    /// The collector doesn't know whether it is serving.
+    Unknown,
+    /// The collector is serving requests.
+    Serving,
+    /// The collector is not serving requests.
+    NotServing,
+    /// The health service doesn't know the queried service.
+    ServiceUnknown,
+    /// The collector doesn't expose the health service, or the exporter doesn't use gRPC.
+    Unsupported,
+}
+
+impl HealthStatus {
+    fn from_serving_status(status: i32) -> Self {
+        match status {
+            1 => HealthStatus::Serving,
+            2 => HealthStatus::NotServing,
+            3 => HealthStatus::ServiceUnknown,
+            _ => HealthStatus::Unknown,
+        }
+    }
+}
+
+/// `grpc.health.v1.HealthCheckRequest`
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct HealthCheckRequest {
+    #[prost(string, tag = "1")]
+    pub(crate) service: String,
+}
+
+/// `grpc.health.v1.HealthCheckResponse`
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct HealthCheckResponse {
+    #[prost(int32, tag = "1")]
+    pub(crate) status: i32,
+}
+
+/// Sends health checks over the channel of an exporter, sharing its interceptor.
+#[derive(Clone)]
+pub(crate) struct HealthClient {
+    channel: ExportChannel,
+    interceptor: Arc<Mutex<BoxInterceptor>>,
+}
+
+impl fmt::Debug for HealthClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HealthClient")
+    }
+}
+
+impl HealthClient {
+    pub(crate) fn new(channel: ExportChannel, interceptor: BoxInterceptor) -> Self {
+        HealthClient {
+            channel,
+            interceptor: Arc::new(Mutex::new(interceptor)),
+        }
+    }
+
+    /// An interceptor for the export client, forwarding to the shared interceptor.
+    // tonic interceptors fail with a `Status`, however large it is
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn interceptor(&self) -> BoxInterceptor {
+        let interceptor = Arc::clone(&self.interceptor);
+        BoxInterceptor(Box::new(move |req| {
+            interceptor
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .call(req)
+        }))
+    }
+
+    /// Query the overall health of the collector.
+    pub(crate) async fn check(&self) -> Result<HealthStatus, crate::Error> {
+        let (metadata, extensions, _) = self
+            .interceptor
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .call(tonic::Request::new(()))?
+            .into_parts();
+        let request = tonic::Request::from_parts(
+            metadata,
+            extensions,
+            // the empty service name queries the health of the whole server
+            HealthCheckRequest::default(),
+        );
+
+        let mut client = tonic::client::Grpc::new(self.channel.clone());
+        // the channel fails with any error, which the status carries
+        client.ready().await.map_err(tonic::Status::from_error)?;
+        let response = client
+            .unary(
+                request,
+                PathAndQuery::from_static(HEALTH_CHECK_PATH),
+                ProstCodec::<HealthCheckRequest, HealthCheckResponse>::default(),
+            )
+            .await;
+
+        match response {
+            Ok(response) => Ok(HealthStatus::from_serving_status(
+                response.into_inner().status,
+            )),
+            Err(status) => match status.code() {
+                tonic::Code::Unimplemented => Ok(HealthStatus::Unsupported),
+                tonic::Code::NotFound => Ok(HealthStatus::ServiceUnknown),
+                _ => Err(status.into()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "trace")]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::task::{Context, Poll};
+
+    use futures_core::future::BoxFuture;
+    use opentelemetry_proto::tonic::collector::logs::v1::{
+        logs_service_server::{LogsService, LogsServiceServer},
+        ExportLogsServiceRequest, ExportLogsServiceResponse,
+    };
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::body::BoxBody;
+    use tonic::codec::ProstCodec;
+    use tonic::codegen::Service;
+    use tonic::server::{NamedService, UnaryService};
+    use tonic::transport::server::Router;
+
+    use super::{HealthCheckRequest, HealthCheckResponse, HealthStatus};
+    use crate::{SpanExporter, WithExportConfig};
+
+    /// A health service always reporting the same serving status.
+    #[derive(Clone)]
+    struct HealthStub(i32);
+
+    impl NamedService for HealthStub {
+        const NAME: &'static str = "grpc.health.v1.Health";
+    }
+
+    impl UnaryService<HealthCheckRequest> for HealthStub {
+        type Response = HealthCheckResponse;
+        type Future =
+            std::future::Ready<Result<tonic::Response<HealthCheckResponse>, tonic::Status>>;
+
+        fn call(&mut self, _request: tonic::Request<HealthCheckRequest>) -> Self::Future {
+            std::future::ready(Ok(tonic::Response::new(HealthCheckResponse {
+                status: self.0,
+            })))
+        }
+    }
+
+    impl Service<http::Request<BoxBody>> for HealthStub {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<'static, Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+            let stub = self.clone();
+            Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(ProstCodec::<
+                    HealthCheckResponse,
+                    HealthCheckRequest,
+                >::default());
+                Ok(grpc.unary(stub, request).await)
+            })
+        }
+    }
+
+    /// A collector without the health service.
+    struct LogsStub;
+
+    #[tonic::async_trait]
+    impl LogsService for LogsStub {
+        async fn export(
+            &self,
+            _request: tonic::Request<ExportLogsServiceRequest>,
+        ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+            Ok(tonic::Response::new(ExportLogsServiceResponse::default()))
+        }
+    }
+
+    async fn serve(router: Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+        addr
+    }
+
+    async fn check_health(addr: SocketAddr) -> Result<HealthStatus, crate::Error> {
+        SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(format!("http://{addr}"))
+            .build()
+            .unwrap()
+            .check_health()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_check_health_against_stub() {
+        let serving = serve(tonic::transport::Server::builder().add_service(HealthStub(1))).await;
+        assert_eq!(check_health(serving).await.unwrap(), HealthStatus::Serving);
+
+        let not_serving =
+            serve(tonic::transport::Server::builder().add_service(HealthStub(2))).await;
+        assert_eq!(
+            check_health(not_serving).await.unwrap(),
+            HealthStatus::NotServing
+        );
+
+        let without_health = serve(
+            tonic::transport::Server::builder().add_service(LogsServiceServer::new(LogsStub)),
+        )
+        .await;
+        assert_eq!(
+            check_health(without_health).await.unwrap(),
+            HealthStatus::Unsupported
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! TLS connections accepting any certificate of the collector, for local development against
+//! collectors with self-signed certificates. Never use this in production: anyone able to
+//! intercept the connection can read and alter the exported telemetry.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::Uri;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{self, ClientConfig, DigitallySignedStruct, SignatureScheme};
+use tokio_rustls::TlsConnector;
+use tonic::codegen::Service;
+
+/// Accepts the certificate of any server, only checking the handshake signatures are valid.
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// The TLS settings of connections to the collector at `server_name` skipping the
+/// verification of its certificate.
+#[derive(Clone)]
+pub(crate) struct InsecureTls {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl fmt::Debug for InsecureTls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InsecureTls")
+            .field("server_name", &self.server_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl InsecureTls {
+    /// The TLS settings sending `server_name` as SNI.
+    pub(crate) fn new(server_name: &str) -> Result<Self, crate::Error> {
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|_| crate::Error::InvalidTlsServerName(server_name.to_string()))?;
+
+        let provider = Arc::new(crypto::ring::default_provider());
+        let mut config = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .expect("the ring provider supports the default protocol versions")
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        Ok(InsecureTls {
+            connector: TlsConnector::from(Arc::new(config)),
+            server_name,
+        })
+    }
+
+    /// Wrap `connector`, establishing TLS over the connections it opens.
+    pub(crate) fn wrap<C>(&self, connector: C) -> Connector<C> {
+        Connector {
+            connector,
+            tls: self.clone(),
+        }
+    }
+}
+
+/// Connects with `C`, then performs the TLS handshake over the connection.
+#[derive(Clone, Debug)]
+pub(crate) struct Connector<C> {
+    connector: C,
+    tls: InsecureTls,
+}
+
+impl<C, T> Service<Uri> for Connector<C>
+where
+    C: Service<Uri, Response = TokioIo<T>> + Clone + Send + 'static,
+    C::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    C::Future: Send + 'static,
+    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Response = TokioIo<TlsStream<T>>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.connector.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        // the connector polled ready is the one called
+        let clone = self.connector.clone();
+        let mut connector = std::mem::replace(&mut self.connector, clone);
+        let tls = self.tls.clone();
+        Box::pin(async move {
+            let io = connector.call(uri).await.map_err(Into::into)?;
+            let stream = tls
+                .connector
+                .connect(tls.server_name, io.into_inner())
+                .await?;
+            Ok(TokioIo::new(stream))
+        })
+    }
+}
+
+/// The plaintext endpoint tonic connects to in place of the `https` `endpoint`, with the
+/// default port made explicit, and the host of `endpoint` as the server name. The TLS is
+/// established by [`Connector`] instead, since tonic only connects to `https` endpoints with
+/// its own TLS settings.
+pub(crate) fn plaintext_endpoint(endpoint: &Uri) -> Option<(String, String)> {
+    if endpoint.scheme_str() != Some("https") {
+        return None;
+    }
+    let host = endpoint.host()?;
+    let port = endpoint.port_u16().unwrap_or(443);
+    let path = endpoint.path_and_query().map_or("", |path| path.as_str());
+    let server_name = host.trim_start_matches('[').trim_end_matches(']');
+    Some((
+        format!("http://{host}:{port}{path}"),
+        server_name.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::plaintext_endpoint;
+
+    #[test]
+    fn test_plaintext_endpoint() {
+        let endpoint = |uri: &str| plaintext_endpoint(&uri.parse().unwrap());
+        assert_eq!(
+            endpoint("https://collector.local"),
+            Some((
+                "http://collector.local:443/".to_string(),
+                "collector.local".to_string()
+            ))
+        );
+        assert_eq!(
+            endpoint("https://[::1]:4317"),
+            Some(("http://[::1]:4317/".to_string(), "::1".to_string()))
+        );
+        assert_eq!(endpoint("http://localhost:4317"), None);
+    }
+
+    #[cfg(feature = "trace")]
+    #[tokio::test]
+    async fn test_self_signed_certificate_is_accepted() {
+        use opentelemetry_proto::tonic::collector::trace::v1::{
+            trace_service_server::{TraceService, TraceServiceServer},
+            ExportTraceServiceRequest, ExportTraceServiceResponse,
+        };
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+        use tokio_stream::wrappers::TcpListenerStream;
+        use tonic::transport::{Identity, Server, ServerTlsConfig};
+
+        use crate::{WithExportConfig, WithTonicConfig};
+
+        struct Collector;
+
+        #[tonic::async_trait]
+        impl TraceService for Collector {
+            async fn export(
+                &self,
+                _request: tonic::Request<ExportTraceServiceRequest>,
+            ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+                Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+            }
+        }
+
+        // the certificate is issued by a CA the client doesn't trust
+        let pem = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/certs/server.pem"
+        ))
+        .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(&pem, &pem)))
+                .unwrap()
+                .add_service(TraceServiceServer::new(Collector))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let exporter = |skip| {
+            crate::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(format!("https://{addr}"))
+                .with_tls_insecure_skip_verify(skip)
+                .build()
+                .unwrap()
+        };
+        assert!(exporter(false).export(vec![]).await.is_err());
+        exporter(true).export(vec![]).await.unwrap();
+    }
+}
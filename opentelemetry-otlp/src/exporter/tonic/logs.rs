@@ -5,11 +5,24 @@ use opentelemetry_proto::tonic::collector::logs::v1::{
 };
 use opentelemetry_sdk::export::logs::{LogBatch, LogExporter};
 use opentelemetry_sdk::logs::{LogError, LogResult};
-use tonic::{codegen::CompressionEncoding, service::Interceptor, transport::Channel, Request};
+use prost::Message;
+use std::sync::Arc;
+use tonic::{service::Interceptor, Request};
 
-use opentelemetry_proto::transform::logs::tonic::group_logs_by_resource_and_scope;
-
-use super::BoxInterceptor;
+use super::balance::ExportChannel;
+use super::compression::SignalCompression;
+use super::retry::{self, Retry};
+#[cfg(feature = "disk-spool")]
+use super::spool::DiskSpool;
+use super::{authorization, check_version, BoxInterceptor, ClientOptions, StatusCodes};
+use crate::exporter::auth::AuthProvider;
+use crate::exporter::conversion::ConversionConfig;
+use crate::exporter::inflight::{self, InflightBytes};
+use crate::exporter::rate::{self, ExportRate};
+use crate::exporter::version::VersionCheck;
+use crate::exporter::PayloadRecorder;
+use crate::stats::StatsRecorder;
+use crate::Signal;
 use tokio::sync::Mutex;
 
 pub(crate) struct TonicLogsClient {
@@ -17,10 +30,22 @@ pub(crate) struct TonicLogsClient {
     #[allow(dead_code)]
     // <allow dead> would be removed once we support set_resource for metrics.
     resource: opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    conversion: ConversionConfig,
+    compression: SignalCompression,
+    inflight: Option<Arc<InflightBytes>>,
+    status_codes: StatusCodes,
+    version_check: Option<VersionCheck>,
+    payload_recorder: Option<PayloadRecorder>,
+    stats: StatsRecorder,
+    retry: Option<Retry>,
+    auth_provider: Option<AuthProvider>,
+    export_rate: Option<Arc<ExportRate>>,
+    #[cfg(feature = "disk-spool")]
+    spool: Option<Arc<DiskSpool>>,
 }
 
 struct ClientInner {
-    client: LogsServiceClient<Channel>,
+    client: LogsServiceClient<ExportChannel>,
     interceptor: Mutex<BoxInterceptor>,
 }
 
@@ -32,15 +57,35 @@ impl fmt::Debug for TonicLogsClient {
 
 impl TonicLogsClient {
     pub(super) fn new(
-        channel: Channel,
+        channel: ExportChannel,
         interceptor: BoxInterceptor,
-        compression: Option<CompressionEncoding>,
+        options: ClientOptions,
     ) -> Self {
+        let ClientOptions {
+            compression,
+            conversion,
+            inflight,
+            status_codes,
+            version_check,
+            payload_recorder,
+            stats,
+            retry,
+            auth_provider,
+            export_rate,
+            max_encoding_message_size,
+            max_decoding_message_size,
+            #[cfg(feature = "disk-spool")]
+            spool,
+        } = options;
         let mut client = LogsServiceClient::new(channel);
-        if let Some(compression) = compression {
-            client = client
-                .send_compressed(compression)
-                .accept_compressed(compression);
+        for encoding in compression.accepted() {
+            client = client.accept_compressed(encoding);
+        }
+        if let Some(limit) = max_encoding_message_size {
+            client = client.max_encoding_message_size(limit);
+        }
+        if let Some(limit) = max_decoding_message_size {
+            client = client.max_decoding_message_size(limit);
         }
 
         TonicLogsClient {
@@ -49,6 +94,18 @@ impl TonicLogsClient {
                 interceptor: Mutex::new(interceptor),
             }),
             resource: Default::default(),
+            conversion,
+            compression,
+            inflight,
+            status_codes,
+            version_check,
+            payload_recorder,
+            stats,
+            retry,
+            auth_provider,
+            export_rate,
+            #[cfg(feature = "disk-spool")]
+            spool,
         }
     }
 }
@@ -56,7 +113,7 @@ impl TonicLogsClient {
 #[async_trait]
 impl LogExporter for TonicLogsClient {
     async fn export(&self, batch: LogBatch<'_>) -> LogResult<()> {
-        let (mut client, metadata, extensions) = match &self.inner {
+        let (mut client, mut metadata, mut extensions) = match &self.inner {
             Some(inner) => {
                 let (m, e, _) = inner
                     .interceptor
@@ -70,16 +127,90 @@ impl LogExporter for TonicLogsClient {
             None => return Err(LogError::Other("exporter is already shut down".into())),
         };
 
-        let resource_logs = group_logs_by_resource_and_scope(batch, &self.resource);
+        if let Some(value) = authorization(self.auth_provider.as_ref()).await? {
+            metadata.insert("authorization", value);
+        }
+
+        let resource_logs = self.conversion.resource_logs(batch, &self.resource);
+        let request = ExportLogsServiceRequest { resource_logs };
+
+        match self.compression.leveled() {
+            Some(leveled) => {
+                extensions.insert(leveled);
+            }
+            None => {
+                if let Some(encoding) = self.compression.encoding() {
+                    client = client.send_compressed(encoding);
+                }
+            }
+        }
+
+        let payload = self
+            .payload_recorder
+            .as_ref()
+            .map(|recorder| (recorder, request.encode_to_vec()));
+        #[cfg(feature = "disk-spool")]
+        let mut spooled = self.spool.as_ref().map(|spool| {
+            let redelivery = (client.clone(), metadata.clone(), extensions.clone());
+            (spool, request.encode_to_vec(), redelivery)
+        });
+        let encoded_len = request.encoded_len();
+        let _permit = inflight::acquire(self.inflight.as_ref(), encoded_len).await;
+        rate::acquire(self.export_rate.as_ref()).await;
+        self.stats.record_bytes_sent(encoded_len);
+        let result = retry::send(
+            self.retry.as_ref(),
+            &self.status_codes,
+            Request::from_parts(metadata, extensions, request),
+            |request| {
+                let mut client = client.clone();
+                async move { client.export(request).await }
+            },
+        )
+        .await;
+        #[cfg(feature = "disk-spool")]
+        if let (Err(status), Some((spool, spooled, _))) = (&result, &mut spooled) {
+            if self.status_codes.is_retryable(status) {
+                spool.store(Signal::Logs, std::mem::take(spooled)).await;
+            }
+        }
+        if let Ok(response) = &result {
+            self.compression.negotiate(response.metadata());
+            check_version(self.version_check.as_ref(), response.metadata());
+        }
+        let partial_success = result
+            .as_ref()
+            .ok()
+            .and_then(|response| response.get_ref().partial_success.clone());
+        self.status_codes.handle(result)?;
+        if let Some((recorder, payload)) = payload {
+            recorder.record(&payload, Signal::Logs);
+        }
+        if let Some(partial_success) = partial_success {
+            self.status_codes.partial_success(
+                partial_success.rejected_log_records,
+                &partial_success.error_message,
+            )?;
+        }
 
-        client
-            .export(Request::from_parts(
-                metadata,
-                extensions,
-                ExportLogsServiceRequest { resource_logs },
-            ))
-            .await
-            .map_err(crate::Error::from)?;
+        #[cfg(feature = "disk-spool")]
+        if let Some((spool, _, (client, metadata, extensions))) = spooled {
+            spool
+                .redeliver(
+                    Signal::Logs,
+                    &self.status_codes,
+                    |request: ExportLogsServiceRequest| {
+                        let mut client = client.clone();
+                        let request =
+                            Request::from_parts(metadata.clone(), extensions.clone(), request);
+                        async move {
+                            rate::acquire(self.export_rate.as_ref()).await;
+                            client.export(request).await
+                        }
+                    },
+                )
+                .await;
+        }
 
         Ok(())
     }
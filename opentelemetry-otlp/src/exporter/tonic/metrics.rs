@@ -1,23 +1,53 @@
 use core::fmt;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
-use opentelemetry_proto::tonic::collector::metrics::v1::{
-    metrics_service_client::MetricsServiceClient, ExportMetricsServiceRequest,
-};
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_client::MetricsServiceClient;
+#[cfg(feature = "disk-spool")]
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
 use opentelemetry_sdk::metrics::data::ResourceMetrics;
 use opentelemetry_sdk::metrics::{MetricError, MetricResult};
-use tonic::{codegen::CompressionEncoding, service::Interceptor, transport::Channel, Request};
+use prost::Message;
+use tonic::{service::Interceptor, Request};
 
-use super::BoxInterceptor;
+use super::balance::ExportChannel;
+use super::compression::SignalCompression;
+use super::retry::{self, Retry};
+#[cfg(feature = "disk-spool")]
+use super::spool::DiskSpool;
+use super::{authorization, check_version, BoxInterceptor, ClientOptions, StatusCodes};
+use crate::exporter::accumulation::MetricAccumulator;
+use crate::exporter::auth::AuthProvider;
+use crate::exporter::conversion::ConversionConfig;
+use crate::exporter::inflight::{self, InflightBytes};
+use crate::exporter::rate::{self, ExportRate};
+use crate::exporter::start_time::MetricStartTimes;
+use crate::exporter::version::VersionCheck;
+use crate::exporter::PayloadRecorder;
 use crate::metric::MetricsClient;
+use crate::stats::StatsRecorder;
+use crate::Signal;
 
 pub(crate) struct TonicMetricsClient {
     inner: Mutex<Option<ClientInner>>,
+    conversion: ConversionConfig,
+    compression: SignalCompression,
+    inflight: Option<Arc<InflightBytes>>,
+    status_codes: StatusCodes,
+    version_check: Option<VersionCheck>,
+    payload_recorder: Option<PayloadRecorder>,
+    stats: StatsRecorder,
+    retry: Option<Retry>,
+    auth_provider: Option<AuthProvider>,
+    export_rate: Option<Arc<ExportRate>>,
+    #[cfg(feature = "disk-spool")]
+    spool: Option<Arc<DiskSpool>>,
+    accumulator: Option<MetricAccumulator>,
+    start_times: Option<MetricStartTimes>,
 }
 
 struct ClientInner {
-    client: MetricsServiceClient<Channel>,
+    client: MetricsServiceClient<ExportChannel>,
     interceptor: BoxInterceptor,
 }
 
@@ -29,15 +59,35 @@ impl fmt::Debug for TonicMetricsClient {
 
 impl TonicMetricsClient {
     pub(super) fn new(
-        channel: Channel,
+        channel: ExportChannel,
         interceptor: BoxInterceptor,
-        compression: Option<CompressionEncoding>,
+        options: ClientOptions,
     ) -> Self {
+        let ClientOptions {
+            compression,
+            conversion,
+            inflight,
+            status_codes,
+            version_check,
+            payload_recorder,
+            stats,
+            retry,
+            auth_provider,
+            export_rate,
+            max_encoding_message_size,
+            max_decoding_message_size,
+            #[cfg(feature = "disk-spool")]
+            spool,
+        } = options;
         let mut client = MetricsServiceClient::new(channel);
-        if let Some(compression) = compression {
-            client = client
-                .send_compressed(compression)
-                .accept_compressed(compression);
+        for encoding in compression.accepted() {
+            client = client.accept_compressed(encoding);
+        }
+        if let Some(limit) = max_encoding_message_size {
+            client = client.max_encoding_message_size(limit);
+        }
+        if let Some(limit) = max_decoding_message_size {
+            client = client.max_decoding_message_size(limit);
         }
 
         TonicMetricsClient {
@@ -45,6 +95,20 @@ impl TonicMetricsClient {
                 client,
                 interceptor,
             })),
+            accumulator: conversion.metric_accumulator(),
+            start_times: conversion.metric_start_times(),
+            conversion,
+            compression,
+            inflight,
+            status_codes,
+            version_check,
+            payload_recorder,
+            stats,
+            retry,
+            auth_provider,
+            export_rate,
+            #[cfg(feature = "disk-spool")]
+            spool,
         }
     }
 }
@@ -52,34 +116,129 @@ impl TonicMetricsClient {
 #[async_trait]
 impl MetricsClient for TonicMetricsClient {
     async fn export(&self, metrics: &mut ResourceMetrics) -> MetricResult<()> {
-        let (mut client, metadata, extensions) =
-            self.inner
-                .lock()
-                .map_err(Into::into)
-                .and_then(|mut inner| match &mut *inner {
-                    Some(inner) => {
-                        let (m, e, _) = inner
-                            .interceptor
-                            .call(Request::new(()))
-                            .map_err(|e| {
-                                MetricError::Other(format!(
-                                    "unexpected status while exporting {e:?}"
-                                ))
-                            })?
-                            .into_parts();
-                        Ok((inner.client.clone(), m, e))
-                    }
-                    None => Err(MetricError::Other("exporter is already shut down".into())),
-                })?;
+        let (mut client, mut metadata, mut extensions) = self
+            .inner
+            .lock()
+            .map_err(Into::into)
+            .and_then(|mut inner| match &mut *inner {
+                Some(inner) => {
+                    let (m, e, _) = inner
+                        .interceptor
+                        .call(Request::new(()))
+                        .map_err(|e| {
+                            MetricError::Other(format!("unexpected status while exporting {e:?}"))
+                        })?
+                        .into_parts();
+                    Ok((inner.client.clone(), m, e))
+                }
+                None => Err(MetricError::Other("exporter is already shut down".into())),
+            })?;
+
+        let mut request = self.conversion.metrics_request(metrics);
+        if let Some(start_times) = &self.start_times {
+            start_times.apply(&mut request);
+        }
+        let retained = self.accumulator.as_ref().map(|accumulator| {
+            accumulator.merge_pending(&mut request);
+            (accumulator, request.clone())
+        });
+        match authorization(self.auth_provider.as_ref()).await {
+            Ok(Some(value)) => {
+                metadata.insert("authorization", value);
+            }
+            Ok(None) => {}
+            Err(error) => {
+                if let Some((accumulator, request)) = retained {
+                    accumulator.keep(request);
+                }
+                return Err(error.into());
+            }
+        }
+
+        match self.compression.leveled() {
+            Some(leveled) => {
+                extensions.insert(leveled);
+            }
+            None => {
+                if let Some(encoding) = self.compression.encoding() {
+                    client = client.send_compressed(encoding);
+                }
+            }
+        }
 
-        client
-            .export(Request::from_parts(
-                metadata,
-                extensions,
-                ExportMetricsServiceRequest::from(&*metrics),
-            ))
-            .await
-            .map_err(crate::Error::from)?;
+        let payload = self
+            .payload_recorder
+            .as_ref()
+            .map(|recorder| (recorder, request.encode_to_vec()));
+        // requests kept by the accumulator are sent with the next export instead
+        #[cfg(feature = "disk-spool")]
+        let mut spooled = self.spool.as_ref().map(|spool| {
+            let redelivery = (client.clone(), metadata.clone(), extensions.clone());
+            let request = retained.is_none().then(|| request.encode_to_vec());
+            (spool, request, redelivery)
+        });
+        let encoded_len = request.encoded_len();
+        let _permit = inflight::acquire(self.inflight.as_ref(), encoded_len).await;
+        rate::acquire(self.export_rate.as_ref()).await;
+        self.stats.record_bytes_sent(encoded_len);
+        let result = retry::send(
+            self.retry.as_ref(),
+            &self.status_codes,
+            Request::from_parts(metadata, extensions, request),
+            |request| {
+                let mut client = client.clone();
+                async move { client.export(request).await }
+            },
+        )
+        .await;
+        if let Ok(response) = &result {
+            self.compression.negotiate(response.metadata());
+            check_version(self.version_check.as_ref(), response.metadata());
+        }
+        if let (Err(status), Some((accumulator, request))) = (&result, retained) {
+            if self.status_codes.is_retryable(status) {
+                accumulator.keep(request);
+            }
+        }
+        #[cfg(feature = "disk-spool")]
+        if let (Err(status), Some((spool, Some(spooled), _))) = (&result, &mut spooled) {
+            if self.status_codes.is_retryable(status) {
+                spool.store(Signal::Metrics, std::mem::take(spooled)).await;
+            }
+        }
+        let partial_success = result
+            .as_ref()
+            .ok()
+            .and_then(|response| response.get_ref().partial_success.clone());
+        self.status_codes.handle(result)?;
+        if let Some((recorder, payload)) = payload {
+            recorder.record(&payload, Signal::Metrics);
+        }
+        if let Some(partial_success) = partial_success {
+            self.status_codes.partial_success(
+                partial_success.rejected_data_points,
+                &partial_success.error_message,
+            )?;
+        }
+
+        #[cfg(feature = "disk-spool")]
+        if let Some((spool, _, (client, metadata, extensions))) = spooled {
+            spool
+                .redeliver(
+                    Signal::Metrics,
+                    &self.status_codes,
+                    |request: ExportMetricsServiceRequest| {
+                        let mut client = client.clone();
+                        let request =
+                            Request::from_parts(metadata.clone(), extensions.clone(), request);
+                        async move {
+                            rate::acquire(self.export_rate.as_ref()).await;
+                            client.export(request).await
+                        }
+                    },
+                )
+                .await;
+        }
 
         Ok(())
     }
@@ -0,0 +1,124 @@
+//! Periodically replace the connection to the collector, so a long-lived connection doesn't stay
+//! pinned to one replica behind a load balancer.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::balance::ExportChannel;
+
+/// Channel replaced by a newly connecting one once the reconnect interval elapsed.
+#[derive(Clone)]
+pub(crate) struct ReconnectingChannel {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    channel: ExportChannel,
+    connect: Box<dyn Fn() -> ExportChannel + Send>,
+    interval: Duration,
+    connected: Instant,
+}
+
+impl fmt::Debug for ReconnectingChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReconnectingChannel")
+            .field("interval", &self.lock().interval)
+            .finish()
+    }
+}
+
+impl ReconnectingChannel {
+    /// Create the channel with `connect`, calling it again for a new channel every `interval`.
+    pub(crate) fn new<F>(connect: F, interval: Duration) -> Self
+    where
+        F: Fn() -> ExportChannel + Send + 'static,
+    {
+        let state = State {
+            channel: connect(),
+            connect: Box::new(connect),
+            interval,
+            connected: Instant::now(),
+        };
+        ReconnectingChannel {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// The channel of the next request, connecting a new one if the interval elapsed. Requests
+    /// in flight complete on the previous channel, whose connection is closed once they did.
+    pub(crate) fn current(&self) -> ExportChannel {
+        let mut state = self.lock();
+        if state.connected.elapsed() >= state.interval {
+            state.channel = (state.connect)();
+            state.connected = Instant::now();
+        }
+        state.channel.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "trace")]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use opentelemetry_sdk::export::trace::SpanExporter as _;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    use crate::{WithExportConfig, WithTonicConfig};
+
+    struct Collector;
+
+    #[tonic::async_trait]
+    impl TraceService for Collector {
+        async fn export(
+            &self,
+            _request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_once_interval_elapsed() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = connections.clone();
+        let incoming = TcpListenerStream::new(listener).inspect(move |_| {
+            accepted.fetch_add(1, Ordering::SeqCst);
+        });
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TraceServiceServer::new(Collector))
+                .serve_with_incoming(incoming),
+        );
+
+        let mut exporter = crate::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(format!("http://{addr}"))
+            .with_periodic_reconnect(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        exporter.export(vec![]).await.unwrap();
+        exporter.export(vec![]).await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        exporter.export(vec![]).await.unwrap();
+        exporter.export(vec![]).await.unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,210 @@
+//! Reload the client certificate of a mutual TLS connection when its file changed.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use opentelemetry::otel_warn;
+use tonic::transport::{Channel, ClientTlsConfig, Identity};
+
+use super::Connector;
+
+/// File the client certificate and key are read from, and how often.
+#[derive(Clone, Debug)]
+pub(crate) struct CertificateReload {
+    pub(crate) path: PathBuf,
+    pub(crate) interval: Duration,
+}
+
+/// Channel authenticating with the client certificate last read from a file.
+#[derive(Clone)]
+pub(crate) struct ReloadingChannel {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    channel: Channel,
+    connector: Connector,
+    tls_config: ClientTlsConfig,
+    reload: CertificateReload,
+    /// Contents of the file the certificate of `channel` was read from.
+    pem: Option<Vec<u8>>,
+    checked: Instant,
+}
+
+impl fmt::Debug for ReloadingChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadingChannel")
+            .field("reload", &self.lock().reload)
+            .finish()
+    }
+}
+
+impl ReloadingChannel {
+    /// Connect with the certificate currently in the file. Without a valid certificate, the
+    /// channel connects with `tls_config` alone until one is read.
+    pub(crate) fn new(
+        connector: Connector,
+        tls_config: ClientTlsConfig,
+        reload: &CertificateReload,
+    ) -> Self {
+        let mut state = State {
+            channel: connector.connect(),
+            connector,
+            tls_config,
+            reload: reload.clone(),
+            pem: None,
+            checked: Instant::now(),
+        };
+        state.reload();
+        ReloadingChannel {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// The channel of the next request, reading the file again if the reload interval elapsed.
+    pub(crate) fn current(&self) -> Channel {
+        let mut state = self.lock();
+        if state.checked.elapsed() >= state.reload.interval {
+            state.reload();
+        }
+        state.channel.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    /// Replace the channel if the file changed and holds a valid certificate and key, keeping
+    /// the previous channel otherwise.
+    fn reload(&mut self) {
+        self.checked = Instant::now();
+        let pem = match fs::read(&self.reload.path) {
+            Ok(pem) => pem,
+            Err(error) => return self.warn(error.to_string()),
+        };
+        if self.pem.as_ref() == Some(&pem) {
+            return;
+        }
+
+        // the certificates and the key are picked from the same file
+        let identity = Identity::from_pem(&pem, &pem);
+        match self
+            .connector
+            .with_tls_config(self.tls_config.clone().identity(identity))
+        {
+            Ok(connector) => {
+                self.channel = connector.connect();
+                self.pem = Some(pem);
+            }
+            Err(error) => self.warn(error.to_string()),
+        }
+    }
+
+    fn warn(&self, error: String) {
+        otel_warn!(
+            name: "TonicExporter.ClientCertificateReloadFailed",
+            path = self.reload.path.display().to_string(),
+            error = error,
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "trace")]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use opentelemetry_sdk::export::trace::SpanExporter as _;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Certificate, ClientTlsConfig, Identity, Server, ServerTlsConfig};
+
+    use crate::{WithExportConfig, WithTonicConfig};
+
+    fn cert(name: &str) -> Vec<u8> {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/certs");
+        fs::read(format!("{path}/{name}.pem")).unwrap()
+    }
+
+    /// A trace collector recording the client certificate of each export.
+    struct Collector(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    #[tonic::async_trait]
+    impl TraceService for Collector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            let certs = request.peer_certs().unwrap();
+            self.0.lock().unwrap().push(certs[0].to_vec());
+            Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+        }
+    }
+
+    /// Whether the DER encoded certificate was issued to `common_name`.
+    fn issued_to(cert: &[u8], common_name: &str) -> bool {
+        cert.windows(common_name.len())
+            .any(|window| window == common_name.as_bytes())
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_is_reloaded() {
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_tls = ServerTlsConfig::new()
+            .identity(Identity::from_pem(cert("server"), cert("server")))
+            .client_ca_root(Certificate::from_pem(cert("ca")));
+        tokio::spawn(
+            Server::builder()
+                .tls_config(server_tls)
+                .unwrap()
+                .add_service(TraceServiceServer::new(Collector(clients.clone())))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let path: PathBuf = std::env::temp_dir().join(format!(
+            "otlp-client-certificate-{}.pem",
+            std::process::id()
+        ));
+        fs::write(&path, cert("client-a")).unwrap();
+        let mut exporter = crate::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(format!("https://{addr}"))
+            .with_tls_config(
+                ClientTlsConfig::new()
+                    .ca_certificate(Certificate::from_pem(cert("ca")))
+                    .domain_name("localhost"),
+            )
+            .with_client_certificate_reload(&path, Duration::ZERO)
+            .build()
+            .unwrap();
+
+        exporter.export(vec![]).await.unwrap();
+        fs::write(&path, cert("client-b")).unwrap();
+        exporter.export(vec![]).await.unwrap();
+        // an invalid file keeps the previous certificate
+        fs::write(&path, "not a certificate").unwrap();
+        exporter.export(vec![]).await.unwrap();
+        fs::remove_file(&path).unwrap();
+        exporter.export(vec![]).await.unwrap();
+
+        let clients = clients.lock().unwrap();
+        assert_eq!(clients.len(), 4);
+        assert!(issued_to(&clients[0], "client-a"));
+        assert!(clients[1..]
+            .iter()
+            .all(|client| issued_to(client, "client-b")));
+    }
+}
@@ -0,0 +1,393 @@
+//! Retries of exports the collector rejected with a retryable status, backing off in between.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use opentelemetry::otel_debug;
+use prost::Message;
+use tokio::time::Instant;
+use tonic::{Code, Request, Response, Status};
+
+use super::{StatusCodeTreatment, StatusCodes};
+use crate::stats::StatsRecorder;
+
+/// The metadata entry the server sets to the milliseconds to wait before retrying, as in
+/// <https://github.com/grpc/proposal/blob/master/A6-client-retries.md>.
+const RETRY_PUSHBACK_KEY: &str = "grpc-retry-pushback-ms";
+
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+/// How often and how long apart the exporter retries an export rejected with a retryable status.
+///
+/// The backoff starts at `initial_backoff` and doubles with every retry, up to `max_backoff`.
+/// Each backoff is randomized by up to `jitter` times its length in either direction, e.g. `0.2`
+/// waits between 80% and 120% of the backoff.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt.
+    pub max_retries: usize,
+    /// The backoff before the first retry.
+    pub initial_backoff: Duration,
+    /// The longest backoff between two attempts.
+    pub max_backoff: Duration,
+    /// The fraction of the backoff it is randomized by, between `0.0` and `1.0`.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.2,
+        }
+    }
+}
+
+/// A retry policy of an exporter, bounded by its export timeout.
+#[derive(Clone, Debug)]
+pub(crate) struct Retry {
+    policy: RetryPolicy,
+    timeout: Duration,
+    stats: StatsRecorder,
+}
+
+impl Retry {
+    pub(crate) fn new(policy: RetryPolicy, timeout: Duration) -> Self {
+        Retry {
+            policy,
+            timeout,
+            stats: StatsRecorder::default(),
+        }
+    }
+
+    /// Count the retries in `stats`.
+    pub(crate) fn with_stats(mut self, stats: StatsRecorder) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// The delay before retry number `retries`, if the export is retried at all.
+    fn delay(
+        &self,
+        status_codes: &StatusCodes,
+        status: &Status,
+        retries: usize,
+        backoff: Duration,
+    ) -> Option<Duration> {
+        if retries >= self.policy.max_retries
+            || status_codes.treatment(status.code()) != StatusCodeTreatment::Retryable
+        {
+            return None;
+        }
+
+        let throttle = match throttle(status) {
+            // the server asked not to retry
+            Some(None) => return None,
+            Some(Some(delay)) => Some(delay),
+            None => None,
+        };
+        // a collector running out of resources is only retried if it says when to retry
+        if status.code() == Code::ResourceExhausted && throttle.is_none() {
+            return None;
+        }
+
+        Some(throttle.unwrap_or_else(|| jittered(backoff, self.policy.jitter)))
+    }
+}
+
+/// Send `request` with `send`, retrying as long as `retry` allows.
+///
+/// Retries are only attempted while they can finish within the export timeout, so an export never
+/// takes longer than the timeout in total.
+pub(crate) async fn send<M, T, F, Fut>(
+    retry: Option<&Retry>,
+    status_codes: &StatusCodes,
+    request: Request<M>,
+    mut send: F,
+) -> Result<Response<T>, Status>
+where
+    M: Clone,
+    F: FnMut(Request<M>) -> Fut,
+    Fut: Future<Output = Result<Response<T>, Status>>,
+{
+    let Some(retry) = retry else {
+        return send(request).await;
+    };
+
+    let deadline = Instant::now() + retry.timeout;
+    let (metadata, extensions, message) = request.into_parts();
+    let mut status = match send(Request::from_parts(
+        metadata.clone(),
+        extensions.clone(),
+        message.clone(),
+    ))
+    .await
+    {
+        Ok(response) => return Ok(response),
+        Err(status) => status,
+    };
+
+    let mut backoff = retry.policy.initial_backoff;
+    let mut retries = 0;
+    loop {
+        let Some(delay) = retry.delay(status_codes, &status, retries, backoff) else {
+            return Err(status);
+        };
+        // the retry needs time to be sent as well, so it must start before the deadline
+        if delay >= deadline.saturating_duration_since(Instant::now()) {
+            return Err(status);
+        }
+        otel_debug!(
+            name: "TonicExporter.RetryingExport",
+            code = format!("{:?}", status.code()),
+            retry = (retries + 1) as i64,
+            delay_ms = delay.as_millis() as i64
+        );
+        tokio::time::sleep(delay).await;
+        backoff = backoff.saturating_mul(2).min(retry.policy.max_backoff);
+        retries += 1;
+        retry.stats.record_retry();
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut request =
+            Request::from_parts(metadata.clone(), extensions.clone(), message.clone());
+        // the collector may only spend the remaining time on the retry
+        request.set_timeout(remaining);
+        status = match tokio::time::timeout(remaining, send(request)).await {
+            Ok(Ok(response)) => return Ok(response),
+            Ok(Err(status)) => status,
+            Err(_) => return Err(Status::deadline_exceeded("export timeout elapsed")),
+        };
+    }
+}
+
+/// The delay the server asked for before retrying, with `Some(None)` if it asked not to retry.
+fn throttle(status: &Status) -> Option<Option<Duration>> {
+    if let Some(pushback) = status.metadata().get(RETRY_PUSHBACK_KEY) {
+        // a negative or invalid pushback means the server doesn't want a retry
+        return Some(
+            pushback
+                .to_str()
+                .ok()
+                .and_then(|millis| millis.parse().ok())
+                .map(Duration::from_millis),
+        );
+    }
+
+    retry_info(status.details()).map(Some)
+}
+
+/// The delay of the `google.rpc.RetryInfo` in the details of a status, if any.
+fn retry_info(details: &[u8]) -> Option<Duration> {
+    let status = RpcStatus::decode(details).ok()?;
+    let info = status
+        .details
+        .iter()
+        .find(|detail| detail.type_url == RETRY_INFO_TYPE_URL)?;
+    let delay = RetryInfo::decode(info.value.as_slice()).ok()?.retry_delay?;
+
+    Some(Duration::new(
+        u64::try_from(delay.seconds).ok()?,
+        u32::try_from(delay.nanos).ok()?,
+    ))
+}
+
+/// `backoff` randomized by up to `jitter` times its length in either direction.
+fn jittered(backoff: Duration, jitter: f64) -> Duration {
+    let jitter = if jitter.is_nan() {
+        0.0
+    } else {
+        jitter.clamp(0.0, 1.0)
+    };
+    // every hasher of a new `RandomState` has different keys, so its hash is random
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    backoff.mul_f64(1.0 - jitter + 2.0 * jitter * random)
+}
+
+/// The `google.rpc.Status` in the details of a status, limited to the fields read here.
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+/// A `google.protobuf.Any`.
+#[derive(Clone, PartialEq, Message)]
+struct Any {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+/// A `google.rpc.RetryInfo`.
+#[derive(Clone, PartialEq, Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<ProtoDuration>,
+}
+
+/// A `google.protobuf.Duration`.
+#[derive(Clone, PartialEq, Message)]
+struct ProtoDuration {
+    #[prost(int64, tag = "1")]
+    seconds: i64,
+    #[prost(int32, tag = "2")]
+    nanos: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use prost::Message;
+    use tonic::{Code, Request, Response, Status};
+
+    use super::{send, Any, ProtoDuration, Retry, RetryInfo, RetryPolicy, RpcStatus};
+    use crate::exporter::tonic::StatusCodes;
+
+    fn retry(max_retries: usize, timeout: Duration) -> Retry {
+        Retry::new(
+            RetryPolicy {
+                max_retries,
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(20),
+                jitter: 0.5,
+            },
+            timeout,
+        )
+    }
+
+    /// Send to a collector answering with `statuses`, then accepting, counting the attempts.
+    async fn attempts(retry: &Retry, statuses: Vec<Status>) -> (Result<(), Code>, usize) {
+        let attempts = AtomicUsize::new(0);
+        let mut statuses = statuses.into_iter();
+        let result = send(
+            Some(retry),
+            &StatusCodes::default(),
+            Request::new(()),
+            |_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                let result = statuses.next().map_or(Ok(Response::new(())), Err);
+                async move { result }
+            },
+        )
+        .await;
+        (
+            result.map(|_| ()).map_err(|status| status.code()),
+            attempts.load(Ordering::SeqCst),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_retries_retryable_statuses() {
+        let retry = retry(3, Duration::from_secs(10));
+
+        let unavailable = || Status::unavailable("restarting");
+        assert_eq!(
+            attempts(&retry, vec![unavailable(), unavailable()]).await,
+            (Ok(()), 3)
+        );
+        // non-retryable statuses fail at once
+        assert_eq!(
+            attempts(&retry, vec![Status::invalid_argument("bad")]).await,
+            (Err(Code::InvalidArgument), 1)
+        );
+        // out of retries
+        assert_eq!(
+            attempts(&retry, vec![unavailable(); 4]).await,
+            (Err(Code::Unavailable), 4)
+        );
+        // resource exhaustion is only retried with a delay from the collector
+        assert_eq!(
+            attempts(&retry, vec![Status::resource_exhausted("full")]).await,
+            (Err(Code::ResourceExhausted), 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_stop_at_export_timeout() {
+        // the backoff of the second retry no longer fits into the timeout
+        let retry = retry(5, Duration::from_millis(25));
+        let (result, attempts) = attempts(&retry, vec![Status::unavailable("down"); 5]).await;
+        assert_eq!(result, Err(Code::Unavailable));
+        assert!(attempts < 4, "{attempts} attempts");
+    }
+
+    #[tokio::test]
+    async fn test_retries_send_remaining_timeout() {
+        let retry = retry(3, Duration::from_secs(10));
+        let timeouts = Mutex::new(Vec::new());
+        let mut statuses = vec![Status::unavailable("down"); 2].into_iter();
+        let mut request = Request::new(());
+        request.set_timeout(retry.timeout);
+        send(Some(&retry), &StatusCodes::default(), request, |request| {
+            let timeout = request.metadata().get("grpc-timeout").unwrap();
+            timeouts
+                .lock()
+                .unwrap()
+                .push(timeout.to_str().unwrap().to_string());
+            let result = statuses.next().map_or(Ok(Response::new(())), Err);
+            async move { result }
+        })
+        .await
+        .unwrap();
+
+        let timeouts = timeouts.into_inner().unwrap();
+        assert_eq!(timeouts[0], "10000000u");
+        // the retries may only take what is left after the backoff
+        let micros = |timeout: &str| timeout.strip_suffix('u').unwrap().parse::<u64>().unwrap();
+        assert!(micros(&timeouts[1]) < 10_000_000, "{}", timeouts[1]);
+        assert!(
+            micros(&timeouts[2]) < micros(&timeouts[1]),
+            "{}",
+            timeouts[2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_pushback() {
+        let retry = retry(3, Duration::from_secs(10));
+
+        let mut pushback = Status::resource_exhausted("slow down");
+        pushback
+            .metadata_mut()
+            .insert("grpc-retry-pushback-ms", "1".parse().unwrap());
+        assert_eq!(attempts(&retry, vec![pushback]).await, (Ok(()), 2));
+
+        let mut refused = Status::unavailable("go away");
+        refused
+            .metadata_mut()
+            .insert("grpc-retry-pushback-ms", "-1".parse().unwrap());
+        assert_eq!(
+            attempts(&retry, vec![refused]).await,
+            (Err(Code::Unavailable), 1)
+        );
+
+        let details = RpcStatus {
+            details: vec![Any {
+                type_url: super::RETRY_INFO_TYPE_URL.to_string(),
+                value: RetryInfo {
+                    retry_delay: Some(ProtoDuration {
+                        seconds: 0,
+                        nanos: 1_000_000,
+                    }),
+                }
+                .encode_to_vec(),
+            }],
+        }
+        .encode_to_vec();
+        let retry_info = Status::with_details(Code::ResourceExhausted, "slow down", details.into());
+        assert_eq!(
+            super::retry_info(retry_info.details()),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(attempts(&retry, vec![retry_info]).await, (Ok(()), 2));
+    }
+}
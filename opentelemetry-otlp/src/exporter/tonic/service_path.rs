@@ -0,0 +1,84 @@
+//! Requests to the OTLP services under a customized path, for gateways namespacing the services
+//! of the collectors behind them.
+
+use std::sync::Arc;
+
+use http::uri::PathAndQuery;
+
+/// The package of the OTLP services, the start of the paths of their methods.
+const OTLP_SERVICE_PACKAGE: &str = "opentelemetry.proto.collector.";
+
+/// The prefix the fully-qualified names of the OTLP services are sent under, handed to the
+/// channel in the extensions of the requests.
+#[derive(Clone, Debug)]
+pub(crate) struct ServicePathPrefix(Arc<str>);
+
+impl ServicePathPrefix {
+    /// The prefix, which must start with `/` and form a valid path with the service names.
+    pub(crate) fn new(prefix: String) -> Result<Self, crate::Error> {
+        let path = format!("{prefix}{OTLP_SERVICE_PACKAGE}");
+        if !prefix.starts_with('/') || path.contains('?') || path.parse::<PathAndQuery>().is_err() {
+            return Err(crate::Error::InvalidServicePathPrefix(prefix));
+        }
+        Ok(ServicePathPrefix(prefix.into()))
+    }
+
+    /// Send `request` to the path under the prefix in its extensions, if it has one and the
+    /// request is to an OTLP service. The prefix is removed, so channels wrapping each other
+    /// only apply it once.
+    pub(crate) fn apply<B>(request: &mut http::Request<B>) {
+        let Some(prefix) = request.extensions_mut().remove::<ServicePathPrefix>() else {
+            return;
+        };
+        let Some(name) = request
+            .uri()
+            .path()
+            .strip_prefix('/')
+            .filter(|name| name.starts_with(OTLP_SERVICE_PACKAGE))
+        else {
+            return;
+        };
+
+        let mut parts = request.uri().clone().into_parts();
+        parts.path_and_query = format!("{}{name}", prefix.0).parse().ok();
+        if let Ok(uri) = http::Uri::from_parts(parts) {
+            *request.uri_mut() = uri;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServicePathPrefix;
+
+    fn path(prefix: &str, path: &str) -> String {
+        let mut request = http::Request::builder()
+            .uri(format!("http://localhost:4317{path}"))
+            .extension(ServicePathPrefix::new(prefix.to_string()).unwrap())
+            .body(())
+            .unwrap();
+        ServicePathPrefix::apply(&mut request);
+        request.uri().path().to_string()
+    }
+
+    #[test]
+    fn test_service_path_prefix() {
+        let trace = "/opentelemetry.proto.collector.trace.v1.TraceService/Export";
+        assert_eq!(
+            path("/gateway/", trace),
+            "/gateway/opentelemetry.proto.collector.trace.v1.TraceService/Export"
+        );
+        assert_eq!(
+            path("/acme.", trace),
+            "/acme.opentelemetry.proto.collector.trace.v1.TraceService/Export"
+        );
+        // other services, such as the health checks, keep their path
+        assert_eq!(
+            path("/gateway/", "/grpc.health.v1.Health/Check"),
+            "/grpc.health.v1.Health/Check"
+        );
+
+        assert!(ServicePathPrefix::new("gateway/".to_string()).is_err());
+        assert!(ServicePathPrefix::new("/gateway?".to_string()).is_err());
+    }
+}
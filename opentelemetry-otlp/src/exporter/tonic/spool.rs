@@ -0,0 +1,406 @@
+//! Export requests failing all retries, spooled to disk and sent again once the collector
+//! accepts exports, so outages outlasting the retries, or even the process, don't lose the
+//! telemetry.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+use opentelemetry::otel_warn;
+use prost::Message;
+
+use super::StatusCodes;
+use crate::Signal;
+
+/// Which requests are dropped when spooling another one would exceed the size of the spool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SpoolDropPolicy {
+    /// Drop the oldest spooled requests to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Drop the new request, keeping the spooled ones.
+    DropNewest,
+}
+
+/// The directory requests are spooled to, and how much of it they may take.
+#[derive(Clone, Debug)]
+pub(crate) struct SpoolConfig {
+    pub(crate) dir: PathBuf,
+    pub(crate) max_bytes: u64,
+    pub(crate) drop_policy: SpoolDropPolicy,
+}
+
+/// The spools opened by the exporters of the process, by their directory, so the exporters of
+/// several signals spooling to the same directory share one spool.
+static SPOOLS: Mutex<Vec<(PathBuf, Weak<DiskSpool>)>> = Mutex::new(Vec::new());
+
+/// A directory holding the encoded requests which failed, one file per request.
+///
+/// The files are named after a sequence number and their signal, e.g.
+/// `00000000000000000042.traces.pb`, so the requests are sent again in the order they failed,
+/// and a new process continues after the requests spooled by the previous one. Files are
+/// written under a temporary name first, so a crash never leaves a partial request behind.
+/// The directory is only scanned when the spool is opened; afterwards the spool keeps track of
+/// the requests in memory, so it must be the only one writing to the directory.
+#[derive(Debug)]
+pub(crate) struct DiskSpool {
+    config: SpoolConfig,
+    state: Mutex<State>,
+    /// Whether the spooled requests of each signal are currently sent again.
+    redelivering: [AtomicBool; 3],
+}
+
+/// The requests in the spool, oldest first.
+#[derive(Debug)]
+struct State {
+    entries: VecDeque<Entry>,
+    /// The total size of the spooled requests.
+    spooled: u64,
+    next_seq: u64,
+}
+
+/// A spooled request.
+#[derive(Clone, Debug)]
+struct Entry {
+    path: PathBuf,
+    seq: u64,
+    signal: Signal,
+    len: u64,
+}
+
+impl DiskSpool {
+    /// Open the spool, creating its directory if needed. Opening a directory already opened by
+    /// another exporter of the process returns its spool, with the size and drop policy it was
+    /// opened with.
+    pub(crate) fn open(config: SpoolConfig) -> Result<Arc<Self>, crate::Error> {
+        let error = |source| crate::Error::DiskSpool {
+            path: config.dir.display().to_string(),
+            source,
+        };
+        fs::create_dir_all(&config.dir).map_err(error)?;
+        let dir = fs::canonicalize(&config.dir).map_err(error)?;
+
+        let mut spools = SPOOLS.lock().unwrap_or_else(|e| e.into_inner());
+        spools.retain(|(_, spool)| spool.strong_count() > 0);
+        if let Some(spool) = spools
+            .iter()
+            .find(|(opened, _)| *opened == dir)
+            .and_then(|(_, spool)| spool.upgrade())
+        {
+            return Ok(spool);
+        }
+
+        let entries = entries(&dir).map_err(error)?;
+        let spool = Arc::new(DiskSpool {
+            state: Mutex::new(State {
+                spooled: entries.iter().map(|entry| entry.len).sum(),
+                next_seq: entries.back().map_or(0, |entry| entry.seq + 1),
+                entries,
+            }),
+            config: SpoolConfig { dir, ..config },
+            redelivering: Default::default(),
+        });
+        spools.push((spool.config.dir.clone(), Arc::downgrade(&spool)));
+        Ok(spool)
+    }
+
+    /// Spool the encoded `request` of `signal`, dropping requests as configured if the spool is
+    /// full. Failing to write the request is logged.
+    pub(crate) async fn store(self: &Arc<Self>, signal: Signal, request: Vec<u8>) {
+        let spool = Arc::clone(self);
+        if let Err(err) = blocking(move || spool.try_store(signal, &request)).await {
+            otel_warn!(
+                name: "TonicExporter.SpoolWriteFailed",
+                error = format!("{err}")
+            );
+        }
+    }
+
+    fn try_store(&self, signal: Signal, request: &[u8]) -> io::Result<()> {
+        let mut state = self.lock();
+        let len = request.len() as u64;
+
+        if len > self.config.max_bytes
+            || (state.spooled + len > self.config.max_bytes
+                && self.config.drop_policy == SpoolDropPolicy::DropNewest)
+        {
+            otel_warn!(name: "TonicExporter.SpooledRequestsDropped", count = 1);
+            return Ok(());
+        }
+        let mut dropped = 0;
+        while state.spooled + len > self.config.max_bytes {
+            let Some(oldest) = state.entries.pop_front() else {
+                break;
+            };
+            state.spooled -= oldest.len;
+            dropped += 1;
+            remove(&oldest.path)?;
+        }
+        if dropped > 0 {
+            otel_warn!(name: "TonicExporter.SpooledRequestsDropped", count = dropped);
+        }
+
+        let seq = state.next_seq;
+        let path = self
+            .config
+            .dir
+            .join(format!("{seq:020}.{}.pb", tag(signal)));
+        let temporary = path.with_extension("pb.tmp");
+        fs::write(&temporary, request)?;
+        fs::rename(&temporary, &path)?;
+        state.next_seq += 1;
+        state.spooled += len;
+        state.entries.push_back(Entry {
+            path,
+            seq,
+            signal,
+            len,
+        });
+        Ok(())
+    }
+
+    /// Send the spooled requests of `signal` again with `send`, oldest first, stopping at the
+    /// first one failing with a retryable status. Requests rejected otherwise are dropped, since
+    /// sending them again would fail the same. Only one redelivery of a signal runs at a time.
+    pub(crate) async fn redeliver<T, R, F, Fut>(
+        self: &Arc<Self>,
+        signal: Signal,
+        status_codes: &StatusCodes,
+        mut send: F,
+    ) where
+        T: Message + Default,
+        F: FnMut(T) -> Fut,
+        Fut: Future<Output = Result<R, tonic::Status>>,
+    {
+        let redelivering = &self.redelivering[signal as usize];
+        if redelivering.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let _redelivering = Redelivering(redelivering);
+
+        let entries: Vec<Entry> = self
+            .lock()
+            .entries
+            .iter()
+            .filter(|entry| entry.signal == signal)
+            .cloned()
+            .collect();
+        for entry in entries {
+            let path = entry.path.clone();
+            let request = match blocking(move || fs::read(path)).await {
+                Ok(request) => request,
+                // dropped to make room for a newer request in the meantime
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => {
+                    otel_warn!(
+                        name: "TonicExporter.SpoolReadFailed",
+                        error = format!("{err}")
+                    );
+                    return;
+                }
+            };
+            match T::decode(request.as_slice()) {
+                Ok(request) => match send(request).await {
+                    Err(status) if status_codes.is_retryable(&status) => return,
+                    result => {
+                        if let Err(err) = status_codes.handle(result) {
+                            otel_warn!(
+                                name: "TonicExporter.SpooledRequestRejected",
+                                error = format!("{err}")
+                            );
+                        }
+                    }
+                },
+                Err(err) => {
+                    otel_warn!(
+                        name: "TonicExporter.SpooledRequestCorrupted",
+                        path = entry.path.display().to_string(),
+                        error = format!("{err}")
+                    );
+                }
+            }
+            let spool = Arc::clone(self);
+            if let Err(err) = blocking(move || spool.remove_entry(entry.seq)).await {
+                otel_warn!(
+                    name: "TonicExporter.SpoolWriteFailed",
+                    error = format!("{err}")
+                );
+                return;
+            }
+        }
+    }
+
+    /// Remove the spooled request numbered `seq`, if it wasn't dropped in the meantime.
+    fn remove_entry(&self, seq: u64) -> io::Result<()> {
+        let mut state = self.lock();
+        let Some(index) = state.entries.iter().position(|entry| entry.seq == seq) else {
+            return Ok(());
+        };
+        if let Some(entry) = state.entries.remove(index) {
+            state.spooled -= entry.len;
+            remove(&entry.path)?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Run the file operation `f` on the blocking threads of the runtime, so it doesn't stall the
+/// exports of other tasks, or in place outside of a runtime.
+async fn blocking<T, F>(f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => runtime
+            .spawn_blocking(f)
+            .await
+            .unwrap_or_else(|err| Err(io::Error::new(io::ErrorKind::Other, err))),
+        Err(_) => f(),
+    }
+}
+
+/// Marks the end of a redelivery, also when it is cancelled.
+struct Redelivering<'a>(&'a AtomicBool);
+
+impl Drop for Redelivering<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+fn tag(signal: Signal) -> &'static str {
+    match signal {
+        Signal::Traces => "traces",
+        Signal::Metrics => "metrics",
+        Signal::Logs => "logs",
+    }
+}
+
+/// The requests spooled in `dir`, oldest first, ignoring any other file.
+fn entries(dir: &Path) -> io::Result<VecDeque<Entry>> {
+    let mut entries = Vec::new();
+    for file in fs::read_dir(dir)? {
+        let file = file?;
+        let name = file.file_name();
+        let Some((seq, signal)) = name.to_str().and_then(|name| {
+            let mut parts = name.split('.');
+            let seq = parts.next()?.parse().ok()?;
+            let signal = match parts.next()? {
+                "traces" => Signal::Traces,
+                "metrics" => Signal::Metrics,
+                "logs" => Signal::Logs,
+                _ => return None,
+            };
+            (parts.next()? == "pb" && parts.next().is_none()).then_some((seq, signal))
+        }) else {
+            continue;
+        };
+        entries.push(Entry {
+            path: file.path(),
+            seq,
+            signal,
+            len: file.metadata()?.len(),
+        });
+    }
+    entries.sort_by_key(|entry| entry.seq);
+    Ok(entries.into())
+}
+
+/// Remove the spooled request at `path`, if it wasn't already.
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{entries, DiskSpool, SpoolConfig, SpoolDropPolicy};
+    use crate::Signal;
+
+    fn spool(name: &str, max_bytes: u64, drop_policy: SpoolDropPolicy) -> SpoolConfig {
+        let dir = std::env::temp_dir().join(format!("otlp-spool-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        SpoolConfig {
+            dir,
+            max_bytes,
+            drop_policy,
+        }
+    }
+
+    fn spooled(config: &SpoolConfig) -> Vec<(u64, Signal)> {
+        entries(&config.dir)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.seq, entry.signal))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_full_spool_drops_oldest_requests() {
+        let config = spool("drop-oldest", 10, SpoolDropPolicy::DropOldest);
+        let spool = DiskSpool::open(config.clone()).unwrap();
+        spool.store(Signal::Traces, vec![0; 4]).await;
+        spool.store(Signal::Logs, vec![1; 4]).await;
+        spool.store(Signal::Traces, vec![2; 4]).await;
+        // larger than the whole spool
+        spool.store(Signal::Traces, vec![3; 11]).await;
+        assert_eq!(
+            spooled(&config),
+            vec![(1, Signal::Logs), (2, Signal::Traces)]
+        );
+        assert_eq!(spool.lock().spooled, 8);
+        drop(spool);
+
+        // the sequence continues after the spooled requests
+        let reopened = DiskSpool::open(config.clone()).unwrap();
+        assert_eq!(reopened.lock().next_seq, 3);
+        assert_eq!(reopened.lock().spooled, 8);
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_full_spool_drops_newest_requests() {
+        let config = spool("drop-newest", 10, SpoolDropPolicy::DropNewest);
+        let spool = DiskSpool::open(config.clone()).unwrap();
+        spool.store(Signal::Traces, vec![0; 4]).await;
+        spool.store(Signal::Traces, vec![1; 4]).await;
+        spool.store(Signal::Traces, vec![2; 4]).await;
+        assert_eq!(
+            spooled(&config),
+            vec![(0, Signal::Traces), (1, Signal::Traces)]
+        );
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_exporters_share_the_spool_of_a_directory() {
+        let config = spool("shared", 10, SpoolDropPolicy::DropOldest);
+        let traces = DiskSpool::open(config.clone()).unwrap();
+        let logs = DiskSpool::open(config.clone()).unwrap();
+        assert!(Arc::ptr_eq(&traces, &logs));
+
+        // the requests of both signals count towards the size, and keep their order
+        traces.store(Signal::Traces, vec![0; 4]).await;
+        logs.store(Signal::Logs, vec![1; 4]).await;
+        traces.store(Signal::Traces, vec![2; 4]).await;
+        assert_eq!(
+            spooled(&config),
+            vec![(1, Signal::Logs), (2, Signal::Traces)]
+        );
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+}
@@ -1,26 +1,52 @@
 use core::fmt;
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 use futures_core::future::BoxFuture;
 use opentelemetry::trace::TraceError;
-use opentelemetry_proto::tonic::collector::trace::v1::{
-    trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
-};
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_client::TraceServiceClient;
+#[cfg(feature = "disk-spool")]
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
 use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
-use tonic::{codegen::CompressionEncoding, service::Interceptor, transport::Channel, Request};
+use prost::Message;
+use tonic::{service::Interceptor, Request};
 
-use opentelemetry_proto::transform::trace::tonic::group_spans_by_resource_and_scope;
-
-use super::BoxInterceptor;
+use super::balance::ExportChannel;
+use super::compression::SignalCompression;
+use super::retry::{self, Retry};
+#[cfg(feature = "disk-spool")]
+use super::spool::DiskSpool;
+use super::{authorization, check_version, BoxInterceptor, ClientOptions, StatusCodes};
+use crate::exporter::auth::AuthProvider;
+use crate::exporter::conversion::ConversionConfig;
+use crate::exporter::inflight::{self, InflightBytes};
+use crate::exporter::rate::{self, ExportRate};
+use crate::exporter::version::VersionCheck;
+use crate::exporter::PayloadRecorder;
+use crate::stats::StatsRecorder;
+use crate::Signal;
 
 pub(crate) struct TonicTracesClient {
     inner: Option<ClientInner>,
     #[allow(dead_code)]
     // <allow dead> would be removed once we support set_resource for metrics.
     resource: opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema,
+    conversion: ConversionConfig,
+    compression: SignalCompression,
+    inflight: Option<Arc<InflightBytes>>,
+    status_codes: StatusCodes,
+    version_check: Option<VersionCheck>,
+    payload_recorder: Option<PayloadRecorder>,
+    stats: StatsRecorder,
+    retry: Option<Retry>,
+    auth_provider: Option<AuthProvider>,
+    export_rate: Option<Arc<ExportRate>>,
+    #[cfg(feature = "disk-spool")]
+    spool: Option<Arc<DiskSpool>>,
 }
 
 struct ClientInner {
-    client: TraceServiceClient<Channel>,
+    client: TraceServiceClient<ExportChannel>,
     interceptor: BoxInterceptor,
 }
 
@@ -32,15 +58,35 @@ impl fmt::Debug for TonicTracesClient {
 
 impl TonicTracesClient {
     pub(super) fn new(
-        channel: Channel,
+        channel: ExportChannel,
         interceptor: BoxInterceptor,
-        compression: Option<CompressionEncoding>,
+        options: ClientOptions,
     ) -> Self {
+        let ClientOptions {
+            compression,
+            conversion,
+            inflight,
+            status_codes,
+            version_check,
+            payload_recorder,
+            stats,
+            retry,
+            auth_provider,
+            export_rate,
+            max_encoding_message_size,
+            max_decoding_message_size,
+            #[cfg(feature = "disk-spool")]
+            spool,
+        } = options;
         let mut client = TraceServiceClient::new(channel);
-        if let Some(compression) = compression {
-            client = client
-                .send_compressed(compression)
-                .accept_compressed(compression);
+        for encoding in compression.accepted() {
+            client = client.accept_compressed(encoding);
+        }
+        if let Some(limit) = max_encoding_message_size {
+            client = client.max_encoding_message_size(limit);
+        }
+        if let Some(limit) = max_decoding_message_size {
+            client = client.max_decoding_message_size(limit);
         }
 
         TonicTracesClient {
@@ -49,21 +95,40 @@ impl TonicTracesClient {
                 interceptor,
             }),
             resource: Default::default(),
+            conversion,
+            compression,
+            inflight,
+            status_codes,
+            version_check,
+            payload_recorder,
+            stats,
+            retry,
+            auth_provider,
+            export_rate,
+            #[cfg(feature = "disk-spool")]
+            spool,
         }
     }
 }
 
 impl SpanExporter for TonicTracesClient {
     fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
-        let (mut client, metadata, extensions) = match &mut self.inner {
+        let (client, requests) = match &mut self.inner {
             Some(inner) => {
-                let (m, e, _) = match inner.interceptor.call(Request::new(())) {
-                    Ok(res) => res.into_parts(),
-                    Err(e) => {
-                        return Box::pin(std::future::ready(Err(TraceError::Other(Box::new(e)))))
-                    }
-                };
-                (inner.client.clone(), m, e)
+                let requests = self.conversion.trace_requests(batch, &self.resource);
+                let mut intercepted = Vec::with_capacity(requests.len());
+                for request in requests {
+                    let (m, e, _) = match inner.interceptor.call(Request::new(())) {
+                        Ok(res) => res.into_parts(),
+                        Err(e) => {
+                            return Box::pin(std::future::ready(Err(TraceError::Other(Box::new(
+                                e,
+                            )))))
+                        }
+                    };
+                    intercepted.push(Request::from_parts(m, e, request));
+                }
+                (inner.client.clone(), intercepted)
             }
             None => {
                 return Box::pin(std::future::ready(Err(TraceError::Other(
@@ -72,17 +137,115 @@ impl SpanExporter for TonicTracesClient {
             }
         };
 
-        let resource_spans = group_spans_by_resource_and_scope(batch, &self.resource);
+        let inflight = self.inflight.clone();
+        let status_codes = self.status_codes.clone();
+        let compression = self.compression.clone();
+        let version_check = self.version_check.clone();
+        let payload_recorder = self.payload_recorder.clone();
+        let stats = self.stats.clone();
+        let retry = self.retry.clone();
+        let auth_provider = self.auth_provider.clone();
+        let export_rate = self.export_rate.clone();
+        #[cfg(feature = "disk-spool")]
+        let spool = self.spool.clone();
 
         Box::pin(async move {
-            client
-                .export(Request::from_parts(
-                    metadata,
-                    extensions,
-                    ExportTraceServiceRequest { resource_spans },
-                ))
-                .await
-                .map_err(crate::Error::from)?;
+            let authorization = authorization(auth_provider.as_ref()).await?;
+            #[cfg(feature = "disk-spool")]
+            let mut redelivery = None;
+            // the requests are sent one after the other, stopping at the first failure
+            let mut requests = VecDeque::from(requests);
+            while let Some(mut request) = requests.pop_front() {
+                if let Some(value) = &authorization {
+                    request
+                        .metadata_mut()
+                        .insert("authorization", value.clone());
+                }
+                let mut client = client.clone();
+                match compression.leveled() {
+                    Some(leveled) => {
+                        request.extensions_mut().insert(leveled);
+                    }
+                    None => {
+                        if let Some(encoding) = compression.encoding() {
+                            client = client.send_compressed(encoding);
+                        }
+                    }
+                }
+                let payload = payload_recorder
+                    .as_ref()
+                    .map(|recorder| (recorder, request.get_ref().encode_to_vec()));
+                #[cfg(feature = "disk-spool")]
+                let spooled = spool
+                    .as_ref()
+                    .map(|spool| (spool, request.get_ref().encode_to_vec()));
+                #[cfg(feature = "disk-spool")]
+                if spool.is_some() {
+                    redelivery = Some((
+                        client.clone(),
+                        request.metadata().clone(),
+                        request.extensions().clone(),
+                    ));
+                }
+                let encoded_len = request.get_ref().encoded_len();
+                let _permit = inflight::acquire(inflight.as_ref(), encoded_len).await;
+                rate::acquire(export_rate.as_ref()).await;
+                stats.record_bytes_sent(encoded_len);
+                let result = retry::send(retry.as_ref(), &status_codes, request, |request| {
+                    let mut client = client.clone();
+                    async move { client.export(request).await }
+                })
+                .await;
+                #[cfg(feature = "disk-spool")]
+                if let (Err(status), Some((spool, spooled))) = (&result, spooled) {
+                    // the requests after the failed one aren't sent, so they are spooled too
+                    if status_codes.is_retryable(status) {
+                        spool.store(Signal::Traces, spooled).await;
+                        for request in requests.drain(..) {
+                            let request = request.get_ref().encode_to_vec();
+                            spool.store(Signal::Traces, request).await;
+                        }
+                    }
+                }
+                if let Ok(response) = &result {
+                    compression.negotiate(response.metadata());
+                    check_version(version_check.as_ref(), response.metadata());
+                }
+                let partial_success = result
+                    .as_ref()
+                    .ok()
+                    .and_then(|response| response.get_ref().partial_success.clone());
+                status_codes.handle(result)?;
+                if let Some((recorder, payload)) = payload {
+                    recorder.record(&payload, Signal::Traces);
+                }
+                if let Some(partial_success) = partial_success {
+                    status_codes.partial_success(
+                        partial_success.rejected_spans,
+                        &partial_success.error_message,
+                    )?;
+                }
+            }
+
+            #[cfg(feature = "disk-spool")]
+            if let (Some(spool), Some((client, metadata, extensions))) = (&spool, redelivery) {
+                spool
+                    .redeliver(
+                        Signal::Traces,
+                        &status_codes,
+                        |request: ExportTraceServiceRequest| {
+                            let mut client = client.clone();
+                            let request =
+                                Request::from_parts(metadata.clone(), extensions.clone(), request);
+                            let export_rate = export_rate.clone();
+                            async move {
+                                rate::acquire(export_rate.as_ref()).await;
+                                client.export(request).await
+                            }
+                        },
+                    )
+                    .await;
+            }
 
             Ok(())
         })
@@ -0,0 +1,82 @@
+//! Connect to a collector listening on a Unix domain socket, e.g. a sidecar.
+
+use std::path::PathBuf;
+
+/// The scheme of endpoints naming the path of a Unix domain socket, as in
+/// `unix:///var/run/otel-collector.sock`.
+pub(crate) const UNIX_SCHEME: &str = "unix://";
+
+/// The endpoint the requests over a Unix domain socket are addressed to. The socket is connected
+/// to regardless of its host.
+pub(crate) const UNIX_ENDPOINT: &str = "http://localhost";
+
+/// The path of the socket of a `unix://` endpoint, if it is one.
+pub(crate) fn socket_path(endpoint: &str) -> Option<PathBuf> {
+    let path = endpoint.strip_prefix(UNIX_SCHEME)?;
+    Some(PathBuf::from(path))
+}
+
+#[cfg(unix)]
+pub(crate) use self::connector::Connector;
+
+#[cfg(unix)]
+mod connector {
+    use std::future::Future;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    use http::Uri;
+    use hyper_util::rt::TokioIo;
+    use tokio::net::UnixStream;
+    use tonic::codegen::Service;
+
+    /// Connects to the socket at `path`, whatever the URI of the endpoint.
+    #[derive(Clone, Debug)]
+    pub(crate) struct Connector {
+        path: Arc<Path>,
+    }
+
+    impl Connector {
+        pub(crate) fn new(path: PathBuf) -> Self {
+            Connector { path: path.into() }
+        }
+    }
+
+    impl Service<Uri> for Connector {
+        type Response = TokioIo<UnixStream>;
+        type Error = io::Error;
+        type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _uri: Uri) -> Self::Future {
+            let path = self.path.clone();
+            Box::pin(async move { Ok(TokioIo::new(UnixStream::connect(path).await?)) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::socket_path;
+
+    #[test]
+    fn test_socket_path() {
+        assert_eq!(
+            socket_path("unix:///var/run/otel.sock"),
+            Some(PathBuf::from("/var/run/otel.sock"))
+        );
+        assert_eq!(
+            socket_path("unix://otel.sock"),
+            Some(PathBuf::from("otel.sock"))
+        );
+        assert_eq!(socket_path("http://localhost:4317"), None);
+    }
+}
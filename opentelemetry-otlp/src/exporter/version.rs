@@ -0,0 +1,111 @@
+//! Detect collectors implementing an older OTLP version than the exporter.
+//!
+//! OTLP doesn't specify how a collector advertises the version it implements, so the version
+//! is read from a response header configured by the user, e.g. one added by a gateway in front
+//! of the collector. A collector implementing an older version silently drops the fields it
+//! doesn't know yet, so the exporter warns once it sees such a collector.
+
+use std::sync::{Arc, OnceLock};
+
+use opentelemetry::otel_warn;
+
+/// The OTLP version of the protobuf definitions the exporter encodes its requests with.
+const OTLP_VERSION: Version = Version(1, 4, 0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Version(u64, u64, u64);
+
+impl Version {
+    /// Parse versions like `1.3`, `1.3.2` or `v1.3.2`, ignoring pre-release and build suffixes.
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = version.split('.').map(|part| part.parse::<u64>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        Some(Version(major, minor, patch))
+    }
+}
+
+/// Compares the OTLP version advertised by the collector with the version of the exporter,
+/// shared by the clones of a client.
+#[derive(Clone, Debug)]
+pub(crate) struct VersionCheck {
+    /// The response header or metadata entry the collector advertises its OTLP version in.
+    header: Arc<str>,
+    /// The version of the collector, once it advertised an older version than the exporter's.
+    outdated: Arc<OnceLock<String>>,
+}
+
+impl VersionCheck {
+    pub(crate) fn new(header: &str) -> Self {
+        VersionCheck {
+            header: header.to_ascii_lowercase().into(),
+            outdated: Arc::default(),
+        }
+    }
+
+    /// The response header or metadata entry the collector advertises its OTLP version in.
+    pub(crate) fn header(&self) -> &str {
+        &self.header
+    }
+
+    /// Check the version advertised in the headers of a response. Collectors not advertising a
+    /// version, or advertising one which can't be parsed, are skipped. Only the first outdated
+    /// version is reported.
+    pub(crate) fn check(&self, advertised: Option<&str>) {
+        let Some(advertised) = advertised else {
+            return;
+        };
+        match Version::parse(advertised) {
+            Some(version) if version < OTLP_VERSION => {}
+            _ => return,
+        }
+        self.outdated.get_or_init(|| {
+            otel_warn!(
+                name: "Exporter.CollectorVersionOutdated",
+                collector_version = advertised.to_string(),
+                exporter_version = format!(
+                    "{}.{}.{}",
+                    OTLP_VERSION.0, OTLP_VERSION.1, OTLP_VERSION.2
+                ),
+                message = "the collector implements an older OTLP version and may drop fields it doesn't know",
+            );
+            advertised.to_string()
+        });
+    }
+
+    /// The outdated version the collector advertised, if any.
+    #[cfg(test)]
+    pub(crate) fn outdated_version(&self) -> Option<&str> {
+        self.outdated.get().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Version, VersionCheck};
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(Version::parse("v1.3.2"), Some(Version(1, 3, 2)));
+        assert_eq!(Version::parse("1.4"), Some(Version(1, 4, 0)));
+        assert_eq!(Version::parse("1.5.0-rc.1"), Some(Version(1, 5, 0)));
+        assert_eq!(Version::parse("latest"), None);
+    }
+
+    #[test]
+    fn test_check_skips_missing_and_newer_versions() {
+        let check = VersionCheck::new("collector-otlp-version");
+        check.check(None);
+        check.check(Some("unknown"));
+        check.check(Some("1.4.0"));
+        check.check(Some("2.0.0"));
+        assert_eq!(check.outdated_version(), None);
+
+        check.check(Some("1.0.0"));
+        check.check(Some("0.9.0"));
+        assert_eq!(check.outdated_version(), Some("1.0.0"));
+    }
+}
@@ -0,0 +1,160 @@
+//! Logging the telemetry of failing exports to stderr, e.g. while no collector runs during local
+//! development.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Number of consecutive failed exports after which the telemetry of failing exports is logged.
+pub(crate) const FAILURES_BEFORE_FALLBACK: usize = 3;
+
+/// Where the telemetry of failing exports is logged to.
+type Sink = dyn Fn(&str) + Send + Sync;
+
+/// Logs the telemetry of exports failing after [`FAILURES_BEFORE_FALLBACK`] consecutive
+/// failures, until an export succeeds again.
+#[derive(Clone)]
+pub(crate) struct Fallback {
+    failures: Arc<AtomicUsize>,
+    sink: Arc<Sink>,
+}
+
+impl fmt::Debug for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fallback")
+            .field("failures", &self.failures.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl Fallback {
+    /// Log to stderr, if enabled.
+    pub(crate) fn stderr(enabled: bool) -> Option<Self> {
+        enabled.then(|| Fallback::new(|line| eprintln!("{line}")))
+    }
+
+    pub(crate) fn new(sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Fallback {
+            failures: Arc::default(),
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// Whether the telemetry of the next export is logged if the export fails, so it must be
+    /// kept until the export finished.
+    #[cfg(any(feature = "trace", feature = "logs"))]
+    pub(crate) fn is_armed(&self) -> bool {
+        self.failures.load(Ordering::Relaxed) + 1 >= FAILURES_BEFORE_FALLBACK
+    }
+
+    /// Record the outcome of an export, logging the `lines` describing its telemetry if it
+    /// failed and enough exports failed in a row.
+    pub(crate) fn record(&self, succeeded: bool, lines: impl FnOnce() -> Vec<String>) {
+        if succeeded {
+            self.failures.store(0, Ordering::Relaxed);
+            return;
+        }
+        if self.failures.fetch_add(1, Ordering::Relaxed) + 1 >= FAILURES_BEFORE_FALLBACK {
+            for line in lines() {
+                (self.sink)(&line);
+            }
+        }
+    }
+}
+
+/// One line per span of `batch`.
+#[cfg(feature = "trace")]
+pub(crate) fn span_lines(batch: &[opentelemetry_sdk::export::trace::SpanData]) -> Vec<String> {
+    batch
+        .iter()
+        .map(|span| {
+            let attributes: Vec<_> = span
+                .attributes
+                .iter()
+                .map(|kv| format!("{}={}", kv.key, kv.value))
+                .collect();
+            format!(
+                "span {:?} trace_id={} span_id={} duration={:?} attributes=[{}]",
+                span.name,
+                span.span_context.trace_id(),
+                span.span_context.span_id(),
+                span.end_time
+                    .duration_since(span.start_time)
+                    .unwrap_or_default(),
+                attributes.join(","),
+            )
+        })
+        .collect()
+}
+
+/// One line per log record of `batch`.
+#[cfg(feature = "logs")]
+pub(crate) fn log_lines(batch: &opentelemetry_sdk::export::logs::LogBatch<'_>) -> Vec<String> {
+    batch
+        .iter()
+        .map(|(record, scope)| {
+            let attributes: Vec<_> = record
+                .attributes_iter()
+                .map(|(key, value)| format!("{key}={value:?}"))
+                .collect();
+            format!(
+                "log scope={:?} severity={:?} body={:?} attributes=[{}]",
+                scope.name(),
+                record.severity_number,
+                record.body,
+                attributes.join(","),
+            )
+        })
+        .collect()
+}
+
+/// One line per metric of `metrics`.
+#[cfg(feature = "metrics")]
+pub(crate) fn metric_lines(
+    metrics: &opentelemetry_sdk::metrics::data::ResourceMetrics,
+) -> Vec<String> {
+    metrics
+        .scope_metrics
+        .iter()
+        .flat_map(|scope_metrics| {
+            scope_metrics.metrics.iter().map(|metric| {
+                format!(
+                    "metric {:?} scope={:?} data={:?}",
+                    metric.name,
+                    scope_metrics.scope.name(),
+                    metric.data,
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "trace", feature = "logs"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{Fallback, FAILURES_BEFORE_FALLBACK};
+
+    #[test]
+    fn test_logs_after_consecutive_failures() {
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&logged);
+        let fallback = Fallback::new(move |line| sink.lock().unwrap().push(line.to_string()));
+        let lines = |n: usize| move || vec![format!("export {n}")];
+
+        for n in 1..FAILURES_BEFORE_FALLBACK {
+            assert!(!fallback.is_armed());
+            fallback.record(false, lines(n));
+        }
+        assert!(fallback.is_armed());
+        fallback.record(false, lines(FAILURES_BEFORE_FALLBACK));
+        assert_eq!(*logged.lock().unwrap(), vec!["export 3"]);
+
+        // a successful export resets the count
+        fallback.record(true, lines(0));
+        assert!(!fallback.is_armed());
+        fallback.record(false, lines(5));
+        assert_eq!(logged.lock().unwrap().len(), 1);
+    }
+}
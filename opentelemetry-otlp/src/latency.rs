@@ -0,0 +1,145 @@
+//! Percentiles of the export latency over a sliding window of the most recent exports.
+//!
+//! The latencies are counted in a histogram with exponentially growing buckets, so tracking
+//! them costs a fixed amount of memory and the percentiles are approximate.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of most recent exports the percentiles are computed over.
+const WINDOW: usize = 1024;
+
+/// Number of buckets per doubling of the latency. Percentiles are off by at most about 4.5%.
+const BUCKETS_PER_DOUBLING: usize = 8;
+
+/// Number of buckets, covering latencies of up to 2^40 microseconds, or about 12 days.
+const BUCKETS: usize = 40 * BUCKETS_PER_DOUBLING + 1;
+
+/// Percentiles of the latency of the most recent exports, successful or not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LatencyPercentiles {
+    /// The median latency.
+    pub p50: Duration,
+    /// The latency 95% of the exports were faster than.
+    pub p95: Duration,
+    /// The latency 99% of the exports were faster than.
+    pub p99: Duration,
+}
+
+/// Records the latency of exports in a histogram of the most recent exports.
+pub(crate) struct LatencyTracker(Mutex<Window>);
+
+struct Window {
+    /// Number of latencies of the window per bucket.
+    buckets: Vec<u32>,
+    /// Bucket of each latency of the window, oldest first.
+    recent: VecDeque<u16>,
+}
+
+impl fmt::Debug for LatencyTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LatencyTracker")
+    }
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        LatencyTracker(Mutex::new(Window {
+            buckets: vec![0; BUCKETS],
+            recent: VecDeque::with_capacity(WINDOW),
+        }))
+    }
+}
+
+impl LatencyTracker {
+    /// Record the latency of an export, evicting the oldest latency once the window is full.
+    pub(crate) fn record(&self, latency: Duration) {
+        let mut window = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if window.recent.len() == WINDOW {
+            if let Some(oldest) = window.recent.pop_front() {
+                window.buckets[oldest as usize] -= 1;
+            }
+        }
+        let bucket = bucket_index(latency);
+        window.buckets[bucket] += 1;
+        window.recent.push_back(bucket as u16);
+    }
+
+    /// The percentiles of the window, or `None` if no export has been recorded yet.
+    pub(crate) fn percentiles(&self) -> Option<LatencyPercentiles> {
+        let window = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if window.recent.is_empty() {
+            return None;
+        }
+        Some(LatencyPercentiles {
+            p50: window.percentile(0.50),
+            p95: window.percentile(0.95),
+            p99: window.percentile(0.99),
+        })
+    }
+}
+
+impl Window {
+    fn percentile(&self, quantile: f64) -> Duration {
+        let rank = ((quantile * self.recent.len() as f64).ceil() as u32).max(1);
+        let mut count = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            count += bucket;
+            if count >= rank {
+                return bucket_value(index);
+            }
+        }
+        bucket_value(BUCKETS - 1)
+    }
+}
+
+/// The bucket of `latency`. Bucket `i` holds the latencies in
+/// `(2^((i - 1) / BUCKETS_PER_DOUBLING), 2^(i / BUCKETS_PER_DOUBLING)]` microseconds.
+fn bucket_index(latency: Duration) -> usize {
+    let micros = latency.as_micros().max(1) as f64;
+    let index = (micros.log2() * BUCKETS_PER_DOUBLING as f64).ceil() as usize;
+    index.min(BUCKETS - 1)
+}
+
+/// The geometric mean of the bounds of bucket `index`, representing its latencies.
+fn bucket_value(index: usize) -> Duration {
+    let exponent = (index as f64 - 0.5) / BUCKETS_PER_DOUBLING as f64;
+    Duration::from_secs_f64(2f64.powf(exponent) / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{LatencyTracker, WINDOW};
+
+    fn assert_approx(actual: Duration, expected: Duration) {
+        let error = (actual.as_secs_f64() - expected.as_secs_f64()).abs() / expected.as_secs_f64();
+        assert!(error < 0.05, "{actual:?} is not close to {expected:?}");
+    }
+
+    #[test]
+    fn test_percentiles_of_known_latencies() {
+        let tracker = LatencyTracker::default();
+        assert_eq!(tracker.percentiles(), None);
+
+        for millis in 1..=100 {
+            tracker.record(Duration::from_millis(millis));
+        }
+        let percentiles = tracker.percentiles().unwrap();
+        assert_approx(percentiles.p50, Duration::from_millis(50));
+        assert_approx(percentiles.p95, Duration::from_millis(95));
+        assert_approx(percentiles.p99, Duration::from_millis(99));
+
+        // the earlier latencies slide out of the window
+        for _ in 0..WINDOW {
+            tracker.record(Duration::from_secs(2));
+        }
+        let percentiles = tracker.percentiles().unwrap();
+        assert_approx(percentiles.p50, Duration::from_secs(2));
+        assert_approx(percentiles.p99, Duration::from_secs(2));
+    }
+}
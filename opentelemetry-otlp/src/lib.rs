@@ -98,8 +98,18 @@
 //!
 //! The following feature flags generate additional code and types:
 //! * `serialize`: Enables serialization support for type defined in this create via `serde`.
+//! * `attribute-key-patterns`: Adds `with_attribute_key_drop_patterns`, removing the attributes
+//!   whose key matches a regular expression.
 //! * `populate-logs-event-name`: Enables sending `LogRecord::event_name` as an attribute
 //!    with the key `name`
+//! * `testing`: Adds `with_inspector` to the exporter builders, receiving the converted OTLP
+//!   messages of every successful export, and the `with_in_memory` transport, capturing them
+//!   into a shared vector instead of sending them to a collector.
+//! * `exporter-stats`: Adds `exporter_stats` to the exporters, returning counters of their
+//!   exports such as the failed exports and the bytes sent.
+//! * `dangerous-configuration`: Adds `with_tls_insecure_skip_verify` to the gRPC and HTTP
+//!   exporters, disabling the verification of the certificate of the collector. For testing
+//!   only, never enable it in production.
 //!
 //! The following feature flags offer additional configurations on gRPC:
 //!
@@ -107,6 +117,8 @@
 //! * `grpc-tonic`: Use `tonic` as grpc layer. This is enabled by default.
 //! * `gzip-tonic`: Use gzip compression for `tonic` grpc layer.
 //! * `zstd-tonic`: Use zstd compression for `tonic` grpc layer.
+//! * `disk-spool`: Adds `with_disk_spool`, spooling the requests failing all retries to disk
+//!   and sending them again once the collector accepts exports.
 //! * `tls-roots`: Adds system trust roots to rustls-based gRPC clients using the rustls-native-certs crate
 //! * `tls-webkpi-roots`: Embeds Mozilla's trust roots to rustls-based gRPC clients using the webkpi-roots crate
 //!
@@ -117,6 +129,14 @@
 //! * `reqwest-client`: Use reqwest http client.
 //! * `reqwest-rustls`: Use reqwest with TLS with system trust roots via `rustls-native-certs` crate.
 //! * `reqwest-rustls-webkpi-roots`: Use reqwest with TLS with Mozilla's trust roots via `webkpi-roots` crate.
+//! * `gzip-http`: Use gzip compression for the http request bodies.
+//! * `request-signing`: Sign the body of the export requests with an HMAC, for gateways
+//!   authenticating requests by their signature.
+//!
+//! The following feature flag offers a transport for collectors without a gRPC or HTTP stack:
+//!
+//! * `tcp`: Send each export request as a 4-byte big-endian length followed by the protobuf
+//!   encoded request over a plain TCP connection.
 //!
 //! # Kitchen Sink Full Configuration
 //!
@@ -218,34 +238,63 @@
 #![cfg_attr(test, deny(warnings))]
 
 mod exporter;
+#[cfg(any(feature = "trace", feature = "metrics", feature = "logs"))]
+#[cfg(otlp_transport)]
+mod fallback;
+#[cfg(any(feature = "trace", feature = "metrics", feature = "logs"))]
+#[cfg(otlp_transport)]
+mod latency;
 #[cfg(feature = "logs")]
-#[cfg(any(feature = "http-proto", feature = "http-json", feature = "grpc-tonic"))]
+#[cfg(otlp_transport)]
 mod logs;
 #[cfg(feature = "metrics")]
-#[cfg(any(feature = "http-proto", feature = "http-json", feature = "grpc-tonic"))]
+#[cfg(otlp_transport)]
 mod metric;
+#[cfg(feature = "metrics")]
+#[cfg(otlp_transport)]
+mod self_metrics;
 #[cfg(feature = "trace")]
-#[cfg(any(feature = "http-proto", feature = "http-json", feature = "grpc-tonic"))]
+#[cfg(otlp_transport)]
 mod span;
+mod stats;
+#[cfg(any(feature = "trace", feature = "metrics", feature = "logs"))]
+#[cfg(otlp_transport)]
+mod unwind;
 
+#[cfg(feature = "metrics")]
+pub use crate::exporter::conversion::MetricConflictPolicy;
+#[cfg(feature = "metrics")]
+pub use crate::exporter::conversion::MetricStartTimeStrategy;
+#[cfg(feature = "trace")]
+pub use crate::exporter::conversion::SpanDurationPolicy;
+pub use crate::exporter::conversion::TimestampPrecision;
+#[cfg(feature = "trace")]
+pub use crate::exporter::submission::SubmissionMode;
 pub use crate::exporter::Compression;
 pub use crate::exporter::ExportConfig;
+pub use crate::exporter::ExportPanicPolicy;
+pub use crate::exporter::Signal;
+#[cfg(any(feature = "trace", feature = "metrics", feature = "logs"))]
+#[cfg(otlp_transport)]
+pub use crate::latency::LatencyPercentiles;
 #[cfg(feature = "trace")]
-#[cfg(any(feature = "http-proto", feature = "http-json", feature = "grpc-tonic"))]
+#[cfg(otlp_transport)]
 pub use crate::span::{
     SpanExporter, OTEL_EXPORTER_OTLP_TRACES_COMPRESSION, OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
     OTEL_EXPORTER_OTLP_TRACES_HEADERS, OTEL_EXPORTER_OTLP_TRACES_TIMEOUT,
 };
+#[cfg(feature = "exporter-stats")]
+pub use crate::stats::ExporterStats;
 
 #[cfg(feature = "metrics")]
-#[cfg(any(feature = "http-proto", feature = "http-json", feature = "grpc-tonic"))]
+#[cfg(otlp_transport)]
 pub use crate::metric::{
     MetricExporter, OTEL_EXPORTER_OTLP_METRICS_COMPRESSION, OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
     OTEL_EXPORTER_OTLP_METRICS_HEADERS, OTEL_EXPORTER_OTLP_METRICS_TIMEOUT,
 };
 
 #[cfg(feature = "logs")]
-#[cfg(any(feature = "http-proto", feature = "http-json", feature = "grpc-tonic"))]
+#[cfg(otlp_transport)]
 pub use crate::logs::{
     LogExporter, OTEL_EXPORTER_OTLP_LOGS_COMPRESSION, OTEL_EXPORTER_OTLP_LOGS_ENDPOINT,
     OTEL_EXPORTER_OTLP_LOGS_HEADERS, OTEL_EXPORTER_OTLP_LOGS_TIMEOUT,
@@ -254,13 +303,30 @@ pub use crate::logs::{
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 pub use crate::exporter::http::{HasHttpConfig, WithHttpConfig};
 
+#[cfg(all(
+    feature = "request-signing",
+    any(feature = "http-proto", feature = "http-json")
+))]
+pub use crate::exporter::http::signing::{
+    HmacAlgorithm, RequestSigner, SignedPayload, DEFAULT_SIGNATURE_HEADER,
+};
+
 #[cfg(feature = "grpc-tonic")]
-pub use crate::exporter::tonic::{HasTonicConfig, WithTonicConfig};
+pub use crate::exporter::tonic::{
+    balance::EndpointSelection, compression::CompressionStrategy, health::HealthStatus,
+    retry::RetryPolicy, HasTonicConfig, StatusCodeTreatment, WithTonicConfig,
+    OTEL_EXPORTER_OTLP_MAX_MESSAGE_SIZE,
+};
+
+#[cfg(feature = "disk-spool")]
+pub use crate::exporter::tonic::spool::SpoolDropPolicy;
 
 pub use crate::exporter::{
     HasExportConfig, WithExportConfig, OTEL_EXPORTER_OTLP_COMPRESSION, OTEL_EXPORTER_OTLP_ENDPOINT,
-    OTEL_EXPORTER_OTLP_ENDPOINT_DEFAULT, OTEL_EXPORTER_OTLP_HEADERS, OTEL_EXPORTER_OTLP_PROTOCOL,
-    OTEL_EXPORTER_OTLP_PROTOCOL_DEFAULT, OTEL_EXPORTER_OTLP_TIMEOUT,
+    OTEL_EXPORTER_OTLP_ENDPOINT_DEFAULT, OTEL_EXPORTER_OTLP_HEADERS,
+    OTEL_EXPORTER_OTLP_MAX_HEADERS_DEFAULT, OTEL_EXPORTER_OTLP_PROTOCOL,
+    OTEL_EXPORTER_OTLP_PROTOCOL_DEFAULT, OTEL_EXPORTER_OTLP_SHUTDOWN_TIMEOUT_DEFAULT,
+    OTEL_EXPORTER_OTLP_THREAD_NAME_PREFIX_DEFAULT, OTEL_EXPORTER_OTLP_TIMEOUT,
     OTEL_EXPORTER_OTLP_TIMEOUT_DEFAULT,
 };
 
@@ -287,6 +353,43 @@ pub struct HttpExporterBuilderSet(HttpExporterBuilder);
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 pub use crate::exporter::http::HttpExporterBuilder;
 
+/// Type to hold the transports to try in order of preference, and the configuration they share.
+///
+/// Only the options of [WithExportConfig] are available, since it's unknown which transport is
+/// built until the exporter is.
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+#[derive(Debug)]
+pub struct PreferredExporterBuilderSet {
+    preference: Vec<Protocol>,
+    exporter_config: ExportConfig,
+}
+
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+impl PreferredExporterBuilderSet {
+    fn new(preference: impl IntoIterator<Item = Protocol>) -> Self {
+        PreferredExporterBuilderSet {
+            preference: preference.into_iter().collect(),
+            exporter_config: ExportConfig::default(),
+        }
+    }
+}
+
+/// Type to hold the [TcpExporterBuilder] and indicate it has been set.
+#[cfg(feature = "tcp")]
+#[derive(Debug, Default)]
+pub struct TcpExporterBuilderSet(TcpExporterBuilder);
+
+#[cfg(feature = "tcp")]
+pub use crate::exporter::tcp::TcpExporterBuilder;
+
+/// Type to hold the [InMemoryExporterBuilder] and indicate it has been set.
+#[cfg(feature = "testing")]
+#[derive(Debug)]
+pub struct InMemoryExporterBuilderSet<T>(InMemoryExporterBuilder<T>);
+
+#[cfg(feature = "testing")]
+pub use crate::exporter::in_memory::InMemoryExporterBuilder;
+
 #[cfg(feature = "grpc-tonic")]
 pub use crate::exporter::tonic::{TonicConfig, TonicExporterBuilder};
 
@@ -301,6 +404,80 @@ pub enum Error {
     #[error("transport error {0}")]
     Transport(#[from] tonic::transport::Error),
 
+    /// The host of the collector endpoint could not be resolved when building the exporter.
+    #[cfg(feature = "reqwest-rustls")]
+    #[error("failed to resolve the collector host {host}: {source}")]
+    DnsResolutionFailed {
+        /// the host of the endpoint
+        host: String,
+        /// the error of the resolver
+        source: std::io::Error,
+    },
+
+    /// A `unix://` endpoint is configured on a platform without Unix domain sockets.
+    #[cfg(all(feature = "grpc-tonic", not(unix)))]
+    #[error(
+        "the endpoint {endpoint} is a Unix domain socket, which this platform doesn't support"
+    )]
+    UnixSocketUnsupported {
+        /// the endpoint
+        endpoint: String,
+    },
+
+    /// A transport option the custom channel of the exporter can't honor is configured as well.
+    #[cfg(feature = "grpc-tonic")]
+    #[error("the option {0} conflicts with the custom channel of the exporter")]
+    ConflictingChannelOption(&'static str),
+
+    /// The auth provider failed to provide a bearer token for the export.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    #[error("the auth provider failed to provide a token: {0}")]
+    AuthProviderFailed(String),
+
+    /// The connect timeout of the exporter is not shorter than its export timeout.
+    #[cfg(feature = "grpc-tonic")]
+    #[error("the connect timeout {connect_timeout:?} is not shorter than the export timeout {timeout:?}")]
+    ConnectTimeoutTooLong {
+        /// the connect timeout
+        connect_timeout: std::time::Duration,
+        /// the export timeout
+        timeout: std::time::Duration,
+    },
+
+    /// The client certificate or private key for mutual TLS is invalid, or they don't match.
+    #[cfg(any(
+        all(feature = "grpc-tonic", feature = "tls"),
+        all(
+            feature = "reqwest-rustls",
+            any(feature = "http-proto", feature = "http-json")
+        )
+    ))]
+    #[error("invalid client certificate or key: {0}")]
+    InvalidClientIdentity(String),
+
+    /// The server name to skip verifying the certificate of the collector for isn't a valid
+    /// domain name or IP address.
+    #[cfg(all(
+        feature = "grpc-tonic",
+        feature = "tls",
+        feature = "dangerous-configuration"
+    ))]
+    #[error("invalid TLS server name {0}")]
+    InvalidTlsServerName(String),
+
+    /// The proxy of the HTTP exporter isn't a valid proxy URL.
+    #[cfg(all(
+        feature = "reqwest",
+        any(feature = "http-proto", feature = "http-json")
+    ))]
+    #[error("invalid proxy {0}")]
+    InvalidProxy(String),
+
+    /// None of the weighted endpoints of the exporter has a weight above zero.
+    #[cfg(feature = "grpc-tonic")]
+    #[error("no weighted endpoint has a weight above zero")]
+    NoWeightedEndpoint,
+
     /// Wrap the [`tonic::codegen::http::uri::InvalidUri`] error
     #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
     #[error("invalid URI {0}")]
@@ -338,6 +515,71 @@ pub enum Error {
     #[error("http header name error {0}")]
     InvalidHeaderName(#[from] http::header::InvalidHeaderName),
 
+    /// More headers or metadata entries are configured than allowed.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    #[error("{count} headers are configured, exceeding the maximum of {max}")]
+    TooManyHeaders {
+        /// number of configured headers
+        count: usize,
+        /// maximum number of headers allowed
+        max: usize,
+    },
+
+    /// The endpoint is on the port collectors conventionally serve OTLP over another transport
+    /// on, with strict endpoint validation enabled.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    #[error("the endpoint {endpoint} is on the conventional OTLP port of another transport than {protocol:?}")]
+    EndpointPortMismatch {
+        /// the endpoint
+        endpoint: String,
+        /// the protocol of the exporter
+        protocol: Protocol,
+    },
+
+    /// The collector asked with a `Retry-After` header not to send exports for a while.
+    #[cfg(any(feature = "http-proto", feature = "http-json"))]
+    #[error("the collector asked not to send exports for another {remaining:?}")]
+    Throttled {
+        /// the time left until exports may be sent again
+        remaining: std::time::Duration,
+    },
+
+    /// The collector rejected some items of the export, with failing on partial success
+    /// enabled.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    #[error("the collector rejected {rejected} items: {message}")]
+    PartialSuccess {
+        /// the number of rejected items
+        rejected: u64,
+        /// the message of the collector
+        message: String,
+    },
+
+    /// Concurrent span exports were configured with the blocking submission mode, which doesn't
+    /// enqueue the exports to send them concurrently.
+    #[cfg(feature = "trace")]
+    #[error("{0} concurrent exports require a submission mode enqueuing the exports")]
+    ConcurrentExportsWithoutQueue(usize),
+
+    /// A thread of the exporter couldn't be spawned.
+    #[error("failed to spawn the {name} thread: {source}")]
+    ThreadSpawn {
+        /// the name of the thread
+        name: String,
+        /// the error spawning it
+        source: std::io::Error,
+    },
+
+    /// None of the transports the exporter prefers is enabled and builds.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    #[error("none of the preferred transports {0:?} is enabled and builds")]
+    NoViableTransport(Vec<Protocol>),
+
+    /// Sending the export request over TCP failed.
+    #[cfg(feature = "tcp")]
+    #[error("tcp transport error {0}")]
+    TcpTransport(#[from] std::io::Error),
+
     /// Prost encode failed
     #[cfg(any(
         feature = "http-proto",
@@ -356,9 +598,33 @@ pub enum Error {
     UnsupportedCompressionAlgorithm(String),
 
     /// Feature required to use the specified compression algorithm.
-    #[cfg(any(not(feature = "gzip-tonic"), not(feature = "zstd-tonic")))]
+    #[cfg(any(
+        not(feature = "gzip-tonic"),
+        not(feature = "zstd-tonic"),
+        not(feature = "gzip-http")
+    ))]
     #[error("feature '{0}' is required to use the compression algorithm '{1}'")]
     FeatureRequiredForCompressionAlgorithm(&'static str, Compression),
+
+    /// Compression level out of the range of the compression algorithm.
+    #[cfg(any(feature = "grpc-tonic", feature = "gzip-http"))]
+    #[error("compression level {1} is out of range for the compression algorithm '{0}'")]
+    UnsupportedCompressionLevel(Compression, i32),
+
+    /// The prefix of the OTLP service paths doesn't start with `/` or doesn't form a valid path.
+    #[cfg(feature = "grpc-tonic")]
+    #[error("invalid service path prefix {0}")]
+    InvalidServicePathPrefix(String),
+
+    /// The directory of the disk spool couldn't be created or read.
+    #[cfg(feature = "disk-spool")]
+    #[error("failed to open the disk spool {path}: {source}")]
+    DiskSpool {
+        /// the directory of the spool
+        path: String,
+        /// the error reading the directory
+        source: std::io::Error,
+    },
 }
 
 #[cfg(feature = "grpc-tonic")]
@@ -5,18 +5,42 @@
 use async_trait::async_trait;
 use std::fmt::Debug;
 
-use opentelemetry_sdk::logs::LogResult;
+use opentelemetry_sdk::logs::{LogError, LogResult};
 
 use opentelemetry_sdk::export::logs::LogBatch;
 
+use crate::fallback::{self, Fallback};
+use crate::latency::{LatencyPercentiles, LatencyTracker};
+#[cfg(feature = "metrics")]
+use crate::self_metrics::SelfMetrics;
+use crate::stats::StatsRecorder;
+use crate::unwind::PanicGuard;
+use crate::ExportPanicPolicy;
+#[cfg(feature = "exporter-stats")]
+use crate::ExporterStats;
 use crate::{HasExportConfig, NoExporterBuilderSet};
 
+#[cfg(feature = "grpc-tonic")]
+use crate::exporter::tonic::health::{HealthClient, HealthStatus};
 #[cfg(feature = "grpc-tonic")]
 use crate::{HasTonicConfig, TonicExporterBuilder, TonicExporterBuilderSet};
 
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 use crate::{HasHttpConfig, HttpExporterBuilder, HttpExporterBuilderSet};
 
+#[cfg(feature = "tcp")]
+use crate::{TcpExporterBuilder, TcpExporterBuilderSet};
+
+#[cfg(feature = "testing")]
+use crate::exporter::conversion::Inspector;
+#[cfg(feature = "testing")]
+use crate::{InMemoryExporterBuilder, InMemoryExporterBuilderSet};
+#[cfg(feature = "testing")]
+use opentelemetry_proto::tonic::logs::v1::ResourceLogs;
+
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+use crate::{exporter::build_with_preference, PreferredExporterBuilderSet, Protocol};
+
 /// Compression algorithm to use, defaults to none.
 pub const OTEL_EXPORTER_OTLP_LOGS_COMPRESSION: &str = "OTEL_EXPORTER_OTLP_LOGS_COMPRESSION";
 
@@ -58,22 +82,145 @@ impl LogExporterBuilder<NoExporterBuilderSet> {
             endpoint: self.endpoint,
         }
     }
+
+    #[cfg(feature = "tcp")]
+    pub fn with_tcp(self) -> LogExporterBuilder<TcpExporterBuilderSet> {
+        LogExporterBuilder {
+            client: TcpExporterBuilderSet(TcpExporterBuilder::default()),
+            endpoint: self.endpoint,
+        }
+    }
+
+    /// Append the exported `ResourceLogs` to `exported` instead of sending them to a collector, to
+    /// assert in tests on exactly what would have been sent.
+    #[cfg(feature = "testing")]
+    pub fn with_in_memory(
+        self,
+        exported: std::sync::Arc<std::sync::Mutex<Vec<ResourceLogs>>>,
+    ) -> LogExporterBuilder<InMemoryExporterBuilderSet<ResourceLogs>> {
+        LogExporterBuilder {
+            client: InMemoryExporterBuilderSet(InMemoryExporterBuilder::new(exported)),
+            endpoint: self.endpoint,
+        }
+    }
+
+    /// Export over the first of the `preference` transports whose feature is enabled and which
+    /// builds, e.g. falling back to HTTP where the endpoint is rejected by the gRPC transport.
+    ///
+    /// The transport is chosen when the exporter is built. The gRPC transport connects lazily,
+    /// so a collector that can't be reached over gRPC doesn't make the exporter fall back.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    pub fn with_transport_preference(
+        self,
+        preference: impl IntoIterator<Item = Protocol>,
+    ) -> LogExporterBuilder<PreferredExporterBuilderSet> {
+        LogExporterBuilder {
+            client: PreferredExporterBuilderSet::new(preference),
+            endpoint: self.endpoint,
+        }
+    }
 }
 
 #[cfg(feature = "grpc-tonic")]
 impl LogExporterBuilder<TonicExporterBuilderSet> {
+    /// Build the log exporter, sending the logs to the collector over gRPC.
     pub fn build(self) -> Result<LogExporter, opentelemetry_sdk::logs::LogError> {
-        self.client.0.build_log_exporter()
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_log_exporter()?,
+            &config,
+        ))
     }
 }
 
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 impl LogExporterBuilder<HttpExporterBuilderSet> {
+    /// Build the log exporter, sending the logs to the collector over HTTP.
     pub fn build(self) -> Result<LogExporter, opentelemetry_sdk::logs::LogError> {
-        self.client.0.build_log_exporter()
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_log_exporter()?,
+            &config,
+        ))
     }
 }
 
+#[cfg(feature = "tcp")]
+impl LogExporterBuilder<TcpExporterBuilderSet> {
+    /// Build the log exporter, streaming the logs to the collector over a TCP connection.
+    pub fn build(self) -> Result<LogExporter, opentelemetry_sdk::logs::LogError> {
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_log_exporter()?,
+            &config,
+        ))
+    }
+}
+
+#[cfg(feature = "testing")]
+impl LogExporterBuilder<InMemoryExporterBuilderSet<ResourceLogs>> {
+    /// Build the log exporter, appending the logs to the vector given to `with_in_memory`
+    /// instead of sending them.
+    pub fn build(self) -> Result<LogExporter, opentelemetry_sdk::logs::LogError> {
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_log_exporter()?,
+            &config,
+        ))
+    }
+}
+
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+impl LogExporterBuilder<PreferredExporterBuilderSet> {
+    /// Build the log exporter with the first transport of the preference which is enabled
+    /// and builds successfully.
+    pub fn build(self) -> Result<LogExporter, opentelemetry_sdk::logs::LogError> {
+        let PreferredExporterBuilderSet {
+            preference,
+            exporter_config,
+        } = self.client;
+        let endpoint = self.endpoint;
+        Ok(build_with_preference(
+            &preference,
+            exporter_config,
+            |exporter_config| match exporter_config.protocol {
+                #[cfg(feature = "grpc-tonic")]
+                Protocol::Grpc => LogExporterBuilder {
+                    client: TonicExporterBuilderSet(TonicExporterBuilder {
+                        exporter_config,
+                        ..Default::default()
+                    }),
+                    endpoint: endpoint.clone(),
+                }
+                .build(),
+                #[cfg(any(feature = "http-proto", feature = "http-json"))]
+                Protocol::HttpBinary | Protocol::HttpJson => LogExporterBuilder {
+                    client: HttpExporterBuilderSet(HttpExporterBuilder {
+                        exporter_config,
+                        ..Default::default()
+                    }),
+                    endpoint: endpoint.clone(),
+                }
+                .build(),
+                #[allow(unreachable_patterns)]
+                _ => unreachable!("only the transports of enabled features are built"),
+            },
+        )?)
+    }
+}
+
+/// Apply the options of `config` which wrap the exporter of any transport.
+fn with_export_options(log_exporter: LogExporter, config: &crate::ExportConfig) -> LogExporter {
+    #[cfg(feature = "metrics")]
+    let log_exporter = log_exporter.with_self_metrics(config.self_metrics);
+    #[cfg(feature = "testing")]
+    let log_exporter = log_exporter.with_inspector(config.conversion.log_inspector.clone());
+    log_exporter
+        .with_fallback(config.fallback_to_logging)
+        .with_stats(config.stats.clone())
+        .with_panic_policy(config.panic_policy)
+}
+
 #[cfg(feature = "grpc-tonic")]
 impl HasExportConfig for LogExporterBuilder<TonicExporterBuilderSet> {
     fn export_config(&mut self) -> &mut crate::ExportConfig {
@@ -81,6 +228,13 @@ impl HasExportConfig for LogExporterBuilder<TonicExporterBuilderSet> {
     }
 }
 
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+impl HasExportConfig for LogExporterBuilder<PreferredExporterBuilderSet> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.exporter_config
+    }
+}
+
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 impl HasExportConfig for LogExporterBuilder<HttpExporterBuilderSet> {
     fn export_config(&mut self) -> &mut crate::ExportConfig {
@@ -88,6 +242,20 @@ impl HasExportConfig for LogExporterBuilder<HttpExporterBuilderSet> {
     }
 }
 
+#[cfg(feature = "tcp")]
+impl HasExportConfig for LogExporterBuilder<TcpExporterBuilderSet> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.0.exporter_config
+    }
+}
+
+#[cfg(feature = "testing")]
+impl HasExportConfig for LogExporterBuilder<InMemoryExporterBuilderSet<ResourceLogs>> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.0.exporter_config
+    }
+}
+
 #[cfg(feature = "grpc-tonic")]
 impl HasTonicConfig for LogExporterBuilder<TonicExporterBuilderSet> {
     fn tonic_config(&mut self) -> &mut crate::TonicConfig {
@@ -102,10 +270,54 @@ impl HasHttpConfig for LogExporterBuilder<HttpExporterBuilderSet> {
     }
 }
 
+impl<C> LogExporterBuilder<C>
+where
+    LogExporterBuilder<C>: HasExportConfig,
+{
+    /// Truncate string bodies of log records longer than `max_bytes` bytes, e.g. stack traces
+    /// or dumped payloads, so backends limiting the size of records don't reject the whole
+    /// batch.
+    ///
+    /// Truncated bodies end with `...[truncated]` and are at most `max_bytes` long, including
+    /// the marker, unless `max_bytes` is shorter than the marker. Bodies of other types are exported as they are. By default bodies aren't
+    /// truncated.
+    pub fn with_max_log_body_bytes(mut self, max_bytes: usize) -> Self {
+        self.export_config().conversion.max_log_body_bytes = Some(max_bytes);
+        self
+    }
+}
+
+#[cfg(feature = "testing")]
+impl<C> LogExporterBuilder<C>
+where
+    LogExporterBuilder<C>: HasExportConfig,
+{
+    /// Call `inspector` with every `ResourceLogs` entry of an export once it succeeded, as
+    /// converted with all conversion options applied, to assert on the exported structure in
+    /// tests without decoding the requests. The log records of failed exports aren't inspected.
+    pub fn with_inspector<F>(mut self, inspector: F) -> Self
+    where
+        F: Fn(&opentelemetry_proto::tonic::logs::v1::ResourceLogs) + Send + Sync + 'static,
+    {
+        self.export_config().conversion.log_inspector = Some(Inspector::new(inspector));
+        self
+    }
+}
+
 /// OTLP exporter that sends log data
 #[derive(Debug)]
 pub struct LogExporter {
     client: Box<dyn opentelemetry_sdk::export::logs::LogExporter>,
+    latency: LatencyTracker,
+    #[cfg(feature = "metrics")]
+    self_metrics: Option<SelfMetrics>,
+    #[cfg(feature = "grpc-tonic")]
+    health: Option<HealthClient>,
+    fallback: Option<Fallback>,
+    stats: StatsRecorder,
+    panic_guard: PanicGuard,
+    #[cfg(feature = "testing")]
+    inspector: Option<Inspector<ResourceLogs>>,
 }
 
 impl LogExporter {
@@ -118,14 +330,119 @@ impl LogExporter {
     pub fn new(client: impl opentelemetry_sdk::export::logs::LogExporter + 'static) -> Self {
         LogExporter {
             client: Box::new(client),
+            latency: LatencyTracker::default(),
+            #[cfg(feature = "metrics")]
+            self_metrics: None,
+            #[cfg(feature = "grpc-tonic")]
+            health: None,
+            fallback: None,
+            stats: StatsRecorder::default(),
+            panic_guard: PanicGuard::new(ExportPanicPolicy::default(), "logs"),
+            #[cfg(feature = "testing")]
+            inspector: None,
         }
     }
+
+    fn with_fallback(mut self, enabled: bool) -> Self {
+        self.fallback = Fallback::stderr(enabled);
+        self
+    }
+
+    fn with_stats(mut self, stats: StatsRecorder) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn with_panic_policy(mut self, policy: ExportPanicPolicy) -> Self {
+        self.panic_guard = PanicGuard::new(policy, "logs");
+        self
+    }
+
+    #[cfg(feature = "testing")]
+    fn with_inspector(mut self, inspector: Option<Inspector<ResourceLogs>>) -> Self {
+        self.inspector = inspector;
+        self
+    }
+
+    #[cfg(feature = "metrics")]
+    fn with_self_metrics(mut self, enabled: bool) -> Self {
+        self.self_metrics = enabled.then(|| SelfMetrics::global("logs"));
+        self
+    }
+
+    #[cfg(feature = "grpc-tonic")]
+    pub(crate) fn with_health(mut self, health: HealthClient) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Query the collector with the gRPC health checking protocol, e.g. to verify it is serving
+    /// before exporting.
+    ///
+    /// Returns [`HealthStatus::Unsupported`] if the collector doesn't expose the health service
+    /// or the exporter doesn't use gRPC.
+    #[cfg(feature = "grpc-tonic")]
+    pub async fn check_health(&self) -> Result<HealthStatus, crate::Error> {
+        match &self.health {
+            Some(health) => health.check().await,
+            None => Ok(HealthStatus::Unsupported),
+        }
+    }
+
+    /// Percentiles of the latency of the most recent exports, successful or not, or `None`
+    /// before the first export.
+    ///
+    /// The percentiles are computed over the last 1024 exports and are approximate.
+    pub fn latency_percentiles(&self) -> Option<LatencyPercentiles> {
+        self.latency.percentiles()
+    }
+
+    /// Counters of the exports since the exporter was built, e.g. to compute the failure rate
+    /// without scraping the logs.
+    #[cfg(feature = "exporter-stats")]
+    pub fn exporter_stats(&self) -> ExporterStats {
+        self.stats.snapshot()
+    }
 }
 
 #[async_trait]
 impl opentelemetry_sdk::export::logs::LogExporter for LogExporter {
     async fn export(&self, batch: LogBatch<'_>) -> LogResult<()> {
-        self.client.export(batch).await
+        let items = batch.iter().count();
+        // the log records are only formatted if they are logged when the export fails
+        let lines = self
+            .fallback
+            .as_ref()
+            .filter(|fallback| fallback.is_armed())
+            .map(|_| fallback::log_lines(&batch));
+        let start = std::time::Instant::now();
+        let result = match self.panic_guard.call(|| self.client.export(batch)) {
+            Ok(export) => self.panic_guard.poll(export).await,
+            Err(message) => Err(message),
+        };
+        let result = result.unwrap_or_else(|message| {
+            self.stats.record_export_panic();
+            #[cfg(feature = "metrics")]
+            if let Some(self_metrics) = &self.self_metrics {
+                self_metrics.record_export_panic();
+            }
+            Err(LogError::Other(message.into()))
+        });
+        let elapsed = start.elapsed();
+        self.latency.record(elapsed);
+        self.stats.record_export(items, elapsed, result.is_ok());
+        if let Some(fallback) = &self.fallback {
+            fallback.record(result.is_ok(), || lines.unwrap_or_default());
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(self_metrics) = &self.self_metrics {
+            self_metrics.record(items, elapsed, result.is_ok());
+        }
+        #[cfg(feature = "testing")]
+        if let Some(inspector) = &self.inspector {
+            inspector.take().finish(result.is_ok());
+        }
+        result
     }
 
     fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
@@ -3,24 +3,49 @@
 //! Defines a [MetricExporter] to send metric data to backend via OTLP protocol.
 //!
 
-#[cfg(any(feature = "http-proto", feature = "http-json", feature = "grpc-tonic"))]
+#[cfg(otlp_transport)]
 use crate::HasExportConfig;
 
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 use crate::{exporter::http::HttpExporterBuilder, HasHttpConfig, HttpExporterBuilderSet};
 
+#[cfg(feature = "tcp")]
+use crate::{TcpExporterBuilder, TcpExporterBuilderSet};
+
+#[cfg(feature = "testing")]
+use crate::exporter::conversion::Inspector;
+#[cfg(feature = "testing")]
+use crate::{InMemoryExporterBuilder, InMemoryExporterBuilderSet};
+
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+use crate::{exporter::build_with_preference, PreferredExporterBuilderSet, Protocol};
+
+#[cfg(feature = "grpc-tonic")]
+use crate::exporter::tonic::health::{HealthClient, HealthStatus};
 #[cfg(feature = "grpc-tonic")]
 use crate::{exporter::tonic::TonicExporterBuilder, HasTonicConfig, TonicExporterBuilderSet};
 
+use crate::exporter::conversion::{
+    MetricConflictPolicy, MetricMetadataOverride, MetricStartTimeStrategy,
+};
+use crate::fallback::{self, Fallback};
+use crate::latency::{LatencyPercentiles, LatencyTracker};
+use crate::self_metrics::SelfMetrics;
+use crate::stats::StatsRecorder;
+use crate::unwind::PanicGuard;
+use crate::ExportPanicPolicy;
+#[cfg(feature = "exporter-stats")]
+use crate::ExporterStats;
 use crate::NoExporterBuilderSet;
 
 use async_trait::async_trait;
 use core::fmt;
-use opentelemetry_sdk::metrics::MetricResult;
+use opentelemetry_sdk::metrics::{MetricError, MetricResult};
 
 use opentelemetry_sdk::metrics::{
     data::ResourceMetrics, exporter::PushMetricExporter, Temporality,
 };
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 
 /// Target to which the exporter is going to send metrics, defaults to https://localhost:4317/v1/metrics.
@@ -66,6 +91,47 @@ impl<C> MetricExporterBuilder<C> {
         }
     }
 
+    #[cfg(feature = "tcp")]
+    pub fn with_tcp(self) -> MetricExporterBuilder<TcpExporterBuilderSet> {
+        MetricExporterBuilder {
+            client: TcpExporterBuilderSet(TcpExporterBuilder::default()),
+            temporality: self.temporality,
+        }
+    }
+
+    /// Append the exported `ResourceMetrics` to `exported` instead of sending them to a collector, to
+    /// assert in tests on exactly what would have been sent.
+    #[cfg(feature = "testing")]
+    pub fn with_in_memory(
+        self,
+        exported: std::sync::Arc<
+            std::sync::Mutex<Vec<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics>>,
+        >,
+    ) -> MetricExporterBuilder<
+        InMemoryExporterBuilderSet<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics>,
+    > {
+        MetricExporterBuilder {
+            client: InMemoryExporterBuilderSet(InMemoryExporterBuilder::new(exported)),
+            temporality: self.temporality,
+        }
+    }
+
+    /// Export over the first of the `preference` transports whose feature is enabled and which
+    /// builds, e.g. falling back to HTTP where the endpoint is rejected by the gRPC transport.
+    ///
+    /// The transport is chosen when the exporter is built. The gRPC transport connects lazily,
+    /// so a collector that can't be reached over gRPC doesn't make the exporter fall back.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    pub fn with_transport_preference(
+        self,
+        preference: impl IntoIterator<Item = Protocol>,
+    ) -> MetricExporterBuilder<PreferredExporterBuilderSet> {
+        MetricExporterBuilder {
+            client: PreferredExporterBuilderSet::new(preference),
+            temporality: self.temporality,
+        }
+    }
+
     pub fn with_temporality(self, temporality: Temporality) -> MetricExporterBuilder<C> {
         MetricExporterBuilder {
             client: self.client,
@@ -76,20 +142,107 @@ impl<C> MetricExporterBuilder<C> {
 
 #[cfg(feature = "grpc-tonic")]
 impl MetricExporterBuilder<TonicExporterBuilderSet> {
+    /// Build the metrics exporter, sending the metrics to the collector over gRPC.
     pub fn build(self) -> MetricResult<MetricExporter> {
-        let exporter = self.client.0.build_metrics_exporter(self.temporality)?;
-        Ok(exporter)
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_metrics_exporter(self.temporality)?,
+            &config,
+        ))
     }
 }
 
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 impl MetricExporterBuilder<HttpExporterBuilderSet> {
+    /// Build the metrics exporter, sending the metrics to the collector over HTTP.
+    pub fn build(self) -> MetricResult<MetricExporter> {
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_metrics_exporter(self.temporality)?,
+            &config,
+        ))
+    }
+}
+
+#[cfg(feature = "tcp")]
+impl MetricExporterBuilder<TcpExporterBuilderSet> {
+    /// Build the metrics exporter, streaming the metrics to the collector over a TCP connection.
+    pub fn build(self) -> MetricResult<MetricExporter> {
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_metrics_exporter(self.temporality)?,
+            &config,
+        ))
+    }
+}
+
+#[cfg(feature = "testing")]
+impl
+    MetricExporterBuilder<
+        InMemoryExporterBuilderSet<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics>,
+    >
+{
+    /// Build the metrics exporter, appending the metrics to the vector given to `with_in_memory`
+    /// instead of sending them.
+    pub fn build(self) -> MetricResult<MetricExporter> {
+        let config = self.client.0.exporter_config.clone();
+        Ok(with_export_options(
+            self.client.0.build_metrics_exporter(self.temporality)?,
+            &config,
+        ))
+    }
+}
+
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+impl MetricExporterBuilder<PreferredExporterBuilderSet> {
+    /// Build the metrics exporter with the first transport of the preference which is enabled
+    /// and builds successfully.
     pub fn build(self) -> MetricResult<MetricExporter> {
-        let exporter = self.client.0.build_metrics_exporter(self.temporality)?;
-        Ok(exporter)
+        let PreferredExporterBuilderSet {
+            preference,
+            exporter_config,
+        } = self.client;
+        let temporality = self.temporality;
+        Ok(build_with_preference(
+            &preference,
+            exporter_config,
+            |exporter_config| match exporter_config.protocol {
+                #[cfg(feature = "grpc-tonic")]
+                Protocol::Grpc => MetricExporterBuilder {
+                    client: TonicExporterBuilderSet(TonicExporterBuilder {
+                        exporter_config,
+                        ..Default::default()
+                    }),
+                    temporality,
+                }
+                .build(),
+                #[cfg(any(feature = "http-proto", feature = "http-json"))]
+                Protocol::HttpBinary | Protocol::HttpJson => MetricExporterBuilder {
+                    client: HttpExporterBuilderSet(HttpExporterBuilder {
+                        exporter_config,
+                        ..Default::default()
+                    }),
+                    temporality,
+                }
+                .build(),
+                #[allow(unreachable_patterns)]
+                _ => unreachable!("only the transports of enabled features are built"),
+            },
+        )?)
     }
 }
 
+/// Apply the options of `config` which wrap the exporter of any transport.
+fn with_export_options(exporter: MetricExporter, config: &crate::ExportConfig) -> MetricExporter {
+    #[cfg(feature = "testing")]
+    let exporter = exporter.with_inspector(config.conversion.metric_inspector.clone());
+    exporter
+        .with_self_metrics(config.self_metrics)
+        .with_fallback(config.fallback_to_logging)
+        .with_stats(config.stats.clone())
+        .with_panic_policy(config.panic_policy)
+}
+
 #[cfg(feature = "grpc-tonic")]
 impl HasExportConfig for MetricExporterBuilder<TonicExporterBuilderSet> {
     fn export_config(&mut self) -> &mut crate::ExportConfig {
@@ -97,6 +250,13 @@ impl HasExportConfig for MetricExporterBuilder<TonicExporterBuilderSet> {
     }
 }
 
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+impl HasExportConfig for MetricExporterBuilder<PreferredExporterBuilderSet> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.exporter_config
+    }
+}
+
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 impl HasExportConfig for MetricExporterBuilder<HttpExporterBuilderSet> {
     fn export_config(&mut self) -> &mut crate::ExportConfig {
@@ -104,6 +264,24 @@ impl HasExportConfig for MetricExporterBuilder<HttpExporterBuilderSet> {
     }
 }
 
+#[cfg(feature = "tcp")]
+impl HasExportConfig for MetricExporterBuilder<TcpExporterBuilderSet> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.0.exporter_config
+    }
+}
+
+#[cfg(feature = "testing")]
+impl HasExportConfig
+    for MetricExporterBuilder<
+        InMemoryExporterBuilderSet<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics>,
+    >
+{
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.0.exporter_config
+    }
+}
+
 #[cfg(feature = "grpc-tonic")]
 impl HasTonicConfig for MetricExporterBuilder<TonicExporterBuilderSet> {
     fn tonic_config(&mut self) -> &mut crate::TonicConfig {
@@ -118,6 +296,122 @@ impl HasHttpConfig for MetricExporterBuilder<HttpExporterBuilderSet> {
     }
 }
 
+impl<C> MetricExporterBuilder<C>
+where
+    MetricExporterBuilder<C>: HasExportConfig,
+{
+    /// Set the description and unit of metrics, keyed by metric name, that lack them.
+    ///
+    /// The `(description, unit)` pairs are applied when converting the metrics into OTLP
+    /// messages. Only empty descriptions and units are filled in, use
+    /// [`with_forced_metric_metadata_overrides`](Self::with_forced_metric_metadata_overrides) to
+    /// replace existing values. Empty override values are ignored.
+    pub fn with_metric_metadata_overrides(
+        self,
+        overrides: HashMap<String, (String, String)>,
+    ) -> Self {
+        self.insert_metric_metadata_overrides(overrides, false)
+    }
+
+    /// Set the description and unit of metrics, keyed by metric name, replacing existing
+    /// non-empty values.
+    ///
+    /// See [`with_metric_metadata_overrides`](Self::with_metric_metadata_overrides).
+    pub fn with_forced_metric_metadata_overrides(
+        self,
+        overrides: HashMap<String, (String, String)>,
+    ) -> Self {
+        self.insert_metric_metadata_overrides(overrides, true)
+    }
+
+    /// Keep the metrics of exports which failed with a retryable error and merge them into the
+    /// next export, instead of losing the intervals collected while the collector is unavailable.
+    ///
+    /// Delta sums and histograms are added up, while cumulative data and gauges keep the latest
+    /// value. Disabled by default.
+    pub fn with_metric_accumulation_on_failure(mut self, enabled: bool) -> Self {
+        self.export_config()
+            .conversion
+            .metric_accumulation_on_failure = enabled;
+        self
+    }
+
+    /// Set the convention the start times of cumulative sums and histograms follow, as backends
+    /// computing rates expect different ones. Defaults to [`MetricStartTimeStrategy::Sdk`],
+    /// keeping the start times of the SDK.
+    ///
+    /// The start times of the streams are kept by the exporter, so they only stay consistent
+    /// across collections exported by the same exporter.
+    pub fn with_metrics_start_time_strategy(mut self, strategy: MetricStartTimeStrategy) -> Self {
+        self.export_config().conversion.metric_start_time_strategy = strategy;
+        self
+    }
+
+    /// Set how metrics of the same resource sharing a name but not a type are handled.
+    /// Conflicts are always logged. Defaults to [`MetricConflictPolicy::Keep`].
+    pub fn with_metric_conflict_policy(mut self, policy: MetricConflictPolicy) -> Self {
+        self.export_config().conversion.metric_conflict_policy = policy;
+        self
+    }
+
+    /// Leave out the data points of sums and gauges with a value of zero, for backends rejecting
+    /// them or treating them as noise. Metrics left without data points are not exported at all.
+    /// Disabled by default.
+    ///
+    /// Dashboards and alerts then see gaps instead of zeros, e.g. a counter which stopped
+    /// increasing can't be told from one whose exports failed, and rates over the gaps may be
+    /// computed from older values.
+    pub fn with_suppress_zero_datapoints(mut self, enabled: bool) -> Self {
+        self.export_config().conversion.suppress_zero_data_points = enabled;
+        self
+    }
+
+    /// Prepend `prefix` to the name of every exported metric, e.g. to namespace the metrics of a
+    /// tenant of a shared backend without renaming the instruments of the SDK.
+    ///
+    /// Names already starting with `prefix` are left as they are, so metrics re-exported from
+    /// another prefixing pipeline aren't prefixed twice. Metadata overrides and conflict
+    /// policies still match the names of the SDK.
+    pub fn with_metric_name_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.export_config().conversion.metric_name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Call `inspector` with every `ResourceMetrics` entry of an export once it succeeded, as
+    /// converted with all conversion options applied, to assert on the exported structure in
+    /// tests without decoding the requests. The metrics of failed exports aren't inspected, also
+    /// not once they are merged into a later export.
+    #[cfg(feature = "testing")]
+    pub fn with_inspector<F>(mut self, inspector: F) -> Self
+    where
+        F: Fn(&opentelemetry_proto::tonic::metrics::v1::ResourceMetrics) + Send + Sync + 'static,
+    {
+        self.export_config().conversion.metric_inspector = Some(Inspector::new(inspector));
+        self
+    }
+
+    fn insert_metric_metadata_overrides(
+        mut self,
+        overrides: HashMap<String, (String, String)>,
+        force: bool,
+    ) -> Self {
+        self.export_config()
+            .conversion
+            .metric_metadata_overrides
+            .extend(overrides.into_iter().map(|(name, (description, unit))| {
+                (
+                    name,
+                    MetricMetadataOverride {
+                        description,
+                        unit,
+                        force,
+                    },
+                )
+            }));
+        self
+    }
+}
+
 /// An interface for OTLP metrics clients
 #[async_trait]
 pub trait MetricsClient: fmt::Debug + Send + Sync + 'static {
@@ -129,6 +423,15 @@ pub trait MetricsClient: fmt::Debug + Send + Sync + 'static {
 pub struct MetricExporter {
     client: Box<dyn MetricsClient>,
     temporality: Temporality,
+    latency: LatencyTracker,
+    self_metrics: Option<SelfMetrics>,
+    #[cfg(feature = "grpc-tonic")]
+    health: Option<HealthClient>,
+    fallback: Option<Fallback>,
+    stats: StatsRecorder,
+    panic_guard: PanicGuard,
+    #[cfg(feature = "testing")]
+    inspector: Option<Inspector<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics>>,
 }
 
 impl Debug for MetricExporter {
@@ -140,7 +443,37 @@ impl Debug for MetricExporter {
 #[async_trait]
 impl PushMetricExporter for MetricExporter {
     async fn export(&self, metrics: &mut ResourceMetrics) -> MetricResult<()> {
-        self.client.export(metrics).await
+        let items = metrics
+            .scope_metrics
+            .iter()
+            .map(|scope_metrics| scope_metrics.metrics.len())
+            .sum();
+        let start = std::time::Instant::now();
+        let result = match self.panic_guard.call(|| self.client.export(metrics)) {
+            Ok(export) => self.panic_guard.poll(export).await,
+            Err(message) => Err(message),
+        };
+        let result = result.unwrap_or_else(|message| {
+            self.stats.record_export_panic();
+            if let Some(self_metrics) = &self.self_metrics {
+                self_metrics.record_export_panic();
+            }
+            Err(MetricError::Other(message))
+        });
+        let elapsed = start.elapsed();
+        self.latency.record(elapsed);
+        self.stats.record_export(items, elapsed, result.is_ok());
+        if let Some(fallback) = &self.fallback {
+            fallback.record(result.is_ok(), || fallback::metric_lines(metrics));
+        }
+        if let Some(self_metrics) = &self.self_metrics {
+            self_metrics.record(items, elapsed, result.is_ok());
+        }
+        #[cfg(feature = "testing")]
+        if let Some(inspector) = &self.inspector {
+            inspector.take().finish(result.is_ok());
+        }
+        result
     }
 
     async fn force_flush(&self) -> MetricResult<()> {
@@ -168,6 +501,78 @@ impl MetricExporter {
         MetricExporter {
             client: Box::new(client),
             temporality,
+            latency: LatencyTracker::default(),
+            self_metrics: None,
+            #[cfg(feature = "grpc-tonic")]
+            health: None,
+            fallback: None,
+            stats: StatsRecorder::default(),
+            panic_guard: PanicGuard::new(ExportPanicPolicy::default(), "metrics"),
+            #[cfg(feature = "testing")]
+            inspector: None,
         }
     }
+
+    fn with_fallback(mut self, enabled: bool) -> Self {
+        self.fallback = Fallback::stderr(enabled);
+        self
+    }
+
+    fn with_stats(mut self, stats: StatsRecorder) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn with_panic_policy(mut self, policy: ExportPanicPolicy) -> Self {
+        self.panic_guard = PanicGuard::new(policy, "metrics");
+        self
+    }
+
+    #[cfg(feature = "testing")]
+    fn with_inspector(
+        mut self,
+        inspector: Option<Inspector<opentelemetry_proto::tonic::metrics::v1::ResourceMetrics>>,
+    ) -> Self {
+        self.inspector = inspector;
+        self
+    }
+
+    fn with_self_metrics(mut self, enabled: bool) -> Self {
+        self.self_metrics = enabled.then(|| SelfMetrics::global("metrics"));
+        self
+    }
+
+    #[cfg(feature = "grpc-tonic")]
+    pub(crate) fn with_health(mut self, health: HealthClient) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Query the collector with the gRPC health checking protocol, e.g. to verify it is serving
+    /// before exporting.
+    ///
+    /// Returns [`HealthStatus::Unsupported`] if the collector doesn't expose the health service
+    /// or the exporter doesn't use gRPC.
+    #[cfg(feature = "grpc-tonic")]
+    pub async fn check_health(&self) -> Result<HealthStatus, crate::Error> {
+        match &self.health {
+            Some(health) => health.check().await,
+            None => Ok(HealthStatus::Unsupported),
+        }
+    }
+
+    /// Percentiles of the latency of the most recent exports, successful or not, or `None`
+    /// before the first export.
+    ///
+    /// The percentiles are computed over the last 1024 exports and are approximate.
+    pub fn latency_percentiles(&self) -> Option<LatencyPercentiles> {
+        self.latency.percentiles()
+    }
+
+    /// Counters of the exports since the exporter was built, e.g. to compute the failure rate
+    /// without scraping the logs.
+    #[cfg(feature = "exporter-stats")]
+    pub fn exporter_stats(&self) -> ExporterStats {
+        self.stats.snapshot()
+    }
 }
@@ -0,0 +1,224 @@
+//! Metrics describing the exporters themselves.
+//!
+//! When enabled via [`WithExportConfig::with_self_metrics`](crate::WithExportConfig::with_self_metrics)
+//! every export records the following instruments on the global meter provider, so they are
+//! exported through the same pipeline as the application metrics:
+//!
+//! * `otel_sdk_exporter_exported_items` - items the collector accepted.
+//! * `otel_sdk_exporter_dropped_items` - items dropped because the export failed.
+//! * `otel_sdk_exporter_export_duration` - duration of each export call, in seconds.
+//! * `otel_sdk_exporter_serialization_failures` - items skipped because they failed to
+//!   serialize, while the rest of their batch was exported.
+//! * `otel_sdk_exporter_export_panics` - batches dropped because exporting them panicked.
+//! * `otel_sdk_exporter_queue_size` - span exports enqueued or being sent, with a
+//!   [submission mode](crate::SubmissionMode) enqueuing them.
+//! * `otel_sdk_exporter_queue_highwater_crossings` - times the queue grew above the high-water
+//!   threshold set with `SpanExporterBuilder::with_queue_highwater_threshold`.
+//!
+//! All instruments carry a `signal` attribute set to `traces`, `metrics` or `logs`.
+
+use std::time::Duration;
+
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::KeyValue;
+
+/// Name of the meter the self-metrics are recorded with.
+pub(crate) const SELF_METRICS_METER_NAME: &str = "opentelemetry-otlp";
+
+/// Instruments recording the outcome of the exports of a single signal.
+#[derive(Clone, Debug)]
+pub(crate) struct SelfMetrics {
+    exported_items: Counter<u64>,
+    dropped_items: Counter<u64>,
+    export_duration: Histogram<f64>,
+    #[allow(dead_code)]
+    // <allow dead> only recorded by the HTTP/JSON exporters.
+    serialization_failures: Counter<u64>,
+    export_panics: Counter<u64>,
+    #[allow(dead_code)]
+    // <allow dead> only recorded by the span submission queue.
+    queue_highwater_crossings: Counter<u64>,
+    attributes: [KeyValue; 1],
+    record_failures: bool,
+}
+
+impl SelfMetrics {
+    /// Create the instruments for `signal` using the global meter provider.
+    pub(crate) fn global(signal: &'static str) -> Self {
+        Self::new(
+            &opentelemetry::global::meter(SELF_METRICS_METER_NAME),
+            signal,
+        )
+    }
+
+    pub(crate) fn new(meter: &Meter, signal: &'static str) -> Self {
+        SelfMetrics {
+            exported_items: meter
+                .u64_counter("otel_sdk_exporter_exported_items")
+                .with_description("Number of items successfully exported by the OTLP exporter")
+                .build(),
+            dropped_items: meter
+                .u64_counter("otel_sdk_exporter_dropped_items")
+                .with_description("Number of items dropped because the OTLP export failed")
+                .build(),
+            export_duration: meter
+                .f64_histogram("otel_sdk_exporter_export_duration")
+                .with_description("Duration of the OTLP export calls")
+                .with_unit("s")
+                .build(),
+            serialization_failures: meter
+                .u64_counter("otel_sdk_exporter_serialization_failures")
+                .with_description("Number of items skipped because they failed to serialize")
+                .build(),
+            export_panics: meter
+                .u64_counter("otel_sdk_exporter_export_panics")
+                .with_description("Number of batches dropped because exporting them panicked")
+                .build(),
+            queue_highwater_crossings: meter
+                .u64_counter("otel_sdk_exporter_queue_highwater_crossings")
+                .with_description(
+                    "Number of times the export queue grew above its high-water threshold",
+                )
+                .build(),
+            attributes: [KeyValue::new("signal", signal)],
+            // Failed metric exports would otherwise produce more self-metrics for the next
+            // export, which in turn fails again.
+            record_failures: signal != "metrics",
+        }
+    }
+
+    /// Record the outcome of an export of `items` items which took `elapsed`.
+    pub(crate) fn record(&self, items: usize, elapsed: Duration, success: bool) {
+        if success {
+            self.exported_items.add(items as u64, &self.attributes);
+        } else if self.record_failures {
+            self.dropped_items.add(items as u64, &self.attributes);
+        } else {
+            return;
+        }
+        self.export_duration
+            .record(elapsed.as_secs_f64(), &self.attributes);
+    }
+
+    /// Record `items` items skipped because they failed to serialize.
+    #[allow(dead_code)]
+    // <allow dead> only recorded by the HTTP/JSON exporters.
+    pub(crate) fn record_serialization_failures(&self, items: usize) {
+        self.serialization_failures
+            .add(items as u64, &self.attributes);
+    }
+
+    /// Record a batch dropped because exporting it panicked.
+    pub(crate) fn record_export_panic(&self) {
+        self.export_panics.add(1, &self.attributes);
+    }
+
+    /// Record the export queue growing above its high-water threshold.
+    #[allow(dead_code)]
+    // <allow dead> only recorded by the span submission queue.
+    pub(crate) fn record_queue_highwater(&self) {
+        self.queue_highwater_crossings.add(1, &self.attributes);
+    }
+
+    /// Observe the size of the export queue with `size`, which returns `None` once the queue
+    /// is gone.
+    #[allow(dead_code)]
+    // <allow dead> only observed by the span submission queue.
+    pub(crate) fn observe_queue_size<F>(signal: &'static str, size: F)
+    where
+        F: Fn() -> Option<usize> + Send + Sync + 'static,
+    {
+        let attributes = [KeyValue::new("signal", signal)];
+        opentelemetry::global::meter(SELF_METRICS_METER_NAME)
+            .u64_observable_gauge("otel_sdk_exporter_queue_size")
+            .with_description("Number of exports enqueued or being sent by the OTLP exporter")
+            .with_callback(move |observer| {
+                if let Some(size) = size() {
+                    observer.observe(size as u64, &attributes);
+                }
+            })
+            .build();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry_sdk::metrics::data::{Histogram, Sum};
+    use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+    use opentelemetry_sdk::runtime;
+    use opentelemetry_sdk::testing::metrics::InMemoryMetricExporter;
+
+    use super::SelfMetrics;
+
+    fn collect(record: impl FnOnce(&SelfMetrics, &SelfMetrics)) -> Vec<(String, u64)> {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone(), runtime::Tokio).build())
+            .build();
+        let meter = provider.meter("test");
+
+        record(
+            &SelfMetrics::new(&meter, "traces"),
+            &SelfMetrics::new(&meter, "metrics"),
+        );
+        provider.force_flush().unwrap();
+
+        let mut result = Vec::new();
+        for resource_metrics in exporter.get_finished_metrics().unwrap() {
+            for scope_metrics in resource_metrics.scope_metrics {
+                for metric in scope_metrics.metrics {
+                    let data = metric.data.as_any();
+                    if let Some(sum) = data.downcast_ref::<Sum<u64>>() {
+                        for point in &sum.data_points {
+                            let signal = point.attributes[0].value.to_string();
+                            result.push((format!("{}/{signal}", metric.name), point.value));
+                        }
+                    } else if let Some(histogram) = data.downcast_ref::<Histogram<f64>>() {
+                        for point in &histogram.data_points {
+                            let signal = point.attributes[0].value.to_string();
+                            result.push((format!("{}/{signal}", metric.name), point.count));
+                        }
+                    }
+                }
+            }
+        }
+        result.sort();
+        result
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_self_metrics_are_produced() {
+        let metrics = collect(|traces, _| {
+            traces.record(3, Duration::from_millis(5), true);
+            traces.record(2, Duration::from_millis(5), false);
+        });
+
+        assert_eq!(
+            metrics,
+            vec![
+                ("otel_sdk_exporter_dropped_items/traces".to_string(), 2),
+                ("otel_sdk_exporter_export_duration/traces".to_string(), 2),
+                ("otel_sdk_exporter_exported_items/traces".to_string(), 3),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_failed_metric_exports_are_not_recorded() {
+        let metrics = collect(|_, metrics| {
+            metrics.record(4, Duration::from_millis(5), true);
+            metrics.record(4, Duration::from_millis(5), false);
+        });
+
+        assert_eq!(
+            metrics,
+            vec![
+                ("otel_sdk_exporter_export_duration/metrics".to_string(), 1),
+                ("otel_sdk_exporter_exported_items/metrics".to_string(), 4),
+            ]
+        );
+    }
+}
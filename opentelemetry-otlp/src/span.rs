@@ -3,8 +3,11 @@
 //! Defines a [SpanExporter] to send trace data via the OpenTelemetry Protocol (OTLP)
 
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use futures_core::future::BoxFuture;
+use opentelemetry::otel_warn;
+use opentelemetry::trace::{TraceError, TraceId};
 use opentelemetry_sdk::export::trace::{ExportResult, SpanData};
 
 #[cfg(feature = "grpc-tonic")]
@@ -13,12 +16,38 @@ use crate::{
     TonicExporterBuilderSet,
 };
 
+#[cfg(feature = "tcp")]
+use crate::{TcpExporterBuilder, TcpExporterBuilderSet};
+
+#[cfg(feature = "testing")]
+use crate::{InMemoryExporterBuilder, InMemoryExporterBuilderSet};
+#[cfg(feature = "testing")]
+use opentelemetry_proto::tonic::trace::v1::ResourceSpans;
+
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+use crate::{exporter::build_with_preference, PreferredExporterBuilderSet, Protocol};
+
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 use crate::{
     exporter::http::{HasHttpConfig, HttpExporterBuilder},
     HttpExporterBuilderSet,
 };
 
+#[cfg(feature = "testing")]
+use crate::exporter::conversion::Inspector;
+use crate::exporter::conversion::{SpanDurationPolicy, TraceIdMapper};
+use crate::exporter::submission::{ExportQueue, SubmissionMode};
+#[cfg(feature = "grpc-tonic")]
+use crate::exporter::tonic::health::{HealthClient, HealthStatus};
+use crate::fallback::{self, Fallback};
+use crate::latency::{LatencyPercentiles, LatencyTracker};
+#[cfg(feature = "metrics")]
+use crate::self_metrics::SelfMetrics;
+use crate::stats::StatsRecorder;
+use crate::unwind::PanicGuard;
+use crate::ExportPanicPolicy;
+#[cfg(feature = "exporter-stats")]
+use crate::ExporterStats;
 use crate::{exporter::HasExportConfig, NoExporterBuilderSet};
 
 /// Target to which the exporter is going to send spans, defaults to https://localhost:4317/v1/traces.
@@ -58,21 +87,115 @@ impl SpanExporterBuilder<NoExporterBuilderSet> {
             client: HttpExporterBuilderSet(HttpExporterBuilder::default()),
         }
     }
+
+    #[cfg(feature = "tcp")]
+    pub fn with_tcp(self) -> SpanExporterBuilder<TcpExporterBuilderSet> {
+        SpanExporterBuilder {
+            client: TcpExporterBuilderSet(TcpExporterBuilder::default()),
+        }
+    }
+
+    /// Append the exported `ResourceSpans` to `exported` instead of sending them to a collector, to
+    /// assert in tests on exactly what would have been sent.
+    #[cfg(feature = "testing")]
+    pub fn with_in_memory(
+        self,
+        exported: Arc<std::sync::Mutex<Vec<ResourceSpans>>>,
+    ) -> SpanExporterBuilder<InMemoryExporterBuilderSet<ResourceSpans>> {
+        SpanExporterBuilder {
+            client: InMemoryExporterBuilderSet(InMemoryExporterBuilder::new(exported)),
+        }
+    }
+
+    /// Export over the first of the `preference` transports whose feature is enabled and which
+    /// builds, e.g. falling back to HTTP where the endpoint is rejected by the gRPC transport.
+    ///
+    /// The transport is chosen when the exporter is built. The gRPC transport connects lazily,
+    /// so a collector that can't be reached over gRPC doesn't make the exporter fall back.
+    #[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+    pub fn with_transport_preference(
+        self,
+        preference: impl IntoIterator<Item = Protocol>,
+    ) -> SpanExporterBuilder<PreferredExporterBuilderSet> {
+        SpanExporterBuilder {
+            client: PreferredExporterBuilderSet::new(preference),
+        }
+    }
 }
 
 #[cfg(feature = "grpc-tonic")]
 impl SpanExporterBuilder<TonicExporterBuilderSet> {
+    /// Build the span exporter, sending the spans to the collector over gRPC.
     pub fn build(self) -> Result<SpanExporter, opentelemetry::trace::TraceError> {
-        let span_exporter = self.client.0.build_span_exporter()?;
-        Ok(SpanExporter::new(span_exporter))
+        let config = self.client.0.exporter_config.clone();
+        with_export_options(self.client.0.build_span_exporter()?, &config)
     }
 }
 
 #[cfg(any(feature = "http-proto", feature = "http-json"))]
 impl SpanExporterBuilder<HttpExporterBuilderSet> {
+    /// Build the span exporter, sending the spans to the collector over HTTP.
+    pub fn build(self) -> Result<SpanExporter, opentelemetry::trace::TraceError> {
+        let config = self.client.0.exporter_config.clone();
+        with_export_options(
+            SpanExporter::new(self.client.0.build_span_exporter()?),
+            &config,
+        )
+    }
+}
+
+#[cfg(feature = "tcp")]
+impl SpanExporterBuilder<TcpExporterBuilderSet> {
+    /// Build the span exporter, streaming the spans to the collector over a TCP connection.
+    pub fn build(self) -> Result<SpanExporter, opentelemetry::trace::TraceError> {
+        let config = self.client.0.exporter_config.clone();
+        with_export_options(self.client.0.build_span_exporter()?, &config)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl SpanExporterBuilder<InMemoryExporterBuilderSet<ResourceSpans>> {
+    /// Build the span exporter, appending the spans to the vector given to `with_in_memory`
+    /// instead of sending them.
+    pub fn build(self) -> Result<SpanExporter, opentelemetry::trace::TraceError> {
+        let config = self.client.0.exporter_config.clone();
+        with_export_options(self.client.0.build_span_exporter()?, &config)
+    }
+}
+
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+impl SpanExporterBuilder<PreferredExporterBuilderSet> {
+    /// Build the span exporter with the first transport of the preference which is enabled
+    /// and builds successfully.
     pub fn build(self) -> Result<SpanExporter, opentelemetry::trace::TraceError> {
-        let span_exporter = self.client.0.build_span_exporter()?;
-        Ok(SpanExporter::new(span_exporter))
+        let PreferredExporterBuilderSet {
+            preference,
+            exporter_config,
+        } = self.client;
+        Ok(build_with_preference(
+            &preference,
+            exporter_config,
+            |exporter_config| match exporter_config.protocol {
+                #[cfg(feature = "grpc-tonic")]
+                Protocol::Grpc => SpanExporterBuilder {
+                    client: TonicExporterBuilderSet(TonicExporterBuilder {
+                        exporter_config,
+                        ..Default::default()
+                    }),
+                }
+                .build(),
+                #[cfg(any(feature = "http-proto", feature = "http-json"))]
+                Protocol::HttpBinary | Protocol::HttpJson => SpanExporterBuilder {
+                    client: HttpExporterBuilderSet(HttpExporterBuilder {
+                        exporter_config,
+                        ..Default::default()
+                    }),
+                }
+                .build(),
+                #[allow(unreachable_patterns)]
+                _ => unreachable!("only the transports of enabled features are built"),
+            },
+        )?)
     }
 }
 
@@ -90,6 +213,27 @@ impl HasExportConfig for SpanExporterBuilder<HttpExporterBuilderSet> {
     }
 }
 
+#[cfg(any(feature = "grpc-tonic", feature = "http-proto", feature = "http-json"))]
+impl HasExportConfig for SpanExporterBuilder<PreferredExporterBuilderSet> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.exporter_config
+    }
+}
+
+#[cfg(feature = "tcp")]
+impl HasExportConfig for SpanExporterBuilder<TcpExporterBuilderSet> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.0.exporter_config
+    }
+}
+
+#[cfg(feature = "testing")]
+impl HasExportConfig for SpanExporterBuilder<InMemoryExporterBuilderSet<ResourceSpans>> {
+    fn export_config(&mut self) -> &mut crate::ExportConfig {
+        &mut self.client.0.exporter_config
+    }
+}
+
 #[cfg(feature = "grpc-tonic")]
 impl HasTonicConfig for SpanExporterBuilder<TonicExporterBuilderSet> {
     fn tonic_config(&mut self) -> &mut crate::TonicConfig {
@@ -104,9 +248,199 @@ impl HasHttpConfig for SpanExporterBuilder<HttpExporterBuilderSet> {
     }
 }
 
+/// Apply the options of `config` which wrap the exporter of any transport.
+fn with_export_options(
+    span_exporter: SpanExporter,
+    config: &crate::ExportConfig,
+) -> Result<SpanExporter, opentelemetry::trace::TraceError> {
+    #[cfg(feature = "metrics")]
+    let span_exporter = span_exporter.with_self_metrics(config.self_metrics);
+    #[cfg(feature = "testing")]
+    let span_exporter = span_exporter.with_inspector(config.conversion.span_inspector.clone());
+    Ok(span_exporter
+        .with_fallback(config.fallback_to_logging)
+        .with_stats(config.stats.clone())
+        .with_panic_policy(config.panic_policy)
+        .with_submission(submission_queue(config)?))
+}
+
+/// Start the worker thread of the configured submission mode, if it enqueues the exports.
+/// Concurrent exports are rejected without a queue, which would silently ignore them.
+fn submission_queue(config: &crate::ExportConfig) -> Result<Option<ExportQueue>, crate::Error> {
+    Ok(match config.submission_mode {
+        SubmissionMode::Blocking if config.max_concurrent_exports > 1 => {
+            return Err(crate::Error::ConcurrentExportsWithoutQueue(
+                config.max_concurrent_exports,
+            ))
+        }
+        SubmissionMode::Blocking => None,
+        SubmissionMode::FireAndForget { capacity } => {
+            Some(ExportQueue::start(capacity, None, config)?)
+        }
+        SubmissionMode::BoundedWait { capacity, timeout } => {
+            Some(ExportQueue::start(capacity, Some(timeout), config)?)
+        }
+    })
+}
+
+impl<C> SpanExporterBuilder<C>
+where
+    SpanExporterBuilder<C>: HasExportConfig,
+{
+    /// Set the maximum number of spans sent in a single `ResourceSpans` entry.
+    ///
+    /// Resources holding more spans are split into multiple entries sharing the same resource.
+    /// By default the number of spans per resource is unbounded. Values below 1 are treated as 1.
+    pub fn with_max_spans_per_resource(mut self, max_spans: usize) -> Self {
+        self.export_config().conversion.max_spans_per_resource = Some(max_spans);
+        self
+    }
+
+    /// Set the maximum number of `ResourceSpans` entries sent in a single export request.
+    ///
+    /// Exports holding more entries are split into multiple requests, sent one after the other.
+    /// The export fails as soon as one of the requests fails. The limit applies on its own,
+    /// counting the entries of distinct resources as well as the entries a resource is split
+    /// into by [`with_max_spans_per_resource`](Self::with_max_spans_per_resource). By default
+    /// the number of entries per request is unbounded. Values below 1 are treated as 1.
+    pub fn with_max_resources_per_request(mut self, max_resources: usize) -> Self {
+        self.export_config().conversion.max_resources_per_request = Some(max_resources);
+        self
+    }
+
+    /// Set how spans whose end time is before their start time are handled. Defaults to
+    /// [`SpanDurationPolicy::Clamp`].
+    pub fn with_span_duration_policy(mut self, policy: SpanDurationPolicy) -> Self {
+        self.export_config().conversion.span_duration_policy = policy;
+        self
+    }
+
+    /// Annotate every span with a `span.depth` integer attribute, the number of its ancestors,
+    /// e.g. to let the backend filter runaway recursion. Disabled by default.
+    ///
+    /// The exporter only sees one batch at a time, so the depth counts the ancestors exported
+    /// in the same batch: a span whose parent isn't part of the batch, e.g. because it ended
+    /// later and is exported with the next batch, has a depth of zero. The attribute is subject
+    /// to the attribute options like any other span attribute.
+    pub fn with_annotate_span_depth(mut self, enabled: bool) -> Self {
+        self.export_config().conversion.annotate_span_depth = enabled;
+        self
+    }
+
+    /// Export spans without a status with an error status if their attribute `key` flags an
+    /// error, for instrumentation recording errors as attributes, e.g. `error=true`. Disabled by
+    /// default.
+    ///
+    /// The attribute is read as a boolean: a `true` boolean, or a string reading `true` in any
+    /// case, sets an error status without a message. Other values, including strings such as
+    /// `0` or `no`, are ignored. Spans with an `Ok` or `Error` status keep it. The attribute
+    /// itself is exported unchanged.
+    pub fn with_status_from_attribute<T: Into<String>>(mut self, key: T) -> Self {
+        self.export_config().conversion.status_from_attribute = Some(key.into());
+        self
+    }
+
+    /// Remap the trace ids of spans and span links before they are exported.
+    ///
+    /// Each trace id is passed to `mapper` once per export batch, so spans of the same trace
+    /// keep sharing a trace id within the batch even if `mapper` is not deterministic. Use a
+    /// deterministic `mapper` to keep traces intact across batches.
+    pub fn with_trace_id_mapper<F>(mut self, mapper: F) -> Self
+    where
+        F: Fn(TraceId) -> TraceId + Send + Sync + 'static,
+    {
+        self.export_config().conversion.trace_id_mapper = Some(TraceIdMapper(Arc::new(mapper)));
+        self
+    }
+
+    /// Set how exports are submitted to the collector. Defaults to
+    /// [`SubmissionMode::Blocking`], completing each export once the collector responded.
+    ///
+    /// The other modes enqueue the exports for a worker thread named
+    /// `<thread name prefix>-span-submission`. On shutdown, the exporter waits for up to the
+    /// [shutdown timeout](Self::with_shutdown_timeout) for the enqueued exports to be sent,
+    /// unless it is shut down on the thread of a current-thread runtime.
+    pub fn with_submission_mode(mut self, mode: SubmissionMode) -> Self {
+        self.export_config().submission_mode = mode;
+        self
+    }
+
+    /// Warn once more than `threshold` exports are enqueued or being sent, to alert before the
+    /// queue of the [submission mode](Self::with_submission_mode) fills up and drops exports.
+    ///
+    /// The warning is logged again only after the queue drained to `threshold` exports. With
+    /// [self-metrics](crate::WithExportConfig::with_self_metrics) enabled, every crossing is
+    /// counted as well. Has no effect with [`SubmissionMode::Blocking`] or a `threshold` at or
+    /// above the capacity of the queue.
+    pub fn with_queue_highwater_threshold(mut self, threshold: usize) -> Self {
+        self.export_config().queue_highwater_threshold = Some(threshold);
+        self
+    }
+
+    /// Send up to `max` of the exports enqueued by the
+    /// [submission mode](Self::with_submission_mode) at the same time, instead of one after the
+    /// other, to keep up with spikes a single collector connection can't absorb. Defaults to 1.
+    /// Values below 1 are treated as 1.
+    ///
+    /// Each export gets its own timeout and retries, as if sent alone. Every export in flight
+    /// holds its spans and encoded requests in memory until the collector responded, so higher
+    /// values raise the memory used while the collector is slow by up to `max` batches; use
+    /// [`with_max_inflight_bytes`](crate::WithExportConfig::with_max_inflight_bytes) to bound
+    /// it.
+    ///
+    /// Only the enqueuing submission modes send exports concurrently. With
+    /// [`SubmissionMode::Blocking`] the span processor decides how many exports run at the same
+    /// time, e.g. with the `max_concurrent_exports` of its batch config, so building the exporter
+    /// fails with [`Error::ConcurrentExportsWithoutQueue`](crate::Error::ConcurrentExportsWithoutQueue)
+    /// for a `max` above 1.
+    pub fn with_max_concurrent_exports(mut self, max: usize) -> Self {
+        self.export_config().max_concurrent_exports = max;
+        self
+    }
+
+    /// Set how long the exporter waits on shutdown for the exports enqueued by the
+    /// [submission mode](Self::with_submission_mode) to be sent. Defaults to
+    /// [`OTEL_EXPORTER_OTLP_SHUTDOWN_TIMEOUT_DEFAULT`](crate::OTEL_EXPORTER_OTLP_SHUTDOWN_TIMEOUT_DEFAULT)
+    /// seconds.
+    ///
+    /// Once the timeout elapsed, shutdown returns and warns about the exports still pending,
+    /// so an unreachable collector doesn't keep the process from exiting. Log and metric
+    /// exports are always sent before `export` returns, so their exporters don't wait on
+    /// shutdown.
+    pub fn with_shutdown_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.export_config().shutdown_timeout = timeout;
+        self
+    }
+
+    /// Call `inspector` with every `ResourceSpans` entry of an export once it succeeded, as
+    /// converted with all conversion options applied, to assert on the exported structure in
+    /// tests without decoding the requests. The spans of failed exports aren't inspected.
+    #[cfg(feature = "testing")]
+    pub fn with_inspector<F>(mut self, inspector: F) -> Self
+    where
+        F: Fn(&opentelemetry_proto::tonic::trace::v1::ResourceSpans) + Send + Sync + 'static,
+    {
+        self.export_config().conversion.span_inspector = Some(Inspector::new(inspector));
+        self
+    }
+}
+
 /// OTLP exporter that sends tracing information
 #[derive(Debug)]
-pub struct SpanExporter(Box<dyn opentelemetry_sdk::export::trace::SpanExporter>);
+pub struct SpanExporter {
+    client: Box<dyn opentelemetry_sdk::export::trace::SpanExporter>,
+    latency: Arc<LatencyTracker>,
+    #[cfg(feature = "metrics")]
+    self_metrics: Option<SelfMetrics>,
+    #[cfg(feature = "grpc-tonic")]
+    health: Option<HealthClient>,
+    submission: Option<ExportQueue>,
+    fallback: Option<Fallback>,
+    stats: StatsRecorder,
+    panic_guard: PanicGuard,
+    #[cfg(feature = "testing")]
+    inspector: Option<Inspector<ResourceSpans>>,
+}
 
 impl SpanExporter {
     /// Obtain a builder to configure a [SpanExporter].
@@ -116,16 +450,634 @@ impl SpanExporter {
 
     /// Build a new span exporter from a client
     pub fn new(client: impl opentelemetry_sdk::export::trace::SpanExporter + 'static) -> Self {
-        SpanExporter(Box::new(client))
+        SpanExporter {
+            client: Box::new(client),
+            latency: Arc::default(),
+            #[cfg(feature = "metrics")]
+            self_metrics: None,
+            #[cfg(feature = "grpc-tonic")]
+            health: None,
+            submission: None,
+            fallback: None,
+            stats: StatsRecorder::default(),
+            panic_guard: PanicGuard::new(ExportPanicPolicy::default(), "traces"),
+            #[cfg(feature = "testing")]
+            inspector: None,
+        }
+    }
+
+    fn with_submission(mut self, submission: Option<ExportQueue>) -> Self {
+        self.submission = submission;
+        self
+    }
+
+    fn with_fallback(mut self, enabled: bool) -> Self {
+        self.fallback = Fallback::stderr(enabled);
+        self
+    }
+
+    fn with_stats(mut self, stats: StatsRecorder) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub(crate) fn with_panic_policy(mut self, policy: ExportPanicPolicy) -> Self {
+        self.panic_guard = PanicGuard::new(policy, "traces");
+        self
+    }
+
+    #[cfg(feature = "testing")]
+    fn with_inspector(mut self, inspector: Option<Inspector<ResourceSpans>>) -> Self {
+        self.inspector = inspector;
+        self
+    }
+
+    #[cfg(feature = "metrics")]
+    fn with_self_metrics(mut self, enabled: bool) -> Self {
+        self.self_metrics = enabled.then(|| SelfMetrics::global("traces"));
+        self
+    }
+
+    #[cfg(feature = "grpc-tonic")]
+    pub(crate) fn with_health(mut self, health: HealthClient) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Query the collector with the gRPC health checking protocol, e.g. to verify it is serving
+    /// before exporting.
+    ///
+    /// Returns [`HealthStatus::Unsupported`] if the collector doesn't expose the health service
+    /// or the exporter doesn't use gRPC.
+    #[cfg(feature = "grpc-tonic")]
+    pub async fn check_health(&self) -> Result<HealthStatus, crate::Error> {
+        match &self.health {
+            Some(health) => health.check().await,
+            None => Ok(HealthStatus::Unsupported),
+        }
+    }
+
+    /// Percentiles of the latency of the most recent exports, successful or not, or `None`
+    /// before the first export.
+    ///
+    /// The percentiles are computed over the last 1024 exports and are approximate.
+    pub fn latency_percentiles(&self) -> Option<LatencyPercentiles> {
+        self.latency.percentiles()
+    }
+
+    /// Counters of the exports since the exporter was built, e.g. to compute the failure rate
+    /// without scraping the logs.
+    #[cfg(feature = "exporter-stats")]
+    pub fn exporter_stats(&self) -> ExporterStats {
+        self.stats.snapshot()
     }
 }
 
 impl opentelemetry_sdk::export::trace::SpanExporter for SpanExporter {
     fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
-        self.0.export(batch)
+        let items = batch.len();
+        #[cfg(feature = "metrics")]
+        let self_metrics = self.self_metrics.clone();
+        let latency = Arc::clone(&self.latency);
+        let stats = self.stats.clone();
+        // the spans are only kept if they are logged when the export fails
+        let fallback = self.fallback.clone().map(|fallback| {
+            let lines = fallback.is_armed().then(|| fallback::span_lines(&batch));
+            (fallback, lines)
+        });
+        let panic_guard = self.panic_guard;
+        let start = std::time::Instant::now();
+        let export = panic_guard.call(|| self.client.export(batch));
+        // the spans are converted when the export is called
+        #[cfg(feature = "testing")]
+        let inspection = self.inspector.as_ref().map(Inspector::take);
+        let export: BoxFuture<'static, ExportResult> = Box::pin(async move {
+            let result = match export {
+                Ok(export) => panic_guard.poll(export).await,
+                Err(message) => Err(message),
+            };
+            let result = result.unwrap_or_else(|message| {
+                stats.record_export_panic();
+                #[cfg(feature = "metrics")]
+                if let Some(self_metrics) = &self_metrics {
+                    self_metrics.record_export_panic();
+                }
+                Err(TraceError::Other(message.into()))
+            });
+            let elapsed = start.elapsed();
+            latency.record(elapsed);
+            stats.record_export(items, elapsed, result.is_ok());
+            if let Some((fallback, lines)) = fallback {
+                fallback.record(result.is_ok(), || lines.unwrap_or_default());
+            }
+            #[cfg(feature = "metrics")]
+            if let Some(self_metrics) = self_metrics {
+                self_metrics.record(items, elapsed, result.is_ok());
+            }
+            #[cfg(feature = "testing")]
+            if let Some(inspection) = inspection {
+                inspection.finish(result.is_ok());
+            }
+            result
+        });
+        match &self.submission {
+            Some(submission) => submission.submit(export),
+            None => export,
+        }
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(submission) = &self.submission {
+            if let Err(error) = submission.shutdown() {
+                otel_warn!(
+                    name: "SpanExporter.ShutdownTimedOut",
+                    error = error.to_string(),
+                );
+            }
+        }
     }
 
     fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
-        self.0.set_resource(resource);
+        self.client.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+    use std::time::Duration;
+
+    use futures_core::future::BoxFuture;
+    use futures_util::FutureExt;
+    use opentelemetry_sdk::export::trace::{ExportResult, SpanData};
+
+    use super::SpanExporter;
+    use crate::exporter::submission::ExportQueue;
+    use crate::ExportConfig;
+
+    /// A collector which only responds once released, one export per release.
+    #[derive(Clone, Debug, Default)]
+    struct SlowCollector {
+        gate: Arc<Mutex<(usize, Vec<Waker>)>>,
+        /// Number of exports polled at least once.
+        started: Arc<AtomicUsize>,
+        exported: Arc<AtomicUsize>,
+    }
+
+    impl SlowCollector {
+        fn release(&self) {
+            let mut gate = self.gate.lock().unwrap();
+            gate.0 += 1;
+            gate.1.drain(..).for_each(Waker::wake);
+        }
+    }
+
+    impl opentelemetry_sdk::export::trace::SpanExporter for SlowCollector {
+        fn export(&mut self, _batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            let collector = self.clone();
+            let mut started = false;
+            Box::pin(std::future::poll_fn(move |cx| {
+                if !started {
+                    started = true;
+                    collector.started.fetch_add(1, Ordering::SeqCst);
+                }
+                let mut gate = collector.gate.lock().unwrap();
+                if gate.0 == 0 {
+                    gate.1.push(cx.waker().clone());
+                    return Poll::Pending;
+                }
+                gate.0 -= 1;
+                collector.exported.fetch_add(1, Ordering::SeqCst);
+                Poll::Ready(Ok(()))
+            }))
+        }
+    }
+
+    fn exporter(
+        collector: &SlowCollector,
+        capacity: usize,
+        timeout: Option<Duration>,
+    ) -> SpanExporter {
+        SpanExporter::new(collector.clone())
+            .with_submission(ExportQueue::start(capacity, timeout, &ExportConfig::default()).ok())
+    }
+
+    #[test]
+    fn test_blocking_export_waits_for_collector() {
+        let collector = SlowCollector::default();
+        let mut exporter = SpanExporter::new(collector.clone());
+
+        let mut export =
+            opentelemetry_sdk::export::trace::SpanExporter::export(&mut exporter, vec![]);
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(export.poll_unpin(&mut cx).is_pending());
+
+        collector.release();
+        assert!(matches!(export.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+        assert_eq!(collector.exported.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_fire_and_forget_drops_exports_when_full() {
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        let collector = SlowCollector::default();
+        let mut exporter = exporter(&collector, 1, None);
+
+        assert!(matches!(
+            exporter.export(vec![]).now_or_never(),
+            Some(Ok(()))
+        ));
+        assert!(matches!(
+            exporter.export(vec![]).now_or_never(),
+            Some(Err(_))
+        ));
+
+        collector.release();
+        exporter.shutdown();
+        assert_eq!(collector.exported.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_bounded_wait_enqueues_once_collector_catches_up() {
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        let collector = SlowCollector::default();
+        let mut exporter = exporter(&collector, 1, Some(Duration::from_secs(5)));
+
+        assert!(matches!(
+            exporter.export(vec![]).now_or_never(),
+            Some(Ok(()))
+        ));
+        // waiting for room doesn't block the thread polling the export
+        let mut export = exporter.export(vec![]);
+        assert!(futures_util::poll!(&mut export).is_pending());
+
+        collector.release();
+        assert!(export.await.is_ok());
+        collector.release();
+        exporter.shutdown();
+        assert_eq!(collector.exported.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_highwater_warning_fires_once_per_crossing() {
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        let mut collector = SlowCollector::default();
+        let config = ExportConfig {
+            queue_highwater_threshold: Some(2),
+            ..ExportConfig::default()
+        };
+        let queue = ExportQueue::start(8, None, &config).unwrap();
+        let drain = |collector: &SlowCollector, exports| {
+            (0..exports).for_each(|_| collector.release());
+            let deadline = std::time::Instant::now() + Duration::from_secs(5);
+            while queue.pending() > 0 {
+                assert!(std::time::Instant::now() < deadline);
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        for _ in 0..2 {
+            queue
+                .submit(collector.export(vec![]))
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(queue.highwater_crossings(), 0);
+        // staying above the threshold doesn't warn again
+        for _ in 0..3 {
+            queue
+                .submit(collector.export(vec![]))
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(queue.highwater_crossings(), 1);
+
+        drain(&collector, 5);
+        for _ in 0..3 {
+            queue
+                .submit(collector.export(vec![]))
+                .now_or_never()
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(queue.highwater_crossings(), 2);
+
+        drain(&collector, 3);
+        queue.shutdown().unwrap();
+        assert_eq!(collector.exported.load(Ordering::SeqCst), 8);
+    }
+
+    #[test]
+    fn test_submission_worker_is_named_after_prefix() {
+        let config = ExportConfig {
+            thread_name_prefix: "my-service".to_string(),
+            ..ExportConfig::default()
+        };
+        let queue = ExportQueue::start(1, None, &config).unwrap();
+        let (sender, names) = std::sync::mpsc::channel();
+        queue
+            .submit(Box::pin(async move {
+                let name = std::thread::current().name().map(String::from);
+                sender.send(name).unwrap();
+                Ok(())
+            }))
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        let name = names.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(name.as_deref(), Some("my-service-span-submission"));
+        queue.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_enqueued_exports_are_sent_concurrently() {
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        let started = |max_concurrent_exports| {
+            let collector = SlowCollector::default();
+            let config = ExportConfig {
+                max_concurrent_exports,
+                ..ExportConfig::default()
+            };
+            let mut exporter = SpanExporter::new(collector.clone())
+                .with_submission(ExportQueue::start(8, None, &config).ok());
+            for _ in 0..3 {
+                assert!(matches!(
+                    exporter.export(vec![]).now_or_never(),
+                    Some(Ok(()))
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+            let started = collector.started.load(Ordering::SeqCst);
+
+            (0..3).for_each(|_| collector.release());
+            exporter.shutdown();
+            assert_eq!(collector.exported.load(Ordering::SeqCst), 3);
+            started
+        };
+
+        assert_eq!(started(1), 1);
+        assert_eq!(started(2), 2);
+    }
+
+    #[cfg(feature = "grpc-tonic")]
+    #[tokio::test]
+    async fn test_concurrent_exports_require_a_queue() {
+        let builder = || {
+            SpanExporter::builder()
+                .with_tonic()
+                .with_max_concurrent_exports(2)
+        };
+        assert!(matches!(
+            builder().build(),
+            Err(opentelemetry::trace::TraceError::ExportFailed(_))
+        ));
+        assert!(builder()
+            .with_submission_mode(crate::SubmissionMode::FireAndForget { capacity: 8 })
+            .build()
+            .is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_bounded_wait_drops_exports_after_timeout() {
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        let collector = SlowCollector::default();
+        let timeout = Duration::from_millis(50);
+        let mut exporter = exporter(&collector, 1, Some(timeout));
+
+        assert!(matches!(
+            exporter.export(vec![]).now_or_never(),
+            Some(Ok(()))
+        ));
+        let start = std::time::Instant::now();
+        assert!(exporter.export(vec![]).await.is_err());
+        assert!(start.elapsed() >= timeout);
+
+        collector.release();
+        exporter.shutdown();
+        assert_eq!(collector.exported.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_shutdown_is_bounded_by_timeout() {
+        use opentelemetry::trace::TraceError;
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        let mut collector = SlowCollector::default();
+        let timeout = Duration::from_millis(50);
+        let config = ExportConfig {
+            shutdown_timeout: timeout,
+            ..ExportConfig::default()
+        };
+        let queue = ExportQueue::start(1, None, &config).unwrap();
+        queue
+            .submit(collector.export(vec![]))
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        // the collector never responds
+        let start = std::time::Instant::now();
+        assert!(matches!(
+            queue.shutdown(),
+            Err(TraceError::ExportTimedOut(elapsed)) if elapsed == timeout
+        ));
+        assert!(start.elapsed() >= timeout);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(collector.exported.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_shutdown_without_representable_deadline() {
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+
+        let mut collector = SlowCollector::default();
+        let config = ExportConfig {
+            shutdown_timeout: Duration::MAX,
+            ..ExportConfig::default()
+        };
+        let queue = ExportQueue::start(1, Some(Duration::MAX), &config).unwrap();
+        queue
+            .submit(collector.export(vec![]))
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+
+        // waits for the export instead of overflowing the deadline
+        collector.release();
+        queue.shutdown().unwrap();
+        assert_eq!(collector.exported.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(all(feature = "testing", feature = "grpc-tonic"))]
+    #[tokio::test]
+    async fn test_inspector_receives_converted_spans() {
+        use opentelemetry::trace::{SpanContext, SpanKind, Status, TraceState};
+        use opentelemetry::KeyValue;
+        use opentelemetry_proto::tonic::common::v1::any_value::Value;
+        use opentelemetry_proto::tonic::trace::v1::ResourceSpans;
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+        use opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+
+        use crate::WithExportConfig;
+
+        let span = || SpanData {
+            span_context: SpanContext::new(
+                1.into(),
+                1.into(),
+                Default::default(),
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: 0.into(),
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Server,
+            name: "request".into(),
+            start_time: std::time::SystemTime::now(),
+            end_time: std::time::SystemTime::now(),
+            attributes: vec![KeyValue::new("http.status_code", 200)],
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status: Status::Unset,
+            instrumentation_scope: Default::default(),
+        };
+        let inspected = Arc::new(Mutex::new(Vec::new()));
+        let inspector = |inspected: &Arc<Mutex<Vec<_>>>| {
+            let sink = Arc::clone(inspected);
+            move |resource_spans: &ResourceSpans| {
+                sink.lock().unwrap().push(resource_spans.clone());
+            }
+        };
+
+        // nothing listens there, so the export fails and its spans aren't inspected
+        let mut failing = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint("http://127.0.0.1:1")
+            .with_timeout(Duration::from_secs(1))
+            .with_inspector(inspector(&inspected))
+            .build()
+            .unwrap();
+        assert!(failing.export(vec![span()]).await.is_err());
+        assert!(inspected.lock().unwrap().is_empty());
+
+        let mut exporter = SpanExporter::builder()
+            .with_in_memory(Arc::default())
+            .with_stringify_attributes(true)
+            .with_inspector(inspector(&inspected))
+            .build()
+            .unwrap();
+        exporter.export(vec![span()]).await.unwrap();
+
+        let inspected = inspected.lock().unwrap();
+        let span = &inspected[0].scope_spans[0].spans[0];
+        assert_eq!(span.name, "request");
+        assert_eq!(span.attributes[0].key, "http.status_code");
+        assert_eq!(
+            span.attributes[0]
+                .value
+                .as_ref()
+                .and_then(|v| v.value.clone()),
+            Some(Value::StringValue("200".into()))
+        );
+    }
+
+    #[cfg(feature = "grpc-tonic")]
+    #[tokio::test]
+    async fn test_fallback_to_logging_with_unreachable_collector() {
+        use opentelemetry::trace::{SpanContext, SpanKind, Status, TraceState};
+        use opentelemetry_sdk::export::trace::SpanExporter as _;
+        use opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+
+        use crate::fallback::{Fallback, FAILURES_BEFORE_FALLBACK};
+        use crate::WithExportConfig;
+
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&logged);
+        let mut exporter = SpanExporter::builder()
+            .with_tonic()
+            // nothing listens there
+            .with_endpoint("http://127.0.0.1:1")
+            .with_timeout(Duration::from_secs(1))
+            .with_fallback_to_logging(true)
+            .build()
+            .unwrap();
+        assert!(exporter.fallback.is_some());
+        exporter.fallback = Some(Fallback::new(move |line| {
+            sink.lock().unwrap().push(line.to_string())
+        }));
+
+        let now = std::time::SystemTime::now();
+        for _ in 0..FAILURES_BEFORE_FALLBACK {
+            let span = SpanData {
+                span_context: SpanContext::new(
+                    1.into(),
+                    2.into(),
+                    Default::default(),
+                    false,
+                    TraceState::default(),
+                ),
+                parent_span_id: 0.into(),
+                parent_span_is_remote: false,
+                span_kind: SpanKind::Server,
+                name: "request".into(),
+                start_time: now,
+                end_time: now + Duration::from_millis(5),
+                attributes: vec![opentelemetry::KeyValue::new("http.status_code", 503)],
+                dropped_attributes_count: 0,
+                events: SpanEvents::default(),
+                links: SpanLinks::default(),
+                status: Status::Unset,
+                instrumentation_scope: Default::default(),
+            };
+            assert!(exporter.export(vec![span]).await.is_err());
+        }
+
+        // only the spans of the export after enough failures in a row are logged
+        let logged = logged.lock().unwrap();
+        assert_eq!(logged.len(), 1);
+        assert_eq!(
+            logged[0],
+            "span \"request\" trace_id=00000000000000000000000000000001 \
+             span_id=0000000000000002 duration=5ms attributes=[http.status_code=503]"
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(all(feature = "grpc-tonic", feature = "http-proto"))]
+    async fn test_transport_preference_falls_back_to_http() {
+        use crate::{Protocol, WithExportConfig};
+
+        // the endpoint only serves HTTP, which fails building the gRPC exporter strictly validating it
+        let grpc = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint("http://localhost:4318")
+            .with_strict_endpoint_validation(true)
+            .build();
+        assert!(grpc.is_err());
+
+        let preferred = SpanExporter::builder()
+            .with_transport_preference([Protocol::Grpc, Protocol::HttpBinary])
+            .with_endpoint("http://localhost:4318")
+            .with_strict_endpoint_validation(true)
+            .build();
+        assert!(preferred.is_ok());
+
+        let grpc_only = SpanExporter::builder()
+            .with_transport_preference([Protocol::Grpc])
+            .with_endpoint("http://localhost:4318")
+            .with_strict_endpoint_validation(true)
+            .build();
+        assert!(grpc_only.is_err());
     }
 }
@@ -0,0 +1,159 @@
+//! Counters of the exports of an exporter, read in-process with `exporter_stats`.
+//!
+//! The counters are only kept with the `exporter-stats` feature. Without it, the recorder
+//! threaded through the exporters records nothing and costs nothing.
+
+#[cfg(not(feature = "exporter-stats"))]
+pub(crate) use self::disabled::StatsRecorder;
+#[cfg(feature = "exporter-stats")]
+pub use self::enabled::ExporterStats;
+#[cfg(feature = "exporter-stats")]
+pub(crate) use self::enabled::StatsRecorder;
+
+#[cfg(feature = "exporter-stats")]
+mod enabled {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Counters of the exports of an exporter since it was built.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    #[non_exhaustive]
+    pub struct ExporterStats {
+        /// Number of export calls, successful or not.
+        pub exports: u64,
+        /// Number of spans, metrics or log records of the successful exports.
+        pub exported_items: u64,
+        /// Number of export calls which failed.
+        pub failed_exports: u64,
+        /// Number of requests sent again after the collector rejected them with a retryable
+        /// status, with a gRPC retry policy.
+        pub retries: u64,
+        /// Number of batches dropped because exporting them panicked.
+        pub export_panics: u64,
+        /// Bytes of the export requests sent. HTTP counts the bodies as sent, after compression,
+        /// while gRPC and TCP count the encoded requests.
+        pub bytes_sent: u64,
+        /// Total duration of the export calls.
+        pub export_duration: Duration,
+    }
+
+    /// Records the exports of an exporter, shared by the exporter and its transport.
+    #[derive(Clone, Debug, Default)]
+    pub(crate) struct StatsRecorder(Arc<Counters>);
+
+    #[derive(Debug, Default)]
+    #[allow(dead_code)]
+    // <allow dead> only read by the exporters of the enabled signals.
+    struct Counters {
+        exports: AtomicU64,
+        exported_items: AtomicU64,
+        failed_exports: AtomicU64,
+        retries: AtomicU64,
+        export_panics: AtomicU64,
+        bytes_sent: AtomicU64,
+        export_duration_nanos: AtomicU64,
+    }
+
+    #[allow(dead_code)]
+    // <allow dead> only called by the exporters of enabled signals and transports.
+    impl StatsRecorder {
+        /// Record an export of `items` items which took `elapsed`.
+        pub(crate) fn record_export(&self, items: usize, elapsed: Duration, success: bool) {
+            let counters = &self.0;
+            counters.exports.fetch_add(1, Ordering::Relaxed);
+            if success {
+                counters
+                    .exported_items
+                    .fetch_add(items as u64, Ordering::Relaxed);
+            } else {
+                counters.failed_exports.fetch_add(1, Ordering::Relaxed);
+            }
+            let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+            counters
+                .export_duration_nanos
+                .fetch_add(nanos, Ordering::Relaxed);
+        }
+
+        /// Record a request sent again.
+        pub(crate) fn record_retry(&self) {
+            self.0.retries.fetch_add(1, Ordering::Relaxed);
+        }
+
+        /// Record a batch dropped because exporting it panicked.
+        pub(crate) fn record_export_panic(&self) {
+            self.0.export_panics.fetch_add(1, Ordering::Relaxed);
+        }
+
+        /// Record a request of `bytes` bytes sent.
+        pub(crate) fn record_bytes_sent(&self, bytes: usize) {
+            self.0.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+
+        pub(crate) fn snapshot(&self) -> ExporterStats {
+            let counters = &self.0;
+            ExporterStats {
+                exports: counters.exports.load(Ordering::Relaxed),
+                exported_items: counters.exported_items.load(Ordering::Relaxed),
+                failed_exports: counters.failed_exports.load(Ordering::Relaxed),
+                retries: counters.retries.load(Ordering::Relaxed),
+                export_panics: counters.export_panics.load(Ordering::Relaxed),
+                bytes_sent: counters.bytes_sent.load(Ordering::Relaxed),
+                export_duration: Duration::from_nanos(
+                    counters.export_duration_nanos.load(Ordering::Relaxed),
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "exporter-stats"))]
+mod disabled {
+    use std::time::Duration;
+
+    /// Records nothing, as the `exporter-stats` feature is disabled.
+    #[derive(Clone, Debug, Default)]
+    pub(crate) struct StatsRecorder(());
+
+    #[allow(dead_code)]
+    // <allow dead> only called by the exporters of enabled signals and transports.
+    impl StatsRecorder {
+        pub(crate) fn record_export(&self, _items: usize, _elapsed: Duration, _success: bool) {}
+
+        pub(crate) fn record_retry(&self) {}
+
+        pub(crate) fn record_export_panic(&self) {}
+
+        pub(crate) fn record_bytes_sent(&self, _bytes: usize) {}
+    }
+}
+
+#[cfg(all(test, feature = "exporter-stats"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ExporterStats, StatsRecorder};
+
+    #[test]
+    fn test_stats_count_exports() {
+        let stats = StatsRecorder::default();
+        stats.record_export(3, Duration::from_millis(5), true);
+        stats.record_bytes_sent(100);
+        stats.record_retry();
+        stats.record_export(2, Duration::from_millis(10), false);
+        stats.record_bytes_sent(80);
+
+        assert_eq!(
+            stats.clone().snapshot(),
+            ExporterStats {
+                exports: 2,
+                exported_items: 3,
+                failed_exports: 1,
+                retries: 1,
+                export_panics: 0,
+                bytes_sent: 180,
+                export_duration: Duration::from_millis(15),
+            }
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Keep the exporters alive when exporting a batch panics, so a bug converting a single
+//! attribute doesn't bring down the export task of the batch processor.
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::task::Poll;
+
+use opentelemetry::otel_error;
+
+use crate::ExportPanicPolicy;
+
+/// Guards the exports of a signal according to an [`ExportPanicPolicy`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PanicGuard {
+    policy: ExportPanicPolicy,
+    signal: &'static str,
+}
+
+impl PanicGuard {
+    pub(crate) fn new(policy: ExportPanicPolicy, signal: &'static str) -> Self {
+        PanicGuard { policy, signal }
+    }
+
+    /// Call `export`, returning the message of its panic instead if the batch is dropped.
+    pub(crate) fn call<T>(&self, export: impl FnOnce() -> T) -> Result<T, String> {
+        match self.policy {
+            ExportPanicPolicy::Propagate => Ok(export()),
+            ExportPanicPolicy::DropBatch => {
+                panic::catch_unwind(AssertUnwindSafe(export)).map_err(|panic| self.dropped(panic))
+            }
+        }
+    }
+
+    /// Await `export`, returning the message of a panic while polling it instead if the batch
+    /// is dropped.
+    pub(crate) async fn poll<F: Future>(&self, export: F) -> Result<F::Output, String> {
+        let mut export = std::pin::pin!(export);
+        std::future::poll_fn(|cx| match self.call(|| export.as_mut().poll(cx)) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(message) => Poll::Ready(Err(message)),
+        })
+        .await
+    }
+
+    fn dropped(&self, panic: Box<dyn Any + Send>) -> String {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        otel_error!(
+            name: "Exporter.ExportPanicked",
+            signal = self.signal,
+            message = format!("exporting a batch panicked, dropping it: {message}"),
+        );
+        format!("exporting the batch panicked: {message}")
+    }
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Arc, Mutex};
+    use std::time::SystemTime;
+
+    use futures_core::future::BoxFuture;
+    use opentelemetry::trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceId, TraceState};
+    use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter as _};
+
+    use crate::{ExportPanicPolicy, SpanExporter};
+
+    fn span(name: &'static str) -> SpanData {
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(1),
+                SpanId::from(1),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Internal,
+            name: name.into(),
+            start_time: SystemTime::now(),
+            end_time: SystemTime::now(),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: Default::default(),
+            instrumentation_scope: Default::default(),
+        }
+    }
+
+    /// A client whose conversion panics on the spans named `poison`.
+    #[derive(Debug)]
+    struct PoisonedClient(Arc<Mutex<Vec<String>>>);
+
+    impl opentelemetry_sdk::export::trace::SpanExporter for PoisonedClient {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            for span in batch {
+                assert_ne!(span.name, "poison", "converting the span failed");
+                self.0.lock().unwrap().push(span.name.into_owned());
+            }
+            Box::pin(std::future::ready(Ok(())))
+        }
+    }
+
+    fn exporter(policy: ExportPanicPolicy, exported: &Arc<Mutex<Vec<String>>>) -> SpanExporter {
+        SpanExporter::new(PoisonedClient(Arc::clone(exported))).with_panic_policy(policy)
+    }
+
+    #[tokio::test]
+    async fn test_panicking_batch_is_dropped() {
+        let exported = Arc::default();
+        let mut exporter = exporter(ExportPanicPolicy::DropBatch, &exported);
+
+        let error = exporter.export(vec![span("poison")]).await.unwrap_err();
+        assert!(
+            error.to_string().contains("converting the span failed"),
+            "{error}"
+        );
+        exporter.export(vec![span("healthy")]).await.unwrap();
+
+        assert_eq!(*exported.lock().unwrap(), ["healthy"]);
+        #[cfg(feature = "exporter-stats")]
+        assert_eq!(exporter.exporter_stats().export_panics, 1);
+    }
+
+    #[test]
+    fn test_panic_is_propagated() {
+        let exported = Arc::default();
+        let mut exporter = exporter(ExportPanicPolicy::Propagate, &exported);
+
+        // the conversion runs when the export is called, before the returned future is polled
+        let export = panic::catch_unwind(AssertUnwindSafe(|| {
+            drop(exporter.export(vec![span("poison")]));
+        }));
+        assert!(export.is_err());
+    }
+}
@@ -0,0 +1,34 @@
+#!/bin/sh
+# Regenerate the certificates of the TLS tests: a CA, a server certificate for `localhost` and
+# two client certificates. The client files hold both the certificate and the private key.
+set -e
+cd "$(dirname "$0")"
+
+key() {
+    openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$1"
+}
+
+sign() {
+    # $1: name, $2: common name, $3: extensions
+    key "$1.key"
+    openssl req -new -key "$1.key" -subj "/CN=$2" -out "$1.csr"
+    printf '%s\n' "$3" > "$1.ext"
+    openssl x509 -req -in "$1.csr" -CA ca.pem -CAkey ca.key -CAcreateserial -days 36500 \
+        -extfile "$1.ext" -out "$1.pem"
+    rm "$1.csr" "$1.ext"
+}
+
+key ca.key
+openssl req -x509 -new -key ca.key -subj "/CN=otlp test ca" -days 36500 \
+    -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign" \
+    -out ca.pem
+
+sign server localhost "subjectAltName=DNS:localhost
+extendedKeyUsage=serverAuth"
+for client in client-a client-b; do
+    sign "$client" "$client" "extendedKeyUsage=clientAuth"
+    cat "$client.key" >> "$client.pem"
+    rm "$client.key"
+done
+cat server.key >> server.pem
+rm server.key ca.key ca.srl
@@ -5,6 +5,7 @@
 pub(crate) mod serializers {
     use crate::tonic::common::v1::any_value::{self, Value};
     use crate::tonic::common::v1::AnyValue;
+    use base64::prelude::{Engine as _, BASE64_STANDARD};
     use serde::de::{self, MapAccess, Visitor};
     use serde::ser::{SerializeMap, SerializeStruct};
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -56,6 +57,12 @@ pub(crate) mod serializers {
                 map.serialize_entry("intValue", &i.to_string());
                 map.end()
             }
+            Some(Value::BytesValue(bytes)) => {
+                // OTLP/JSON encodes bytes as base64 strings
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("bytesValue", &BASE64_STANDARD.encode(bytes))?;
+                map.end()
+            }
             Some(value) => value.serialize(serializer),
             None => serializer.serialize_none(),
         }
@@ -127,7 +134,10 @@ pub(crate) mod serializers {
                             value = Some(any_value::Value::KvlistValue(kv));
                         }
                         "bytesValue" => {
-                            let bytes = map.next_value()?;
+                            let encoded = map.next_value::<String>()?;
+                            let bytes = BASE64_STANDARD
+                                .decode(encoded)
+                                .map_err(de::Error::custom)?;
                             value = Some(any_value::Value::BytesValue(bytes));
                         }
                         _ => {
@@ -15,6 +15,7 @@ pub mod tonic {
     use opentelemetry_sdk::export::logs::LogBatch;
     use std::borrow::Cow;
     use std::collections::HashMap;
+    use std::time::SystemTime;
 
     impl From<LogsAnyValue> for AnyValue {
         fn from(value: LogsAnyValue) -> Self {
@@ -89,7 +90,13 @@ pub mod tonic {
 
             LogRecord {
                 time_unix_nano: log_record.timestamp.map(to_nanos).unwrap_or_default(),
-                observed_time_unix_nano: to_nanos(log_record.observed_timestamp.unwrap()),
+                // Records which didn't go through a logger (e.g. built by a bridge or a test)
+                // may not carry an observed time, but some backends require it.
+                observed_time_unix_nano: to_nanos(
+                    log_record
+                        .observed_timestamp
+                        .unwrap_or_else(SystemTime::now),
+                ),
                 attributes: {
                     let attributes: Vec<KeyValue> = log_record
                         .attributes_iter()
@@ -301,4 +308,17 @@ mod tests {
         assert_eq!(scope_logs_1.log_records.len(), 1);
         assert_eq!(scope_logs_2.log_records.len(), 1);
     }
+
+    #[test]
+    fn test_missing_observed_time_is_populated() {
+        let before = crate::transform::common::to_nanos(SystemTime::now());
+        let mut log_record = LogRecord::default();
+        log_record.set_timestamp(SystemTime::UNIX_EPOCH);
+        assert!(log_record.observed_timestamp.is_none());
+
+        let proto_record: crate::tonic::logs::v1::LogRecord = (&log_record).into();
+
+        assert_eq!(proto_record.time_unix_nano, 0);
+        assert!(proto_record.observed_time_unix_nano >= before);
+    }
 }
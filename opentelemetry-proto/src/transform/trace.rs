@@ -1,13 +1,15 @@
 #[cfg(feature = "gen-tonic-messages")]
 pub mod tonic {
     use crate::proto::tonic::resource::v1::Resource;
-    use crate::proto::tonic::trace::v1::{span, status, ResourceSpans, ScopeSpans, Span, Status};
+    use crate::proto::tonic::trace::v1::{
+        span, status, ResourceSpans, ScopeSpans, Span, SpanFlags, Status,
+    };
     use crate::transform::common::{
         to_nanos,
         tonic::{Attributes, ResourceAttributesWithSchema},
     };
     use opentelemetry::trace;
-    use opentelemetry::trace::{Link, SpanId, SpanKind};
+    use opentelemetry::trace::{Link, SpanId, SpanKind, TraceFlags};
     use opentelemetry_sdk::export::trace::SpanData;
     use std::collections::HashMap;
 
@@ -33,6 +35,16 @@ pub mod tonic {
         }
     }
 
+    /// The OTLP flags of a span or link: the trace flags, and whether the parent span or the
+    /// linked span is remote.
+    fn build_span_flags(trace_flags: TraceFlags, is_remote: bool) -> u32 {
+        let mut flags = trace_flags.to_u8() as u32 | SpanFlags::ContextHasIsRemoteMask as u32;
+        if is_remote {
+            flags |= SpanFlags::ContextIsRemoteMask as u32;
+        }
+        flags
+    }
+
     impl From<Link> for span::Link {
         fn from(link: Link) -> Self {
             span::Link {
@@ -41,7 +53,10 @@ pub mod tonic {
                 trace_state: link.span_context.trace_state().header(),
                 attributes: Attributes::from(link.attributes).0,
                 dropped_attributes_count: link.dropped_attributes_count,
-                flags: link.span_context.trace_flags().to_u8() as u32,
+                flags: build_span_flags(
+                    link.span_context.trace_flags(),
+                    link.span_context.is_remote(),
+                ),
             }
         }
     }
@@ -59,7 +74,10 @@ pub mod tonic {
                         vec![]
                     }
                 },
-                flags: source_span.span_context.trace_flags().to_u8() as u32,
+                flags: build_span_flags(
+                    source_span.span_context.trace_flags(),
+                    source_span.parent_span_is_remote,
+                ),
                 name: source_span.name.into_owned(),
                 kind: span_kind as i32,
                 start_time_unix_nano: to_nanos(source_span.start_time),
@@ -117,7 +135,10 @@ pub mod tonic {
                                 vec![]
                             }
                         },
-                        flags: source_span.span_context.trace_flags().to_u8() as u32,
+                        flags: build_span_flags(
+                            source_span.span_context.trace_flags(),
+                            source_span.parent_span_is_remote,
+                        ),
                         name: source_span.name.into_owned(),
                         kind: span_kind as i32,
                         start_time_unix_nano: to_nanos(source_span.start_time),
@@ -216,6 +237,7 @@ mod tests {
         SpanData {
             span_context,
             parent_span_id: SpanId::from_u64(0),
+            parent_span_is_remote: false,
             span_kind: SpanKind::Internal,
             name: Cow::Borrowed("test_span"),
             start_time: SystemTime::now(),
@@ -698,6 +698,44 @@ mod json_serde {
                 assert_eq!(actual, expected);
             }
         }
+
+        mod bytes {
+            use super::*;
+
+            fn value() -> KeyValue {
+                KeyValue {
+                    key: String::from("service.token"),
+                    value: Some(AnyValue {
+                        value: Some(Value::BytesValue(b"otel".to_vec())),
+                    }),
+                }
+            }
+
+            // base64 encoded bytes
+            // language=json
+            const CANONICAL: &str = r#"{
+  "key": "service.token",
+  "value": {
+    "bytesValue": "b3RlbA=="
+  }
+}"#;
+
+            #[test]
+            fn serialize() {
+                let input: KeyValue = value();
+                let actual =
+                    serde_json::to_string_pretty(&input).expect("serialization must succeed");
+                assert_eq!(actual, CANONICAL);
+            }
+
+            #[test]
+            fn deserialize_canonical() {
+                let actual: KeyValue =
+                    serde_json::from_str(CANONICAL).expect("deserialization must succeed");
+                let expected: KeyValue = value();
+                assert_eq!(actual, expected);
+            }
+        }
     }
 
     #[cfg(feature = "trace")]
@@ -23,6 +23,7 @@ fn get_span_data() -> Vec<SpanData> {
                 TraceState::default(),
             ),
             parent_span_id: SpanId::from_u64(12),
+            parent_span_is_remote: false,
             span_kind: SpanKind::Client,
             name: Default::default(),
             start_time: SystemTime::now(),
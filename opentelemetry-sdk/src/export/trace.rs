@@ -76,6 +76,8 @@ pub struct SpanData {
     pub span_context: SpanContext,
     /// Span parent id
     pub parent_span_id: SpanId,
+    /// Whether the parent span is remote, i.e. was propagated from another process
+    pub parent_span_is_remote: bool,
     /// Span kind
     pub span_kind: SpanKind,
     /// Span name
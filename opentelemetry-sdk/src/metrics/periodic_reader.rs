@@ -1,7 +1,7 @@
 use std::{
     env, fmt, mem,
     sync::{Arc, Mutex, Weak},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use futures_channel::{mpsc, oneshot};
@@ -45,6 +45,7 @@ const METRIC_EXPORT_TIMEOUT_NAME: &str = "OTEL_METRIC_EXPORT_TIMEOUT";
 pub struct PeriodicReaderBuilder<E, RT> {
     interval: Duration,
     timeout: Duration,
+    aligned_flush: bool,
     exporter: E,
     runtime: RT,
 }
@@ -67,6 +68,7 @@ where
         PeriodicReaderBuilder {
             interval,
             timeout,
+            aligned_flush: false,
             exporter,
             runtime,
         }
@@ -101,6 +103,19 @@ where
         self
     }
 
+    /// Configures whether exports are aligned to wall-clock boundaries.
+    ///
+    /// When enabled, exports happen at multiples of the interval since the
+    /// UNIX epoch (e.g. at the top of every minute for the default interval)
+    /// instead of relative to the time the reader was started. This lines up
+    /// the exports of replicas started at different times.
+    ///
+    /// Defaults to `false`.
+    pub fn with_aligned_flush(mut self, aligned_flush: bool) -> Self {
+        self.aligned_flush = aligned_flush;
+        self
+    }
+
     /// Create a [PeriodicReader] with the given config.
     pub fn build(self) -> PeriodicReader {
         let (message_sender, message_receiver) = mpsc::channel(256);
@@ -109,10 +124,20 @@ where
             let runtime = self.runtime.clone();
             let reader = reader.clone();
             self.runtime.spawn(Box::pin(async move {
-                let ticker = runtime
-                    .interval(self.interval)
-                    .skip(1) // The ticker is fired immediately, so we should skip the first one to align with the interval.
-                    .map(|_| Message::Export);
+                let ticker = if self.aligned_flush {
+                    let interval = self.interval;
+                    let interval_runtime = runtime.clone();
+                    // The ticker is fired immediately, which is the first boundary after the delay.
+                    stream::once(runtime.delay(delay_to_next_boundary(SystemTime::now(), interval)))
+                        .flat_map(move |_| interval_runtime.interval(interval))
+                        .boxed()
+                } else {
+                    runtime
+                        .interval(self.interval)
+                        .skip(1) // The ticker is fired immediately, so we should skip the first one to align with the interval.
+                        .boxed()
+                }
+                .map(|_| Message::Export);
                 let messages = Box::pin(stream::select(message_receiver, ticker));
                 PeriodicReaderWorker {
                     reader,
@@ -146,6 +171,17 @@ where
     }
 }
 
+/// Time from `now` until the next multiple of `interval` since the UNIX epoch.
+fn delay_to_next_boundary(now: SystemTime, interval: Duration) -> Duration {
+    let since_epoch = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let interval = interval.as_nanos().max(1);
+    let remaining = interval - since_epoch % interval;
+    Duration::from_nanos(remaining.try_into().unwrap_or(u64::MAX))
+}
+
 /// A [MetricReader] that continuously collects and exports metric data at a set
 /// interval.
 ///
@@ -421,6 +457,7 @@ mod tests {
     };
     use opentelemetry::metrics::MeterProvider;
     use std::sync::mpsc;
+    use std::time::{Duration, SystemTime};
 
     #[test]
     fn collection_triggered_by_interval_tokio_current() {
@@ -479,6 +516,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn delay_to_next_boundary() {
+        let interval = Duration::from_secs(60);
+        let at = |millis| SystemTime::UNIX_EPOCH + Duration::from_millis(millis);
+
+        assert_eq!(
+            super::delay_to_next_boundary(at(120_000), interval),
+            interval
+        );
+        assert_eq!(
+            super::delay_to_next_boundary(at(120_001), interval),
+            Duration::from_millis(59_999)
+        );
+        assert_eq!(
+            super::delay_to_next_boundary(at(179_500), interval),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn aligned_flush_happens_near_wall_clock_boundaries() {
+        let interval = Duration::from_millis(200);
+        let exporter = InMemoryMetricExporter::default();
+        let reader = PeriodicReader::builder(exporter.clone(), runtime::Tokio)
+            .with_interval(interval)
+            .with_aligned_flush(true)
+            .build();
+        let (sender, receiver) = mpsc::channel();
+
+        let meter_provider = SdkMeterProvider::builder().with_reader(reader).build();
+        let meter = meter_provider.meter("test");
+        let _counter = meter
+            .u64_observable_counter("testcounter")
+            .with_callback(move |_| {
+                let _ = sender.send(SystemTime::now());
+            })
+            .build();
+
+        for _ in 0..2 {
+            let collected_at = receiver
+                .recv_timeout(Duration::from_secs(5))
+                .expect("message should be available in channel, indicating a collection occurred");
+            let offset = collected_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_millis()
+                % interval.as_millis();
+            assert!(
+                offset < 100,
+                "collection happened {offset}ms after a boundary"
+            );
+        }
+    }
+
     fn collection_triggered_by_interval_helper<RT>(runtime: RT)
     where
         RT: crate::runtime::Runtime,
@@ -27,6 +27,8 @@ pub struct Span {
 pub(crate) struct SpanData {
     /// Span parent id
     pub(crate) parent_span_id: SpanId,
+    /// Whether the parent span is remote
+    pub(crate) parent_span_is_remote: bool,
     /// Span kind
     pub(crate) span_kind: SpanKind,
     /// Span name
@@ -254,6 +256,7 @@ fn build_export_data(
     crate::export::trace::SpanData {
         span_context,
         parent_span_id: data.parent_span_id,
+        parent_span_is_remote: data.parent_span_is_remote,
         span_kind: data.span_kind,
         name: data.name,
         start_time: data.start_time,
@@ -286,6 +289,7 @@ mod tests {
         let tracer = provider.tracer("opentelemetry");
         let data = SpanData {
             parent_span_id: SpanId::from_u64(0),
+            parent_span_is_remote: false,
             span_kind: trace::SpanKind::Internal,
             name: "opentelemetry".into(),
             start_time: opentelemetry::time::now(),
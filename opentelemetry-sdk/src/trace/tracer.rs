@@ -134,6 +134,7 @@ impl Tracer {
             sc,
             Some(SpanData {
                 parent_span_id: psc.span_id(),
+                parent_span_is_remote: psc.is_valid() && psc.is_remote(),
                 span_kind: builder.span_kind.take().unwrap_or(SpanKind::Internal),
                 name,
                 start_time,
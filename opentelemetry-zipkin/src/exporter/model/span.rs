@@ -156,6 +156,7 @@ mod tests {
                     Default::default(),
                 ),
                 parent_span_id: SpanId::from_u64(1),
+                parent_span_is_remote: false,
                 span_kind: SpanKind::Client,
                 name: "".into(),
                 start_time: SystemTime::now(),